@@ -0,0 +1,231 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use memchr::{memchr, memchr2};
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use cpp_parser::args::CompilationDB;
+use cpp_parser::driver::{self, Analysis, DriverOptions, TranslationUnit};
+use cpp_parser::lexer::preprocessor::context::DefaultContext;
+use cpp_parser::lexer::{Lexer, TLexer, Token};
+
+fn lex(buf: &[u8]) -> usize {
+    let mut lexer = Lexer::<DefaultContext>::new(buf);
+    let mut n = 0;
+    while lexer.next_useful() != Token::Eof {
+        n += 1;
+    }
+    n
+}
+
+fn save_and_replay(buf: &[u8]) -> usize {
+    let mut lexer = Lexer::<DefaultContext>::new(buf);
+    let (_, mut saved) = lexer.save_until(Token::Eof, 0);
+    let mut n = 0;
+    while saved.next_useful() != Token::Eof {
+        n += 1;
+    }
+    n
+}
+
+/// Lex (or save and replay) each file in benches/basic
+fn bench_basic(c: &mut Criterion) {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("benches/basic");
+    let mut files: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|e| e == "cpp"))
+        .collect();
+    files.sort();
+
+    let mut group = c.benchmark_group("basic");
+    for path in files {
+        let name = path.file_name().unwrap().to_str().unwrap().to_string();
+        let data = fs::read(&path).unwrap();
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        group.bench_function(format!("lex/{}", name), |b| b.iter(|| lex(&data)));
+        group.bench_function(format!("save/{}", name), |b| {
+            b.iter(|| save_and_replay(&data))
+        });
+    }
+    group.finish();
+}
+
+/// Inputs where most of the time is spent in comments, strings or disabled blocks
+fn generated() -> Vec<(&'static str, Vec<u8>)> {
+    let line = "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor\n";
+    let mut comments = String::new();
+    let mut if0 = String::new();
+    let mut strings = String::new();
+    for i in 0..500 {
+        comments.push_str(&format!(
+            "/*\n{}*/\n// {}int a{} = 0;\n",
+            line.repeat(8),
+            line,
+            i
+        ));
+        if0.push_str(&format!(
+            "#if 0\n{}#endif\nint a{} = 0;\n",
+            line.repeat(8),
+            i
+        ));
+        strings.push_str(&format!(
+            "const char* s{} = \"{}\";\n",
+            i,
+            line.trim_end().repeat(8)
+        ));
+    }
+    vec![
+        ("comments", comments.into_bytes()),
+        ("if0", if0.into_bytes()),
+        ("strings", strings.into_bytes()),
+    ]
+}
+
+fn bench_generated(c: &mut Criterion) {
+    let mut group = c.benchmark_group("generated");
+    for (name, data) in generated() {
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        group.bench_function(format!("lex/{}", name), |b| b.iter(|| lex(&data)));
+    }
+    group.finish();
+}
+
+// The byte at a time loops used before the scanners in lexer/tools.rs
+fn comment_end_bytes(buf: &[u8]) -> Option<usize> {
+    (1..buf.len()).find(|&i| buf[i] == b'/' && buf[i - 1] == b'*')
+}
+
+fn comment_end_memchr(buf: &[u8]) -> Option<usize> {
+    let mut pos = 1;
+    while pos < buf.len() {
+        let i = pos + memchr(b'/', &buf[pos..])?;
+        if buf[i - 1] == b'*' {
+            return Some(i);
+        }
+        pos = i + 1;
+    }
+    None
+}
+
+fn delim_end_bytes(buf: &[u8], delim: u8) -> usize {
+    let mut i = 0;
+    while i < buf.len() {
+        if buf[i] == delim {
+            return i + 1;
+        } else if buf[i] == b'\\' {
+            i += 1;
+        }
+        i += 1;
+    }
+    buf.len()
+}
+
+fn delim_end_memchr(buf: &[u8], delim: u8) -> usize {
+    let mut pos = 0;
+    while let Some(i) = memchr2(delim, b'\\', &buf[pos..]) {
+        let i = pos + i;
+        if buf[i] == delim {
+            return i + 1;
+        }
+        pos = i + 2;
+        if pos >= buf.len() {
+            break;
+        }
+    }
+    buf.len()
+}
+
+fn newlines_bytes(buf: &[u8]) -> usize {
+    buf.iter().filter(|&&c| c == b'\n').count()
+}
+
+fn newlines_memchr(buf: &[u8]) -> usize {
+    memchr::memchr_iter(b'\n', buf).count()
+}
+
+/// Compare the scanning loops alone on a 64KB chunk of text
+fn bench_scan(c: &mut Criterion) {
+    let line = b"Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor\n";
+    let mut data = line.repeat(64 * 1024 / line.len());
+    data.extend_from_slice(b"*/\"#");
+
+    let mut group = c.benchmark_group("scan");
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    group.bench_function("comment_end/bytes", |b| {
+        b.iter(|| comment_end_bytes(black_box(&data)))
+    });
+    group.bench_function("comment_end/memchr", |b| {
+        b.iter(|| comment_end_memchr(black_box(&data)))
+    });
+    group.bench_function("delim_end/bytes", |b| {
+        b.iter(|| delim_end_bytes(black_box(&data), b'"'))
+    });
+    group.bench_function("delim_end/memchr", |b| {
+        b.iter(|| delim_end_memchr(black_box(&data), b'"'))
+    });
+    group.bench_function("hash/bytes", |b| {
+        b.iter(|| black_box(&data).iter().position(|&c| c == b'#'))
+    });
+    group.bench_function("hash/memchr", |b| b.iter(|| memchr(b'#', black_box(&data))));
+    group.bench_function("newlines/bytes", |b| {
+        b.iter(|| newlines_bytes(black_box(&data)))
+    });
+    group.bench_function("newlines/memchr", |b| {
+        b.iter(|| newlines_memchr(black_box(&data)))
+    });
+    group.finish();
+}
+
+struct CountTokens;
+
+impl Analysis for CountTokens {
+    type Context = DefaultContext;
+    type Output = usize;
+
+    fn analyze(&self, _tu: TranslationUnit<Self::Context>) -> Self::Output {
+        1
+    }
+
+    fn reduce(&self, acc: Self::Output, output: Self::Output) -> Self::Output {
+        acc + output
+    }
+}
+
+/// Preprocess all the translation units of the compilation database
+/// given in CPP_PARSER_BENCH_DB (skipped when the variable isn't set)
+fn bench_compile_db(c: &mut Criterion) {
+    let path = if let Ok(path) = env::var("CPP_PARSER_BENCH_DB") {
+        path
+    } else {
+        return;
+    };
+
+    let mut group = c.benchmark_group("compile_db");
+    group.sample_size(10);
+    group.bench_function("lex", |b| {
+        b.iter(|| {
+            let database = CompilationDB::from_json(&path).unwrap();
+            let opt = DriverOptions {
+                cache_size: 0,
+                ..Default::default()
+            };
+            driver::run(database, &CountTokens, &opt).output
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_basic,
+    bench_generated,
+    bench_scan,
+    bench_compile_db
+);
+criterion_main!(benches);
@@ -7,15 +7,40 @@
 extern crate clap;
 
 use clap::{App, Arg};
-use cpp_parser::args::{Language, PreprocOptions};
+use cpp_parser::args::{
+    ArgsError, Command, CompilationDB, CompileFlags, Driver, Language, PreprocOptions,
+};
 use cpp_parser::defaults;
+use cpp_parser::lexer::charset::Charset;
+use cpp_parser::lexer::persist;
 use cpp_parser::lexer::preprocessor::cache::IfCache;
 use cpp_parser::lexer::preprocessor::context::{DefaultContext, PreprocContext};
-use cpp_parser::lexer::source;
+use cpp_parser::lexer::source::SourceLocator;
 use cpp_parser::lexer::Lexer;
 use cpp_parser::parser::{Context, Dump, UnitParser};
-use std::path::PathBuf;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, Mutex};
+
+fn get_command(
+    file: &str,
+    database: Option<&str>,
+    compile_flags: Option<&str>,
+) -> Result<Option<Command>, ArgsError> {
+    let path = std::env::current_dir().unwrap().join(file);
+    if let Some(database) = database {
+        if let Some(cmd) = CompilationDB::from_json(database)?.get_command(&path) {
+            return cmd.map(Some);
+        }
+    }
+
+    let flags = if let Some(compile_flags) = compile_flags {
+        Some(CompileFlags::from_file(Path::new(compile_flags))?)
+    } else {
+        CompileFlags::find(&path)
+    };
+    flags.map(|flags| flags.get_command(&path)).transpose()
+}
 
 fn main() {
     let matches = App::new("AST dump")
@@ -27,22 +52,76 @@ fn main() {
                 .help("File to dump")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("database")
+                .help("Compilation database path")
+                .short("c")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("compile_flags")
+                .help("compile_flags.txt to use when the file isn't in the database")
+                .long("compile-flags")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("cache_dir")
+                .help("Directory where the caches are stored between runs")
+                .long("cache-dir")
+                .value_name("DIR")
+                .takes_value(true),
+        )
         .get_matches();
 
     let file = matches.value_of("file").unwrap().to_string();
 
-    let source = source::get_source_mutex();
-    let if_cache = Arc::new(IfCache::default());
-    let opt = PreprocOptions {
-        def: defaults::get_defined(),
-        sys_paths: defaults::get_sys_paths(),
-        includes: vec![],
-        current_dir: PathBuf::from("."),
-        file: PathBuf::from(""),
-        lang: Language::CPP,
+    let cache_dir = matches.value_of("cache_dir").map(PathBuf::from);
+
+    let (source, if_cache) = if let Some(cache_dir) = cache_dir.as_ref() {
+        persist::load_caches(cache_dir)
+    } else {
+        (SourceLocator::default(), IfCache::default())
+    };
+    let source = Arc::new(Mutex::new(source));
+    let if_cache = Arc::new(if_cache);
+    let opt = match get_command(
+        &file,
+        matches.value_of("database"),
+        matches.value_of("compile_flags"),
+    ) {
+        Ok(Some(cmd)) => {
+            let mut opt = cmd.opt;
+            opt.sys_paths
+                .extend(defaults::get_sys_paths_for(&opt.driver));
+            let mut def = defaults::get_defined_for(&opt.driver);
+            def.extend_from_slice(&opt.def);
+            opt.def = def;
+            opt
+        }
+        Ok(None) => PreprocOptions {
+            def: defaults::get_defined(),
+            sys_paths: defaults::get_sys_paths(),
+            includes: vec![],
+            current_dir: PathBuf::from("."),
+            file: PathBuf::from(""),
+            lang: Language::CPP,
+            std: None,
+            driver: Driver::GCC,
+            charset: Charset::Utf8,
+            cuda: false,
+        },
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
     };
 
-    let lexer = Lexer::<DefaultContext>::new_from_file(&file, source, if_cache, opt);
+    let lexer = Lexer::<DefaultContext>::new_from_file(
+        &file,
+        Arc::clone(&source),
+        Arc::clone(&if_cache),
+        opt,
+    );
 
     let context = Context::default();
     let mut parser = UnitParser { lexer, context };
@@ -55,4 +134,10 @@ fn main() {
             eprintln!("{:?}", e);
         }
     }
+
+    if let Some(cache_dir) = cache_dir.as_ref() {
+        if let Err(e) = persist::save_caches(cache_dir, &source.lock().unwrap(), &if_cache) {
+            eprintln!("Cannot save the caches: {}", e);
+        }
+    }
 }
@@ -0,0 +1,367 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+#[macro_use]
+extern crate clap;
+#[macro_use]
+extern crate serde;
+
+use clap::{App, Arg};
+use cpp_parser::args::CompilationDB;
+use cpp_parser::driver::{
+    self, Analysis, ContextObserver, DriverOptions, ObservedContext, PathFilter, TranslationUnit,
+};
+use cpp_parser::lexer::buffer::FileInfo;
+use cpp_parser::lexer::source::{FileId, SourceMutex};
+use globset::GlobSet;
+use hashbrown::{HashMap, HashSet};
+use std::fs::File;
+use std::io::Write;
+use std::process;
+
+#[derive(Debug, PartialEq, Serialize)]
+struct HeaderCost {
+    path: String,
+    tus: usize,
+    inclusions: usize,
+    tokens: usize,
+    tokens_per_inclusion: f64,
+    macro_definitions: usize,
+    transitive_includes: usize,
+}
+
+impl HeaderCost {
+    fn csv_header() -> &'static str {
+        "path,tus,inclusions,tokens,tokens_per_inclusion,macro_definitions,transitive_includes\n"
+    }
+
+    fn to_csv(&self) -> String {
+        let path = if self.path.contains(&[',', '"', '\n'][..]) {
+            format!("\"{}\"", self.path.replace('"', "\"\""))
+        } else {
+            self.path.clone()
+        };
+        format!(
+            "{},{},{},{},{:.2},{},{}\n",
+            path,
+            self.tus,
+            self.inclusions,
+            self.tokens,
+            self.tokens_per_inclusion,
+            self.macro_definitions,
+            self.transitive_includes
+        )
+    }
+}
+
+/// Data collected for one header across all the TUs
+#[derive(Debug, Default)]
+struct HeaderData {
+    tus: usize,
+    inclusions: usize,
+    tokens: usize,
+    macros: HashSet<String>,
+    includes: HashSet<FileId>,
+}
+
+#[derive(Clone, Default)]
+struct CostObserver {
+    includes: Vec<(FileId, FileId)>,
+    macros: Vec<(FileId, String)>,
+    tokens: HashMap<FileId, usize>,
+}
+
+impl ContextObserver for CostObserver {
+    fn on_define(&mut self, name: &str, info: &FileInfo) {
+        if let Some(source_id) = info.source_id {
+            self.macros.push((source_id, name.to_string()));
+        }
+    }
+
+    fn on_include(&mut self, from: FileId, to: FileId) {
+        self.includes.push((from, to));
+    }
+
+    fn on_token(&mut self, file: Option<FileId>) {
+        if let Some(file) = file {
+            *self.tokens.entry(file).or_insert(0) += 1;
+        }
+    }
+}
+
+struct Cost;
+
+impl Analysis for Cost {
+    type Context = ObservedContext<CostObserver>;
+    type Output = HashMap<FileId, HeaderData>;
+
+    fn analyze(&self, tu: TranslationUnit<Self::Context>) -> Self::Output {
+        let context = &tu.lexer.get_context().observer;
+        let mut data: Self::Output = HashMap::default();
+        let mut included = HashSet::new();
+
+        for (from, to) in context.includes.iter() {
+            included.insert(*to);
+            data.entry(*to).or_default().inclusions += 1;
+            data.entry(*from).or_default().includes.insert(*to);
+        }
+
+        for id in included {
+            data.entry(id).or_default().tus += 1;
+        }
+
+        for (id, name) in context.macros.iter() {
+            data.entry(*id).or_default().macros.insert(name.clone());
+        }
+
+        for (id, n) in context.tokens.iter() {
+            data.entry(*id).or_default().tokens += n;
+        }
+
+        data
+    }
+
+    fn reduce(&self, mut acc: Self::Output, output: Self::Output) -> Self::Output {
+        for (id, header) in output {
+            let h = acc.entry(id).or_default();
+            h.tus += header.tus;
+            h.inclusions += header.inclusions;
+            h.tokens += header.tokens;
+            h.macros.extend(header.macros);
+            h.includes.extend(header.includes);
+        }
+        acc
+    }
+}
+
+fn get_transitive_includes(data: &HashMap<FileId, HeaderData>, id: FileId) -> usize {
+    let mut seen = HashSet::new();
+    let mut stack = vec![id];
+
+    while let Some(id) = stack.pop() {
+        if let Some(header) = data.get(&id) {
+            for inc in header.includes.iter() {
+                if *inc != id && seen.insert(*inc) {
+                    stack.push(*inc);
+                }
+            }
+        }
+    }
+
+    seen.remove(&id);
+    seen.len()
+}
+
+/// Get the report for the headers sorted by cost (the most expensive first)
+fn get_report(
+    all_data: &HashMap<FileId, HeaderData>,
+    source: &SourceMutex,
+    exclude: &GlobSet,
+) -> Vec<HeaderCost> {
+    let mut report = Vec::new();
+
+    for (id, header) in all_data.iter() {
+        if id.0 == 0 || header.inclusions == 0 {
+            continue;
+        }
+        let path = source.lock().unwrap().get_path(*id);
+        let path = path.to_string_lossy();
+        if exclude.is_match(path.as_ref()) {
+            continue;
+        }
+        report.push(HeaderCost {
+            path: path.into_owned(),
+            tus: header.tus,
+            inclusions: header.inclusions,
+            tokens: header.tokens,
+            tokens_per_inclusion: header.tokens as f64 / header.inclusions as f64,
+            macro_definitions: header.macros.len(),
+            transitive_includes: get_transitive_includes(all_data, *id),
+        });
+    }
+
+    report.sort_by(|a, b| b.tokens.cmp(&a.tokens).then_with(|| a.path.cmp(&b.path)));
+    report
+}
+
+fn main() {
+    let matches = App::new("Header cost")
+        .version(crate_version!())
+        .author(&*env!("CARGO_PKG_AUTHORS").replace(':', "\n"))
+        .about("Report the cost of the included headers")
+        .arg(
+            Arg::with_name("database")
+                .help("Compilation database path")
+                .short("c")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("exclude")
+                .help("Paths to file which contains path pattern")
+                .short("x")
+                .multiple(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("exclude_pattern")
+                .help("Path glob to exclude")
+                .short("X")
+                .multiple(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("num_jobs")
+                .help("Number of jobs")
+                .short("j")
+                .value_name("NUMBER")
+                .default_value("")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("output")
+                .help("Output file/directory")
+                .short("o")
+                .long("output")
+                .default_value("")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("format")
+                .help("Output format")
+                .short("f")
+                .long("format")
+                .possible_values(&["json", "csv"])
+                .default_value("json")
+                .takes_value(true),
+        )
+        .get_matches();
+
+    let database = matches.value_of("database").unwrap().to_string();
+    let num_jobs = matches
+        .value_of("num_jobs")
+        .unwrap()
+        .parse::<usize>()
+        .unwrap_or(0);
+
+    let exclude = matches.values_of("exclude").unwrap_or_default();
+    let exclude_pat = matches.values_of("exclude_pattern").unwrap_or_default();
+    let exclude = match driver::get_globset(exclude_pat, exclude) {
+        Ok(exclude) => exclude,
+        Err(e) => {
+            eprintln!("Cannot read the exclusion file: {}", e);
+            process::exit(1);
+        }
+    };
+
+    let output = matches.value_of("output").unwrap().to_string();
+    let format = matches.value_of("format").unwrap().to_string();
+
+    let database = match CompilationDB::from_json(&database) {
+        Ok(database) => database,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+    };
+    // The excluded TUs aren't preprocessed and the excluded headers aren't reported
+    let opt = DriverOptions {
+        num_jobs,
+        filter: PathFilter {
+            exclude: exclude.clone(),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let res = driver::run(database, &Cost, &opt);
+
+    for e in res.errors.iter() {
+        eprintln!("Error: {}", e);
+    }
+
+    let all_data = res.output.unwrap_or_default();
+    let report = get_report(&all_data, &res.source, &exclude);
+
+    let data = if format == "csv" {
+        let mut data = HeaderCost::csv_header().to_string();
+        for header in report.iter() {
+            data.push_str(&header.to_csv());
+        }
+        data
+    } else {
+        serde_json::to_string(&report).unwrap()
+    };
+
+    if output.is_empty() {
+        print!("{}", data);
+    } else {
+        let mut file = File::create(output).unwrap();
+        file.write_all(data.as_bytes()).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_header_cost() {
+        let tmp = TempDir::new("test_header_cost").unwrap();
+        let dir = tmp.path();
+        fs::write(
+            dir.join("a.h"),
+            "#ifndef A_H\n#define A_H\n#define A 1\nint a;\n#endif\n",
+        )
+        .unwrap();
+        fs::write(dir.join("b.h"), "#include \"a.h\"\nint b;\n").unwrap();
+        fs::write(dir.join("x.c"), "#include \"a.h\"\n#include \"b.h\"\n").unwrap();
+        fs::write(dir.join("y.c"), "#include \"b.h\"\n").unwrap();
+        let db = format!(
+            r#"[
+                {{"directory": "{0}", "file": "x.c", "command": "clang -c x.c"}},
+                {{"directory": "{0}", "file": "y.c", "command": "clang -c y.c"}}
+            ]"#,
+            dir.display()
+        );
+        let database = CompilationDB::from_slice(db.as_bytes(), dir.to_path_buf()).unwrap();
+
+        let opt = DriverOptions {
+            num_jobs: 2,
+            ..Default::default()
+        };
+        let res = driver::run(database, &Cost, &opt);
+        assert!(res.errors.is_empty());
+        let report = get_report(&res.output.unwrap(), &res.source, &GlobSet::empty());
+
+        let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+        assert_eq!(
+            report,
+            vec![
+                // a.h is included twice in x.c but its tokens are only got once
+                HeaderCost {
+                    path: path("a.h"),
+                    tus: 2,
+                    inclusions: 3,
+                    tokens: 6,
+                    tokens_per_inclusion: 2.,
+                    macro_definitions: 2,
+                    transitive_includes: 0,
+                },
+                HeaderCost {
+                    path: path("b.h"),
+                    tus: 2,
+                    inclusions: 2,
+                    tokens: 6,
+                    tokens_per_inclusion: 3.,
+                    macro_definitions: 0,
+                    transitive_includes: 1,
+                },
+            ]
+        );
+    }
+}
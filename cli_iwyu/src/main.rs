@@ -0,0 +1,548 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+#[macro_use]
+extern crate clap;
+#[macro_use]
+extern crate serde;
+
+use clap::{App, Arg};
+use cpp_parser::args::CompilationDB;
+use cpp_parser::driver::{
+    self, Analysis, ContextObserver, DriverOptions, Mode, ObservedContext, PathFilter,
+    TranslationUnit,
+};
+use cpp_parser::lexer::preprocessor::macros::{Macro, MacroType};
+use cpp_parser::lexer::source::FileId;
+use cpp_parser::parser::Context;
+use globset::GlobSet;
+use hashbrown::{HashMap, HashSet};
+use std::cell::RefCell;
+use std::collections::{btree_map, BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// An IWYU mapping:
+/// - `{ "include": ["<private.h>", "private", "<public.h>", "public"] }`
+/// - `{ "symbol": ["NULL", "private", "<cstddef>", "public"] }` (the symbols aren't handled)
+/// - `{ "ref": "other.imp" }`
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Mapping {
+    Include {
+        include: (String, String, String, String),
+    },
+    Symbol {
+        #[allow(dead_code)]
+        symbol: (String, String, String, String),
+    },
+    Ref {
+        #[serde(rename = "ref")]
+        reference: String,
+    },
+}
+
+#[derive(Debug, Default)]
+struct Mappings {
+    private: Vec<(String, String)>,
+}
+
+impl Mappings {
+    fn new(path: &str) -> Result<Self, String> {
+        let mut mappings = Self::default();
+        let mut seen = HashSet::default();
+        mappings.load(Path::new(path), &mut seen)?;
+        Ok(mappings)
+    }
+
+    fn load(&mut self, path: &Path, seen: &mut HashSet<PathBuf>) -> Result<(), String> {
+        if !seen.insert(path.to_path_buf()) {
+            return Ok(());
+        }
+
+        let mut data = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut data))
+            .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        // The lines starting with # are comments
+        let data: Vec<_> = data
+            .lines()
+            .filter(|l| !l.trim_start().starts_with('#'))
+            .collect();
+        let mappings: Vec<Mapping> = serde_json::from_str(&data.join("\n"))
+            .map_err(|e| format!("Invalid mapping file {}: {}", path.display(), e))?;
+
+        for mapping in mappings {
+            match mapping {
+                Mapping::Include { include } => {
+                    self.private
+                        .push((Self::strip(&include.0).to_string(), include.2));
+                }
+                Mapping::Symbol { .. } => {}
+                Mapping::Ref { reference } => {
+                    // The path is relative to the file containing the ref
+                    let path = path
+                        .parent()
+                        .unwrap_or_else(|| Path::new(""))
+                        .join(reference);
+                    self.load(&path, seen)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn strip(name: &str) -> &str {
+        name.trim_matches(|c| c == '"' || c == '<' || c == '>')
+    }
+
+    fn matches(path: &str, name: &str) -> bool {
+        path == name || path.ends_with(&format!("/{}", name))
+    }
+
+    /// Get the public header to use in place of path
+    fn get_public(&self, path: &str) -> Option<&str> {
+        self.private
+            .iter()
+            .find(|(private, _)| Self::matches(path, private))
+            .map(|(_, public)| public.as_str())
+    }
+}
+
+#[derive(Clone, Default)]
+struct IwyuObserver {
+    includes: Vec<(FileId, FileId)>,
+    pending: RefCell<Vec<(String, FileId)>>,
+    uses: Uses,
+}
+
+impl IwyuObserver {
+    fn add_use(&self, name: &str, origin: Option<FileId>) {
+        if let Some(origin) = origin {
+            self.pending.borrow_mut().push((name.to_string(), origin));
+        }
+    }
+}
+
+impl ContextObserver for IwyuObserver {
+    fn on_get(&self, name: &str, mac: Option<&Macro>) {
+        if let Some(Macro::Function(mac)) = mac {
+            self.add_use(name, mac.get_file_info().source_id);
+        }
+    }
+
+    fn on_get_type(&self, name: &str, typ: &MacroType) {
+        if let MacroType::Object(mac) = typ {
+            self.add_use(name, mac.get_file_info().source_id);
+        }
+    }
+
+    fn on_include(&mut self, from: FileId, to: FileId) {
+        self.includes.push((from, to));
+    }
+
+    // The macros used while getting a token are attributed to the file containing it
+    fn on_token(&mut self, file: Option<FileId>) {
+        if let Some(file) = file {
+            for (name, origin) in self.pending.get_mut().drain(..) {
+                if file != origin {
+                    self.uses.push((name, file, origin));
+                }
+            }
+        }
+    }
+}
+
+/// (name, file where it's used, file where it's defined)
+type Uses = Vec<(String, FileId, FileId)>;
+
+/// The includes to add and the ones to remove in a file
+#[derive(Debug, Default)]
+struct FileSuggestions {
+    /// include -> symbols
+    add: BTreeMap<String, BTreeSet<String>>,
+    /// The includes giving no symbol to the file
+    remove: BTreeSet<String>,
+}
+
+/// file -> suggestions
+type Suggestions = BTreeMap<String, FileSuggestions>;
+
+struct Iwyu {
+    parse: bool,
+    mappings: Mappings,
+    exclude: GlobSet,
+}
+
+impl Analysis for Iwyu {
+    type Context = ObservedContext<IwyuObserver>;
+    type Output = Suggestions;
+
+    fn mode(&self) -> Mode {
+        if self.parse {
+            Mode::Parse
+        } else {
+            Mode::Lex
+        }
+    }
+
+    fn get_parser_context(&self) -> Context {
+        let mut context = Context::default();
+        context.track_name_uses();
+        context
+    }
+
+    fn analyze(&self, tu: TranslationUnit<Self::Context>) -> Self::Output {
+        let context = &tu.lexer.get_context().observer;
+        let includes = &context.includes;
+        let mut uses = context.uses.clone();
+        if let Some((context, unit)) = tu.parser.as_ref() {
+            if let Err(e) = unit {
+                eprintln!("Cannot parse {}: {:?}", tu.cmd.file.display(), e);
+            }
+            uses.extend(
+                context
+                    .get_name_uses()
+                    .into_iter()
+                    .map(|u| (u.name, u.file, u.origin)),
+            );
+        }
+
+        let mut direct: HashMap<FileId, HashSet<FileId>> = HashMap::default();
+        for (from, to) in includes.iter() {
+            direct.entry(*from).or_default().insert(*to);
+        }
+
+        // The symbols coming from the forced includes are always available
+        let mut forced = HashSet::new();
+        let mut stack = vec![FileId(0)];
+        while let Some(id) = stack.pop() {
+            if let Some(incs) = direct.get(&id) {
+                for inc in incs.iter() {
+                    if forced.insert(*inc) {
+                        stack.push(*inc);
+                    }
+                }
+            }
+        }
+
+        let source = tu.source.lock().unwrap();
+        let path = |id| source.get_path(id).to_string_lossy().into_owned();
+        let mut suggestions = Suggestions::default();
+        // The (file, include) where the include gives a symbol to the file
+        let mut used = HashSet::new();
+
+        for (name, file, origin) in uses {
+            if file.0 == 0 || origin.0 == 0 || forced.contains(&origin) {
+                continue;
+            }
+
+            let file_path = path(file);
+            if self.exclude.is_match(&file_path) {
+                continue;
+            }
+
+            let origin_path = path(origin);
+            let includes = direct.get(&file);
+
+            let include = if let Some(public) = self.mappings.get_public(&origin_path) {
+                let name = Mappings::strip(public);
+                let included = includes.and_then(|incs| {
+                    incs.iter()
+                        .find(|inc| Mappings::matches(&path(**inc), name))
+                });
+                if let Some(inc) = included {
+                    used.insert((file, *inc));
+                    continue;
+                }
+                if Mappings::matches(&file_path, name) {
+                    continue;
+                }
+                public.to_string()
+            } else {
+                if includes.is_some_and(|incs| incs.contains(&origin)) {
+                    used.insert((file, origin));
+                    continue;
+                }
+                origin_path
+            };
+
+            suggestions
+                .entry(file_path)
+                .or_default()
+                .add
+                .entry(include)
+                .or_default()
+                .insert(name);
+        }
+
+        for (file, incs) in direct.iter() {
+            if file.0 == 0 {
+                continue;
+            }
+
+            let file_path = path(*file);
+            if self.exclude.is_match(&file_path) {
+                continue;
+            }
+
+            suggestions.entry(file_path).or_default().remove = incs
+                .iter()
+                .filter(|inc| !used.contains(&(*file, **inc)))
+                .map(|inc| path(*inc))
+                .collect();
+        }
+
+        suggestions
+    }
+
+    fn reduce(&self, mut acc: Self::Output, output: Self::Output) -> Self::Output {
+        for (file, suggestions) in output {
+            match acc.entry(file) {
+                btree_map::Entry::Vacant(e) => {
+                    e.insert(suggestions);
+                }
+                btree_map::Entry::Occupied(e) => {
+                    let acc = e.into_mut();
+                    for (include, symbols) in suggestions.add {
+                        acc.add.entry(include).or_default().extend(symbols);
+                    }
+                    // An include is unused when it's unused in all the translation units
+                    acc.remove = acc
+                        .remove
+                        .intersection(&suggestions.remove)
+                        .cloned()
+                        .collect();
+                }
+            }
+        }
+        acc
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct Suggestion {
+    include: String,
+    symbols: BTreeSet<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct Report {
+    add: Vec<Suggestion>,
+    remove: BTreeSet<String>,
+}
+
+fn get_reports(suggestions: Suggestions) -> BTreeMap<String, Report> {
+    suggestions
+        .into_iter()
+        .filter(|(_, s)| !s.add.is_empty() || !s.remove.is_empty())
+        .map(|(file, s)| {
+            let add = s
+                .add
+                .into_iter()
+                .map(|(include, symbols)| Suggestion { include, symbols })
+                .collect();
+            let remove = s.remove;
+            (file, Report { add, remove })
+        })
+        .collect()
+}
+
+fn main() {
+    let matches = App::new("Include what you use")
+        .version(crate_version!())
+        .author(&*env!("CARGO_PKG_AUTHORS").replace(':', "\n"))
+        .about("Suggest the includes to add and the ones to remove")
+        .arg(
+            Arg::with_name("database")
+                .help("Compilation database path")
+                .short("c")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("exclude")
+                .help("Paths to file which contains path pattern")
+                .short("x")
+                .multiple(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("exclude_pattern")
+                .help("Path glob to exclude")
+                .short("X")
+                .multiple(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("mappings")
+                .help("IWYU mapping file (json)")
+                .short("m")
+                .long("mappings")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("parse")
+                .help("Parse the files to get the used names")
+                .short("p")
+                .long("parse"),
+        )
+        .arg(
+            Arg::with_name("num_jobs")
+                .help("Number of jobs")
+                .short("j")
+                .value_name("NUMBER")
+                .default_value("")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("output")
+                .help("Output file/directory")
+                .short("o")
+                .long("output")
+                .default_value("")
+                .takes_value(true),
+        )
+        .get_matches();
+
+    let database = matches.value_of("database").unwrap().to_string();
+    let num_jobs = matches
+        .value_of("num_jobs")
+        .unwrap()
+        .parse::<usize>()
+        .unwrap_or(0);
+
+    let exclude = matches.values_of("exclude").unwrap_or_default();
+    let exclude_pat = matches.values_of("exclude_pattern").unwrap_or_default();
+    let exclude = match driver::get_globset(exclude_pat, exclude) {
+        Ok(exclude) => exclude,
+        Err(e) => {
+            eprintln!("Cannot read the exclusion file: {}", e);
+            process::exit(1);
+        }
+    };
+
+    let mappings = match matches.value_of("mappings").map(Mappings::new) {
+        Some(Ok(mappings)) => mappings,
+        Some(Err(e)) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+        None => Mappings::default(),
+    };
+    let parse = matches.is_present("parse");
+    let output = matches.value_of("output").unwrap().to_string();
+
+    let database = match CompilationDB::from_json(&database) {
+        Ok(database) => database,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+    };
+    let iwyu = Iwyu {
+        parse,
+        mappings,
+        exclude: exclude.clone(),
+    };
+    let opt = DriverOptions {
+        num_jobs,
+        filter: PathFilter {
+            exclude,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let res = driver::run(database, &iwyu, &opt);
+
+    for e in res.errors.iter() {
+        eprintln!("Error: {}", e);
+    }
+
+    let reports = get_reports(res.output.unwrap_or_default());
+    let data = serde_json::to_string(&reports).unwrap();
+    if output.is_empty() {
+        println!("{}", data);
+    } else {
+        let mut file = File::create(output).unwrap();
+        file.write_all(data.as_bytes()).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use tempdir::TempDir;
+
+    fn get_reports_for(parse: bool) -> (TempDir, BTreeMap<String, Report>) {
+        let tmp = TempDir::new("test_iwyu").unwrap();
+        let dir = tmp.path().to_path_buf();
+        fs::write(dir.join("a.h"), "#define A 1\n").unwrap();
+        fs::write(dir.join("b.h"), "#include \"a.h\"\nint b;\n").unwrap();
+        fs::write(dir.join("c.h"), "int c;\n").unwrap();
+        fs::write(
+            dir.join("main.c"),
+            "#include \"b.h\"\n#include \"c.h\"\nint x = A + b;\n",
+        )
+        .unwrap();
+        let db = format!(
+            r#"[{{"directory": "{}", "file": "main.c", "command": "clang -c main.c"}}]"#,
+            dir.display()
+        );
+        let database = CompilationDB::from_slice(db.as_bytes(), dir.clone()).unwrap();
+
+        let iwyu = Iwyu {
+            parse,
+            mappings: Mappings::default(),
+            exclude: GlobSet::empty(),
+        };
+        let res = driver::run(database, &iwyu, &DriverOptions::default());
+        assert!(res.errors.is_empty());
+        (tmp, get_reports(res.output.unwrap()))
+    }
+
+    #[test]
+    fn test_iwyu() {
+        for parse in &[false, true] {
+            let (tmp, reports) = get_reports_for(*parse);
+            let path = |name: &str| tmp.path().join(name).to_string_lossy().into_owned();
+
+            // b is only seen by the parser
+            let remove: BTreeSet<_> = if *parse {
+                vec![path("c.h")]
+            } else {
+                vec![path("b.h"), path("c.h")]
+            }
+            .into_iter()
+            .collect();
+            let expected: BTreeMap<_, _> = vec![
+                (
+                    path("main.c"),
+                    Report {
+                        add: vec![Suggestion {
+                            include: path("a.h"),
+                            symbols: vec!["A".to_string()].into_iter().collect(),
+                        }],
+                        remove,
+                    },
+                ),
+                (
+                    path("b.h"),
+                    Report {
+                        add: vec![],
+                        remove: vec![path("a.h")].into_iter().collect(),
+                    },
+                ),
+            ]
+            .into_iter()
+            .collect();
+            assert_eq!(reports, expected);
+        }
+    }
+}
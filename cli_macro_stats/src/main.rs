@@ -11,27 +11,19 @@ extern crate serde;
 extern crate serde_json;
 
 use clap::{App, Arg};
-use cpp_parser::args::{Command, CompilationDB};
-use cpp_parser::defaults;
-use cpp_parser::lexer::buffer::{BufferData, FileInfo, Position};
-use cpp_parser::lexer::preprocessor::cache::IfCache;
-use cpp_parser::lexer::preprocessor::context::{DefaultContext, IfState, PreprocContext};
-use cpp_parser::lexer::preprocessor::include::{IncludeLocator, PathIndex};
-use cpp_parser::lexer::preprocessor::macros::{Macro, MacroFunction, MacroObject, MacroType};
-use cpp_parser::lexer::source::{self, FileId, SourceMutex};
-use cpp_parser::lexer::{Lexer, TLexer, Token};
-use crossbeam::channel::{Receiver, Sender};
-use crossbeam::crossbeam_channel::unbounded;
-use globset::{Glob, GlobSet, GlobSetBuilder};
-use hashbrown::{hash_map, HashMap, HashSet};
-use num_cpus;
+use cpp_parser::args::CompilationDB;
+use cpp_parser::driver::{
+    self, Analysis, ContextObserver, DriverOptions, ObservedContext, TranslationUnit,
+};
+use cpp_parser::lexer::buffer::FileInfo;
+use cpp_parser::lexer::preprocessor::macros::{Macro, MacroType};
+use hashbrown::{hash_map, HashMap};
 use std::cell::Cell;
 use std::collections::BTreeSet;
 use std::fs::File;
-use std::io::{Read, Write};
-use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
-use std::{process, thread};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process;
 
 #[derive(Debug, PartialOrd, Ord, PartialEq, Serialize)]
 struct Res {
@@ -49,56 +41,27 @@ struct Stats {
     counter: Cell<usize>,
 }
 
-#[derive(Default)]
-struct StatsContext {
-    default: DefaultContext,
+#[derive(Clone, Default)]
+struct StatsObserver {
     stats: HashMap<String, Stats>,
 }
 
-impl StatsContext {
+impl StatsObserver {
     fn get_stats(&self) -> &HashMap<String, Stats> {
         &self.stats
     }
-}
-
-impl PreprocContext for StatsContext {
-    fn add_if(&mut self, state: IfState) {
-        self.default.add_if(state);
-    }
-
-    fn rm_if(&mut self) {
-        self.default.rm_if();
-    }
-
-    fn if_state(&self) -> Option<&IfState> {
-        self.default.if_state()
-    }
-
-    fn if_change(&mut self, state: IfState) {
-        self.default.if_change(state);
-    }
 
-    fn add_function(&mut self, name: String, mac: MacroFunction) {
-        let info = mac.get_file_info().clone();
-        self.default.add_function(name.clone(), mac);
-        match self.stats.entry(name) {
-            hash_map::Entry::Occupied(p) => {
-                let p = p.into_mut();
-                p.info = info;
-            }
-            hash_map::Entry::Vacant(p) => {
-                p.insert(Stats {
-                    info,
-                    counter: Cell::new(0),
-                });
-            }
+    fn count(&self, name: &str) {
+        if let Some(stat) = self.stats.get(name) {
+            stat.counter.set(stat.counter.get() + 1);
         }
     }
+}
 
-    fn add_object(&mut self, name: String, mac: MacroObject) {
-        let info = mac.get_file_info().clone();
-        self.default.add_object(name.clone(), mac);
-        match self.stats.entry(name) {
+impl ContextObserver for StatsObserver {
+    fn on_define(&mut self, name: &str, info: &FileInfo) {
+        let info = info.clone();
+        match self.stats.entry(name.to_string()) {
             hash_map::Entry::Occupied(p) => {
                 let p = p.into_mut();
                 p.info = info;
@@ -112,16 +75,9 @@ impl PreprocContext for StatsContext {
         }
     }
 
-    fn undef(&mut self, name: &str) {
-        self.default.undef(name);
-    }
-
-    fn defined(&mut self, name: &str) -> bool {
-        if self.default.defined(name) {
-            if let Some(stat) = self.stats.get(name) {
-                stat.counter.set(stat.counter.get() + 1);
-            }
-            true
+    fn on_defined(&mut self, name: &str, defined: bool) {
+        if defined {
+            self.count(name);
         } else {
             self.stats.insert(
                 name.to_string(),
@@ -130,75 +86,22 @@ impl PreprocContext for StatsContext {
                     counter: Cell::new(1),
                 },
             );
-            false
         }
     }
 
-    fn get(&self, name: &str) -> Option<&Macro> {
-        let mac = self.default.get(name);
+    fn on_get(&self, name: &str, mac: Option<&Macro>) {
         if mac.is_some() {
-            if let Some(stat) = self.stats.get(name) {
-                stat.counter.set(stat.counter.get() + 1);
-            }
+            self.count(name);
         }
-        mac
     }
 
-    fn get_type(&self, name: &str) -> MacroType {
-        let typ = self.default.get_type(name);
+    fn on_get_type(&self, name: &str, typ: &MacroType) {
         if let MacroType::Object(_) = typ {
-            if let Some(stat) = self.stats.get(name) {
-                stat.counter.set(stat.counter.get() + 1);
-            }
-        }
-        typ
-    }
-
-    fn skip_until_next(&self, file: FileId, pos: usize) -> Option<Position> {
-        self.default.skip_until_next(file, pos)
-    }
-
-    fn save_switch(&self, file: FileId, pos: usize, next: Position) {
-        self.default.save_switch(file, pos, next);
-    }
-
-    fn new_with_if_cache(if_cache: Arc<IfCache>) -> Self {
-        Self {
-            default: DefaultContext::new_with_if_cache(if_cache),
-            stats: HashMap::default(),
+            self.count(name);
         }
     }
 }
 
-impl IncludeLocator for StatsContext {
-    fn find(
-        &mut self,
-        angle: bool,
-        path: &str,
-        next: bool,
-        current: FileId,
-        path_index: PathIndex,
-    ) -> Option<BufferData> {
-        self.default.find(angle, path, next, current, path_index)
-    }
-
-    fn get_id(&mut self, path: &PathBuf) -> FileId {
-        self.default.get_id(path)
-    }
-
-    fn get_path(&self, id: FileId) -> PathBuf {
-        self.default.get_path(id)
-    }
-
-    fn set_source(&mut self, source: SourceMutex) {
-        self.default.set_source(source)
-    }
-
-    fn set_sys_paths<P: AsRef<Path>>(&mut self, paths: &[P]) {
-        self.default.set_sys_paths(paths);
-    }
-}
-
 #[derive(Clone, Debug, Hash, PartialEq)]
 struct Key {
     info: FileInfo,
@@ -207,105 +110,32 @@ struct Key {
 
 impl Eq for Key {}
 
-struct JobItem {
-    cmd: Command,
-    if_cache: Arc<IfCache>,
-    source: SourceMutex,
-    stats: Arc<Mutex<HashMap<Key, usize>>>,
-}
+struct MacroStats;
 
-type JobReceiver = Receiver<Option<JobItem>>;
-type JobSender = Sender<Option<JobItem>>;
+impl Analysis for MacroStats {
+    type Context = ObservedContext<StatsObserver>;
+    type Output = HashMap<Key, usize>;
 
-fn consumer(receiver: JobReceiver) {
-    while let Ok(job) = receiver.recv() {
-        if job.is_none() {
-            break;
-        }
-        let JobItem {
-            cmd,
-            if_cache,
-            source,
-            stats,
-        } = job.unwrap();
-        let file = cmd.file.to_str().unwrap();
-        //eprintln!("File {}", file);
-
-        if !file.contains("ecp_25519.c") {
-            //continue;
-        }
-
-        let mut lexer = Lexer::<StatsContext>::new_from_file(
-            cmd.file.to_str().unwrap(),
-            source,
-            if_cache,
-            cmd.opt,
-        );
-
-        loop {
-            let tok = lexer.next_useful();
-            //eprintln!("{:?} -- {:?}", tok, lexer.span());
-            if tok == Token::Eof {
-                break;
-            }
-        }
-
-        let context = lexer.get_context();
-        let lexer_stats = context.get_stats();
-
-        let mut stats = stats.lock().unwrap();
+    fn analyze(&self, tu: TranslationUnit<Self::Context>) -> Self::Output {
+        let lexer_stats = tu.lexer.get_context().observer.get_stats();
+        let mut stats = HashMap::default();
 
         for (name, data) in lexer_stats.iter() {
             let k = Key {
                 info: data.info.clone(),
                 name: name.clone(),
             };
-            match stats.entry(k) {
-                hash_map::Entry::Occupied(p) => {
-                    let c = p.into_mut();
-                    *c += data.counter.get();
-                }
-                hash_map::Entry::Vacant(p) => {
-                    p.insert(data.counter.get());
-                }
-            }
+            *stats.entry(k).or_insert(0) += data.counter.get();
         }
-    }
-}
 
-fn mk_globset(elems: clap::Values, files: clap::Values) -> GlobSet {
-    let mut globset = GlobSetBuilder::new();
-    for e in elems {
-        if !e.is_empty() {
-            if let Ok(glob) = Glob::new(e) {
-                globset.add(glob);
-            }
-        }
+        stats
     }
 
-    for file in files {
-        let mut file = File::open(file).unwrap();
-        let mut content = String::new();
-        file.read_to_string(&mut content).unwrap();
-        for line in content.split('\n').filter(|s| !s.is_empty()) {
-            let mut glob = String::new();
-            if !line.starts_with('/') {
-                glob.push_str("**/");
-            }
-            glob.push_str(line);
-            if line.ends_with('/') {
-                glob.push_str("**");
-            }
-            if let Ok(glob) = Glob::new(&glob) {
-                globset.add(glob);
-            }
+    fn reduce(&self, mut acc: Self::Output, output: Self::Output) -> Self::Output {
+        for (k, counter) in output {
+            *acc.entry(k).or_insert(0) += counter;
         }
-    }
-
-    if let Ok(globset) = globset.build() {
-        globset
-    } else {
-        GlobSet::empty()
+        acc
     }
 }
 
@@ -350,82 +180,103 @@ fn main() {
                 .default_value("")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("cache_size")
+                .help(
+                    "Maximum size in MB of the shared cache of the file contents (0 to disable it): \
+                     the files aren't read again but they're lexed for each inclusion",
+                )
+                .long("cache-size")
+                .value_name("MB")
+                .default_value("1024")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("map_files")
+                .help(
+                    "Map the big files in the file cache (they mustn't be modified while running)",
+                )
+                .long("map-files"),
+        )
+        .arg(
+            Arg::with_name("cache_dir")
+                .help("Directory where the caches are stored between runs")
+                .long("cache-dir")
+                .value_name("DIR")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("cache_stats")
+                .help("Print the file cache statistics on stderr")
+                .long("cache-stats"),
+        )
         .get_matches();
 
     let database = matches.value_of("database").unwrap().to_string();
-    let num_jobs = if let Ok(num_jobs) = matches.value_of("num_jobs").unwrap().parse::<usize>() {
-        num_jobs
-    } else {
-        num_cpus::get()
+    let num_jobs = matches
+        .value_of("num_jobs")
+        .unwrap()
+        .parse::<usize>()
+        .unwrap_or(0);
+
+    let exclude = matches.values_of("exclude").unwrap_or_default();
+    let exclude_pat = matches.values_of("exclude_pattern").unwrap_or_default();
+    let exclude = match driver::get_globset(exclude_pat, exclude) {
+        Ok(exclude) => exclude,
+        Err(e) => {
+            eprintln!("Cannot read the exclusion file: {}", e);
+            process::exit(1);
+        }
     };
 
-    let exclude = matches.values_of("exclude").unwrap();
-    let exclude_pat = matches.values_of("exclude_pattern").unwrap();
-    let exclude = mk_globset(exclude_pat, exclude);
-
     let output = matches.value_of("output").unwrap().to_string();
 
-    let all_stats = Arc::new(Mutex::new(HashMap::default()));
-    let source = source::get_source_mutex();
-    let if_cache = Arc::new(IfCache::default());
-
-    let (sender, receiver) = unbounded();
-
-    let mut receivers = Vec::with_capacity(num_jobs);
-    for i in 0..num_jobs {
-        let receiver = receiver.clone();
-
-        let t = thread::Builder::new()
-            .name(format!("Consumer {}", i))
-            .spawn(|| {
-                consumer(receiver);
-            })
-            .unwrap();
-
-        receivers.push(t);
-    }
-
-    let mut sent: HashSet<PathBuf> = HashSet::default();
-    let sys_paths = defaults::get_sys_paths();
-    for mut cmd in CompilationDB::from_json(&database) {
-        let file = if cmd.opt.file.is_absolute() {
-            cmd.opt.file.clone()
+    let cache_size =
+        if let Ok(cache_size) = matches.value_of("cache_size").unwrap().parse::<usize>() {
+            cache_size
         } else {
-            cmd.opt.current_dir.join(&cmd.opt.file)
+            eprintln!("Invalid cache size");
+            process::exit(1);
         };
-        if file.exists() && !sent.contains(&file) {
-            cmd.file = file.clone();
-            sent.insert(file.clone());
-
-            cmd.opt.sys_paths.extend_from_slice(&sys_paths);
-            let mut def = defaults::get_defined();
-            def.extend_from_slice(&cmd.opt.def);
-            cmd.opt.def = def;
-
-            sender
-                .send(Some(JobItem {
-                    cmd,
-                    if_cache: Arc::clone(&if_cache),
-                    source: Arc::clone(&source),
-                    stats: Arc::clone(&all_stats),
-                }))
-                .unwrap();
+    let cache_stats = matches.is_present("cache_stats");
+    let map_files = matches.is_present("map_files");
+    let cache_dir = matches.value_of("cache_dir").map(PathBuf::from);
+
+    let database = match CompilationDB::from_json(&database) {
+        Ok(database) => database,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
         }
-    }
+    };
+    let opt = DriverOptions {
+        num_jobs,
+        cache_size: cache_size.saturating_mul(1024 * 1024),
+        map_files,
+        cache_dir,
+        ..Default::default()
+    };
+    let res = driver::run(database, &MacroStats, &opt);
 
-    // Poison the receiver, now that the producer is finished.
-    for _ in 0..num_jobs {
-        sender.send(None).unwrap();
+    for e in res.errors.iter() {
+        eprintln!("Error: {}", e);
     }
 
-    for receiver in receivers {
-        if let Err(e) = receiver.join() {
-            eprintln!("Error: {:?}", e);
-            process::exit(1);
+    if cache_stats {
+        if let Some(stats) = res.file_cache_stats.as_ref() {
+            eprintln!(
+                "File cache: {} hits, {} misses, {} evictions, {} bytes",
+                stats.hits, stats.misses, stats.evictions, stats.bytes
+            );
         }
     }
 
-    let all_stats = Arc::try_unwrap(all_stats).unwrap().into_inner().unwrap();
+    if let Some(e) = res.cache_error.as_ref() {
+        eprintln!("Cannot save the caches: {}", e);
+    }
+
+    let all_stats = res.output.unwrap_or_default();
+    let source = res.source;
     let mut set = BTreeSet::default();
     let mut total = 0;
 
@@ -4,16 +4,18 @@
 // copied, modified, or distributed except according to those terms.
 
 use serde::Deserialize;
-use std::fs::File;
-use std::io::Read;
-use std::path::PathBuf;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::lexer::charset::Charset;
 
 macro_rules! skip_whites {
     ( $lexer: expr) => {{
         loop {
             if $lexer.has_char() {
                 let c = $lexer.next_char();
-                if c != b' ' && c != b'\t' {
+                if !Args::is_white(c) {
                     break;
                 }
                 $lexer.pos += 1;
@@ -42,7 +44,103 @@ impl Default for Language {
     }
 }
 
-#[derive(Debug, Default)]
+/// The syntax of the command line: gcc/clang or cl.exe/clang-cl
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Driver {
+    #[default]
+    GCC,
+    MSVC,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Standard {
+    C89,
+    C94,
+    C99,
+    C11,
+    C17,
+    C2x,
+    Cpp98,
+    Cpp11,
+    Cpp14,
+    Cpp17,
+    Cpp20,
+    Cpp2b,
+}
+
+impl Standard {
+    pub fn default_for(lang: &Language) -> Self {
+        match lang {
+            Language::C => Standard::C17,
+            Language::CPP => Standard::Cpp17,
+        }
+    }
+
+    /// Parse the value of -std= (gnu variants are treated as the iso ones)
+    pub fn from_name(name: &str) -> Option<Self> {
+        use self::Standard::*;
+        let name = name
+            .strip_prefix("gnu")
+            .or_else(|| name.strip_prefix('c'))
+            .unwrap_or(name);
+
+        Some(match name {
+            "89" | "90" | "iso9899:1990" => C89,
+            "iso9899:199409" => C94,
+            "99" | "9x" | "iso9899:1999" | "iso9899:199x" => C99,
+            "11" | "1x" | "iso9899:2011" => C11,
+            "17" | "18" | "iso9899:2017" | "iso9899:2018" => C17,
+            "2x" | "23" => C2x,
+            "++98" | "++03" => Cpp98,
+            "++11" | "++0x" => Cpp11,
+            "++14" | "++1y" => Cpp14,
+            "++17" | "++1z" => Cpp17,
+            "++20" | "++2a" => Cpp20,
+            "++2b" | "++23" => Cpp2b,
+            _ => return None,
+        })
+    }
+
+    pub fn is_cpp(self) -> bool {
+        self >= Standard::Cpp98
+    }
+
+    /// Parse the value of /std:
+    pub fn from_msvc_name(name: &str) -> Option<Self> {
+        use self::Standard::*;
+        Some(match name {
+            "c11" => C11,
+            "c17" => C17,
+            "clatest" => C2x,
+            "c++14" => Cpp14,
+            "c++17" => Cpp17,
+            "c++20" => Cpp20,
+            "c++latest" => Cpp2b,
+            _ => return None,
+        })
+    }
+
+    /// Get the value of __cplusplus or __STDC_VERSION__
+    pub fn get_version(self) -> Option<&'static str> {
+        use self::Standard::*;
+        match self {
+            C89 => None,
+            C94 => Some("199409L"),
+            C99 => Some("199901L"),
+            C11 => Some("201112L"),
+            C17 => Some("201710L"),
+            C2x => Some("202311L"),
+            Cpp98 => Some("199711L"),
+            Cpp11 => Some("201103L"),
+            Cpp14 => Some("201402L"),
+            Cpp17 => Some("201703L"),
+            Cpp20 => Some("202002L"),
+            Cpp2b => Some("202302L"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
 pub struct PreprocOptions {
     pub def: Vec<Macro>,
     pub sys_paths: Vec<String>,
@@ -50,13 +148,78 @@ pub struct PreprocOptions {
     pub current_dir: PathBuf,
     pub file: PathBuf,
     pub lang: Language,
+    pub std: Option<Standard>,
+    pub driver: Driver,
+    pub charset: Charset,
+    /// The file is a CUDA one (.cu or -x cuda)
+    pub cuda: bool,
+}
+
+impl PreprocOptions {
+    /// Get the standard to use: the one from -std= if it's compatible with the language
+    pub fn get_standard(&self) -> Standard {
+        match self.std {
+            Some(std) if std.is_cpp() == (self.lang == Language::CPP) => std,
+            _ if self.driver == Driver::MSVC && self.lang == Language::CPP => Standard::Cpp14,
+            _ => Standard::default_for(&self.lang),
+        }
+    }
+}
+
+/// Maximal depth of nested response files
+const MAX_RESPONSE_DEPTH: usize = 16;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ArgsError {
+    Io { path: PathBuf, msg: String },
+    InvalidDatabase { path: PathBuf, msg: String },
+    InvalidEntry { index: usize, msg: String },
+    MissingCommand { file: PathBuf },
+    MissingArgument { file: PathBuf, option: String },
+    UnknownCharset { file: PathBuf, name: String },
+    NonUtf8Path { path: PathBuf },
+}
+
+impl fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::ArgsError::*;
+        match self {
+            Io { path, msg } => write!(f, "can't read {}: {}", path.display(), msg),
+            InvalidDatabase { path, msg } => {
+                write!(
+                    f,
+                    "invalid compilation database {}: {}",
+                    path.display(),
+                    msg
+                )
+            }
+            InvalidEntry { index, msg } => write!(f, "invalid entry {}: {}", index, msg),
+            MissingCommand { file } => write!(
+                f,
+                "no command or arguments for file {} in the database",
+                file.display()
+            ),
+            MissingArgument { file, option } => write!(
+                f,
+                "missing argument for option {} in the command for {}",
+                option,
+                file.display()
+            ),
+            UnknownCharset { file, name } => write!(
+                f,
+                "unknown charset {} in the command for {}",
+                name,
+                file.display()
+            ),
+            NonUtf8Path { path } => write!(f, "the path {} isn't valid UTF-8", path.display()),
+        }
+    }
 }
 
 struct Args<'a> {
     buf: &'a [u8],
     pos: usize,
     len: usize,
-    opt: PreprocOptions,
 }
 
 impl<'a> Args<'a> {
@@ -65,7 +228,6 @@ impl<'a> Args<'a> {
             buf,
             pos: 0,
             len: buf.len(),
-            opt: PreprocOptions::default(),
         }
     }
 
@@ -73,10 +235,6 @@ impl<'a> Args<'a> {
         self.pos += 1;
     }
 
-    fn dec(&mut self) {
-        self.pos -= 1;
-    }
-
     fn has_char(&self) -> bool {
         self.pos < self.len
     }
@@ -85,103 +243,132 @@ impl<'a> Args<'a> {
         unsafe { *self.buf.get_unchecked(self.pos) }
     }
 
-    fn prev_char(&self) -> u8 {
-        unsafe { *self.buf.get_unchecked(self.pos - 1) }
+    fn is_white(c: u8) -> bool {
+        c == b' ' || c == b'\t' || c == b'\n' || c == b'\r'
     }
 
-    fn slice(&self, start: usize, end: usize) -> String {
-        unsafe { String::from_utf8_unchecked(self.buf.get_unchecked(start..end).to_vec()) }
-    }
-
-    fn slice_to_end(&self, start: usize) -> String {
-        unsafe { String::from_utf8_unchecked(self.buf.get_unchecked(start..).to_vec()) }
-    }
-
-    fn get_app_name(&mut self) -> Option<String> {
-        loop {
-            if self.has_char() {
-                if self.next_char() == b' ' {
-                    let name = self.slice(0, self.pos);
-                    self.inc();
-                    return Some(name);
+    fn get_bash_string(&mut self, buf: &mut Vec<u8>) {
+        // In double quotes, a backslash only escapes $, `, ", \ and newline
+        while self.has_char() {
+            let c = self.next_char();
+            self.inc();
+            match c {
+                b'\"' => {
+                    return;
+                }
+                b'\\' => {
+                    if self.has_char() {
+                        let c = self.next_char();
+                        self.inc();
+                        match c {
+                            b'$' | b'`' | b'\"' | b'\\' => buf.push(c),
+                            b'\n' => {}
+                            _ => {
+                                buf.push(b'\\');
+                                buf.push(c);
+                            }
+                        }
+                    } else {
+                        buf.push(b'\\');
+                    }
+                }
+                _ => {
+                    buf.push(c);
                 }
-                self.inc();
-            } else {
-                return None;
             }
         }
     }
 
-    fn get_hex_digit(c: u8) -> u8 {
-        match c {
-            b'0'..=b'9' => c - b'0',
-            b'a'..=b'f' => c - b'a' + 10,
-            b'A'..=b'F' => c - b'A' + 10,
-            _ => 16,
+    fn get_bash_single_string(&mut self, buf: &mut Vec<u8>) {
+        while self.has_char() {
+            let c = self.next_char();
+            self.inc();
+            if c == b'\'' {
+                return;
+            }
+            buf.push(c);
         }
     }
 
-    fn get_bash_string(&mut self, buf: &mut String) {
-        loop {
-            if self.has_char() {
-                let c = self.next_char();
-                self.inc();
-                match c {
-                    b'\"' => {
-                        break;
-                    }
-                    b'\\' => {
+    fn get_element(&mut self) -> String {
+        // consume everything until an unquoted white or eof
+        let mut buf = Vec::new();
+        while self.has_char() {
+            let c = self.next_char();
+            self.inc();
+            match c {
+                b'\"' => {
+                    self.get_bash_string(&mut buf);
+                }
+                b'\'' => {
+                    self.get_bash_single_string(&mut buf);
+                }
+                b'\\' => {
+                    if self.has_char() {
                         let c = self.next_char();
                         self.inc();
-                        match c {
-                            b'a' => buf.push('\x07'),
-                            b'b' => buf.push('\x08'),
-                            b'e' => buf.push('\x1b'),
-                            b'f' => buf.push('\x0c'),
-                            b'n' => buf.push('\n'),
-                            b'r' => buf.push('\r'),
-                            b't' => buf.push('\t'),
-                            b'v' => buf.push('\x0b'),
-                            b'\\' => buf.push('\\'),
-                            b'\'' => buf.push('\''),
-                            b'\"' => buf.push('\"'),
-                            b'0'..=b'7' => {
-                                let mut n = (c - b'0') as u8;
-                                for _ in 0..1 {
-                                    self.inc();
-                                    let c = self.next_char();
-                                    if b'0' <= c && c <= b'7' {
-                                        n = 8 * n + ((c - b'0') as u8);
-                                    } else {
-                                        break;
-                                    }
-                                }
-                                buf.push(n as char);
-                            }
-                            b'x' => {
-                                self.inc();
-                                let c = self.next_char();
-                                let n = Self::get_hex_digit(c);
-                                self.inc();
-                                let c = self.next_char();
-                                let m = Self::get_hex_digit(c);
-                                if m < 16 {
-                                    buf.push((16 * n + m) as char);
-                                } else {
-                                    buf.push(n as char);
-                                }
-                            }
-                            _ => {
-                                unreachable!();
-                            }
+                        if c != b'\n' {
+                            buf.push(c);
                         }
+                    } else {
+                        buf.push(b'\\');
                     }
-                    _ => {
-                        buf.push(c as char);
-                    }
+                }
+                c if Self::is_white(c) => {
+                    break;
+                }
+                _ => {
+                    buf.push(c);
+                }
+            }
+        }
+        String::from_utf8_lossy(&buf).into_owned()
+    }
+
+    fn get_parts(&mut self) -> Vec<String> {
+        let mut parts = Vec::new();
+        loop {
+            skip_whites!(self);
+            if !self.has_char() {
+                break;
+            }
+            if self.next_char() == b'\\'
+                && self.pos + 1 < self.len
+                && self.buf[self.pos + 1] == b'\n'
+            {
+                // line continuation
+                self.pos += 2;
+                continue;
+            }
+            parts.push(self.get_element());
+        }
+        parts
+    }
+
+    /// Split a command line following the POSIX shell rules
+    fn split(cl: &[u8]) -> Vec<String> {
+        Args::new(cl).get_parts()
+    }
+
+    /// Replace the @file arguments by the content of the file.
+    /// As gcc does, the argument is kept when the file can't be read.
+    fn expand_response_files(parts: Vec<String>, dir: &Path, depth: usize) -> Vec<String> {
+        if depth >= MAX_RESPONSE_DEPTH || !parts.iter().any(|p| p.starts_with('@')) {
+            return parts;
+        }
+
+        let mut res = Vec::with_capacity(parts.len());
+        for part in parts {
+            if part.len() > 1 && part.starts_with('@') {
+                if let Ok(data) = fs::read(dir.join(&part[1..])) {
+                    let inner = Self::split(&data);
+                    res.extend(Self::expand_response_files(inner, dir, depth + 1));
+                    continue;
                 }
             }
+            res.push(part);
         }
+        res
     }
 
     fn get_language(s: &str) -> Language {
@@ -192,91 +379,194 @@ impl<'a> Args<'a> {
         }
     }
 
-    fn get_bash_single_string(&mut self, buf: &mut String) {
-        loop {
-            if self.has_char() {
-                let c = self.next_char();
-                self.inc();
-                match c {
-                    b'\'' => {
-                        break;
-                    }
-                    _ => {
-                        buf.push(c as char);
-                    }
-                }
-            }
+    fn get_language_from_file(file: &Path) -> Option<Language> {
+        match file.extension().and_then(|e| e.to_str()) {
+            Some("cpp") | Some("cc") | Some("cp") | Some("cxx") | Some("c++") | Some("C")
+            | Some("CPP") | Some("hpp") | Some("hh") | Some("hxx") | Some("h++") | Some("H")
+            | Some("ii") | Some("mm") | Some("M") | Some("ixx") | Some("cppm") | Some("cu")
+            | Some("cuh") => Some(Language::CPP),
+            Some("c") | Some("h") | Some("i") | Some("m") => Some(Language::C),
+            _ => None,
         }
     }
 
-    fn get_element(&mut self, buf: &mut String) {
-        // consume everything until space or eof
-        loop {
-            if self.has_char() {
-                let c = self.next_char();
-                self.inc();
-                match c {
-                    b' ' => {
-                        break;
-                    }
-                    b'\"' => {
-                        self.get_bash_string(buf);
-                    }
-                    b'\'' => {
-                        self.get_bash_single_string(buf);
-                    }
-                    _ => {
-                        buf.push(c as char);
-                    }
-                }
-            } else {
-                break;
+    fn get_language_from_x(x: &str) -> Option<Language> {
+        match x {
+            "c" | "c-header" | "cpp-output" | "objective-c" | "objective-c-header" => {
+                Some(Language::C)
             }
+            "c++"
+            | "c++-header"
+            | "c++-cpp-output"
+            | "objective-c++"
+            | "objective-c++-header"
+            | "cuda" => Some(Language::CPP),
+            _ => None,
         }
     }
 
-    fn get_parts(&mut self) -> Vec<String> {
-        let mut parts = Vec::new();
-        loop {
-            if self.has_char() {
-                let mut buf = String::new();
-                let c = self.next_char();
-                self.inc();
-                match c {
-                    b'\'' => {
-                        self.get_bash_single_string(&mut buf);
-                    }
-                    b'\"' => {
-                        self.get_bash_string(&mut buf);
-                    }
-                    b' ' => {
-                        skip_whites!(self);
-                        continue;
+    fn get_charset(name: &str, file: &Path) -> Result<Charset, ArgsError> {
+        Charset::from_name(name).ok_or_else(|| ArgsError::UnknownCharset {
+            file: file.to_path_buf(),
+            name: name.to_string(),
+        })
+    }
+
+    fn get_arg<'b>(
+        parts: &'b [String],
+        i: &mut usize,
+        n: usize,
+        file: &Path,
+    ) -> Result<&'b str, ArgsError> {
+        let part = &parts[*i];
+        if part.len() > n {
+            Ok(&part[n..])
+        } else {
+            *i += 1;
+            parts
+                .get(*i)
+                .map(|s| s.as_str())
+                .ok_or_else(|| ArgsError::MissingArgument {
+                    file: file.to_path_buf(),
+                    option: part.clone(),
+                })
+        }
+    }
+
+    fn get_define(defined: &str, seps: &[char]) -> Macro {
+        let toks: Vec<_> = defined.splitn(2, seps).collect();
+        if toks.len() == 1 {
+            Macro::Defined((toks[0].to_string(), "1".to_string()))
+        } else {
+            Macro::Defined((toks[0].to_string(), toks[1].to_string()))
+        }
+    }
+
+    /// Get the driver from the compiler name (cl.exe, clang-cl) or from --driver-mode=
+    fn get_driver(app: &str, args: &[String]) -> Driver {
+        let name = app.rsplit(['/', '\\']).next().unwrap_or(app);
+        let name = name.to_lowercase();
+        let name = name.strip_suffix(".exe").unwrap_or(&name);
+        if name == "cl" || name.starts_with("clang-cl") {
+            return Driver::MSVC;
+        }
+        match args
+            .iter()
+            .rev()
+            .find_map(|a| a.strip_prefix("--driver-mode="))
+        {
+            Some("cl") => Driver::MSVC,
+            _ => Driver::GCC,
+        }
+    }
+
+    /// Get the options from cl.exe or clang-cl arguments without the compiler name.
+    /// Options can start with a slash or a dash, and the language is got from /TC, /TP
+    /// or the file extension (only .c files are C ones).
+    fn parse_msvc(parts: &[String], opt: &mut PreprocOptions) -> Result<(), ArgsError> {
+        let file = opt.file.clone();
+        let mut t_lang = None;
+
+        let mut i = 0;
+        while i < parts.len() {
+            let part = &parts[i];
+            if !(part.starts_with('-') || part.starts_with('/')) || part.len() < 2 {
+                i += 1;
+                continue;
+            }
+
+            let opt_name = &part[1..];
+            match opt_name.as_bytes()[0] {
+                b'D' => {
+                    let defined = Self::get_arg(parts, &mut i, 2, &file)?;
+                    opt.def.push(Self::get_define(defined, &['=', '#']));
+                }
+                b'U' => {
+                    let undef = Self::get_arg(parts, &mut i, 2, &file)?;
+                    opt.def.push(Macro::Undef(undef.to_string()));
+                }
+                b'I' => {
+                    let path = Self::get_arg(parts, &mut i, 2, &file)?;
+                    opt.sys_paths.push(path.to_string());
+                }
+                b'F' if opt_name.starts_with("FI") => {
+                    let path = Self::get_arg(parts, &mut i, 3, &file)?;
+                    opt.includes.push(path.to_string());
+                }
+                b'T' => match opt_name {
+                    "TC" => t_lang = Some(Language::C),
+                    "TP" => t_lang = Some(Language::CPP),
+                    _ if opt_name.starts_with("Tc") || opt_name.starts_with("Tp") => {
+                        t_lang = Some(if opt_name.starts_with("Tc") {
+                            Language::C
+                        } else {
+                            Language::CPP
+                        });
+                        let path = Self::get_arg(parts, &mut i, 3, &file)?;
+                        opt.file = PathBuf::from(path);
                     }
-                    _ => {
-                        buf.push(c as char);
-                        self.get_element(&mut buf);
+                    _ => {}
+                },
+                b's' => {
+                    if let Some(name) = opt_name.strip_prefix("std:") {
+                        opt.std = Standard::from_msvc_name(name);
+                    } else if let Some(name) = opt_name.strip_prefix("source-charset:") {
+                        opt.charset = Self::get_charset(name.trim_start_matches('.'), &file)?;
                     }
                 }
-                parts.push(buf);
-            } else {
-                break;
+                b'u' if opt_name == "utf-8" => {
+                    opt.charset = Charset::Utf8;
+                }
+                b'i' if opt_name.starts_with("imsvc") => {
+                    let path = Self::get_arg(parts, &mut i, "-imsvc".len(), &file)?;
+                    opt.sys_paths.push(path.to_string());
+                }
+                b'e' if opt_name.starts_with("external:I") => {
+                    let path = Self::get_arg(parts, &mut i, "/external:I".len(), &file)?;
+                    opt.sys_paths.push(path.to_string());
+                }
+                b'o' if opt_name == "o" => {
+                    // skip the output
+                    i += 1;
+                }
+                b'l' if opt_name == "link" => {
+                    // the remaining arguments are for the linker
+                    break;
+                }
+                _ => {}
             }
+
+            i += 1;
         }
-        parts
+
+        opt.driver = Driver::MSVC;
+        opt.lang = t_lang.unwrap_or_else(|| {
+            if opt.file.extension().and_then(|e| e.to_str()) == Some("c") {
+                Language::C
+            } else {
+                Language::CPP
+            }
+        });
+
+        Ok(())
     }
 
-    fn parse(&mut self) {
-        let parts = self.get_parts();
-        self.opt.lang = Self::get_language(&parts[0]);
-        let file = parts.last().unwrap();
-        let path = PathBuf::from(file);
-        self.opt.file = path;
+    /// Get the options from the arguments without the compiler name.
+    /// The language is got from -x, the driver (e.g. g++) or the file extension.
+    fn parse(
+        parts: &[String],
+        opt: &mut PreprocOptions,
+        driver: Language,
+    ) -> Result<(), ArgsError> {
+        let file = opt.file.clone();
+        let mut x_lang = None;
+        let mut x_cuda = false;
+        let mut ansi = false;
 
-        let mut i = 1;
+        let mut i = 0;
         while i < parts.len() {
             let part = &parts[i];
-            if !part.starts_with('-') {
+            if !part.starts_with('-') || part.len() < 2 {
                 i += 1;
                 continue;
             }
@@ -285,106 +575,290 @@ impl<'a> Args<'a> {
 
             match bytes[1] {
                 b'D' => {
-                    let defined = if part.len() > 2 {
-                        &part[2..]
-                    } else {
-                        i += 1;
-                        &parts[i]
-                    };
-                    let toks: Vec<_> = defined.splitn(2, '=').collect();
-                    if toks.len() == 1 {
-                        self.opt
-                            .def
-                            .push(Macro::Defined((toks[0].to_string(), "1".to_string())));
-                    } else {
-                        self.opt
-                            .def
-                            .push(Macro::Defined((toks[0].to_string(), toks[1].to_string())));
-                    }
+                    let defined = Self::get_arg(parts, &mut i, 2, &file)?;
+                    opt.def.push(Self::get_define(defined, &['=']));
                 }
                 b'I' => {
-                    let path = if part.len() > 2 {
-                        &part[2..]
-                    } else {
-                        i += 1;
-                        &parts[i]
-                    };
-                    self.opt.sys_paths.push(path.to_string());
+                    let path = Self::get_arg(parts, &mut i, 2, &file)?;
+                    opt.sys_paths.push(path.to_string());
                 }
                 b'i' => {
                     if part.starts_with("-include") {
-                        let n = "-include".len();
-                        let path = if part.len() > n {
-                            &part[n..]
-                        } else {
-                            i += 1;
-                            &parts[i]
-                        };
-                        self.opt.includes.push(path.to_string());
+                        let path = Self::get_arg(parts, &mut i, "-include".len(), &file)?;
+                        opt.includes.push(path.to_string());
                     }
                 }
                 b'U' => {
-                    let undef = if part.len() > 2 {
-                        &part[2..]
-                    } else {
-                        i += 1;
-                        &parts[i]
-                    };
-                    self.opt.def.push(Macro::Undef(undef.to_string()));
+                    let undef = Self::get_arg(parts, &mut i, 2, &file)?;
+                    opt.def.push(Macro::Undef(undef.to_string()));
+                }
+                b'x' => {
+                    let x = Self::get_arg(parts, &mut i, 2, &file)?;
+                    x_lang = Self::get_language_from_x(x);
+                    x_cuda = x == "cuda";
+                }
+                b's' => {
+                    if let Some(name) = part.strip_prefix("-std=") {
+                        opt.std = Standard::from_name(name);
+                    }
+                }
+                b'f' => {
+                    if let Some(name) = part.strip_prefix("-finput-charset=") {
+                        opt.charset = Self::get_charset(name, &file)?;
+                    }
+                }
+                b'a' if part == "-ansi" => {
+                    opt.std = None;
+                    ansi = true;
+                }
+                b'o' if part == "-o" => {
+                    // skip the output
+                    i += 1;
                 }
                 _ => {}
             }
 
             i += 1;
         }
+
+        opt.cuda = if x_lang.is_some() {
+            x_cuda
+        } else {
+            matches!(
+                file.extension().and_then(|e| e.to_str()),
+                Some("cu") | Some("cuh")
+            )
+        };
+        opt.lang = x_lang.unwrap_or_else(|| {
+            if driver == Language::CPP {
+                driver
+            } else {
+                Self::get_language_from_file(&file).unwrap_or(driver)
+            }
+        });
+
+        if ansi && opt.std.is_none() {
+            opt.std = Some(if opt.lang == Language::CPP {
+                Standard::Cpp98
+            } else {
+                Standard::C89
+            });
+        }
+
+        Ok(())
+    }
+
+    fn get_options_from_parts(parts: &[String]) -> Result<PreprocOptions, ArgsError> {
+        let mut opt = PreprocOptions::default();
+        if let Some(last) = parts.last().filter(|_| parts.len() > 1) {
+            if !last.starts_with('-') {
+                opt.file = PathBuf::from(last);
+            }
+        }
+        Self::parse_command(parts, &mut opt)?;
+        Ok(opt)
     }
 
-    pub fn get_options(cl: &[u8]) -> PreprocOptions {
-        let mut args = Args::new(cl);
-        args.parse();
+    /// Get the options from the compiler name and its arguments with the parser
+    /// for the driver (GCC or MSVC like)
+    fn parse_command(parts: &[String], opt: &mut PreprocOptions) -> Result<(), ArgsError> {
+        if let Some((app, args)) = parts.split_first() {
+            match Self::get_driver(app, args) {
+                Driver::MSVC => Self::parse_msvc(args, opt)?,
+                Driver::GCC => Self::parse(args, opt, Self::get_language(app))?,
+            }
+        }
+        Ok(())
+    }
 
-        args.opt
+    pub fn get_options(cl: &[u8]) -> Result<PreprocOptions, ArgsError> {
+        Self::get_options_from_parts(&Self::split(cl))
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 struct JsonCommand {
     directory: PathBuf,
-    command: String,
     file: PathBuf,
+    command: Option<String>,
+    arguments: Option<Vec<String>>,
+    output: Option<PathBuf>,
+}
+
+impl JsonCommand {
+    fn get_command(self, db_dir: &Path) -> Result<Command, ArgsError> {
+        let directory = if self.directory.is_relative() {
+            db_dir.join(&self.directory)
+        } else {
+            self.directory
+        };
+        let file = if self.file.is_relative() {
+            directory.join(&self.file)
+        } else {
+            self.file
+        };
+
+        let parts = if let Some(arguments) = self.arguments {
+            arguments
+        } else if let Some(command) = self.command {
+            Args::split(command.as_bytes())
+        } else {
+            return Err(ArgsError::MissingCommand { file });
+        };
+        let parts = Args::expand_response_files(parts, &directory, 0);
+
+        let mut opt = PreprocOptions {
+            file: file.clone(),
+            ..Default::default()
+        };
+        Args::parse_command(&parts, &mut opt)?;
+        opt.current_dir = directory;
+
+        Ok(Command {
+            opt,
+            file,
+            output: self.output,
+        })
+    }
 }
 
 #[derive(Debug)]
 pub struct Command {
     pub opt: PreprocOptions,
     pub file: PathBuf,
+    pub output: Option<PathBuf>,
 }
 
 pub struct CompilationDB {
-    vec: Vec<JsonCommand>,
+    dir: PathBuf,
+    vec: Vec<Result<JsonCommand, ArgsError>>,
 }
 
 impl Iterator for CompilationDB {
-    type Item = Command;
+    type Item = Result<Command, ArgsError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(jc) = self.vec.pop() {
-            let mut opt = Args::get_options(jc.command.as_bytes());
-            opt.current_dir = jc.directory;
-            Some(Command { opt, file: jc.file })
-        } else {
-            None
-        }
+        let dir = &self.dir;
+        self.vec
+            .pop()
+            .map(|jc| jc.and_then(|jc| jc.get_command(dir)))
     }
 }
 
 impl CompilationDB {
-    pub fn from_json(path: &str) -> CompilationDB {
-        let mut file = File::open(&path).unwrap();
-        let mut data = Vec::new();
-        file.read_to_end(&mut data).unwrap();
-        let vec: Vec<JsonCommand> = serde_json::de::from_slice(&data).unwrap();
-        CompilationDB { vec }
+    pub fn from_json(path: &str) -> Result<CompilationDB, ArgsError> {
+        let data = fs::read(path).map_err(|e| ArgsError::Io {
+            path: PathBuf::from(path),
+            msg: e.to_string(),
+        })?;
+        let dir = Path::new(path)
+            .parent()
+            .map_or_else(PathBuf::new, |p| p.to_path_buf());
+
+        Self::from_slice(&data, dir).map_err(|msg| ArgsError::InvalidDatabase {
+            path: PathBuf::from(path),
+            msg,
+        })
+    }
+
+    /// Relative directories in the entries are resolved from dir
+    pub fn from_slice(data: &[u8], dir: PathBuf) -> Result<CompilationDB, String> {
+        let entries: Vec<serde_json::Value> =
+            serde_json::de::from_slice(data).map_err(|e| e.to_string())?;
+        let vec = entries
+            .into_iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                serde_json::from_value(entry).map_err(|e| ArgsError::InvalidEntry {
+                    index,
+                    msg: e.to_string(),
+                })
+            })
+            .collect();
+
+        Ok(CompilationDB { dir, vec })
+    }
+
+    /// Get the command for the given file
+    pub fn get_command(&self, file: &Path) -> Option<Result<Command, ArgsError>> {
+        self.vec.iter().rev().find_map(|jc| match jc {
+            Ok(jc) => {
+                let jc = jc.clone().get_command(&self.dir);
+                match jc {
+                    Ok(ref cmd) if cmd.file != file => None,
+                    Err(ArgsError::MissingCommand { file: ref f })
+                    | Err(ArgsError::MissingArgument { file: ref f, .. })
+                    | Err(ArgsError::UnknownCharset { file: ref f, .. })
+                        if f != file =>
+                    {
+                        None
+                    }
+                    _ => Some(jc),
+                }
+            }
+            Err(_) => None,
+        })
+    }
+}
+
+/// The flags in a compile_flags.txt file (one argument per line)
+/// to use for the files which aren't in a compilation database.
+#[derive(Clone, Debug)]
+pub struct CompileFlags {
+    dir: PathBuf,
+    parts: Vec<String>,
+}
+
+impl CompileFlags {
+    pub fn from_file(path: &Path) -> Result<CompileFlags, ArgsError> {
+        let data = fs::read_to_string(path).map_err(|e| ArgsError::Io {
+            path: path.to_path_buf(),
+            msg: e.to_string(),
+        })?;
+        let dir = path.parent().map_or_else(PathBuf::new, |p| p.to_path_buf());
+
+        Ok(Self::from_str(&data, dir))
+    }
+
+    pub fn from_str(data: &str, dir: PathBuf) -> CompileFlags {
+        let parts = data
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty())
+            .map(|l| l.to_string())
+            .collect();
+        CompileFlags { dir, parts }
+    }
+
+    /// Find a compile_flags.txt in the parent directories of file
+    pub fn find(file: &Path) -> Option<CompileFlags> {
+        file.ancestors()
+            .skip(1)
+            .map(|dir| dir.join("compile_flags.txt"))
+            .find(|path| path.is_file())
+            .and_then(|path| Self::from_file(&path).ok())
+    }
+
+    pub fn get_command(&self, file: &Path) -> Result<Command, ArgsError> {
+        let file = if file.is_relative() {
+            self.dir.join(file)
+        } else {
+            file.to_path_buf()
+        };
+        let parts = Args::expand_response_files(self.parts.clone(), &self.dir, 0);
+
+        let mut opt = PreprocOptions {
+            file: file.clone(),
+            ..Default::default()
+        };
+        Args::parse(&parts, &mut opt, Language::C)?;
+        opt.current_dir = self.dir.clone();
+
+        Ok(Command {
+            opt,
+            file,
+            output: None,
+        })
     }
 }
 
@@ -392,16 +866,18 @@ impl CompilationDB {
 mod tests {
 
     use super::*;
+    use pretty_assertions::assert_eq;
+    use tempdir::TempDir;
 
     #[test]
     fn test_args_i_basic() {
         let cl = b"/usr/bin/clang-9 -std=gnu99 -o /dev/null -c -IA -IB -fPIC -IC";
-        let opt = Args::get_options(cl);
+        let opt = Args::get_options(cl).unwrap();
 
         assert_eq!(opt.sys_paths, vec!["A", "B", "C"]);
 
         let cl = b"/usr/bin/clang-9 -std=gnu99 -o /dev/null -c -IA -I\"B C D\" -fPIC -IC";
-        let opt = Args::get_options(cl);
+        let opt = Args::get_options(cl).unwrap();
 
         assert_eq!(opt.sys_paths, vec!["A", "B C D", "C"]);
     }
@@ -409,7 +885,7 @@ mod tests {
     #[test]
     fn test_args_i_spaces() {
         let cl = b"/usr/bin/clang-9 -std=gnu99 -o /dev/null -c -IA -I\"B C D\" -fPIC -IC";
-        let opt = Args::get_options(cl);
+        let opt = Args::get_options(cl).unwrap();
 
         assert_eq!(opt.sys_paths, vec!["A", "B C D", "C"]);
     }
@@ -417,7 +893,7 @@ mod tests {
     #[test]
     fn test_args_i_arg_with_dash() {
         let cl = b"/usr/bin/clang-9 -std=gnu99 -o /foo/bar-oof/rab -c -IA -I\"B\" -Qunused-arguments -fPIC -IC";
-        let opt = Args::get_options(cl);
+        let opt = Args::get_options(cl).unwrap();
 
         assert_eq!(opt.sys_paths, vec!["A", "B", "C"]);
     }
@@ -425,7 +901,7 @@ mod tests {
     #[test]
     fn test_args_ud() {
         let cl = b"/usr/bin/clang-9 -std=gnu99 -D_ABC_ -D foo=23 -UTITI -DFOO123=\"foo bar\" -D bar(a,b,c)  -U  toto -D rab(a,b)=a##b '-DR_PLATFORM_INT_TYPES=<stdint.h>'  '-D__UNUSED__=__attribute__((unused))'";
-        let opt = Args::get_options(cl);
+        let opt = Args::get_options(cl).unwrap();
 
         assert_eq!(
             opt.def,
@@ -449,7 +925,7 @@ mod tests {
     #[test]
     fn test_args_quote() {
         let cl = b"/usr/bin/clang-9 -std=gnu99 '-D_ABC_=\"abc\"'";
-        let opt = Args::get_options(cl);
+        let opt = Args::get_options(cl).unwrap();
 
         assert_eq!(
             opt.def,
@@ -460,7 +936,7 @@ mod tests {
     #[test]
     fn test_args_include() {
         let cl = b"/usr/bin/clang-9 -std=gnu99 -o /foo/bar-oof/rab -include A -include B -i blah-blah -include C";
-        let opt = Args::get_options(cl);
+        let opt = Args::get_options(cl).unwrap();
 
         assert_eq!(opt.includes, vec!["A", "B", "C"]);
     }
@@ -468,7 +944,7 @@ mod tests {
     #[test]
     fn test_args_real() {
         let cl = b"/usr/bin/clang-9 -std=gnu99 -o /dev/null -c -I/home/calixte/dev/mozilla/mozilla-central.hg/obj-x86_64-pc-linux-gnu/dist/system_wrappers -include /home/calixte/dev/mozilla/mozilla-central.hg/config/gcc_hidden.h -U_FORTIFY_SOURCE -D_FORTIFY_SOURCE=2 -fstack-protector-strong -DNDEBUG=1 -DTRIMMED=1 -DIMPL_MFBT -DLZ4LIB_VISIBILITY= -I/home/calixte/dev/mozilla/mozilla-central.hg/mfbt -I/home/calixte/dev/mozilla/mozilla-central.hg/obj-x86_64-pc-linux-gnu/mfbt -I/home/calixte/dev/mozilla/mozilla-central.hg/mfbt/double-conversion -I/home/calixte/dev/mozilla/mozilla-central.hg/obj-x86_64-pc-linux-gnu/dist/include -I/home/calixte/dev/mozilla/mozilla-central.hg/obj-x86_64-pc-linux-gnu/dist/include/nspr -I/home/calixte/dev/mozilla/mozilla-central.hg/obj-x86_64-pc-linux-gnu/dist/include/nss -fPIC -include /home/calixte/dev/mozilla/mozilla-central.hg/obj-x86_64-pc-linux-gnu/mozilla-config.h -DMOZILLA_CLIENT -Qunused-arguments -fno-strict-aliasing -fno-math-errno -pthread -fPIC -pipe -g -O2 -fno-omit-frame-pointer -funwind-tables -Qunused-arguments -Wall -Wbitfield-enum-conversion -Wempty-body -Wignored-qualifiers -Wpointer-arith -Wshadow-field-in-constructor-modified -Wsign-compare -Wtype-limits -Wunreachable-code -Wunreachable-code-return -Wclass-varargs -Wfloat-overflow-conversion -Wfloat-zero-conversion -Wloop-analysis -Wstring-conversion -Wtautological-overlap-compare -Wtautological-unsigned-enum-zero-compare -Wtautological-unsigned-zero-compare -Wno-error=tautological-type-limit-compare -Wno-error=deprecated-declarations -Wno-error=array-bounds -Wno-error=backend-plugin -Wno-error=return-std-move -Wno-error=atomic-alignment -Wformat -Wformat-security -Wno-gnu-zero-variadic-macro-arguments /home/calixte/dev/mozilla/mozilla-central.hg/mfbt/lz4/lz4.c";
-        let opt = Args::get_options(cl);
+        let opt = Args::get_options(cl).unwrap();
 
         assert_eq!(opt.includes, vec![
             "/home/calixte/dev/mozilla/mozilla-central.hg/config/gcc_hidden.h".to_string(),
@@ -498,4 +974,302 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_args_split() {
+        let cl = b"clang -DA=\"a b\"'c d' -DB=\\\"x\\\" \\\n  -DC=\"\\$\\n\" -DD='\\' \"\" -DE\\ F";
+        assert_eq!(
+            Args::split(cl),
+            vec![
+                "clang",
+                "-DA=a bc d",
+                "-DB=\"x\"",
+                "-DC=$\\n",
+                "-DD=\\",
+                "",
+                "-DE F"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_args_missing_argument() {
+        let cl = b"clang -c foo.c -I";
+        assert_eq!(
+            Args::get_options(cl).unwrap_err(),
+            ArgsError::MissingArgument {
+                file: PathBuf::new(),
+                option: "-I".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_compilation_db() {
+        let data = br#"[
+            {
+                "directory": "/build",
+                "arguments": ["clang++", "-DA=1", "-I", "inc", "-c", "-o", "foo.o", "foo.cpp"],
+                "file": "foo.cpp",
+                "output": "foo.o"
+            },
+            {
+                "directory": "sub",
+                "command": "clang -DB -c ../bar.c",
+                "file": "../bar.c"
+            },
+            {
+                "directory": "/build",
+                "file": "oof.c"
+            },
+            {
+                "file": "rab.c"
+            }
+        ]"#;
+        let db = CompilationDB::from_slice(data, PathBuf::from("/db")).unwrap();
+        let cmds: Vec<_> = db.collect();
+
+        assert_eq!(
+            cmds[0].as_ref().unwrap_err(),
+            &ArgsError::InvalidEntry {
+                index: 3,
+                msg: "missing field `directory`".to_string(),
+            }
+        );
+        assert_eq!(
+            cmds[1].as_ref().unwrap_err(),
+            &ArgsError::MissingCommand {
+                file: PathBuf::from("/build/oof.c"),
+            }
+        );
+
+        let bar = cmds[2].as_ref().unwrap();
+        assert_eq!(bar.file, PathBuf::from("/db/sub/../bar.c"));
+        assert_eq!(bar.opt.current_dir, PathBuf::from("/db/sub"));
+        assert_eq!(bar.opt.lang, Language::C);
+        assert_eq!(
+            bar.opt.def,
+            vec![Macro::Defined(("B".to_string(), "1".to_string()))]
+        );
+
+        let foo = cmds[3].as_ref().unwrap();
+        assert_eq!(foo.file, PathBuf::from("/build/foo.cpp"));
+        assert_eq!(foo.output, Some(PathBuf::from("foo.o")));
+        assert_eq!(foo.opt.lang, Language::CPP);
+        assert_eq!(foo.opt.sys_paths, vec!["inc"]);
+    }
+
+    #[test]
+    fn test_compilation_db_msvc() {
+        let data = br#"[
+            {
+                "directory": "C:/build",
+                "command": "clang-cl.exe /nologo /c /DFOO /DBAR=2 /Iinc /std:c++17 /utf-8 -- foo.cpp",
+                "file": "foo.cpp"
+            },
+            {
+                "directory": "C:/build",
+                "arguments": ["cl.exe", "/c", "/source-charset:windows-1252", "/TC", "bar.cpp"],
+                "file": "bar.cpp"
+            }
+        ]"#;
+        let db = CompilationDB::from_slice(data, PathBuf::from("/db")).unwrap();
+        let cmds: Vec<_> = db.collect();
+
+        let bar = cmds[0].as_ref().unwrap();
+        assert_eq!(bar.opt.driver, Driver::MSVC);
+        assert_eq!(bar.opt.lang, Language::C);
+        assert_eq!(bar.opt.charset, Charset::Windows1252);
+
+        let foo = cmds[1].as_ref().unwrap();
+        assert_eq!(foo.opt.driver, Driver::MSVC);
+        assert_eq!(foo.opt.lang, Language::CPP);
+        assert_eq!(foo.opt.std, Some(Standard::Cpp17));
+        assert_eq!(foo.opt.charset, Charset::Utf8);
+        assert_eq!(
+            foo.opt.def,
+            vec![
+                Macro::Defined(("FOO".to_string(), "1".to_string())),
+                Macro::Defined(("BAR".to_string(), "2".to_string())),
+            ]
+        );
+        assert_eq!(foo.opt.sys_paths, vec!["inc"]);
+    }
+
+    #[test]
+    fn test_compilation_db_get_command() {
+        let data = br#"[
+            { "directory": "/build", "command": "clang -DA -c a.c", "file": "a.c" },
+            { "directory": "/build", "command": "clang -DB -c b.c", "file": "b.c" }
+        ]"#;
+        let db = CompilationDB::from_slice(data, PathBuf::from("/")).unwrap();
+
+        let cmd = db.get_command(Path::new("/build/b.c")).unwrap().unwrap();
+        assert_eq!(
+            cmd.opt.def,
+            vec![Macro::Defined(("B".to_string(), "1".to_string()))]
+        );
+        assert!(db.get_command(Path::new("/build/c.c")).is_none());
+    }
+
+    #[test]
+    fn test_args_response_file() {
+        let tmp = TempDir::new("test").unwrap();
+        std::fs::write(tmp.path().join("a.rsp"), "-DA @b.rsp\n-I 'x y'").unwrap();
+        std::fs::write(tmp.path().join("b.rsp"), "-DB").unwrap();
+
+        let data = format!(
+            r#"[{{ "directory": "{}", "command": "clang @a.rsp @none.rsp -c a.c", "file": "a.c" }}]"#,
+            tmp.path().to_str().unwrap()
+        );
+        let mut db = CompilationDB::from_slice(data.as_bytes(), PathBuf::from("/")).unwrap();
+        let cmd = db.next().unwrap().unwrap();
+
+        assert_eq!(
+            cmd.opt.def,
+            vec![
+                Macro::Defined(("A".to_string(), "1".to_string())),
+                Macro::Defined(("B".to_string(), "1".to_string())),
+            ]
+        );
+        assert_eq!(cmd.opt.sys_paths, vec!["x y"]);
+    }
+
+    #[test]
+    fn test_compile_flags() {
+        let tmp = TempDir::new("test").unwrap();
+        let src = tmp.path().join("src");
+        std::fs::create_dir_all(&src).unwrap();
+        std::fs::write(
+            tmp.path().join("compile_flags.txt"),
+            "-DFOO=a b\n\n-I\ninclude dir\n-std=c++14\n",
+        )
+        .unwrap();
+
+        let flags = CompileFlags::find(&src.join("foo.cpp")).unwrap();
+        let cmd = flags.get_command(&src.join("foo.cpp")).unwrap();
+
+        assert_eq!(cmd.opt.lang, Language::CPP);
+        assert_eq!(cmd.opt.get_standard(), Standard::Cpp14);
+        assert_eq!(cmd.opt.current_dir, tmp.path());
+        assert_eq!(
+            cmd.opt.def,
+            vec![Macro::Defined(("FOO".to_string(), "a b".to_string()))]
+        );
+        assert_eq!(cmd.opt.sys_paths, vec!["include dir"]);
+
+        let cmd = flags.get_command(Path::new("foo.c")).unwrap();
+        assert_eq!(cmd.file, tmp.path().join("foo.c"));
+        assert_eq!(cmd.opt.lang, Language::C);
+        assert_eq!(cmd.opt.get_standard(), Standard::C17);
+    }
+
+    #[test]
+    fn test_args_language() {
+        let opt = Args::get_options(b"/usr/bin/clang -c foo.c").unwrap();
+        assert_eq!(opt.lang, Language::C);
+        assert_eq!(opt.get_standard(), Standard::C17);
+
+        let opt = Args::get_options(b"/usr/bin/clang -std=gnu99 -c foo.cpp").unwrap();
+        assert_eq!(opt.lang, Language::CPP);
+        assert_eq!(opt.get_standard(), Standard::Cpp17);
+
+        let opt = Args::get_options(b"/usr/bin/g++ -std=c++2a -c foo.c").unwrap();
+        assert_eq!(opt.lang, Language::CPP);
+        assert_eq!(opt.get_standard(), Standard::Cpp20);
+
+        let opt = Args::get_options(b"/usr/bin/clang -x c++-header -std=c++11 foo.h").unwrap();
+        assert_eq!(opt.lang, Language::CPP);
+        assert_eq!(opt.get_standard(), Standard::Cpp11);
+
+        let opt = Args::get_options(b"/usr/bin/clang++ -xc -ansi foo.cpp").unwrap();
+        assert_eq!(opt.lang, Language::C);
+        assert_eq!(opt.get_standard(), Standard::C89);
+
+        let opt = Args::get_options(b"cc -std=iso9899:2011 foo.c").unwrap();
+        assert_eq!(opt.get_standard(), Standard::C11);
+    }
+
+    #[test]
+    fn test_args_msvc() {
+        let opt = Args::get_options(
+            br#"C:\\VS\\bin\\cl.exe /nologo /c /DFOO /DBAR#2 -DBAZ=3 /UQUX /Iinc /I "a b" -imsvc sdk /FIpch.h /std:c++17 /Fofoo.obj foo.cpp"#,
+        )
+        .unwrap();
+        assert_eq!(opt.driver, Driver::MSVC);
+        assert_eq!(opt.lang, Language::CPP);
+        assert_eq!(opt.get_standard(), Standard::Cpp17);
+        assert_eq!(opt.file, PathBuf::from("foo.cpp"));
+        assert_eq!(
+            opt.def,
+            vec![
+                Macro::Defined(("FOO".to_string(), "1".to_string())),
+                Macro::Defined(("BAR".to_string(), "2".to_string())),
+                Macro::Defined(("BAZ".to_string(), "3".to_string())),
+                Macro::Undef("QUX".to_string()),
+            ]
+        );
+        assert_eq!(opt.sys_paths, vec!["inc", "a b", "sdk"]);
+        assert_eq!(opt.includes, vec!["pch.h"]);
+
+        let opt = Args::get_options(b"clang-cl /c foo.h").unwrap();
+        assert_eq!(opt.driver, Driver::MSVC);
+        assert_eq!(opt.lang, Language::CPP);
+        assert_eq!(opt.get_standard(), Standard::Cpp14);
+
+        let opt = Args::get_options(b"clang-cl.exe /c /TC foo.cpp").unwrap();
+        assert_eq!(opt.lang, Language::C);
+
+        let opt = Args::get_options(b"clang --driver-mode=cl /TP /Tcfoo.c").unwrap();
+        assert_eq!(opt.driver, Driver::MSVC);
+        assert_eq!(opt.lang, Language::C);
+        assert_eq!(opt.file, PathBuf::from("foo.c"));
+
+        let opt = Args::get_options(b"/usr/bin/clang -I inc -c foo.c").unwrap();
+        assert_eq!(opt.driver, Driver::GCC);
+    }
+
+    #[test]
+    fn test_args_cuda() {
+        let opt = Args::get_options(b"nvcc -c kernel.cu").unwrap();
+        assert_eq!(opt.lang, Language::CPP);
+        assert!(opt.cuda);
+
+        let opt = Args::get_options(b"clang -x cuda -c kernel.cpp").unwrap();
+        assert_eq!(opt.lang, Language::CPP);
+        assert!(opt.cuda);
+
+        let opt = Args::get_options(b"clang -x c++ -c kernel.cu").unwrap();
+        assert!(!opt.cuda);
+    }
+
+    #[test]
+    fn test_args_charset() {
+        let opt = Args::get_options(b"gcc -c foo.c").unwrap();
+        assert_eq!(opt.charset, Charset::Utf8);
+
+        let opt = Args::get_options(b"gcc -finput-charset=ISO-8859-1 -c foo.c").unwrap();
+        assert_eq!(opt.charset, Charset::Latin1);
+
+        let opt = Args::get_options(b"cl.exe /source-charset:windows-1252 /c foo.cpp").unwrap();
+        assert_eq!(opt.charset, Charset::Windows1252);
+
+        let opt = Args::get_options(b"cl.exe /source-charset:.1252 /utf-8 /c foo.cpp").unwrap();
+        assert_eq!(opt.charset, Charset::Utf8);
+
+        assert_eq!(
+            Args::get_options(b"gcc -finput-charset=EBCDIC-US -c foo.c").unwrap_err(),
+            ArgsError::UnknownCharset {
+                file: PathBuf::from("foo.c"),
+                name: "EBCDIC-US".to_string(),
+            }
+        );
+        assert_eq!(
+            Args::get_options(b"cl.exe /source-charset:.932 /c foo.cpp").unwrap_err(),
+            ArgsError::UnknownCharset {
+                file: PathBuf::from("foo.cpp"),
+                name: "932".to_string(),
+            }
+        );
+    }
 }
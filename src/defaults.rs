@@ -3,7 +3,7 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use crate::args::Macro;
+use crate::args::{Driver, Macro};
 
 macro_rules! defined {
     ( $lhs: expr, $rhs: expr) => {{
@@ -236,7 +236,6 @@ pub fn get_defined() -> Vec<Macro> {
         defined!("__STDC_HOSTED__", "1"),
         defined!("__STDC_UTF_16__", "1"),
         defined!("__STDC_UTF_32__", "1"),
-        defined!("__STDC_VERSION__", "201112L"),
         defined!("__STDC__", "1"),
         defined!("__UINT16_C_SUFFIX__", ""),
         defined!("__UINT16_FMTX__", "\"hX\""),
@@ -364,6 +363,54 @@ pub fn get_defined() -> Vec<Macro> {
     ]
 }
 
+pub fn get_msvc_defined() -> Vec<Macro> {
+    // cl.exe 19.29 (Visual Studio 2019) for x64
+    vec![
+        defined!("__has_include(x)", "0"),
+        defined!("__STDC_HOSTED__", "1"),
+        defined!("_INTEGRAL_MAX_BITS", "64"),
+        defined!("_M_AMD64", "100"),
+        defined!("_M_X64", "100"),
+        defined!("_MSC_BUILD", "1"),
+        defined!("_MSC_EXTENSIONS", "1"),
+        defined!("_MSC_FULL_VER", "192930133"),
+        defined!("_MSC_VER", "1929"),
+        defined!("_MSVC_TRADITIONAL", "1"),
+        defined!("_MT", "1"),
+        defined!("_NATIVE_WCHAR_T_DEFINED", "1"),
+        defined!("_WCHAR_T_DEFINED", "1"),
+        defined!("_WIN32", "1"),
+        defined!("_WIN64", "1"),
+    ]
+}
+
+pub fn get_defined_for(driver: &Driver) -> Vec<Macro> {
+    match driver {
+        Driver::GCC => get_defined(),
+        Driver::MSVC => get_msvc_defined(),
+    }
+}
+
+pub fn get_msvc_sys_paths() -> Vec<String> {
+    // cl.exe gets the system include directories from INCLUDE
+    std::env::var("INCLUDE")
+        .map(|paths| {
+            paths
+                .split(';')
+                .filter(|p| !p.is_empty())
+                .map(|p| p.to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn get_sys_paths_for(driver: &Driver) -> Vec<String> {
+    match driver {
+        Driver::GCC => get_sys_paths(),
+        Driver::MSVC => get_msvc_sys_paths(),
+    }
+}
+
 pub fn get_sys_paths() -> Vec<String> {
     // echo | gcc -Wp,-v -x c++ - -fsyntax-only
     vec![
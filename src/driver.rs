@@ -0,0 +1,575 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use crossbeam::crossbeam_channel::unbounded;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use hashbrown::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::args::{ArgsError, Command, CompilationDB};
+use crate::defaults;
+use crate::lexer::buffer::{BufferData, FileInfo, Position};
+use crate::lexer::charset::Charset;
+use crate::lexer::persist;
+use crate::lexer::preprocessor::cache::{FileCache, FileCacheStats, IfCache, PrefixCache};
+use crate::lexer::preprocessor::context::{DefaultContext, IfState, PreprocContext};
+use crate::lexer::preprocessor::include::{IncludeLocator, PathIndex};
+use crate::lexer::preprocessor::macros::{Macro, MacroFunction, MacroObject, MacroType};
+use crate::lexer::source::{FileId, SourceLocator, SourceMutex};
+use crate::lexer::{Lexer, TLexer, Token};
+use crate::parser::errors::ParserError;
+use crate::parser::{Context, Unit, UnitParser};
+
+/// Build a globset from some patterns and from files containing one pattern per line
+/// (relative patterns match anywhere and a trailing slash matches a whole directory)
+pub fn get_globset<'a, P, F>(patterns: P, files: F) -> io::Result<GlobSet>
+where
+    P: IntoIterator<Item = &'a str>,
+    F: IntoIterator<Item = &'a str>,
+{
+    let mut globset = GlobSetBuilder::new();
+    for p in patterns {
+        if !p.is_empty() {
+            if let Ok(glob) = Glob::new(p) {
+                globset.add(glob);
+            }
+        }
+    }
+
+    for file in files {
+        let content = fs::read_to_string(file)?;
+        for line in content.split('\n').filter(|s| !s.is_empty()) {
+            let mut glob = String::new();
+            if !line.starts_with('/') {
+                glob.push_str("**/");
+            }
+            glob.push_str(line);
+            if line.ends_with('/') {
+                glob.push_str("**");
+            }
+            if let Ok(glob) = Glob::new(&glob) {
+                globset.add(glob);
+            }
+        }
+    }
+
+    Ok(globset.build().unwrap_or_else(|_| GlobSet::empty()))
+}
+
+/// Select the paths matching include (when not empty) and not matching exclude
+pub struct PathFilter {
+    pub include: GlobSet,
+    pub exclude: GlobSet,
+}
+
+impl Default for PathFilter {
+    fn default() -> Self {
+        Self {
+            include: GlobSet::empty(),
+            exclude: GlobSet::empty(),
+        }
+    }
+}
+
+impl PathFilter {
+    pub fn is_match<P: AsRef<Path>>(&self, path: P) -> bool {
+        let path = path.as_ref();
+        (self.include.is_empty() || self.include.is_match(path)) && !self.exclude.is_match(path)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    /// The translation unit is lexed until the end
+    Lex,
+    /// The translation unit is parsed
+    Parse,
+}
+
+/// A processed translation unit given to Analysis::analyze
+pub struct TranslationUnit<'a, PC: PreprocContext> {
+    pub cmd: Command,
+    pub lexer: Lexer<'a, PC>,
+    /// The parser context and the result of the parsing in Parse mode
+    pub parser: Option<(Context, Result<Unit, ParserError>)>,
+    pub source: SourceMutex,
+    pub if_cache: Arc<IfCache>,
+}
+
+/// An analysis run on each translation unit of a compilation database
+pub trait Analysis: Sync {
+    type Context: PreprocContext + Clone + Send;
+    type Output: Send;
+
+    fn mode(&self) -> Mode {
+        Mode::Lex
+    }
+
+    /// Get the parser context to use in Parse mode
+    fn get_parser_context(&self) -> Context {
+        Context::default()
+    }
+
+    /// Called for each token in Lex mode
+    fn on_token(&self, _lexer: &mut Lexer<Self::Context>, _tok: &Token) {}
+
+    /// Get the result for a translation unit
+    fn analyze(&self, tu: TranslationUnit<Self::Context>) -> Self::Output;
+
+    /// Merge the results of two translation units
+    fn reduce(&self, acc: Self::Output, output: Self::Output) -> Self::Output;
+}
+
+/// The hooks called by an ObservedContext to collect some data while preprocessing
+pub trait ContextObserver: Clone + Default {
+    /// A macro has been defined
+    fn on_define(&mut self, _name: &str, _info: &FileInfo) {}
+
+    /// A macro has been checked with defined(...) or #ifdef
+    fn on_defined(&mut self, _name: &str, _defined: bool) {}
+
+    /// A macro has been got
+    fn on_get(&self, _name: &str, _mac: Option<&Macro>) {}
+
+    /// The type of a macro has been got
+    fn on_get_type(&self, _name: &str, _typ: &MacroType) {}
+
+    /// A file has been included
+    fn on_include(&mut self, _from: FileId, _to: FileId) {}
+
+    /// A token has been got from a file
+    fn on_token(&mut self, _file: Option<FileId>) {}
+}
+
+/// A DefaultContext calling the hooks of an observer
+#[derive(Clone, Default)]
+pub struct ObservedContext<O: ContextObserver> {
+    pub default: DefaultContext,
+    pub observer: O,
+}
+
+impl<O: ContextObserver> PreprocContext for ObservedContext<O> {
+    fn add_if(&mut self, state: IfState) {
+        self.default.add_if(state);
+    }
+
+    fn rm_if(&mut self) {
+        self.default.rm_if();
+    }
+
+    fn if_state(&self) -> Option<&IfState> {
+        self.default.if_state()
+    }
+
+    fn if_change(&mut self, state: IfState) {
+        self.default.if_change(state);
+    }
+
+    fn add_function(&mut self, name: String, mac: MacroFunction) {
+        self.observer.on_define(&name, mac.get_file_info());
+        self.default.add_function(name, mac);
+    }
+
+    fn add_object(&mut self, name: String, mac: MacroObject) {
+        self.observer.on_define(&name, mac.get_file_info());
+        self.default.add_object(name, mac);
+    }
+
+    fn undef(&mut self, name: &str) {
+        self.default.undef(name);
+    }
+
+    fn defined(&mut self, name: &str) -> bool {
+        let defined = self.default.defined(name);
+        self.observer.on_defined(name, defined);
+        defined
+    }
+
+    fn get(&self, name: &str) -> Option<&Macro> {
+        let mac = self.default.get(name);
+        self.observer.on_get(name, mac);
+        mac
+    }
+
+    fn get_type(&self, name: &str) -> MacroType<'_> {
+        let typ = self.default.get_type(name);
+        self.observer.on_get_type(name, &typ);
+        typ
+    }
+
+    fn skip_until_next(&self, file: FileId, pos: usize) -> Option<Position> {
+        self.default.skip_until_next(file, pos)
+    }
+
+    fn save_switch(&self, file: FileId, pos: usize, next: Position) {
+        self.default.save_switch(file, pos, next);
+    }
+
+    fn set_in_use(&self, id: usize, in_use: bool) {
+        self.default.set_in_use(id, in_use);
+    }
+
+    fn next_counter(&self) -> u64 {
+        self.default.next_counter()
+    }
+
+    fn share_macros(&mut self) {
+        self.default.share_macros();
+    }
+
+    fn new_with_if_cache(if_cache: Arc<IfCache>) -> Self {
+        Self {
+            default: DefaultContext::new_with_if_cache(if_cache),
+            observer: O::default(),
+        }
+    }
+
+    fn on_token(&mut self, file: Option<FileId>) {
+        self.default.on_token(file);
+        self.observer.on_token(file);
+    }
+}
+
+impl<O: ContextObserver> IncludeLocator for ObservedContext<O> {
+    fn find(
+        &mut self,
+        angle: bool,
+        path: &str,
+        next: bool,
+        current: FileId,
+        path_index: PathIndex,
+    ) -> Option<BufferData> {
+        let buf = self.default.find(angle, path, next, current, path_index);
+        if let Some(buf) = buf.as_ref() {
+            self.observer.on_include(current, buf.get_source_id());
+        }
+        buf
+    }
+
+    fn get_id(&mut self, path: &PathBuf) -> FileId {
+        self.default.get_id(path)
+    }
+
+    fn get_path(&self, id: FileId) -> PathBuf {
+        self.default.get_path(id)
+    }
+
+    fn set_source(&mut self, source: SourceMutex) {
+        self.default.set_source(source)
+    }
+
+    fn set_sys_paths<P: AsRef<Path>>(&mut self, paths: &[P]) {
+        self.default.set_sys_paths(paths);
+    }
+
+    fn set_charset(&mut self, charset: Charset) {
+        self.default.set_charset(charset);
+    }
+}
+
+#[derive(Default)]
+pub struct DriverOptions {
+    /// Number of threads (0 to use all the cpus)
+    pub num_jobs: usize,
+    /// Filter on the main file of the translation units
+    pub filter: PathFilter,
+    /// Maximum size in bytes of the file cache (0 to disable it): it keeps the
+    /// contents of the files but they're lexed again for each inclusion
+    pub cache_size: usize,
+    /// Map the big files in the file cache instead of reading them: they mustn't
+    /// be modified during the run
+    pub map_files: bool,
+    /// Directory where the caches are stored between runs
+    pub cache_dir: Option<PathBuf>,
+}
+
+pub struct DriverResults<T> {
+    /// The reduced output or None if there was no translation unit
+    pub output: Option<T>,
+    pub source: SourceMutex,
+    /// The invalid entries in the compilation database
+    pub errors: Vec<ArgsError>,
+    pub file_cache_stats: Option<FileCacheStats>,
+    pub cache_error: Option<io::Error>,
+}
+
+/// Get the commands to run: one per existing file with the default paths and macros
+fn get_commands(database: CompilationDB, filter: &PathFilter) -> (Vec<Command>, Vec<ArgsError>) {
+    let mut commands = Vec::new();
+    let mut errors = Vec::new();
+    let mut sent: HashSet<PathBuf> = HashSet::default();
+
+    for cmd in database {
+        let mut cmd = match cmd {
+            Ok(cmd) => cmd,
+            Err(e) => {
+                errors.push(e);
+                continue;
+            }
+        };
+        let file = if cmd.opt.file.is_absolute() {
+            cmd.opt.file.clone()
+        } else {
+            cmd.opt.current_dir.join(&cmd.opt.file)
+        };
+        if !file.exists() || !filter.is_match(&file) || !sent.insert(file.clone()) {
+            continue;
+        }
+        if file.to_str().is_none() {
+            // The lexer only takes the UTF-8 paths
+            errors.push(ArgsError::NonUtf8Path { path: file });
+            continue;
+        }
+
+        cmd.file = file;
+        cmd.opt
+            .sys_paths
+            .extend(defaults::get_sys_paths_for(&cmd.opt.driver));
+        let mut def = defaults::get_defined_for(&cmd.opt.driver);
+        def.extend_from_slice(&cmd.opt.def);
+        cmd.opt.def = def;
+
+        commands.push(cmd);
+    }
+
+    (commands, errors)
+}
+
+fn process<A: Analysis>(
+    analysis: &A,
+    cmd: Command,
+    source: &SourceMutex,
+    if_cache: &Arc<IfCache>,
+    prefix_cache: &PrefixCache<A::Context>,
+) -> A::Output {
+    // The non UTF-8 paths have been reported in get_commands
+    let mut lexer = Lexer::<A::Context>::new_from_file_with_prefix(
+        cmd.file.to_str().unwrap(),
+        Arc::clone(source),
+        Arc::clone(if_cache),
+        prefix_cache,
+        cmd.opt.clone(),
+    );
+
+    let (lexer, parser) = match analysis.mode() {
+        Mode::Lex => {
+            loop {
+                let tok = lexer.next_useful();
+                if tok == Token::Eof {
+                    break;
+                }
+                analysis.on_token(&mut lexer, &tok);
+            }
+            (lexer, None)
+        }
+        Mode::Parse => {
+            let mut parser = UnitParser {
+                lexer,
+                context: analysis.get_parser_context(),
+            };
+            let unit = parser.parse();
+            (parser.lexer, Some((parser.context, unit)))
+        }
+    };
+
+    analysis.analyze(TranslationUnit {
+        cmd,
+        lexer,
+        parser,
+        source: Arc::clone(source),
+        if_cache: Arc::clone(if_cache),
+    })
+}
+
+/// Run the analysis on the translation units of the database in parallel
+/// and reduce the results got in each thread
+pub fn run<A: Analysis>(
+    database: CompilationDB,
+    analysis: &A,
+    opt: &DriverOptions,
+) -> DriverResults<A::Output> {
+    let num_jobs = if opt.num_jobs == 0 {
+        thread::available_parallelism().map_or(1, |n| n.get())
+    } else {
+        opt.num_jobs
+    };
+
+    let (mut source, if_cache) = if let Some(cache_dir) = opt.cache_dir.as_ref() {
+        persist::load_caches(cache_dir)
+    } else {
+        (SourceLocator::default(), IfCache::default())
+    };
+    let file_cache = if opt.cache_size != 0 {
+        let mut file_cache = FileCache::new(opt.cache_size);
+        file_cache.set_map_files(opt.map_files);
+        let file_cache = Arc::new(file_cache);
+        source.set_file_cache(Arc::clone(&file_cache));
+        Some(file_cache)
+    } else {
+        None
+    };
+    let source = Arc::new(Mutex::new(source));
+    let if_cache = Arc::new(if_cache);
+    let prefix_cache = PrefixCache::default();
+
+    let (commands, errors) = get_commands(database, &opt.filter);
+    let (sender, receiver) = unbounded();
+    for cmd in commands {
+        sender.send(cmd).unwrap();
+    }
+    drop(sender);
+
+    let output = thread::scope(|scope| {
+        let workers: Vec<_> = (0..num_jobs)
+            .map(|_| {
+                let receiver = receiver.clone();
+                let (source, if_cache, prefix_cache) = (&source, &if_cache, &prefix_cache);
+                scope.spawn(move || {
+                    let mut acc = None;
+                    while let Ok(cmd) = receiver.recv() {
+                        let output = process(analysis, cmd, source, if_cache, prefix_cache);
+                        acc = Some(match acc {
+                            Some(acc) => analysis.reduce(acc, output),
+                            None => output,
+                        });
+                    }
+                    acc
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .filter_map(|w| w.join().unwrap())
+            .fold(None, |acc, output| {
+                Some(match acc {
+                    Some(acc) => analysis.reduce(acc, output),
+                    None => output,
+                })
+            })
+    });
+
+    let cache_error = opt.cache_dir.as_ref().and_then(|cache_dir| {
+        persist::save_caches(cache_dir, &source.lock().unwrap(), &if_cache).err()
+    });
+
+    DriverResults {
+        output,
+        source,
+        errors,
+        file_cache_stats: file_cache.map(|c| c.stats()),
+        cache_error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::lexer::preprocessor::context::DefaultContext;
+    use pretty_assertions::assert_eq;
+    use tempdir::TempDir;
+
+    struct ParsedFiles;
+
+    impl Analysis for ParsedFiles {
+        type Context = DefaultContext;
+        type Output = Vec<String>;
+
+        fn mode(&self) -> Mode {
+            Mode::Parse
+        }
+
+        fn analyze(&self, mut tu: TranslationUnit<Self::Context>) -> Self::Output {
+            assert_eq!(tu.lexer.next_useful(), Token::Eof);
+            match tu.parser {
+                Some((_, Ok(_))) => {
+                    vec![tu
+                        .cmd
+                        .file
+                        .file_name()
+                        .unwrap()
+                        .to_str()
+                        .unwrap()
+                        .to_string()]
+                }
+                _ => vec![],
+            }
+        }
+
+        fn reduce(&self, mut acc: Self::Output, output: Self::Output) -> Self::Output {
+            acc.extend(output);
+            acc.sort();
+            acc
+        }
+    }
+
+    #[test]
+    fn test_driver() {
+        let tmp = TempDir::new("test_driver").unwrap();
+        fs::write(tmp.path().join("a.c"), "int a = FOO + 1;\n").unwrap();
+        fs::write(tmp.path().join("b.c"), "int b;\n").unwrap();
+        fs::write(tmp.path().join("c.c"), "int c;\n").unwrap();
+        let db = format!(
+            r#"[
+                {{"directory": "{0}", "file": "a.c", "command": "clang -DFOO=1 -c a.c"}},
+                {{"directory": "{0}", "file": "a.c", "command": "clang -c a.c"}},
+                {{"directory": "{0}", "file": "b.c", "command": "clang -c b.c"}},
+                {{"directory": "{0}", "file": "c.c", "command": "clang -c c.c"}},
+                {{"directory": "{0}", "file": "d.c"}}
+            ]"#,
+            tmp.path().display()
+        );
+        let database = CompilationDB::from_slice(db.as_bytes(), tmp.path().to_path_buf()).unwrap();
+
+        let opt = DriverOptions {
+            num_jobs: 2,
+            filter: PathFilter {
+                include: GlobSet::empty(),
+                exclude: get_globset(vec!["**/c.c"], vec![]).unwrap(),
+            },
+            ..Default::default()
+        };
+        let res = run(database, &ParsedFiles, &opt);
+
+        assert_eq!(res.output.unwrap(), vec!["a.c", "b.c"]);
+        assert_eq!(res.errors.len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_driver_non_utf8_path() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let tmp = TempDir::new("test_driver").unwrap();
+        let dir = tmp.path().join(OsStr::from_bytes(b"\xFF"));
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("a.c"), "int a;\n").unwrap();
+        fs::write(tmp.path().join("b.c"), "int b;\n").unwrap();
+        let db = format!(
+            r#"[
+                {{"directory": ".", "file": "a.c", "command": "clang -c a.c"}},
+                {{"directory": "{}", "file": "b.c", "command": "clang -c b.c"}}
+            ]"#,
+            tmp.path().display()
+        );
+        let database = CompilationDB::from_slice(db.as_bytes(), dir.clone()).unwrap();
+
+        let res = run(database, &ParsedFiles, &DriverOptions::default());
+
+        assert_eq!(res.output.unwrap(), vec!["b.c"]);
+        assert_eq!(
+            res.errors,
+            vec![ArgsError::NonUtf8Path {
+                path: dir.join(".").join("a.c")
+            }]
+        );
+    }
+}
@@ -3,8 +3,17 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use memchr::{memchr, memchr_iter, memrchr};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use super::charset::{self, Charset, OffsetMap};
+use super::lexer::Location;
+use super::line_index::LineIndex;
+use super::lines::{self, LineMap};
 use super::preprocessor::include::PathIndex;
-use super::source::FileId;
+use super::source::{FileData, FileId};
 
 #[derive(Debug)]
 pub(crate) struct OutBuf {
@@ -30,7 +39,7 @@ impl OutBuf {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Position {
     pub(crate) pos: usize,
     pub(crate) line: u32,
@@ -47,9 +56,121 @@ impl Default for Position {
     }
 }
 
+#[derive(Debug)]
+enum Content {
+    Owned(Vec<u8>),
+    /// A file shared with the other lexers
+    Shared(Arc<FileData>),
+    /// The input given to Lexer::new: it lives as long as the Buffer
+    Borrowed(&'static [u8]),
+}
+
+impl Content {
+    #[inline(always)]
+    fn as_slice(&self) -> &[u8] {
+        match self {
+            Content::Owned(buf) => buf,
+            Content::Shared(data) => data,
+            Content::Borrowed(buf) => buf,
+        }
+    }
+}
+
+/// The text to lex got from a source: it's transcoded to UTF-8 and cleaned when needed
+/// and the maps give back the positions in the source.
+/// The text of a file is kept in the FileCache so it's only made once.
+#[derive(Debug)]
+pub struct SourceText {
+    source: Content,
+    charset: Charset,
+    /// The source in UTF-8 when it's in another charset
+    transcoded: Option<Vec<u8>>,
+    /// The text to lex when it's different from the transcoded one
+    cleaned: Option<Vec<u8>>,
+    /// To get the physical positions when the source has been cleaned
+    map: LineMap,
+    /// To get the positions in the source when it's been transcoded
+    offsets: OffsetMap,
+}
+
+impl SourceText {
+    fn new(source: Content, charset: Charset) -> Self {
+        let (transcoded, offsets) = match charset::transcode(source.as_slice(), charset) {
+            Some((text, offsets)) => (Some(text), offsets),
+            None => (None, OffsetMap::default()),
+        };
+        let text = transcoded.as_deref().unwrap_or_else(|| source.as_slice());
+        let (cleaned, map) = match lines::clean(text) {
+            Some((text, map)) => (Some(text), map),
+            None => (None, LineMap::default()),
+        };
+        Self {
+            cleaned,
+            map,
+            offsets,
+            transcoded,
+            ..Self::raw(source, charset)
+        }
+    }
+
+    /// The source is lexed as is
+    fn raw(source: Content, charset: Charset) -> Self {
+        Self {
+            source,
+            charset,
+            transcoded: None,
+            cleaned: None,
+            map: LineMap::default(),
+            offsets: OffsetMap::default(),
+        }
+    }
+
+    pub(crate) fn from_data(data: Arc<FileData>, charset: Charset) -> Self {
+        Self::new(Content::Shared(data), charset)
+    }
+
+    pub(crate) fn data(&self) -> Option<&Arc<FileData>> {
+        match &self.source {
+            Content::Shared(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    pub fn charset(&self) -> Charset {
+        self.charset
+    }
+
+    /// The number of bytes used besides the source
+    pub fn extra_bytes(&self) -> usize {
+        self.transcoded.as_ref().map_or(0, |t| t.len())
+            + self.cleaned.as_ref().map_or(0, |t| t.len())
+    }
+
+    /// Get the lines of the source (the offsets are the ones in the source)
+    pub(crate) fn line_index(&self) -> LineIndex {
+        match &self.transcoded {
+            Some(text) => LineIndex::with_offsets(text, self.offsets.clone()),
+            None => LineIndex::new(self.source.as_slice()),
+        }
+    }
+
+    #[inline(always)]
+    fn as_slice(&self) -> &[u8] {
+        match (&self.cleaned, &self.transcoded) {
+            (Some(text), _) | (None, Some(text)) => text,
+            (None, None) => self.source.as_slice(),
+        }
+    }
+
+    #[inline(always)]
+    fn is_mapped(&self) -> bool {
+        !self.map.is_empty() || !self.offsets.is_empty()
+    }
+}
+
 #[derive(Debug)]
 pub struct BufferData {
-    buf: Vec<u8>,
+    text: Arc<SourceText>,
     position: Position,
     source_id: FileId,
     path_index: PathIndex,
@@ -57,15 +178,117 @@ pub struct BufferData {
 }
 
 impl BufferData {
-    pub fn new(buf: Vec<u8>, source_id: FileId, path_index: PathIndex) -> Self {
+    /// Lex a text which can be shared with other lexers
+    pub fn with_text(text: Arc<SourceText>, source_id: FileId, path_index: PathIndex) -> Self {
         Self {
-            buf,
+            text,
             position: Position::default(),
             source_id,
             path_index,
             fake_source_id: None,
         }
     }
+
+    fn with_content(
+        buf: Content,
+        source_id: FileId,
+        path_index: PathIndex,
+        charset: Charset,
+    ) -> Self {
+        Self::with_text(
+            Arc::new(SourceText::new(buf, charset)),
+            source_id,
+            path_index,
+        )
+    }
+
+    /// The content is lexed as is
+    fn raw(buf: Content, source_id: FileId, path_index: PathIndex) -> Self {
+        Self::with_text(
+            Arc::new(SourceText::raw(buf, Charset::default())),
+            source_id,
+            path_index,
+        )
+    }
+
+    pub fn new(buf: Vec<u8>, source_id: FileId, path_index: PathIndex) -> Self {
+        Self::with_content(
+            Content::Owned(buf),
+            source_id,
+            path_index,
+            Charset::default(),
+        )
+    }
+
+    pub fn new_shared(data: Arc<FileData>, source_id: FileId, path_index: PathIndex) -> Self {
+        Self::with_charset(data, source_id, path_index, Charset::default())
+    }
+
+    /// The data is transcoded to UTF-8 when it isn't in UTF-8
+    pub fn with_charset(
+        data: Arc<FileData>,
+        source_id: FileId,
+        path_index: PathIndex,
+        charset: Charset,
+    ) -> Self {
+        Self::with_content(Content::Shared(data), source_id, path_index, charset)
+    }
+
+    pub fn get_source_id(&self) -> FileId {
+        self.source_id
+    }
+
+    #[inline(always)]
+    fn as_slice(&self) -> &[u8] {
+        self.text.as_slice()
+    }
+
+    #[inline(always)]
+    fn is_mapped(&self) -> bool {
+        self.text.is_mapped()
+    }
+
+    #[inline(always)]
+    fn map(&self) -> &LineMap {
+        &self.text.map
+    }
+
+    /// Get the location in the source of a position in the text
+    fn location(&self, pos: usize, line: u32, lpos: usize) -> Location {
+        let loc = self.text.map.location(pos, line, lpos);
+        let offsets = &self.text.offsets;
+        if offsets.is_empty() {
+            loc
+        } else {
+            // The column is counted in the units of the source too
+            // (0 is for a newline when the next line has begun)
+            let pos = offsets.original(loc.pos);
+            let column = if loc.column == 0 {
+                0
+            } else {
+                let start = loc.pos + 1 - loc.column as usize;
+                (pos + 1 - offsets.original(start)) as u32
+            };
+            Location { pos, column, ..loc }
+        }
+    }
+
+    /// Get the position in the text of a location in the source
+    fn position(&self, loc: Location) -> Position {
+        let offsets = &self.text.offsets;
+        if offsets.is_empty() {
+            self.text.map.position(loc)
+        } else {
+            let pos = offsets.transcoded(loc.pos);
+            let column = if loc.column == 0 {
+                0
+            } else {
+                let start = (loc.pos + 1).saturating_sub(loc.column as usize);
+                (pos + 1 - offsets.transcoded(start)) as u32
+            };
+            self.text.map.position(Location { pos, column, ..loc })
+        }
+    }
 }
 
 #[derive(Clone, Debug, Default, Hash, PartialEq)]
@@ -86,25 +309,42 @@ pub(crate) struct Buffer<'a> {
 }
 
 impl<'a> Buffer<'a> {
-    pub(crate) fn new(buf: Vec<u8>, source_id: FileId, path_index: PathIndex) -> Self {
+    /// Lex the given input without copying it
+    pub(crate) fn new(buf: &'a [u8], source_id: FileId, path_index: PathIndex) -> Self {
+        // The Buffer can't outlive buf so it's safe to consider it as static
+        let buf = unsafe { &*(buf as *const [u8]) };
+        Self::from_data(BufferData::with_content(
+            Content::Borrowed(buf),
+            source_id,
+            path_index,
+            Charset::default(),
+        ))
+    }
+
+    /// Lex a macro expansion (it's been cleaned with the source it comes from)
+    pub(crate) fn new_expansion(buf: &'a [u8]) -> Self {
+        let buf = unsafe { &*(buf as *const [u8]) };
+        Self::from_data(BufferData::raw(
+            Content::Borrowed(buf),
+            FileId(0),
+            PathIndex(0),
+        ))
+    }
+
+    pub(crate) fn from_data(data: BufferData) -> Self {
         let mut ret = Self {
             stack: Vec::new(),
             preproc: OutBuf::default(),
             current: &[],
-            len: buf.len(),
+            len: 0,
             position: Position::default(),
             saved_position: Position::default(),
             saved_buf: &[],
         };
-        ret.stack.push(BufferData {
-            buf,
-            position: Position::default(),
-            source_id,
-            fake_source_id: None,
-            path_index,
-        });
-        ret.current =
-            unsafe { &*std::mem::transmute::<&[u8], *const [u8]>(&ret.stack.last().unwrap().buf) };
+        ret.stack.push(data);
+        let last = ret.stack.last().unwrap();
+        ret.current = unsafe { &*(last.as_slice() as *const [u8]) };
+        ret.len = ret.current.len();
         ret
     }
 
@@ -139,7 +379,8 @@ impl<'a> Buffer<'a> {
 
         self.stack.push(buf);
         let last = self.stack.last().unwrap();
-        self.current = unsafe { &*std::mem::transmute::<&[u8], *const [u8]>(&last.buf) };
+        // The contents are on the heap (or mapped) so they don't move with the stack
+        self.current = unsafe { &*(last.as_slice() as *const [u8]) };
         self.position = Position::default();
         self.len = self.current.len()
     }
@@ -153,14 +394,27 @@ impl<'a> Buffer<'a> {
             return true;
         }
 
-        if self.stack.pop().is_none() {
+        let last = if let Some(last) = self.stack.pop() {
+            last
+        } else {
             // the stack is empty
             return false;
+        };
+
+        if self.stack.is_empty() && last.is_mapped() {
+            // Keep the physical location of the end
+            let Position { pos, line, lpos } = self.position;
+            let loc = last.location(pos, line, lpos);
+            self.position = Position {
+                pos: loc.pos,
+                line: loc.line,
+                lpos: (loc.pos + 1).saturating_sub(loc.column as usize),
+            };
         }
 
         while let Some(data) = self.stack.last() {
-            if data.position.pos < data.buf.len() {
-                self.current = unsafe { &*std::mem::transmute::<&[u8], *const [u8]>(&data.buf) };
+            if data.position.pos < data.as_slice().len() {
+                self.current = unsafe { &*(data.as_slice() as *const [u8]) };
                 self.len = self.current.len();
                 self.position = data.position.clone();
                 return true;
@@ -171,17 +425,74 @@ impl<'a> Buffer<'a> {
         !self.stack.is_empty()
     }
 
+    /// Get the current file if its positions must be mapped
+    /// (it's been cleaned or transcoded)
+    #[inline(always)]
+    fn mapped(&self) -> Option<&BufferData> {
+        if self.preproc_use() {
+            return None;
+        }
+        self.stack.last().filter(|data| data.is_mapped())
+    }
+
+    /// Get the location in the source of a position on the current line
+    #[inline(always)]
+    pub(crate) fn location_at(&self, pos: usize) -> Location {
+        let Position { line, lpos, .. } = self.position;
+        if let Some(data) = self.mapped() {
+            data.location(pos, line, lpos)
+        } else {
+            Location {
+                pos,
+                line,
+                column: ((pos + 1) - lpos) as u32,
+            }
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn location(&self) -> Location {
+        self.location_at(self.position.pos)
+    }
+
+    /// Move to a location in the source
+    pub(crate) fn reset_location(&mut self, loc: Location) {
+        self.position = if let Some(data) = self.mapped() {
+            data.position(loc)
+        } else {
+            Position {
+                pos: loc.pos,
+                line: loc.line,
+                lpos: (loc.pos + 1).saturating_sub(loc.column as usize),
+            }
+        };
+    }
+
+    /// Get the text between two positions with the line splices (for the raw strings)
+    pub(crate) fn unspliced(&self, start: usize, end: usize) -> Cow<'a, [u8]> {
+        if let Some(data) = self.mapped() {
+            data.map().unsplice(self.current, start, end)
+        } else {
+            Cow::Borrowed(self.slice_p(start, end))
+        }
+    }
+
     pub(crate) fn add_new_line(&mut self) {
         self.position.line += 1;
         self.position.lpos = self.position.pos + 1;
     }
 
+    /// Get the physical line
     pub(crate) fn get_line(&self) -> u32 {
-        self.position.line
+        let Position { pos, line, .. } = self.position;
+        self.mapped()
+            .map_or(line, |data| line + data.map().lines(pos))
     }
 
     pub(crate) fn set_line(&mut self, line: u32) {
-        self.position.line = line;
+        let Position { pos, .. } = self.position;
+        let spliced = self.mapped().map_or(0, |data| data.map().lines(pos));
+        self.position.line = line.saturating_sub(spliced);
     }
 
     pub(crate) fn get_source_id(&self) -> Option<FileId> {
@@ -199,10 +510,6 @@ impl<'a> Buffer<'a> {
         last.fake_source_id = Some(id);
     }
 
-    pub(crate) fn get_column(&self) -> u32 {
-        ((self.position.pos + 1) - self.position.lpos) as u32
-    }
-
     pub(crate) fn reset(&mut self) {
         self.position.pos = 0;
     }
@@ -285,6 +592,28 @@ impl<'a> Buffer<'a> {
         unsafe { std::str::from_utf8_unchecked(&self.current[self.position.pos..]) }
     }
 
+    #[inline(always)]
+    pub(crate) fn remainder(&self) -> &'a [u8] {
+        unsafe { self.current.get_unchecked(self.position.pos..self.len) }
+    }
+
+    /// Move forward by n chars and count the newlines we're skipping
+    #[inline(always)]
+    pub(crate) fn advance(&mut self, n: usize) {
+        let skipped = &self.remainder()[..n];
+        if let Some(last) = memrchr(b'\n', skipped) {
+            self.position.line += memchr_iter(b'\n', skipped).count() as u32;
+            self.position.lpos = self.position.pos + last + 1;
+        }
+        self.position.pos += n;
+    }
+
+    /// Move to the next occurrence of c (or to the end) without counting the newlines
+    #[inline(always)]
+    pub(crate) fn skip_until(&mut self, c: u8) {
+        self.position.pos = memchr(c, self.remainder()).map_or(self.len, |i| self.position.pos + i);
+    }
+
     #[inline(always)]
     pub(crate) fn inc(&mut self) {
         self.position.pos += 1;
@@ -332,7 +661,7 @@ mod tests {
 
     #[test]
     fn test_buffer() {
-        let mut buf = Buffer::new(b"abc".to_vec(), FileId(0), PathIndex(0));
+        let mut buf = Buffer::new(b"abc", FileId(0), PathIndex(0));
         assert_eq!(buf.next_char(), b'a');
         buf.inc();
 
@@ -348,6 +677,16 @@ mod tests {
         assert_eq!(buf.next_char(), b'g');
         buf.rm_buffer();
 
+        let data = Arc::new(FileData::from(b"jkl".to_vec()));
+        buf.add_buffer(BufferData::new_shared(
+            Arc::clone(&data),
+            FileId(0),
+            PathIndex(0),
+        ));
+        assert_eq!(buf.next_char(), b'j');
+        assert_eq!(buf.current.as_ptr(), data.as_ptr());
+        buf.rm_buffer();
+
         assert_eq!(buf.next_char(), b'c');
     }
 }
@@ -3,10 +3,12 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use super::lexer::{Lexer, Token};
+use super::errors::LexerError;
+use super::lexer::{Lexer, TLexer, Token};
 use super::preprocessor::context::PreprocContext;
 use super::string::StringType;
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Clone, Copy, PartialEq, PartialOrd)]
 #[repr(u8)]
 pub(crate) enum Kind {
@@ -20,9 +22,11 @@ pub(crate) enum Kind {
     TTT,
     VVV,
     OCT, // octal
+    OOO, // o{...}
     HEX, // x...
     UNS, // u...
     UNL, // U...
+    NAM, // N{...}
     NLI, // \n
 }
 
@@ -47,7 +51,7 @@ const ECHARS: [Kind; 256] = [
     // 40  @   41  A      42  B      43  C      44  D      45  E      46  F      47  G
     Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, //
     // 48  H   49  I      4A  J      4B  K      4C  L      4D  M      4E  N      4F  O
-    Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, //
+    Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NAM, Kind::NON, //
     // 50  P   51  Q      52  R      53  S      54  T      55  U      56  V      57  W
     Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::UNL, Kind::NON, Kind::NON, //
     // 58  X   59  Y      5A  Z      5B  [      5C  \      5D  ]      5E  ^      5F  _
@@ -55,7 +59,7 @@ const ECHARS: [Kind; 256] = [
     // 60  `   61  a      62  b      63  c      64  d      65  e      66  f      67  g
     Kind::NON, Kind::AAA, Kind::BBB, Kind::NON, Kind::NON, Kind::NON, Kind::FFF, Kind::NON, //
     // 68  h   69  i      6A  j      6B  k      6C  l      6D  m      6E  n      6F  o
-    Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NNN, Kind::NON, //
+    Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NNN, Kind::OOO, //
     // 70  p   71  q      72  r      73  s      74  t      75  u      76  v      77  w
     Kind::NON, Kind::NON, Kind::RRR, Kind::NON, Kind::TTT, Kind::UNS, Kind::VVV, Kind::NON, //
     // 78  x   79  y      7A  z      7B  {      7C  |      7D  }      7E  ~      7F DEL
@@ -100,73 +104,149 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
     }
 
     #[inline(always)]
-    pub(crate) fn get_hex_char(&mut self) -> u32 {
-        let mut num = 0;
-        for _ in 0..3 {
-            if self.buf.has_char() {
-                let c = self.buf.next_char();
-                let n = Self::get_hex_digit(c);
-                if n != 16 {
-                    self.buf.inc();
-                    num = num << 4 | n;
-                } else {
-                    break;
-                }
-            } else {
+    pub(crate) fn get_hex_char(&mut self, start: usize) -> u32 {
+        if self.is_next_char(b'{') {
+            return self.get_delimited(start, 16, false);
+        }
+
+        // All the hex digits are part of the escape (too large values are saturated)
+        let mut num: u32 = 0;
+        let mut digits = 0;
+        while self.buf.has_char() {
+            let n = Self::get_hex_digit(self.buf.next_char());
+            if n == 16 {
                 break;
             }
+            self.buf.inc();
+            digits += 1;
+            num = num.checked_mul(16).map_or(u32::MAX, |num| num | n as u32);
+        }
+        if digits == 0 {
+            self.push_escape_error(start, false);
         }
-        num as u32
+        num
     }
 
+    /// Get a \uXXXX or \UXXXXXXXX (with n digits) or a \u{X...}
     #[inline(always)]
-    pub(crate) fn get_universal_short(&mut self) -> u32 {
-        // it has 4 digits
-        if self.buf.rem() >= 4 {
-            let c1 = self.buf.next_char();
-            let c2 = self.buf.next_char_n(1);
-            let c3 = self.buf.next_char_n(2);
-            let c4 = self.buf.next_char_n(3);
-            self.buf.inc_n(4);
-            // TODO: maybe check if we've hex digits...
-            (Self::get_hex_digit(c1) << 12
-                | Self::get_hex_digit(c2) << 8
-                | Self::get_hex_digit(c3) << 4
-                | Self::get_hex_digit(c4)) as u32
+    pub(crate) fn get_universal(&mut self, start: usize, n: usize) -> u32 {
+        if n == 4 && self.is_next_char(b'{') {
+            return self.get_delimited(start, 16, true);
+        }
+
+        let mut num = 0;
+        for _ in 0..n {
+            let n = if self.buf.has_char() {
+                Self::get_hex_digit(self.buf.next_char())
+            } else {
+                16
+            };
+            if n == 16 {
+                self.push_escape_error(start, true);
+                return 0xFFFD;
+            }
+            self.buf.inc();
+            num = num << 4 | n as u32;
+        }
+
+        if num > 0x10_FFFF || (0xD800..=0xDFFF).contains(&num) {
+            self.push_escape_error(start, true);
+            0xFFFD
         } else {
-            0
+            num
         }
     }
 
-    #[inline(always)]
-    pub(crate) fn get_universal_long(&mut self) -> u32 {
-        // it has 8 digits
-        if self.buf.rem() >= 8 {
-            let c1 = self.buf.next_char();
-            let c2 = self.buf.next_char_n(1);
-            let c3 = self.buf.next_char_n(2);
-            let c4 = self.buf.next_char_n(3);
-            let c5 = self.buf.next_char_n(4);
-            let c6 = self.buf.next_char_n(5);
-            let c7 = self.buf.next_char_n(6);
-            let c8 = self.buf.next_char_n(7);
-            self.buf.inc_n(8);
-            // TODO: maybe check if we've hex digits...
-            (Self::get_hex_digit(c1) << 28
-                | Self::get_hex_digit(c2) << 24
-                | Self::get_hex_digit(c3) << 20
-                | Self::get_hex_digit(c4) << 16
-                | Self::get_hex_digit(c5) << 12
-                | Self::get_hex_digit(c6) << 8
-                | Self::get_hex_digit(c7) << 4
-                | Self::get_hex_digit(c8)) as u32
+    fn is_next_char(&self, c: u8) -> bool {
+        self.buf.has_char() && self.buf.next_char() == c
+    }
+
+    /// Get a delimited escape (C++23): \o{...}, \x{...} or \u{...} when ucn is true.
+    /// The next char is the opening brace.
+    fn get_delimited(&mut self, start: usize, base: u64, ucn: bool) -> u32 {
+        self.buf.inc();
+        let mut num: u32 = 0;
+        let mut digits = 0;
+        let mut valid = true;
+        loop {
+            if !self.buf.has_char() {
+                valid = false;
+                break;
+            }
+            let c = self.buf.next_char();
+            if c == b'}' {
+                self.buf.inc();
+                break;
+            }
+            let d = Self::get_hex_digit(c);
+            if d >= base {
+                // Don't eat the quote or the end of the line
+                valid = false;
+                break;
+            }
+            self.buf.inc();
+            digits += 1;
+            match num.checked_mul(base as u32) {
+                Some(n) => num = n | d as u32,
+                None => valid = false,
+            }
+        }
+
+        let valid = valid && digits != 0;
+        if ucn && (!valid || std::char::from_u32(num).is_none()) {
+            self.push_escape_error(start, true);
+            0xFFFD
+        } else {
+            if !valid {
+                self.push_escape_error(start, false);
+            }
+            num
+        }
+    }
+
+    /// Get a named escape (C++23): \N{LATIN SMALL LETTER A}
+    fn get_named(&mut self, start: usize) -> u32 {
+        let mut c = None;
+        if self.is_next_char(b'{') {
+            self.buf.inc();
+            let name = self.buf.pos();
+            while self.buf.has_char() {
+                let x = self.buf.next_char();
+                if x == b'}' {
+                    c = std::str::from_utf8(self.buf.slice(name))
+                        .ok()
+                        .and_then(unicode_names2::character);
+                    self.buf.inc();
+                    break;
+                } else if !(x.is_ascii_uppercase() || x.is_ascii_digit() || x == b' ' || x == b'-')
+                {
+                    break;
+                }
+                self.buf.inc();
+            }
+        }
+
+        if let Some(c) = c {
+            u32::from(c)
         } else {
-            0
+            self.push_escape_error(start, true);
+            0xFFFD
         }
     }
 
+    fn push_escape_error(&mut self, start: usize, ucn: bool) {
+        let sp = self.span_of(start, self.buf.pos());
+        let escape = String::from_utf8_lossy(self.buf.slice(start)).into_owned();
+        self.errors.push(if ucn {
+            LexerError::InvalidUcn { sp, ucn: escape }
+        } else {
+            LexerError::InvalidEscape { sp, escape }
+        });
+    }
+
     #[inline(always)]
     pub(crate) fn get_escape(&mut self) -> Option<u32> {
+        let start = self.buf.pos() - 1;
         if self.buf.has_char() {
             let c = self.buf.next_char();
             self.buf.inc();
@@ -184,14 +264,24 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                     let first = u32::from(c - b'0');
                     Some(self.get_oct_char(first))
                 }
-                Kind::HEX => Some(self.get_hex_char()),
-                Kind::UNS => Some(self.get_universal_short()),
-                Kind::UNL => Some(self.get_universal_long()),
+                Kind::OOO => Some(if self.is_next_char(b'{') {
+                    self.get_delimited(start, 8, false)
+                } else {
+                    self.push_escape_error(start, false);
+                    u32::from(c)
+                }),
+                Kind::HEX => Some(self.get_hex_char(start)),
+                Kind::UNS => Some(self.get_universal(start, 4)),
+                Kind::UNL => Some(self.get_universal(start, 8)),
+                Kind::NAM => Some(self.get_named(start)),
                 Kind::NLI => {
                     self.buf.add_new_line();
                     None
                 }
-                Kind::NON => unreachable!(),
+                Kind::NON => {
+                    self.push_escape_error(start, false);
+                    Some(u32::from(c))
+                }
             }
         } else {
             None
@@ -209,29 +299,87 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
     }
 
     #[inline(always)]
-    pub(crate) fn get_c_char_u32(&mut self) -> u32 {
-        // TODO: LiteralSupport.cpp
+    pub(crate) fn get_c_char_u32(&mut self, typ: StringType) -> u32 {
         let mut val: u32 = 0;
+        let mut count = 0;
+        // The last char is an octal or hexadecimal escape (so it's a code unit)
+        let mut unit = false;
         loop {
             if self.buf.has_char() {
                 let c = self.buf.next_char();
                 if c == b'\\' {
                     self.buf.inc();
+                    unit = self.buf.has_char()
+                        && matches!(self.buf.next_char(), b'0'..=b'7' | b'x' | b'o');
                     if let Some(e) = self.get_escape() {
                         val = (val << Self::get_shift(e)) | e;
+                        count += 1;
                     }
                 } else if c == b'\'' {
                     self.buf.inc();
                     break;
+                } else if c == b'\n' {
+                    // Stop at the end of the line to not eat the rest of the file
+                    let sp = self.span();
+                    self.errors.push(LexerError::UnterminatedChar { sp });
+                    return val;
                 } else {
                     self.buf.inc();
+                    unit = false;
                     val = (val << 8) | u32::from(c);
+                    // Count the chars and not the UTF-8 bytes
+                    if c & 0xC0 != 0x80 {
+                        count += 1;
+                    }
                 }
             } else {
-                break;
+                let sp = self.span();
+                self.errors.push(LexerError::UnterminatedChar { sp });
+                return val;
+            }
+        }
+
+        if count != 1 {
+            let sp = self.span();
+            self.errors.push(if count == 0 {
+                LexerError::EmptyChar { sp }
+            } else {
+                LexerError::MultiCharConstant { sp }
+            });
+        }
+
+        let val = Self::decode_c_char(val);
+        if count == 1 {
+            self.check_c_char(val, unit, typ);
+        }
+        val
+    }
+
+    /// Check that a char fits in one code unit of the type of the literal
+    fn check_c_char(&mut self, val: u32, unit: bool, typ: StringType) {
+        let bits = match typ {
+            StringType::None | StringType::U8 => 8,
+            StringType::U => 16,
+            StringType::L => self.dialect.wchar_bits(),
+            _ => 32,
+        };
+        let fits = |bits: u32| bits == 32 || val >> bits == 0;
+        if unit {
+            if !fits(bits) {
+                let sp = self.span();
+                self.errors.push(LexerError::EscapeOutOfRange { sp });
+            }
+        } else if typ != StringType::None {
+            // A UTF-8 code unit is an ASCII char
+            if !fits(if typ == StringType::U8 { 7 } else { bits }) {
+                let sp = self.span();
+                self.errors.push(LexerError::CharTooLarge { sp });
             }
         }
+    }
 
+    /// Get the value of the chars got as UTF-8 bytes
+    fn decode_c_char(val: u32) -> u32 {
         // TODO: try to improve that stuff and check that's valid
         if val <= 0xFF {
             val
@@ -260,15 +408,14 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
 
     #[inline(always)]
     pub(super) fn get_char(&mut self, typ: StringType) -> Token {
-        let c = self.get_c_char_u32();
+        let c = self.get_c_char_u32(typ);
         if let Some(suf) = self.get_suffix() {
-            let c = Box::new((c, suf));
             match typ {
-                StringType::None => Token::LiteralCharUD(c),
-                StringType::L => Token::LiteralLCharUD(c),
-                StringType::UU => Token::LiteralUUCharUD(c),
-                StringType::U => Token::LiteralUCharUD(c),
-                StringType::U8 => Token::LiteralU8CharUD(c),
+                StringType::None => Token::LiteralCharUD(c, suf),
+                StringType::L => Token::LiteralLCharUD(c, suf),
+                StringType::UU => Token::LiteralUUCharUD(c, suf),
+                StringType::U => Token::LiteralUCharUD(c, suf),
+                StringType::U8 => Token::LiteralU8CharUD(c, suf),
                 _ => unreachable!(),
             }
         } else {
@@ -288,12 +435,15 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
 mod tests {
 
     use super::*;
+    use crate::args::Standard;
+    use crate::lexer::lexer::Dialect;
     use crate::lexer::preprocessor::context::DefaultContext;
+    use crate::lexer::symbol::Symbol;
     use pretty_assertions::assert_eq;
 
     #[test]
     fn test_char() {
-        let mut p = Lexer::<DefaultContext>::new(b"'a' 'b' 'c' '\\t' '\\n' '\\\'' '\\\"' '\\12' '\\1' '\\x12' '\\x1f\\x85' 'abcd' '\\u1a2b' '\\U1a2B3c4D'");
+        let mut p = Lexer::<DefaultContext>::new(b"'a' 'b' 'c' '\\t' '\\n' '\\\'' '\\\"' '\\12' '\\1' '\\x12' '\\x1f\\x85' 'abcd' '\\u1a2b' '\\U0001f47F'");
         assert_eq!(p.next_token(), Token::LiteralChar(u32::from('a')));
         assert_eq!(p.next_token(), Token::LiteralChar(u32::from('b')));
         assert_eq!(p.next_token(), Token::LiteralChar(u32::from('c')));
@@ -307,7 +457,7 @@ mod tests {
         assert_eq!(p.next_token(), Token::LiteralChar(0x1f85));
         assert_eq!(p.next_token(), Token::LiteralChar(0x61626364));
         assert_eq!(p.next_token(), Token::LiteralChar(0x1a2b));
-        assert_eq!(p.next_token(), Token::LiteralChar(0x1a2b3c4d));
+        assert_eq!(p.next_token(), Token::LiteralChar(0x1f47f));
     }
 
     #[test]
@@ -331,23 +481,181 @@ mod tests {
         let mut p = Lexer::<DefaultContext>::new(b"'a'_f u'b'_g U'c'_h u8'd'_i L'e'_j");
         assert_eq!(
             p.next_token(),
-            Token::LiteralCharUD(Box::new((u32::from('a'), "_f".to_string())))
+            Token::LiteralCharUD(u32::from('a'), Symbol::intern("_f"))
         );
         assert_eq!(
             p.next_token(),
-            Token::LiteralUCharUD(Box::new((u32::from('b'), "_g".to_string())))
+            Token::LiteralUCharUD(u32::from('b'), Symbol::intern("_g"))
         );
         assert_eq!(
             p.next_token(),
-            Token::LiteralUUCharUD(Box::new((u32::from('c'), "_h".to_string())))
+            Token::LiteralUUCharUD(u32::from('c'), Symbol::intern("_h"))
         );
         assert_eq!(
             p.next_token(),
-            Token::LiteralU8CharUD(Box::new((u32::from('d'), "_i".to_string())))
+            Token::LiteralU8CharUD(u32::from('d'), Symbol::intern("_i"))
         );
         assert_eq!(
             p.next_token(),
-            Token::LiteralLCharUD(Box::new((u32::from('e'), "_j".to_string())))
+            Token::LiteralLCharUD(u32::from('e'), Symbol::intern("_j"))
+        );
+    }
+
+    #[test]
+    fn test_char_delimited_and_named() {
+        let mut p = Lexer::<DefaultContext>::new(
+            b"'\\x{41}' '\\o{101}' '\\u{e9}' U'\\u{1F600}' '\\N{LATIN SMALL LETTER A}' U'\\N{GRINNING FACE}'",
+        );
+        assert_eq!(p.next_token(), Token::LiteralChar(0x41));
+        assert_eq!(p.next_token(), Token::LiteralChar(0o101));
+        assert_eq!(p.next_token(), Token::LiteralChar(0xe9));
+        assert_eq!(p.next_token(), Token::LiteralUUChar(0x1F600));
+        assert_eq!(p.next_token(), Token::LiteralChar(u32::from('a')));
+        assert_eq!(p.next_token(), Token::LiteralUUChar(0x1F600));
+        assert!(p.errors.is_empty());
+
+        let mut p = Lexer::<DefaultContext>::new(
+            b"'\\x{}' '\\o12' '\\u{110000}' '\\N{NOT A NAME}' '\\N{latin}'",
+        );
+        let toks: Vec<_> = std::iter::from_fn(|| match p.next_token() {
+            Token::Eof => None,
+            tok => Some(tok),
+        })
+        .collect();
+        assert_eq!(toks.len(), 5);
+
+        let errors: Vec<_> = p
+            .errors
+            .iter()
+            .map(|e| {
+                let e = e.stringly();
+                (e.message, e.sp.start.pos, e.sp.end.pos)
+            })
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                ("invalid escape sequence \\x{}".to_string(), 1, 5),
+                ("invalid escape sequence \\o".to_string(), 8, 10),
+                ("multi-character character constant".to_string(), 7, 13),
+                (
+                    "invalid universal character \\u{110000}".to_string(),
+                    15,
+                    25
+                ),
+                (
+                    "invalid universal character \\N{NOT A NAME}".to_string(),
+                    28,
+                    42
+                ),
+                // The rest of the name is taken as the content of the char
+                ("invalid universal character \\N{".to_string(), 45, 48),
+                ("multi-character character constant".to_string(), 44, 55),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_char_range() {
+        let mut p = Lexer::<DefaultContext>::new(
+            "u'\\U0001F600' u8'é' u8'\\u00e9' u'\\xffff1' '\\x123' '\\777' L'\\x10000' \
+             u8'\\xe9' u'\\uffff' U'\\U0001F600' L'\\U0001F600' '\\xff' u8'a'"
+                .as_bytes(),
+        );
+        let toks: Vec<_> = std::iter::from_fn(|| match p.next_token() {
+            Token::Eof => None,
+            tok => Some(tok),
+        })
+        .collect();
+        assert_eq!(toks[0], Token::LiteralUChar(0x1F600));
+        assert_eq!(toks[3], Token::LiteralUChar(0xFFFF1));
+        assert_eq!(toks.len(), 13);
+
+        let errors: Vec<_> = p
+            .errors
+            .iter()
+            .map(|e| {
+                let e = e.stringly();
+                (e.message, e.sp.start.pos)
+            })
+            .collect();
+        let too_large = "character too large for enclosing character literal type".to_string();
+        let out_of_range = "escape sequence out of range".to_string();
+        assert_eq!(
+            errors,
+            vec![
+                (too_large.clone(), 0),
+                (too_large.clone(), 14),
+                (too_large, 21),
+                (out_of_range.clone(), 32),
+                (out_of_range.clone(), 43),
+                (out_of_range, 51),
+            ]
+        );
+
+        // wchar_t has 16 bits on Windows
+        let mut p = Lexer::<DefaultContext>::new(b"L'\\x10000' L'\\U0001F600' L'\\xffff'");
+        p.set_dialect(Dialect::from_standard(Standard::Cpp17) | Dialect::MS);
+        p.consume_all();
+        let errors: Vec<_> = p.errors.iter().map(|e| e.stringly().message).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "escape sequence out of range",
+                "character too large for enclosing character literal type",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_char_errors() {
+        let mut p =
+            Lexer::<DefaultContext>::new(b"'' 'ab' '\\q' '\\x' '\\u12' '\\UFFFFFFFF' 'a\nb");
+        let mut toks = Vec::new();
+        loop {
+            match p.next_token() {
+                Token::Eof => break,
+                Token::Eol => {}
+                tok => toks.push(tok),
+            }
+        }
+        assert_eq!(
+            toks,
+            vec![
+                Token::LiteralChar(0),
+                Token::LiteralChar(0x6162),
+                Token::LiteralChar(u32::from('q')),
+                Token::LiteralChar(0),
+                Token::LiteralChar(0xFFFD),
+                Token::LiteralChar(0xFFFD),
+                Token::LiteralChar(u32::from('a')),
+                Token::Identifier(Symbol::intern("b")),
+            ]
+        );
+
+        let errors: Vec<_> = p
+            .errors
+            .iter()
+            .map(|e| {
+                let e = e.stringly();
+                (e.message, e.sp.start.pos, e.sp.end.pos)
+            })
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                ("empty character constant".to_string(), 0, 2),
+                ("multi-character character constant".to_string(), 3, 7),
+                ("invalid escape sequence \\q".to_string(), 9, 11),
+                ("invalid escape sequence \\x".to_string(), 14, 16),
+                ("invalid universal character \\u12".to_string(), 19, 23),
+                (
+                    "invalid universal character \\UFFFFFFFF".to_string(),
+                    26,
+                    36
+                ),
+                ("missing terminating ' character".to_string(), 38, 40),
+            ]
         );
     }
 }
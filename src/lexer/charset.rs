@@ -0,0 +1,221 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+/// The encoding of the source files (-finput-charset or /source-charset).
+/// A BOM (UTF-8 or UTF-16) takes precedence over it.
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub enum Charset {
+    #[default]
+    Utf8,
+    Latin1,
+    Windows1252,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Charset {
+    pub fn from_name(name: &str) -> Option<Self> {
+        use self::Charset::*;
+        Some(match name.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" | "65001" => Utf8,
+            "iso-8859-1" | "iso8859-1" | "iso_8859-1" | "latin1" | "l1" | "28591" => Latin1,
+            "windows-1252" | "cp1252" | "1252" => Windows1252,
+            "utf-16le" | "utf16le" | "1200" => Utf16Le,
+            // Without a BOM, UTF-16 is big endian
+            "utf-16" | "utf16" | "utf-16be" | "utf16be" | "1201" => Utf16Be,
+            _ => return None,
+        })
+    }
+
+    /// Get the charset to use for a file: the one from the BOM if any
+    fn detect(self, src: &[u8]) -> Self {
+        if src.starts_with(b"\xEF\xBB\xBF") {
+            Charset::Utf8
+        } else if src.starts_with(b"\xFF\xFE") {
+            Charset::Utf16Le
+        } else if src.starts_with(b"\xFE\xFF") {
+            Charset::Utf16Be
+        } else {
+            self
+        }
+    }
+}
+
+/// The chars 0x80 to 0x9F in Windows-1252 (the unassigned ones are kept as is)
+const WINDOWS_1252: [u16; 32] = [
+    0x20AC, 0x0081, 0x201A, 0x0192, 0x201E, 0x2026, 0x2020, 0x2021, 0x02C6, 0x2030, 0x0160, 0x2039,
+    0x0152, 0x008D, 0x017D, 0x008F, 0x0090, 0x2018, 0x2019, 0x201C, 0x201D, 0x2022, 0x2013, 0x2014,
+    0x02DC, 0x2122, 0x0161, 0x203A, 0x0153, 0x009D, 0x017E, 0x0178,
+];
+
+/// A run of chars which have the same lengths in the text and in the source
+#[derive(Clone, Copy, Debug)]
+struct Run {
+    /// The position in the text
+    start: usize,
+    /// The position in the source
+    orig: usize,
+    len: u8,
+    orig_len: u8,
+}
+
+/// The map from the positions in the transcoded text to the ones in the source
+#[derive(Clone, Debug, Default)]
+pub(crate) struct OffsetMap {
+    runs: Vec<Run>,
+}
+
+impl OffsetMap {
+    #[inline(always)]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+
+    fn push(&mut self, start: usize, orig: usize, len: usize, orig_len: usize) {
+        let (len, orig_len) = (len as u8, orig_len as u8);
+        if let Some(last) = self.runs.last() {
+            if last.len == len && last.orig_len == orig_len {
+                return;
+            }
+        }
+        self.runs.push(Run {
+            start,
+            orig,
+            len,
+            orig_len,
+        });
+    }
+
+    /// Get the position in the source of a position in the text
+    /// (a position in the middle of a char gives the beginning of the char)
+    pub(crate) fn original(&self, pos: usize) -> usize {
+        let i = self.runs.partition_point(|r| r.start <= pos);
+        if let Some(r) = i.checked_sub(1).map(|i| &self.runs[i]) {
+            r.orig + (pos - r.start) / r.len as usize * r.orig_len as usize
+        } else {
+            pos
+        }
+    }
+
+    /// Get the position in the text of a position in the source
+    pub(crate) fn transcoded(&self, orig: usize) -> usize {
+        let i = self.runs.partition_point(|r| r.orig <= orig);
+        if let Some(r) = i.checked_sub(1).map(|i| &self.runs[i]) {
+            r.start + (orig - r.orig) / r.orig_len as usize * r.len as usize
+        } else {
+            0
+        }
+    }
+}
+
+/// Get the source in UTF-8 and the map to go back to the source
+/// (None when it's already in UTF-8)
+pub(crate) fn transcode(src: &[u8], charset: Charset) -> Option<(Vec<u8>, OffsetMap)> {
+    let charset = charset.detect(src);
+    let mut text = Vec::with_capacity(src.len() + src.len() / 8);
+    let mut map = OffsetMap::default();
+    let mut push = |text: &mut Vec<u8>, orig: usize, orig_len: usize, c: char| {
+        let start = text.len();
+        text.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+        map.push(start, orig, text.len() - start, orig_len);
+    };
+
+    match charset {
+        Charset::Utf8 => return None,
+        Charset::Latin1 | Charset::Windows1252 => {
+            if src.is_ascii() {
+                return None;
+            }
+            for (i, &c) in src.iter().enumerate() {
+                let c = match c {
+                    0x80..=0x9F if charset == Charset::Windows1252 => {
+                        u32::from(WINDOWS_1252[(c - 0x80) as usize])
+                    }
+                    c => u32::from(c),
+                };
+                push(&mut text, i, 1, std::char::from_u32(c).unwrap());
+            }
+        }
+        Charset::Utf16Le | Charset::Utf16Be => {
+            let start = if src.starts_with(b"\xFF\xFE") || src.starts_with(b"\xFE\xFF") {
+                2
+            } else {
+                0
+            };
+            let units = src[start..].chunks_exact(2).map(|u| {
+                if charset == Charset::Utf16Le {
+                    u16::from_le_bytes([u[0], u[1]])
+                } else {
+                    u16::from_be_bytes([u[0], u[1]])
+                }
+            });
+
+            let mut orig = start;
+            for c in std::char::decode_utf16(units) {
+                let (c, orig_len) = match c {
+                    Ok(c) => (c, c.len_utf16() * 2),
+                    Err(_) => ('\u{FFFD}', 2),
+                };
+                push(&mut text, orig, orig_len, c);
+                orig += orig_len;
+            }
+        }
+    }
+
+    Some((text, map))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_latin1() {
+        assert!(transcode(b"int a;", Charset::Latin1).is_none());
+
+        let src = b"// caf\xE9 \x80\nint a;";
+        let (latin1, map) = transcode(src, Charset::Latin1).unwrap();
+        assert_eq!(latin1, "// café \u{80}\nint a;".as_bytes());
+        let (text, map1252) = transcode(src, Charset::Windows1252).unwrap();
+        assert_eq!(text, "// café €\nint a;".as_bytes());
+
+        let int = src.len() - 6;
+        assert_eq!(map.original(latin1.len() - 6), int);
+        assert_eq!(map.transcoded(int), latin1.len() - 6);
+        assert_eq!(map1252.original(text.len() - 6), int);
+        assert_eq!(map1252.transcoded(int), text.len() - 6);
+        // In the middle of €
+        assert_eq!(map1252.original(10), 8);
+    }
+
+    #[test]
+    fn test_utf16() {
+        let s = "int a = 1; // 😀é\n";
+        let le: Vec<u8> = s.encode_utf16().flat_map(|u| u.to_le_bytes()).collect();
+        let be: Vec<u8> = s.encode_utf16().flat_map(|u| u.to_be_bytes()).collect();
+
+        let (text, map) = transcode(&le, Charset::Utf16Le).unwrap();
+        assert_eq!(text, s.as_bytes());
+        let (text, _) = transcode(&be, Charset::Utf16Be).unwrap();
+        assert_eq!(text, s.as_bytes());
+
+        for (i, _) in s.char_indices() {
+            let orig = s[..i].encode_utf16().count() * 2;
+            assert_eq!(map.original(i), orig);
+            assert_eq!(map.transcoded(orig), i);
+        }
+
+        // The BOM takes precedence over the charset
+        let mut bom = b"\xFE\xFF".to_vec();
+        bom.extend_from_slice(&be);
+        let (text, map) = transcode(&bom, Charset::Latin1).unwrap();
+        assert_eq!(text, s.as_bytes());
+        assert_eq!(map.original(0), 2);
+        assert_eq!(map.original(4), 10);
+        assert!(transcode(b"\xEF\xBB\xBF\xE9", Charset::Latin1).is_none());
+    }
+}
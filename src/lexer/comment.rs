@@ -5,32 +5,109 @@
 
 use super::lexer::{Lexer, Token};
 use super::preprocessor::context::PreprocContext;
+use super::tools::{find_comment_end, find_line_end};
+
+/// Collect the doc comments (///, //!, /** */ and /*! */) between the useful tokens
+#[derive(Clone, Debug, Default)]
+pub(crate) struct DocComments {
+    /// The doc comment before the next token
+    pending: Option<String>,
+    /// The trailing doc comment (///<, /**< ...) before the next token
+    pending_trailing: Option<String>,
+    /// The doc comments before the last returned token
+    leading: Option<String>,
+    trailing: Option<String>,
+    /// The last doc comment is a single line one
+    last_line: bool,
+    /// Number of newlines since the last doc comment
+    newlines: usize,
+    /// A newline has been seen since the last token
+    new_line: bool,
+}
+
+impl DocComments {
+    /// Get None if the comment isn't a doc one or Some(true) for a trailing doc comment
+    /// (the comment is given without its leading // or /*)
+    fn get_kind(comment: &[u8], line: bool) -> Option<bool> {
+        let rest = match (line, comment) {
+            (true, [b'/', rest @ ..]) if rest.first() != Some(&b'/') => rest,
+            (false, [b'*', rest @ ..]) if !rest.is_empty() && rest[0] != b'*' => rest,
+            (_, [b'!', rest @ ..]) => rest,
+            _ => {
+                return None;
+            }
+        };
+        Some(rest.first() == Some(&b'<'))
+    }
+
+    pub(crate) fn on_comment(&mut self, comment: &[u8], line: bool) {
+        let trailing = if let Some(trailing) = Self::get_kind(comment, line) {
+            trailing
+        } else {
+            return;
+        };
+
+        let comment = String::from_utf8_lossy(comment);
+        let raw = if line {
+            format!("//{}", comment)
+        } else {
+            format!("/*{}*/", comment)
+        };
+
+        let mergeable = line && self.last_line && self.newlines <= 1;
+        let target = if trailing {
+            &mut self.pending_trailing
+        } else {
+            &mut self.pending
+        };
+
+        if let (true, Some(target)) = (mergeable, target.as_mut()) {
+            // Consecutive single line comments are a single doc comment
+            target.push('\n');
+            target.push_str(&raw);
+        } else if !trailing || !self.new_line {
+            // A trailing comment must be on the line of the previous token
+            *target = Some(raw);
+        }
+
+        self.last_line = line;
+        self.newlines = 0;
+    }
+
+    pub(crate) fn on_newline(&mut self) {
+        self.newlines += 1;
+        self.new_line = true;
+    }
+
+    pub(crate) fn on_token(&mut self) {
+        self.leading = self.pending.take();
+        self.trailing = self.pending_trailing.take();
+        self.last_line = false;
+        self.new_line = false;
+    }
+
+    pub(crate) fn take_leading(&mut self) -> Option<String> {
+        self.leading.take()
+    }
+
+    pub(crate) fn take_trailing(&mut self) -> Option<String> {
+        self.trailing.take()
+    }
+}
 
 impl<'a, PC: PreprocContext> Lexer<'a, PC> {
     pub(crate) fn get_multiline_comment(&mut self) -> Token {
         self.buf.inc();
         let spos = self.buf.pos();
-        loop {
-            if self.buf.has_char() {
-                let c = self.buf.next_char();
-                if c == b'/' {
-                    // TODO: we can have a continuation line between '*' and '/'
-                    let c = self.buf.prev_char();
-                    if c == b'*' && self.buf.pos() != spos {
-                        let comment = self.buf.slice_m_n(spos, 1);
-                        self.buf.inc();
-                        self.comment = Some(comment);
-                        return Token::Comment;
-                    }
-                } else if c == b'\n' {
-                    self.buf.add_new_line();
-                }
-                self.buf.inc();
-            } else {
-                break;
-            }
+        if let Some(end) = find_comment_end(self.buf.remainder()) {
+            self.buf.advance(end);
+            let comment = self.buf.slice_m_n(spos, 1);
+            self.buf.inc();
+            self.comment = Some(comment);
+            return Token::Comment;
         }
 
+        self.buf.advance(self.buf.rem());
         let comment = self.buf.slice(spos);
         self.comment = Some(comment);
         Token::Comment
@@ -39,30 +116,9 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
     pub(crate) fn get_single_comment(&mut self) -> Token {
         let spos = self.buf.pos() + 1;
         self.buf.inc();
-        loop {
-            if self.buf.has_char() {
-                let c = self.buf.next_char();
-                self.buf.inc();
-                if c == b'\\' {
-                    self.buf.inc();
-                    if self.buf.has_char() {
-                        let c = self.buf.next_char();
-                        if c == b'\n' {
-                            self.buf.add_new_line();
-                        }
-                    }
-                } else if c == b'\n' {
-                    //self.buf.add_new_line();
-                    let comment = self.buf.slice_m_n(spos, 1);
-                    self.buf.dec();
-                    self.comment = Some(comment);
-                    return Token::Comment;
-                }
-            } else {
-                break;
-            }
-        }
-
+        // the final newline (if any) isn't consumed: it'll be counted later
+        let end = find_line_end(self.buf.remainder());
+        self.buf.advance(end);
         let comment = self.buf.slice(spos);
         self.comment = Some(comment);
         Token::Comment
@@ -70,48 +126,18 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
 
     #[inline(always)]
     pub(crate) fn skip_multiline_comment(&mut self) {
-        let spos = self.buf.pos();
-        loop {
-            if self.buf.has_char() {
-                let c = self.buf.next_char();
-                if c == b'/' {
-                    let c = self.buf.prev_char();
-                    if c == b'*' && self.buf.pos() != spos {
-                        self.buf.inc();
-                        break;
-                    }
-                } else if c == b'\n' {
-                    self.buf.add_new_line();
-                }
-                self.buf.inc();
-            } else {
-                break;
-            }
+        if let Some(end) = find_comment_end(self.buf.remainder()) {
+            self.buf.advance(end + 1);
+        } else {
+            self.buf.advance(self.buf.rem());
         }
     }
 
     #[inline(always)]
     pub(crate) fn skip_single_comment(&mut self) {
-        loop {
-            if self.buf.has_char() {
-                let c = self.buf.next_char();
-                if c == b'\n' {
-                    // no buf.add_new_line here (will be done later)
-                    break;
-                } else if c == b'\\' {
-                    self.buf.inc();
-                    if self.buf.has_char() {
-                        let c = self.buf.next_char();
-                        if c == b'\n' {
-                            self.buf.add_new_line();
-                        }
-                    }
-                }
-                self.buf.inc();
-            } else {
-                break;
-            }
-        }
+        // no buf.add_new_line for the final newline here (will be done later)
+        let end = find_line_end(self.buf.remainder());
+        self.buf.advance(end);
     }
 }
 
@@ -119,7 +145,9 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
 mod tests {
 
     use super::*;
+    use crate::lexer::lexer::TLexer;
     use crate::lexer::preprocessor::context::DefaultContext;
+    use crate::lexer::Symbol;
     use pretty_assertions::assert_eq;
 
     #[test]
@@ -142,4 +170,30 @@ mod tests {
         assert_eq!(p.next_token(), Token::Comment);
         assert_eq!(p.get_comment().unwrap(), b"/ ");
     }
+
+    #[test]
+    fn test_comment_lines() {
+        let mut p = Lexer::<DefaultContext>::new(b"/* a\n b\n*/ x // c \\\n d\ny");
+        assert_eq!(p.next_token(), Token::Comment);
+        assert_eq!(p.next_useful(), Token::Identifier(Symbol::intern("x")));
+        assert_eq!(p.span().start.line, 3);
+        assert_eq!(p.span().start.column, 4);
+        assert_eq!(p.next_token(), Token::Comment);
+        assert_eq!(p.get_comment().unwrap(), b" c  d");
+        assert_eq!(p.next_useful(), Token::Identifier(Symbol::intern("y")));
+        assert_eq!(p.span().start.line, 5);
+    }
+
+    #[test]
+    fn test_comment_eof() {
+        let mut p = Lexer::<DefaultContext>::new(b"/* a\n b");
+        assert_eq!(p.next_token(), Token::Comment);
+        assert_eq!(p.get_comment().unwrap(), b" a\n b");
+        assert_eq!(p.next_token(), Token::Eof);
+
+        let mut p = Lexer::<DefaultContext>::new(b"// a \\");
+        assert_eq!(p.next_token(), Token::Comment);
+        assert_eq!(p.get_comment().unwrap(), b" a \\");
+        assert_eq!(p.next_token(), Token::Eof);
+    }
 }
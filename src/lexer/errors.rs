@@ -3,13 +3,75 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use super::number::Radix;
 use crate::errors::{Span, StringlyError};
 
 #[derive(Clone, Debug)]
 pub enum LexerError {
-    ErrorDirective { sp: Span, msg: String },
-    EndifWithoutPreceedingIf { sp: Span },
-    FileIncludeError { sp: Span, file: String },
+    ErrorDirective {
+        sp: Span,
+        msg: String,
+    },
+    EndifWithoutPreceedingIf {
+        sp: Span,
+    },
+    FileIncludeError {
+        sp: Span,
+        file: String,
+    },
+    IntegerTooLarge {
+        sp: Span,
+        literal: String,
+    },
+    FloatTooLarge {
+        sp: Span,
+        literal: String,
+    },
+    InvalidNumberSuffix {
+        sp: Span,
+        literal: String,
+        suffix: String,
+    },
+    InvalidDigit {
+        sp: Span,
+        digit: char,
+        radix: Radix,
+    },
+    MissingDigits {
+        sp: Span,
+        literal: String,
+        radix: Radix,
+    },
+    UnterminatedString {
+        sp: Span,
+    },
+    UnterminatedChar {
+        sp: Span,
+    },
+    EmptyChar {
+        sp: Span,
+    },
+    MultiCharConstant {
+        sp: Span,
+    },
+    InvalidEscape {
+        sp: Span,
+        escape: String,
+    },
+    InvalidUcn {
+        sp: Span,
+        ucn: String,
+    },
+    EscapeOutOfRange {
+        sp: Span,
+    },
+    CharTooLarge {
+        sp: Span,
+    },
+    StrayCharacter {
+        sp: Span,
+        c: u8,
+    },
 }
 
 impl LexerError {
@@ -23,6 +85,64 @@ impl LexerError {
             FileIncludeError { sp, file } => {
                 (*sp, format!("can't open file {} for inclusion", file))
             }
+            IntegerTooLarge { sp, literal } => (
+                *sp,
+                format!("integer literal {} is too large for its type", literal),
+            ),
+            FloatTooLarge { sp, literal } => (
+                *sp,
+                format!(
+                    "floating point literal {} is too large for its type",
+                    literal
+                ),
+            ),
+            InvalidNumberSuffix {
+                sp,
+                literal,
+                suffix,
+            } => (
+                *sp,
+                format!("invalid suffix {} on numeric literal {}", suffix, literal),
+            ),
+            InvalidDigit { sp, digit, radix } => (
+                *sp,
+                format!(
+                    "invalid digit '{}' in {} literal",
+                    digit,
+                    if *radix == Radix::Bin {
+                        "binary"
+                    } else {
+                        "octal"
+                    }
+                ),
+            ),
+            MissingDigits { sp, literal, radix } => (
+                *sp,
+                format!(
+                    "no digits in {} literal {}",
+                    if *radix == Radix::Bin {
+                        "binary"
+                    } else {
+                        "hexadecimal"
+                    },
+                    literal
+                ),
+            ),
+            UnterminatedString { sp } => (*sp, "missing terminating \" character".to_owned()),
+            UnterminatedChar { sp } => (*sp, "missing terminating ' character".to_owned()),
+            EmptyChar { sp } => (*sp, "empty character constant".to_owned()),
+            MultiCharConstant { sp } => (*sp, "multi-character character constant".to_owned()),
+            InvalidEscape { sp, escape } => (*sp, format!("invalid escape sequence {}", escape)),
+            InvalidUcn { sp, ucn } => (*sp, format!("invalid universal character {}", ucn)),
+            EscapeOutOfRange { sp } => (*sp, "escape sequence out of range".to_owned()),
+            CharTooLarge { sp } => (
+                *sp,
+                "character too large for enclosing character literal type".to_owned(),
+            ),
+            StrayCharacter { sp, c } => (
+                *sp,
+                format!("stray '{}' in program", std::ascii::escape_default(*c)),
+            ),
         };
         StringlyError { message, sp }
     }
@@ -8,32 +8,38 @@ use crate::lexer::{TLexer, Token};
 
 #[derive(Clone, Debug)]
 pub struct SavedLexer {
-    toks: Vec<Token>,
+    toks: Vec<(Token, Span)>,
     pos: usize,
 }
 
 impl TLexer for SavedLexer {
     fn next_useful(&mut self) -> Token {
-        if let Some(tok) = self.toks.get(self.pos) {
+        if let Some((tok, _)) = self.toks.get(self.pos) {
             self.pos += 1;
-            tok.clone()
+            *tok
         } else {
             Token::Eof
         }
     }
 
+    /// The span of the last returned token (Eof has the span of the last saved token)
     fn span(&self) -> Span {
-        Span::default()
+        let pos = self.pos.min(self.toks.len());
+        if pos == 0 {
+            Span::default()
+        } else {
+            self.toks[pos - 1].1
+        }
     }
 }
 
 impl SavedLexer {
-    pub fn new(toks: Vec<Token>) -> Self {
+    pub fn new(toks: Vec<(Token, Span)>) -> Self {
         Self { toks, pos: 0 }
     }
 
-    pub fn push(&mut self, tok: Token) {
-        self.toks.push(tok);
+    pub fn push(&mut self, tok: Token, span: Span) {
+        self.toks.push((tok, span));
     }
 
     pub fn is_consumed(&self) -> bool {
@@ -69,11 +75,27 @@ impl<'l1, 'l2> TLexer for CombinedLexers<'l1, 'l2> {
 
     fn span(&self) -> Span {
         if self.state {
-            Span::default()
+            self.first.span()
         } else {
             self.second.span()
         }
     }
+
+    fn take_doc(&mut self) -> Option<String> {
+        if self.state {
+            self.first.take_doc()
+        } else {
+            self.second.take_doc()
+        }
+    }
+
+    fn take_trailing_doc(&mut self) -> Option<String> {
+        if self.state {
+            self.first.take_trailing_doc()
+        } else {
+            self.second.take_trailing_doc()
+        }
+    }
 }
 
 impl<'l1, 'l2> CombinedLexers<'l1, 'l2> {
@@ -86,11 +108,33 @@ impl<'l1, 'l2> CombinedLexers<'l1, 'l2> {
     }
 }
 
+/// Iterator over the tokens with their spans until Eof
+pub struct Tokens<'l, L: TLexer + ?Sized> {
+    lexer: &'l mut L,
+}
+
+impl<'l, L: TLexer + ?Sized> Tokens<'l, L> {
+    pub fn new(lexer: &'l mut L) -> Self {
+        Self { lexer }
+    }
+}
+
+impl<'l, L: TLexer + ?Sized> Iterator for Tokens<'l, L> {
+    type Item = (Token, Span);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.lexer.next_with_span() {
+            (Token::Eof, _) => None,
+            x => Some(x),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
     use super::*;
-    use crate::lexer::{preprocessor::context::DefaultContext, Lexer, Token};
+    use crate::lexer::{preprocessor::context::DefaultContext, Lexer, Symbol, Token};
     use pretty_assertions::assert_eq;
 
     #[test]
@@ -98,24 +142,56 @@ mod tests {
         let mut l = Lexer::<DefaultContext>::new(b"(1 + 2 * 3) + (4 - 5))");
         let (_, saved) = l.save_until(Token::RightParen, 1);
 
+        let toks: Vec<_> = saved.toks.into_iter().map(|(tok, _)| tok).collect();
         assert_eq!(
-            saved.toks,
+            toks,
             vec![
                 Token::LeftParen,
-                Token::LiteralInt(1),
+                Token::from_number("1"),
                 Token::Plus,
-                Token::LiteralInt(2),
+                Token::from_number("2"),
                 Token::Star,
-                Token::LiteralInt(3),
+                Token::from_number("3"),
                 Token::RightParen,
                 Token::Plus,
                 Token::LeftParen,
-                Token::LiteralInt(4),
+                Token::from_number("4"),
                 Token::Minus,
-                Token::LiteralInt(5),
+                Token::from_number("5"),
                 Token::RightParen,
                 Token::RightParen
             ]
         );
     }
+
+    #[test]
+    fn test_saved_lexer_spans() {
+        let mut l = Lexer::<DefaultContext>::new(b"{\n  a +\n  bc;\n}\nd");
+        let (_, mut saved) = l.save_until(Token::RightBrace, 0);
+
+        assert_eq!(saved.span().start.line, 0);
+        let toks: Vec<_> = Tokens::new(&mut saved)
+            .map(|(tok, sp)| (tok, sp.start.line, sp.end.line))
+            .collect();
+        assert_eq!(
+            toks,
+            vec![
+                (Token::LeftBrace, 1, 1),
+                (Token::Identifier(Symbol::intern("a")), 2, 2),
+                (Token::Plus, 2, 2),
+                (Token::Identifier(Symbol::intern("bc")), 3, 3),
+                (Token::SemiColon, 3, 3),
+                (Token::RightBrace, 4, 4),
+            ]
+        );
+        assert_eq!(saved.span().start.line, 4);
+
+        saved.reset();
+        let mut combined = CombinedLexers::new(&mut saved, &mut l);
+        assert_eq!(combined.next_useful(), Token::LeftBrace);
+        assert_eq!(combined.span().start.line, 1);
+        let (tok, sp) = combined.tokens().last().unwrap();
+        assert_eq!(tok, Token::Identifier(Symbol::intern("d")));
+        assert_eq!(sp.start.line, 5);
+    }
 }
@@ -0,0 +1,445 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::io::{self, Write};
+use termcolor::{Ansi, Color, ColorSpec, WriteColor};
+
+use super::lexer::{Lexer, Token, TokenKind};
+use super::preprocessor::context::EmptyContext;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HighlightKind {
+    Keyword,
+    Identifier,
+    Literal,
+    Punctuator,
+    Comment,
+    /// A preprocessor directive line (but the macro name and the comments)
+    Directive,
+    /// The name of the macro in a #define
+    MacroName,
+    /// A disabled #if/#elif/#else branch
+    Disabled,
+}
+
+impl HighlightKind {
+    /// The class of the span in the html output
+    pub fn class(self) -> &'static str {
+        match self {
+            HighlightKind::Keyword => "kw",
+            HighlightKind::Identifier => "id",
+            HighlightKind::Literal => "lit",
+            HighlightKind::Punctuator => "punct",
+            HighlightKind::Comment => "comment",
+            HighlightKind::Directive => "directive",
+            HighlightKind::MacroName => "macro",
+            HighlightKind::Disabled => "disabled",
+        }
+    }
+
+    fn color(self) -> Option<ColorSpec> {
+        let mut spec = ColorSpec::new();
+        match self {
+            HighlightKind::Keyword => spec.set_fg(Some(Color::Blue)).set_bold(true),
+            HighlightKind::Identifier | HighlightKind::Punctuator => return None,
+            HighlightKind::Literal => spec.set_fg(Some(Color::Green)),
+            HighlightKind::Comment => spec.set_fg(Some(Color::Cyan)),
+            HighlightKind::Directive => spec.set_fg(Some(Color::Magenta)),
+            HighlightKind::MacroName => spec.set_fg(Some(Color::Magenta)).set_bold(true),
+            HighlightKind::Disabled => spec.set_fg(Some(Color::Black)).set_intense(true),
+        };
+        Some(spec)
+    }
+}
+
+/// A piece of the source: start and end are byte offsets in the source
+#[derive(Clone, Debug, PartialEq)]
+pub struct Highlight {
+    pub kind: HighlightKind,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A #if group: the branch is enabled when the condition isn't known to be false
+#[derive(Debug)]
+struct Group {
+    enabled: bool,
+    /// A previous branch is known to be taken
+    taken: bool,
+}
+
+fn is_splice(text: &[u8], pos: usize) -> bool {
+    text[pos] == b'\\' && matches!(text.get(pos + 1), Some(b'\n') | Some(b'\r'))
+}
+
+/// Remove the whitespaces and the line splices around text
+fn trim(text: &[u8]) -> Option<(usize, usize)> {
+    let mut start = 0;
+    while start < text.len() {
+        if is_splice(text, start) {
+            start += 2;
+        } else if text[start].is_ascii_whitespace() {
+            start += 1;
+        } else {
+            break;
+        }
+    }
+
+    let mut end = text.len();
+    while end > start {
+        // The backslash of a line splice is before a whitespace
+        if text[end - 1].is_ascii_whitespace() || (end < text.len() && is_splice(text, end - 1)) {
+            end -= 1;
+        } else {
+            break;
+        }
+    }
+
+    if start < end {
+        Some((start, end))
+    } else {
+        None
+    }
+}
+
+struct Highlighter<'a> {
+    src: &'a [u8],
+    out: Vec<Highlight>,
+    groups: Vec<Group>,
+}
+
+impl<'a> Highlighter<'a> {
+    fn is_disabled(groups: &[Group]) -> bool {
+        groups.iter().any(|g| !g.enabled)
+    }
+
+    /// The directives and the disabled lines are merged with the previous piece
+    /// when there are only whitespaces between them
+    fn push(&mut self, kind: HighlightKind, start: usize, end: usize) {
+        let (start, end) = match trim(&self.src[start..end]) {
+            Some((first, last)) => (start + first, start + last),
+            None => return,
+        };
+        if let Some(last) = self.out.last_mut() {
+            if last.kind == kind
+                && (kind == HighlightKind::Directive || kind == HighlightKind::Disabled)
+                && self.src[last.end..start]
+                    .iter()
+                    .all(|c| c.is_ascii_whitespace())
+            {
+                last.end = end;
+                return;
+            }
+        }
+        self.out.push(Highlight { kind, start, end });
+    }
+
+    /// Get the value of a #if condition when it's a constant
+    fn eval(cond: &[(Token, usize, usize)]) -> Option<bool> {
+        match cond {
+            [(Token::LiteralInt(id), _, _)] => id.get().to_u64().map(|v| v != 0),
+            [(Token::True, _, _)] => Some(true),
+            [(Token::False, _, _)] => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Handle a directive: toks are the tokens on the line (the comments included)
+    /// and end is the end of the line
+    fn directive(&mut self, toks: &[(Token, usize, usize)], end: usize) {
+        let keyword = toks[0].0;
+        let cond: Vec<_> = toks[1..]
+            .iter()
+            .filter(|(tok, _, _)| *tok != Token::Comment)
+            .copied()
+            .collect();
+
+        // The state of the enclosing groups
+        let outer = match keyword {
+            Token::PreprocElif | Token::PreprocElse | Token::PreprocEndif
+                if !self.groups.is_empty() =>
+            {
+                &self.groups[..self.groups.len() - 1]
+            }
+            _ => &self.groups[..],
+        };
+        let disabled = Self::is_disabled(outer);
+
+        match keyword {
+            Token::PreprocIf | Token::PreprocIfdef | Token::PreprocIfndef => {
+                let value = if keyword == Token::PreprocIf {
+                    Self::eval(&cond)
+                } else {
+                    None
+                };
+                self.groups.push(Group {
+                    enabled: value != Some(false),
+                    taken: value == Some(true),
+                });
+            }
+            Token::PreprocElif => {
+                if let Some(group) = self.groups.last_mut() {
+                    let value = Self::eval(&cond);
+                    group.enabled = !group.taken && value != Some(false);
+                    group.taken |= value == Some(true);
+                }
+            }
+            Token::PreprocElse => {
+                if let Some(group) = self.groups.last_mut() {
+                    group.enabled = !group.taken;
+                }
+            }
+            Token::PreprocEndif => {
+                self.groups.pop();
+            }
+            _ => {}
+        }
+
+        if disabled {
+            self.push(HighlightKind::Disabled, toks[0].1, end);
+            return;
+        }
+
+        let mut pos = toks[0].1;
+        let mut name = keyword == Token::PreprocDefine;
+        for &(tok, start, tend) in toks[1..].iter() {
+            let kind = if tok == Token::Comment {
+                HighlightKind::Comment
+            } else if name {
+                name = false;
+                if let Token::Identifier(_) = tok {
+                    HighlightKind::MacroName
+                } else {
+                    continue;
+                }
+            } else {
+                continue;
+            };
+            self.push(HighlightKind::Directive, pos, start);
+            self.push(kind, start, tend);
+            pos = tend;
+        }
+        self.push(HighlightKind::Directive, pos, end);
+    }
+}
+
+/// Highlight a source file: the macros aren't expanded and the includes aren't followed.
+/// Only the #if branches with a constant condition (#if 0, #if 1, ...) are known
+/// to be disabled or not: the other ones are highlighted as the enabled ones.
+pub fn highlight(src: &[u8]) -> Vec<Highlight> {
+    let mut lexer = Lexer::<EmptyContext>::new(src);
+    lexer.raw = true;
+
+    let mut hl = Highlighter {
+        src,
+        out: Vec::new(),
+        groups: Vec::new(),
+    };
+    // The tokens of the current directive
+    let mut directive = Vec::new();
+    let mut first = true;
+
+    loop {
+        let tok = lexer.next_token();
+        let start = lexer.start.pos.min(src.len());
+        let end = lexer.location().pos.min(src.len());
+
+        if tok == Token::Eol || tok == Token::Eof {
+            if !directive.is_empty() {
+                let end = if tok == Token::Eof { src.len() } else { start };
+                hl.directive(&directive, end);
+                directive.clear();
+            }
+            if tok == Token::Eof {
+                break;
+            }
+            first = true;
+            continue;
+        }
+
+        if !directive.is_empty() {
+            directive.push((tok, start, end));
+            continue;
+        }
+        if first && tok != Token::Comment && src.get(start) == Some(&b'#') {
+            directive.push((tok, start, end));
+            continue;
+        }
+        if tok != Token::Comment {
+            first = false;
+        }
+
+        let kind = if Highlighter::is_disabled(&hl.groups) {
+            HighlightKind::Disabled
+        } else {
+            match tok.kind() {
+                TokenKind::Keyword => HighlightKind::Keyword,
+                TokenKind::Identifier => HighlightKind::Identifier,
+                TokenKind::Literal => HighlightKind::Literal,
+                TokenKind::Punctuator => HighlightKind::Punctuator,
+                TokenKind::Comment => HighlightKind::Comment,
+                TokenKind::PreprocKeyword => HighlightKind::Directive,
+                TokenKind::Other => continue,
+            }
+        };
+        hl.push(kind, start, end);
+    }
+
+    hl.out
+}
+
+fn write_escaped<W: Write>(out: &mut W, text: &[u8]) -> io::Result<()> {
+    let mut last = 0;
+    for (i, c) in text.iter().enumerate() {
+        let esc: &[u8] = match c {
+            b'<' => b"&lt;",
+            b'>' => b"&gt;",
+            b'&' => b"&amp;",
+            b'"' => b"&quot;",
+            _ => continue,
+        };
+        out.write_all(&text[last..i])?;
+        out.write_all(esc)?;
+        last = i + 1;
+    }
+    out.write_all(&text[last..])
+}
+
+/// Write the source in html: the pieces are in <span class="...">
+/// (see HighlightKind::class) and the whole is in a <pre>
+pub fn write_html<W: Write>(src: &[u8], hl: &[Highlight], out: &mut W) -> io::Result<()> {
+    out.write_all(b"<pre class=\"cpp\">")?;
+    let mut pos = 0;
+    for h in hl {
+        write_escaped(out, &src[pos..h.start])?;
+        write!(out, "<span class=\"{}\">", h.kind.class())?;
+        write_escaped(out, &src[h.start..h.end])?;
+        out.write_all(b"</span>")?;
+        pos = h.end;
+    }
+    write_escaped(out, &src[pos..])?;
+    out.write_all(b"</pre>\n")
+}
+
+/// Write the source with ANSI colors
+pub fn write_ansi<W: Write>(src: &[u8], hl: &[Highlight], out: W) -> io::Result<()> {
+    let mut out = Ansi::new(out);
+    let mut pos = 0;
+    for h in hl {
+        if let Some(spec) = h.kind.color() {
+            out.write_all(&src[pos..h.start])?;
+            out.set_color(&spec)?;
+            out.write_all(&src[h.start..h.end])?;
+            out.reset()?;
+            pos = h.end;
+        }
+    }
+    out.write_all(&src[pos..])
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn pieces(src: &str) -> Vec<(HighlightKind, &str)> {
+        highlight(src.as_bytes())
+            .iter()
+            .map(|h| (h.kind, &src[h.start..h.end]))
+            .collect()
+    }
+
+    #[test]
+    fn test_highlight_tokens() {
+        use self::HighlightKind::*;
+        assert_eq!(
+            pieces("const char* s = u8\"a<b\"; // comment\nreturn 0x12 + x;"),
+            vec![
+                (Keyword, "const"),
+                (Keyword, "char"),
+                (Punctuator, "*"),
+                (Identifier, "s"),
+                (Punctuator, "="),
+                (Literal, "u8\"a<b\""),
+                (Punctuator, ";"),
+                (Comment, "// comment"),
+                (Keyword, "return"),
+                (Literal, "0x12"),
+                (Punctuator, "+"),
+                (Identifier, "x"),
+                (Punctuator, ";"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_highlight_directives() {
+        use self::HighlightKind::*;
+        let src = "#include <foo.h>\n  # define FOO(a) a + 1 /* x */\n#define BAR\\\n 1\nint x = FOO(BAR);\n#pragma once\n";
+        assert_eq!(
+            pieces(src),
+            vec![
+                (Directive, "#include <foo.h>\n  # define"),
+                (MacroName, "FOO"),
+                (Directive, "(a) a + 1"),
+                (Comment, "/* x */"),
+                (Directive, "#define"),
+                (MacroName, "BAR"),
+                (Directive, "1"),
+                (Keyword, "int"),
+                (Identifier, "x"),
+                (Punctuator, "="),
+                (Identifier, "FOO"),
+                (Punctuator, "("),
+                (Identifier, "BAR"),
+                (Punctuator, ")"),
+                (Punctuator, ";"),
+                (Directive, "#pragma once"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_highlight_disabled() {
+        use self::HighlightKind::*;
+        let src = "#if 0\nint a; don't\n#if 1\nb\n#endif\n#elif FOO\nc\n#else\nd\n#endif\n#if 1\ne\n#else\nf\n#endif\n";
+        assert_eq!(
+            pieces(src),
+            vec![
+                (Directive, "#if 0"),
+                (Disabled, "int a; don't\n#if 1\nb\n#endif"),
+                (Directive, "#elif FOO"),
+                (Identifier, "c"),
+                (Directive, "#else"),
+                (Identifier, "d"),
+                (Directive, "#endif\n#if 1"),
+                (Identifier, "e"),
+                (Directive, "#else"),
+                (Disabled, "f"),
+                (Directive, "#endif"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_highlight_output() {
+        let src = b"#define A 1\nint x = A < 2;";
+        let hl = highlight(src);
+
+        let mut html = Vec::new();
+        write_html(src, &hl, &mut html).unwrap();
+        assert_eq!(
+            String::from_utf8(html).unwrap(),
+            "<pre class=\"cpp\"><span class=\"directive\">#define</span> <span class=\"macro\">A</span> <span class=\"directive\">1</span>\n<span class=\"kw\">int</span> <span class=\"id\">x</span> <span class=\"punct\">=</span> <span class=\"id\">A</span> <span class=\"punct\">&lt;</span> <span class=\"lit\">2</span><span class=\"punct\">;</span></pre>\n"
+        );
+
+        let mut ansi = Vec::new();
+        write_ansi(src, &hl, &mut ansi).unwrap();
+        let ansi = String::from_utf8(ansi).unwrap();
+        assert!(ansi.starts_with("\x1B[0m\x1B[35m#define\x1B[0m"));
+        assert!(ansi.ends_with("\x1B[0m\x1B[32m2\x1B[0m;"));
+    }
+}
@@ -3,21 +3,24 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use bitflags::bitflags;
 use hashbrown::HashMap;
 use lazy_static::lazy_static;
-use std::fs::{self, File};
-use std::io::Read;
 use std::path::PathBuf;
 use std::sync::Arc;
 
 use super::buffer::{Buffer, BufferData, Position};
+use super::charset::Charset;
+use super::comment::DocComments;
 use super::errors::LexerError;
-use super::extra::SavedLexer;
-use super::preprocessor::cache::IfCache;
+use super::extra::{SavedLexer, Tokens};
+use super::number::{FloatId, IntId};
+use super::preprocessor::cache::{IfCache, PrefixCache, PrefixKey};
 use super::preprocessor::context::PreprocContext;
 use super::preprocessor::include::PathIndex;
-use super::source::{FileId, SourceMutex};
+use super::source::{FileData, FileId, SourceMutex};
 use super::string::StringType;
+use super::symbol::Symbol;
 use crate::args;
 use crate::errors::Span;
 
@@ -80,8 +83,67 @@ pub(super) const CHARS: [Kind; 256] = [
     Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, //
 ];
 
+bitflags! {
+    /// The language, the standard and the extensions: they select the keywords.
+    /// A standard comes with the previous ones (C11 is C89 | C99 | C11).
+    pub struct Dialect: u16 {
+        const C89 = 0b1;
+        const C99 = 0b10;
+        const C11 = 0b100;
+        const C23 = 0b1000;
+        const CPP98 = 0b1_0000;
+        const CPP11 = 0b10_0000;
+        const CPP20 = 0b100_0000;
+        const GNU = 0b1000_0000;
+        const MS = 0b1_0000_0000;
+        const CUDA = 0b10_0000_0000;
+    }
+}
+
+impl Dialect {
+    pub fn from_standard(std: args::Standard) -> Self {
+        use args::Standard::*;
+        match std {
+            C89 | C94 => Self::C89,
+            C99 => Self::C89 | Self::C99,
+            C11 | C17 => Self::C89 | Self::C99 | Self::C11,
+            C2x => Self::C89 | Self::C99 | Self::C11 | Self::C23,
+            Cpp98 => Self::CPP98,
+            Cpp11 | Cpp14 | Cpp17 => Self::CPP98 | Self::CPP11,
+            Cpp20 | Cpp2b => Self::CPP98 | Self::CPP11 | Self::CPP20,
+        }
+    }
+
+    /// The extensions come with the driver (GNU for gcc and clang, MS for cl)
+    pub fn from_options(opt: &args::PreprocOptions) -> Self {
+        let mut dialect = Self::from_standard(opt.get_standard());
+        dialect |= match opt.driver {
+            args::Driver::GCC => Self::GNU,
+            args::Driver::MSVC => Self::MS,
+        };
+        if opt.cuda {
+            dialect |= Self::CUDA;
+        }
+        dialect
+    }
+
+    /// The size in bits of wchar_t (16 on Windows, so for cl but not with all the extensions)
+    pub fn wchar_bits(self) -> u32 {
+        if self.contains(Self::MS) && !self.contains(Self::GNU) {
+            16
+        } else {
+            32
+        }
+    }
+
+    /// C and not C++
+    pub fn is_c(self) -> bool {
+        !self.contains(Self::CPP98)
+    }
+}
+
 macro_rules! mk_maps {
-    ( $name: ident, $conv_name: ident, $test_name: ident, $( $tok_val: expr, $tok_name: path ), *) => {
+    ( $name: ident, $conv_name: ident, $is_name: ident, $test_name: ident, $( $tok_val: expr, $tok_name: path ), *) => {
         lazy_static! {
             static ref $name: HashMap<&'static str, Token> = {
                 use Token::*;
@@ -100,6 +162,11 @@ macro_rules! mk_maps {
             }
         }
 
+        fn $is_name(tok: Token) -> bool {
+            use Token::*;
+            matches!(tok, $($tok_name)|*)
+        }
+
         #[allow(dead_code)]
         pub fn $test_name() -> Vec<(&'static str, Token)> {
             use Token::*;
@@ -111,8 +178,36 @@ macro_rules! mk_maps {
     }
 }
 
+macro_rules! mk_keywords {
+    ( $name: ident, $is_name: ident, $test_name: ident;
+      $( $tok_val: literal, $tok_name: ident, [$( $dialect: ident ),*]; )*
+      aliases:
+      $( $alias_val: literal, $alias_name: ident, [$( $alias_dialect: ident ),*]; )* ) => {
+        lazy_static! {
+            static ref $name: HashMap<&'static str, (Token, Dialect)> = {
+                let mut map = HashMap::new();
+                $(map.insert($tok_val, (Token::$tok_name, Dialect::empty() $(| Dialect::$dialect)*));)*
+                $(map.insert($alias_val, (Token::$alias_name, Dialect::empty() $(| Dialect::$alias_dialect)*));)*
+                map
+            };
+        }
+
+        fn $is_name(tok: Token) -> bool {
+            matches!(tok, $(Token::$tok_name)|*)
+        }
+
+        #[allow(dead_code)]
+        pub fn $test_name() -> Vec<(&'static str, Token, Dialect)> {
+            vec![
+                $(($tok_val, Token::$tok_name, Dialect::empty() $(| Dialect::$dialect)*),)*
+                $(($alias_val, Token::$alias_name, Dialect::empty() $(| Dialect::$alias_dialect)*),)*
+            ]
+        }
+    }
+}
+
 mk_maps! {
-    PREPROC_KEYWORDS, preproc_kw_to_str, test_preproc_kw,
+    PREPROC_KEYWORDS, preproc_kw_to_str, is_preproc_kw, test_preproc_kw,
     "define", PreprocDefine,
     "elif", PreprocElif,
     "else", PreprocElse,
@@ -133,130 +228,153 @@ mk_maps! {
 // first letters are:
 // a, b, c, d, e, f, g, i, l, m, n, o, p, r, s, t, u, v, w, x and _
 // So if there is some change then need to fix next_token too
-mk_maps! {
-    CPP_KEYWORDS, cpp_kw_to_str, test_cpp_kw,
-    "alignas", Alignas,
-    "alignof", Alignof,
-    "and", AndKw,
-    "and_eq", AndEq,
-    "asm", Asm,
-    "auto", Auto,
-    "bitand", BitAnd,
-    "bitor", BitOr,
-    "bool", Bool,
-    "break", Break,
-    "case", Case,
-    "catch", Catch,
-    "__cdecl", Cdecl,
-    "char", Char,
-    "char8_t", Char8T,
-    "char16_t", Char16T,
-    "char32_t", Char32T,
-    "class", Class,
-    "__clrcall", Clrcall,
-    "co_await", CoAwait,
-    "co_return", CoReturn,
-    "co_yield", CoYield,
-    "compl", Compl,
-    "concept", Concept,
-    "const", Const,
-    "consteval", Consteval,
-    "constexpr", Constexpr,
-    "constinit", Constinit,
-    "const_cast", ConstCast,
-    "continue", Continue,
-    "_Complex", Complex,
-    "decltype", Decltype,
-    "default", Default,
-    "delete", Delete,
-    "do", Do,
-    "double", Double,
-    "dynamic_cast", DynamicCast,
-    "else", Else,
-    "endif", Endif,
-    "enum", Enum,
-    "explicit", Explicit,
-    "export", Export,
-    "extern", Extern,
-    "false", False,
-    "__fastcall", Fastcall,
-    "final", Final,
-    "float", Float,
-    "for", For,
-    "friend", Friend,
-    "__func__", Func,
-    "__FUNCTION__", Function,
-    "goto", Goto,
-    "if", If,
-    "_Imaginary", Imaginary,
-    "import", Import,
-    "inline", Inline,
-    "__inline", UInline,
-    "__inline__", UInlineU,
-    "int", Int,
-    "long", Long,
-    "module", Module,
-    "mutable", Mutable,
-    "namespace", Namespace,
-    "new", New,
-    "noexcept", Noexcept,
-    "not", NotKw,
-    "not_eq", NotEq,
-    "nullptr", Nullptr,
-    "operator", Operator,
-    "or", OrKw,
-    "or_eq", OrEq,
-    "override", Override,
-    "__PRETTY_FUNCTION__", PrettyFunction,
-    "private", Private,
-    "protected", Protected,
-    "public", Public,
-    "requires", Requires,
-    "register", Register,
-    "reinterpret_cast", ReinterpretCast,
-    "restrict", Restrict,
-    "return", Return,
-    "__restrict", MSRestrict,
-    "short", Short,
-    "signed", Signed,
-    "sizeof", Sizeof,
-    "__sptr", MSSptr,
-    "static", Static,
-    "static_assert", StaticAssert,
-    "_Static_assert", CStaticAssert,
-    "static_cast", StaticCast,
-    "__stdcall", Stdcall,
-    "struct", Struct,
-    "switch", Switch,
-    "template", Template,
-    "this", This,
-    "__thiscall", Thiscall,
-    "thread_local", ThreadLocal,
-    "throw", Throw,
-    "true", True,
-    "try", Try,
-    "typedef", Typedef,
-    "typeid", Typeid,
-    "typename", Typename,
-    "_unaligned", MS1Unaligned,
-    "__unaligned", MSUnaligned,
-    "union", Union,
-    "unsigned", Unsigned,
-    "__uptr", MSUptr,
-    "using", Using,
-    "__vectorcall", Vectorcall,
-    "virtual", Virtual,
-    "void", Void,
-    "volatile", Volatile,
-    "wchar_t", WcharT,
-    "while", While,
-    "xor", XorKw,
-    "xor_eq", XorEq
+// The dialects are the ones where the keyword exists and the aliases are the other
+// spellings of a keyword (C11 _Alignas for alignas, GNU __asm__ for asm, ...)
+mk_keywords! {
+    KEYWORDS, is_kw, test_kw;
+    "alignas", Alignas, [CPP11, C23];
+    "alignof", Alignof, [CPP11, C23];
+    "and", AndKw, [CPP98];
+    "and_eq", AndEq, [CPP98];
+    "asm", Asm, [CPP98, GNU];
+    "_Atomic", Atomic, [C11];
+    "auto", Auto, [C89, CPP98];
+    "bitand", BitAnd, [CPP98];
+    "bitor", BitOr, [CPP98];
+    "bool", Bool, [CPP98, C23];
+    "break", Break, [C89, CPP98];
+    "case", Case, [C89, CPP98];
+    "catch", Catch, [CPP98];
+    "__cdecl", Cdecl, [MS];
+    "char", Char, [C89, CPP98];
+    "char8_t", Char8T, [CPP20];
+    "char16_t", Char16T, [CPP11];
+    "char32_t", Char32T, [CPP11];
+    "class", Class, [CPP98];
+    "__clrcall", Clrcall, [MS];
+    "co_await", CoAwait, [CPP20];
+    "co_return", CoReturn, [CPP20];
+    "co_yield", CoYield, [CPP20];
+    "compl", Compl, [CPP98];
+    "concept", Concept, [CPP20];
+    "const", Const, [C89, CPP98];
+    "__constant__", CudaConstant, [CUDA];
+    "consteval", Consteval, [CPP20];
+    "constexpr", Constexpr, [CPP11, C23];
+    "constinit", Constinit, [CPP20];
+    "const_cast", ConstCast, [CPP98];
+    "continue", Continue, [C89, CPP98];
+    "_Complex", Complex, [C99, GNU];
+    "decltype", Decltype, [CPP11];
+    "default", Default, [C89, CPP98];
+    "delete", Delete, [CPP98];
+    "__device__", CudaDevice, [CUDA];
+    "do", Do, [C89, CPP98];
+    "double", Double, [C89, CPP98];
+    "dynamic_cast", DynamicCast, [CPP98];
+    "else", Else, [C89, CPP98];
+    "endif", Endif, [C89, CPP98];
+    "enum", Enum, [C89, CPP98];
+    "explicit", Explicit, [CPP98];
+    "export", Export, [CPP98];
+    "extern", Extern, [C89, CPP98];
+    "false", False, [CPP98, C23];
+    "__fastcall", Fastcall, [MS];
+    "final", Final, [CPP11];
+    "float", Float, [C89, CPP98];
+    "for", For, [C89, CPP98];
+    "friend", Friend, [CPP98];
+    "__func__", Func, [C99, CPP11, GNU];
+    "__FUNCTION__", Function, [GNU, MS];
+    "_Generic", Generic, [C11];
+    "__global__", CudaGlobal, [CUDA];
+    "goto", Goto, [C89, CPP98];
+    "__host__", CudaHost, [CUDA];
+    "if", If, [C89, CPP98];
+    "_Imaginary", Imaginary, [C99];
+    "import", Import, [CPP20];
+    "inline", Inline, [C99, CPP98];
+    "__inline", UInline, [GNU, MS];
+    "__inline__", UInlineU, [GNU];
+    "int", Int, [C89, CPP98];
+    "long", Long, [C89, CPP98];
+    "__managed__", CudaManaged, [CUDA];
+    "module", Module, [CPP20];
+    "mutable", Mutable, [CPP98];
+    "namespace", Namespace, [CPP98];
+    "new", New, [CPP98];
+    "noexcept", Noexcept, [CPP11];
+    "_Noreturn", Noreturn, [C11];
+    "not", NotKw, [CPP98];
+    "not_eq", NotEq, [CPP98];
+    "nullptr", Nullptr, [CPP11, C23];
+    "operator", Operator, [CPP98];
+    "or", OrKw, [CPP98];
+    "or_eq", OrEq, [CPP98];
+    "override", Override, [CPP11];
+    "__PRETTY_FUNCTION__", PrettyFunction, [GNU];
+    "private", Private, [CPP98];
+    "protected", Protected, [CPP98];
+    "public", Public, [CPP98];
+    "requires", Requires, [CPP20];
+    "register", Register, [C89, CPP98];
+    "reinterpret_cast", ReinterpretCast, [CPP98];
+    "restrict", Restrict, [C99];
+    "return", Return, [C89, CPP98];
+    "__restrict", MSRestrict, [GNU, MS];
+    "__shared__", CudaShared, [CUDA];
+    "short", Short, [C89, CPP98];
+    "signed", Signed, [C89, CPP98];
+    "sizeof", Sizeof, [C89, CPP98];
+    "__sptr", MSSptr, [MS];
+    "static", Static, [C89, CPP98];
+    "static_assert", StaticAssert, [CPP11, C23];
+    "_Static_assert", CStaticAssert, [C11];
+    "static_cast", StaticCast, [CPP98];
+    "__stdcall", Stdcall, [MS];
+    "struct", Struct, [C89, CPP98];
+    "switch", Switch, [C89, CPP98];
+    "template", Template, [CPP98];
+    "this", This, [CPP98];
+    "__thiscall", Thiscall, [MS];
+    "thread_local", ThreadLocal, [CPP11, C23];
+    "throw", Throw, [CPP98];
+    "true", True, [CPP98, C23];
+    "try", Try, [CPP98];
+    "typedef", Typedef, [C89, CPP98];
+    "typeid", Typeid, [CPP98];
+    "typename", Typename, [CPP98];
+    "_unaligned", MS1Unaligned, [MS];
+    "__unaligned", MSUnaligned, [MS];
+    "union", Union, [C89, CPP98];
+    "unsigned", Unsigned, [C89, CPP98];
+    "__uptr", MSUptr, [MS];
+    "using", Using, [CPP98];
+    "__vectorcall", Vectorcall, [MS];
+    "virtual", Virtual, [CPP98];
+    "void", Void, [C89, CPP98];
+    "volatile", Volatile, [C89, CPP98];
+    "wchar_t", WcharT, [CPP98];
+    "while", While, [C89, CPP98];
+    "xor", XorKw, [CPP98];
+    "xor_eq", XorEq, [CPP98];
+    aliases:
+    "_Alignas", Alignas, [C11];
+    "_Alignof", Alignof, [C11];
+    "__alignof__", Alignof, [GNU];
+    "__asm", Asm, [GNU];
+    "__asm__", Asm, [GNU];
+    "_Bool", Bool, [C99];
+    "__complex__", Complex, [GNU];
+    "__const", Const, [GNU];
+    "__restrict__", MSRestrict, [GNU];
+    "__signed__", Signed, [GNU];
+    "__thread", ThreadLocal, [GNU];
+    "_Thread_local", ThreadLocal, [C11];
+    "__volatile__", Volatile, [GNU];
 }
 
-// TODO: group token by kind, for example put all the literal together
-// it should speed up literal detection for example in using range
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Token {
     None,
     Eof,
@@ -275,11 +393,11 @@ pub enum Token {
     LiteralUUChar(u32),
     LiteralUChar(u32),
     LiteralU8Char(u32),
-    LiteralCharUD(Box<(u32, String)>),
-    LiteralLCharUD(Box<(u32, String)>),
-    LiteralUUCharUD(Box<(u32, String)>),
-    LiteralUCharUD(Box<(u32, String)>),
-    LiteralU8CharUD(Box<(u32, String)>),
+    LiteralCharUD(u32, Symbol),
+    LiteralLCharUD(u32, Symbol),
+    LiteralUUCharUD(u32, Symbol),
+    LiteralUCharUD(u32, Symbol),
+    LiteralU8CharUD(u32, Symbol),
     LeftParen,
     RightParen,
     Star,
@@ -295,40 +413,32 @@ pub enum Token {
     MinusEqual,
     Arrow,
     ArrowStar,
-    LiteralDouble(f64),
-    LiteralFloat(f64),
-    LiteralLongDouble(f64),
-    LiteralFloatUD(Box<(f64, String)>),
+    LiteralFloat(FloatId),
     Dot,
     DotStar,
     Ellipsis,
-    LiteralInt(u64),
-    LiteralUInt(u64),
-    LiteralLong(u64),
-    LiteralLongLong(u64),
-    LiteralULong(u64),
-    LiteralULongLong(u64),
-    LiteralIntUD(Box<(u64, String)>),
-    LiteralString(String),
-    LiteralLString(String),
-    LiteralUString(String),
-    LiteralUUString(String),
-    LiteralU8String(String),
-    LiteralRString(String),
-    LiteralLRString(String),
-    LiteralURString(String),
-    LiteralUURString(String),
-    LiteralU8RString(String),
-    LiteralStringUD(Box<(String, String)>),
-    LiteralLStringUD(Box<(String, String)>),
-    LiteralUStringUD(Box<(String, String)>),
-    LiteralUUStringUD(Box<(String, String)>),
-    LiteralU8StringUD(Box<(String, String)>),
-    LiteralRStringUD(Box<(String, String)>),
-    LiteralLRStringUD(Box<(String, String)>),
-    LiteralURStringUD(Box<(String, String)>),
-    LiteralUURStringUD(Box<(String, String)>),
-    LiteralU8RStringUD(Box<(String, String)>),
+    LiteralInt(IntId),
+    // The strings are spelled as in the source (the escapes are decoded by the parser)
+    LiteralString(Symbol),
+    LiteralLString(Symbol),
+    LiteralUString(Symbol),
+    LiteralUUString(Symbol),
+    LiteralU8String(Symbol),
+    LiteralRString(Symbol),
+    LiteralLRString(Symbol),
+    LiteralURString(Symbol),
+    LiteralUURString(Symbol),
+    LiteralU8RString(Symbol),
+    LiteralStringUD(Symbol, Symbol),
+    LiteralLStringUD(Symbol, Symbol),
+    LiteralUStringUD(Symbol, Symbol),
+    LiteralUUStringUD(Symbol, Symbol),
+    LiteralU8StringUD(Symbol, Symbol),
+    LiteralRStringUD(Symbol, Symbol),
+    LiteralLRStringUD(Symbol, Symbol),
+    LiteralURStringUD(Symbol, Symbol),
+    LiteralUURStringUD(Symbol, Symbol),
+    LiteralU8RStringUD(Symbol, Symbol),
     ColonColon,
     Colon,
     SemiColon,
@@ -345,7 +455,7 @@ pub enum Token {
     RightShiftEqual,
     Question,
     At,
-    Identifier(String),
+    Identifier(Symbol),
     LeftBrack,
     DoubleLeftBrack,
     Backslash,
@@ -489,9 +599,89 @@ pub enum Token {
     MSSptr,
     MS1Unaligned,
     MSUnaligned,
+    Atomic,
+    Generic,
+    Noreturn,
+    CudaConstant,
+    CudaDevice,
+    CudaGlobal,
+    CudaHost,
+    CudaManaged,
+    CudaShared,
+}
+
+/// The class of a token
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    Keyword,
+    Identifier,
+    Literal,
+    Punctuator,
+    PreprocKeyword,
+    Comment,
+    /// None, Eof and Eol
+    Other,
+}
+
+impl Token {
+    pub fn kind(&self) -> TokenKind {
+        use Token::*;
+        match *self {
+            None | Eof | Eol => TokenKind::Other,
+            Comment => TokenKind::Comment,
+            Identifier(_) => TokenKind::Identifier,
+            LiteralChar(_)
+            | LiteralLChar(_)
+            | LiteralUUChar(_)
+            | LiteralUChar(_)
+            | LiteralU8Char(_)
+            | LiteralCharUD(..)
+            | LiteralLCharUD(..)
+            | LiteralUUCharUD(..)
+            | LiteralUCharUD(..)
+            | LiteralU8CharUD(..)
+            | LiteralFloat(_)
+            | LiteralInt(_)
+            | LiteralString(_)
+            | LiteralLString(_)
+            | LiteralUString(_)
+            | LiteralUUString(_)
+            | LiteralU8String(_)
+            | LiteralRString(_)
+            | LiteralLRString(_)
+            | LiteralURString(_)
+            | LiteralUURString(_)
+            | LiteralU8RString(_)
+            | LiteralStringUD(..)
+            | LiteralLStringUD(..)
+            | LiteralUStringUD(..)
+            | LiteralUUStringUD(..)
+            | LiteralU8StringUD(..)
+            | LiteralRStringUD(..)
+            | LiteralLRStringUD(..)
+            | LiteralURStringUD(..)
+            | LiteralUURStringUD(..)
+            | LiteralU8RStringUD(..) => TokenKind::Literal,
+            tok if is_kw(tok) => TokenKind::Keyword,
+            tok if is_preproc_kw(tok) => TokenKind::PreprocKeyword,
+            _ => TokenKind::Punctuator,
+        }
+    }
+
+    pub fn is_keyword(&self) -> bool {
+        self.kind() == TokenKind::Keyword
+    }
+
+    pub fn is_literal(&self) -> bool {
+        self.kind() == TokenKind::Literal
+    }
+
+    pub fn is_punctuator(&self) -> bool {
+        self.kind() == TokenKind::Punctuator
+    }
 }
 
-#[derive(Clone, Debug, Copy, Default)]
+#[derive(Clone, Debug, Copy, Default, PartialEq)]
 pub struct Location {
     pub pos: usize,
     pub line: u32,
@@ -508,34 +698,28 @@ impl Location {
     }
 }
 
-impl Token {
-    pub(crate) fn get_string(self) -> Option<String> {
-        match self {
-            Self::LiteralString(s)
-            | Self::LiteralLString(s)
-            | Self::LiteralUString(s)
-            | Self::LiteralUUString(s)
-            | Self::LiteralU8String(s)
-            | Self::LiteralRString(s)
-            | Self::LiteralLRString(s)
-            | Self::LiteralURString(s)
-            | Self::LiteralUURString(s)
-            | Self::LiteralU8RString(s) => Some(s),
-            _ => None,
-        }
-    }
-}
-
 pub trait TLexer {
     fn next_useful(&mut self) -> Token;
 
+    fn next_with_span(&mut self) -> (Token, Span) {
+        let tok = self.next_useful();
+        (tok, self.span())
+    }
+
+    fn tokens(&mut self) -> Tokens<'_, Self>
+    where
+        Self: Sized,
+    {
+        Tokens::new(self)
+    }
+
     fn save_until(&mut self, term: Token, level: usize) -> (Token, SavedLexer) {
         let mut level = level;
 
         // TODO: tune the capacity
         let mut stole = Vec::with_capacity(64);
         loop {
-            let tok = self.next_useful();
+            let (tok, span) = self.next_with_span();
             match tok {
                 Token::LeftParen | Token::LeftBrack | Token::LeftBrace | Token::DoubleLeftBrack => {
                     level += 1;
@@ -549,7 +733,7 @@ pub trait TLexer {
                 _ => {}
             }
 
-            stole.push(tok.clone());
+            stole.push((tok, span));
 
             if (tok == term && level == 0) || tok == Token::Eof {
                 return (tok, SavedLexer::new(stole));
@@ -558,6 +742,16 @@ pub trait TLexer {
     }
 
     fn span(&self) -> Span;
+
+    /// Take the doc comment just before the last returned token
+    fn take_doc(&mut self) -> Option<String> {
+        None
+    }
+
+    /// Take the trailing doc comment (///< ...) just before the last returned token
+    fn take_trailing_doc(&mut self) -> Option<String> {
+        None
+    }
 }
 
 pub struct Lexer<'a, PC: PreprocContext> {
@@ -565,7 +759,17 @@ pub struct Lexer<'a, PC: PreprocContext> {
     pub(crate) context: PC,
     pub(crate) comment: Option<&'a [u8]>,
     pub(crate) start: Location,
+    /// The position of the start of the last token in the text
+    pub(crate) start_pos: usize,
     pub(crate) errors: Vec<LexerError>,
+    pub(crate) std: args::Standard,
+    /// Don't expand the macros and don't follow the includes (see LosslessLexer)
+    pub(crate) lossless: bool,
+    /// Don't run the directives: the preprocessor keywords are returned as tokens (see highlight)
+    pub(crate) raw: bool,
+    /// The keywords are the ones of the dialect (all of them by default)
+    pub(crate) dialect: Dialect,
+    pub(crate) docs: DocComments,
 }
 
 impl<'a, PC: PreprocContext> TLexer for Lexer<'a, PC> {
@@ -574,8 +778,17 @@ impl<'a, PC: PreprocContext> TLexer for Lexer<'a, PC> {
             let tok = self.next_token();
             //eprintln!("{:?} -- {:?} -- {:?}", tok, self.span(), self.context.get_path(self.buf.get_source_id().unwrap()));
             match tok {
-                Token::Comment | Token::Eol => {}
+                Token::Comment => {
+                    let start = self.start_pos;
+                    let line = self.buf.slice_p(start, start + 2) == b"//";
+                    self.docs.on_comment(self.comment.unwrap(), line);
+                }
+                Token::Eol => {
+                    self.docs.on_newline();
+                }
                 _ => {
+                    self.docs.on_token();
+                    self.context.on_token(self.buf.get_source_id());
                     return tok;
                 }
             }
@@ -589,6 +802,14 @@ impl<'a, PC: PreprocContext> TLexer for Lexer<'a, PC> {
             end: self.location(),
         }
     }
+
+    fn take_doc(&mut self) -> Option<String> {
+        self.docs.take_leading()
+    }
+
+    fn take_trailing_doc(&mut self) -> Option<String> {
+        self.docs.take_trailing()
+    }
 }
 
 macro_rules! get_operator {
@@ -643,48 +864,80 @@ macro_rules! get_basic_operator {
 impl<'a, PC: PreprocContext> Lexer<'a, PC> {
     pub fn new(buf: &'a [u8]) -> Self {
         Self {
-            buf: Buffer::new(buf.to_vec(), FileId(0), PathIndex(0)),
+            buf: Buffer::new(buf, FileId(0), PathIndex(0)),
             context: PC::default(),
             comment: None,
             start: Location::dummy(),
+            start_pos: 0,
             errors: Vec::new(),
+            std: args::Standard::default_for(&args::Language::CPP),
+            lossless: false,
+            raw: false,
+            dialect: Dialect::all(),
+            docs: DocComments::default(),
+        }
+    }
+
+    pub(crate) fn new_expansion(buf: &'a [u8]) -> Self {
+        Self {
+            buf: Buffer::new_expansion(buf),
+            ..Self::new(&[])
         }
     }
 
     pub fn new_with_context(buf: &'a [u8], source_id: FileId, context: PC) -> Self {
         Self {
-            buf: Buffer::new(buf.to_vec(), source_id, PathIndex(0)),
+            buf: Buffer::new(buf, source_id, PathIndex(0)),
             context,
             comment: None,
             start: Location::dummy(),
+            start_pos: 0,
             errors: Vec::new(),
+            std: args::Standard::default_for(&args::Language::CPP),
+            lossless: false,
+            raw: false,
+            dialect: Dialect::all(),
+            docs: DocComments::default(),
         }
     }
 
-    pub fn new_from_file(
-        file: &str,
-        source: SourceMutex,
-        if_cache: Arc<IfCache>,
-        opt: args::PreprocOptions,
-    ) -> Self {
-        let path = PathBuf::from(file); //std::fs::canonicalize(file).unwrap();
-                                        //let path = std::fs::canonicalize(file).unwrap();
-        let file_size = fs::metadata(&path).map_or(1024 * 1024, |m| m.len() as usize);
-        let mut file = File::open(&path).unwrap();
-        let mut data = Vec::with_capacity(file_size + 1);
-        file.read_to_end(&mut data).unwrap();
-
-        let mut context = PC::new_with_if_cache(if_cache);
-        context.set_source(source);
-        let source_id = context.get_id(&path);
-        let mut buffer = Buffer::new(data, source_id, PathIndex(0));
-
-        context.set_sys_paths(&opt.sys_paths);
+    fn get_main_buffer(
+        context: &mut PC,
+        path: &PathBuf,
+        source: &SourceMutex,
+        charset: Charset,
+    ) -> Buffer<'a> {
+        let file_cache = source.lock().unwrap().get_file_cache();
+        let source_id = context.get_id(path);
+
+        let data = if let Some(file_cache) = file_cache {
+            let text = file_cache.get_text(source_id, charset, || file_cache.read_file(path));
+            BufferData::with_text(text.unwrap(), source_id, PathIndex(0))
+        } else {
+            let data = FileData::read(path).map(Arc::new);
+            BufferData::with_charset(data.unwrap(), source_id, PathIndex(0), charset)
+        };
+        Buffer::from_data(data)
+    }
 
+    fn get_command_line(opt: &args::PreprocOptions) -> Vec<u8> {
         let mut cl = Vec::with_capacity(16384);
-        if opt.lang == args::Language::CPP {
-            // TODO: be more smart here
-            cl.extend_from_slice(b"#define __cplusplus 201703L\n");
+        let std = opt.get_standard();
+        if let Some(version) = std.get_version() {
+            if std.is_cpp() {
+                cl.extend_from_slice(b"#define __cplusplus ");
+            } else {
+                cl.extend_from_slice(b"#define __STDC_VERSION__ ");
+            }
+            cl.extend_from_slice(version.as_bytes());
+            cl.push(b'\n');
+        }
+        if opt.driver == args::Driver::MSVC && std.is_cpp() {
+            if let Some(version) = std.get_version() {
+                cl.extend_from_slice(b"#define _MSVC_LANG ");
+                cl.extend_from_slice(version.as_bytes());
+                cl.push(b'\n');
+            }
         }
         for mac in opt.def.iter() {
             match mac {
@@ -721,6 +974,25 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
             cl.push(b'\n');
         }
 
+        cl
+    }
+
+    pub fn new_from_file(
+        file: &str,
+        source: SourceMutex,
+        if_cache: Arc<IfCache>,
+        opt: args::PreprocOptions,
+    ) -> Self {
+        let path = PathBuf::from(file); //std::fs::canonicalize(file).unwrap();
+                                        //let path = std::fs::canonicalize(file).unwrap();
+        let mut context = PC::new_with_if_cache(if_cache);
+        context.set_source(Arc::clone(&source));
+        let mut buffer = Self::get_main_buffer(&mut context, &path, &source, opt.charset);
+
+        context.set_sys_paths(&opt.sys_paths);
+        context.set_charset(opt.charset);
+
+        let cl = Self::get_command_line(&opt);
         if !cl.is_empty() {
             buffer.add_buffer(BufferData::new(cl, FileId(0), PathIndex(0)));
         }
@@ -730,7 +1002,74 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
             context,
             comment: None,
             start: Location::dummy(),
+            start_pos: 0,
             errors: Vec::new(),
+            std: opt.get_standard(),
+            lossless: false,
+            raw: false,
+            dialect: Dialect::from_options(&opt),
+            docs: DocComments::default(),
+        }
+    }
+
+    /// Same as new_from_file but the context got after having processed the command line
+    /// definitions and the forced includes is taken from the prefix cache when it exists.
+    /// The prefix cache must always be used with the same source.
+    pub fn new_from_file_with_prefix(
+        file: &str,
+        source: SourceMutex,
+        if_cache: Arc<IfCache>,
+        prefix_cache: &PrefixCache<PC>,
+        opt: args::PreprocOptions,
+    ) -> Self
+    where
+        PC: Clone,
+    {
+        let path = PathBuf::from(file);
+        let cl = Self::get_command_line(&opt);
+        let key = PrefixKey {
+            cl: cl.clone(),
+            sys_paths: opt.sys_paths.clone(),
+            charset: opt.charset,
+        };
+
+        let mut context = prefix_cache.get_or_insert(key, || {
+            let mut context = PC::new_with_if_cache(if_cache);
+            context.set_source(Arc::clone(&source));
+            context.set_sys_paths(&opt.sys_paths);
+            context.set_charset(opt.charset);
+
+            let mut lexer = Self {
+                buf: Buffer::from_data(BufferData::new(cl, FileId(0), PathIndex(0))),
+                context,
+                comment: None,
+                start: Location::dummy(),
+                start_pos: 0,
+                errors: Vec::new(),
+                std: opt.get_standard(),
+                lossless: false,
+                raw: false,
+                dialect: Dialect::from_options(&opt),
+                docs: DocComments::default(),
+            };
+            lexer.consume_all();
+            lexer.context.share_macros();
+            lexer.context
+        });
+        let buffer = Self::get_main_buffer(&mut context, &path, &source, opt.charset);
+
+        Self {
+            buf: buffer,
+            context,
+            comment: None,
+            start: Location::dummy(),
+            start_pos: 0,
+            errors: Vec::new(),
+            std: opt.get_standard(),
+            lossless: false,
+            raw: false,
+            dialect: Dialect::from_options(&opt),
+            docs: DocComments::default(),
         }
     }
 
@@ -742,6 +1081,22 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
         &self.context
     }
 
+    pub fn get_mut_context(&mut self) -> &mut PC {
+        &mut self.context
+    }
+
+    pub fn get_dialect(&self) -> Dialect {
+        self.dialect
+    }
+
+    pub fn set_dialect(&mut self, dialect: Dialect) {
+        self.dialect = dialect;
+    }
+
+    pub fn get_standard(&self) -> args::Standard {
+        self.std
+    }
+
     pub fn get_errors(&self) -> &[LexerError] {
         &self.errors
     }
@@ -765,7 +1120,7 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
     }
 
     pub(crate) fn get_column(&self) -> u32 {
-        self.buf.get_column()
+        self.location().column
     }
 
     pub fn debug(&self, msg: &str) {
@@ -780,11 +1135,11 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
 
     pub(crate) fn get_identifier(&mut self) -> Option<Token> {
         let id = self.get_identifier_str();
-        if !self.buf.preproc_use() && self.macro_eval(id) {
+        if !self.lossless && !self.buf.preproc_use() && self.macro_eval(id) {
             self.buf.switch_to_preproc();
             None
         } else {
-            Some(Token::Identifier(id.to_string()))
+            Some(Token::Identifier(Symbol::intern(id)))
         }
     }
 
@@ -823,19 +1178,28 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
 
         let id = unsafe { std::str::from_utf8_unchecked(&self.buf.slice(spos)) };
         if let Some(keyword) = PREPROC_KEYWORDS.get(id) {
-            if let Err(e) = self.preproc_parse(keyword.clone(), pos) {
+            if self.raw {
+                // The paths and the messages aren't made of tokens
+                if let Token::PreprocInclude | Token::PreprocIncludeNext | Token::PreprocError =
+                    keyword
+                {
+                    skip_until!(self, b'\n');
+                }
+                return *keyword;
+            }
+            if let Err(e) = self.preproc_parse(*keyword, pos) {
                 self.errors.push(e.clone());
                 eprintln!("ERRRRRRRRRRor {:?}", e);
                 Token::Eof
             } else {
                 if cfg!(test) {
-                    keyword.clone()
+                    *keyword
                 } else {
                     Token::Eol
                 }
             }
         } else {
-            Token::Identifier(id.to_string())
+            Token::Identifier(Symbol::intern(id))
         }
     }
 
@@ -874,13 +1238,14 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
         }
 
         let id = unsafe { std::str::from_utf8_unchecked(&self.buf.slice(spos)) };
-        if !self.buf.preproc_use() && self.macro_eval(id) {
+        if !self.lossless && !self.buf.preproc_use() && self.macro_eval(id) {
             self.buf.switch_to_preproc();
             None
-        } else if let Some(keyword) = CPP_KEYWORDS.get(id) {
-            Some(keyword.clone())
         } else {
-            Some(Token::Identifier(id.to_string()))
+            match KEYWORDS.get(id) {
+                Some((keyword, dialect)) if dialect.intersects(self.dialect) => Some(*keyword),
+                _ => Some(Token::Identifier(Symbol::intern(id))),
+            }
         }
     }
 
@@ -1077,14 +1442,14 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
     }
 
     #[inline(always)]
-    pub(crate) fn get_suffix(&mut self) -> Option<String> {
+    pub(crate) fn get_suffix(&mut self) -> Option<Symbol> {
         if self.buf.has_char() {
             let c = self.buf.next_char();
             let kind = unsafe { CHARS.get_unchecked(c as usize) };
             if *kind != Kind::NON {
                 // we've a suffix
                 self.buf.inc();
-                Some(self.get_identifier_str().to_string())
+                Some(Symbol::intern(self.get_identifier_str()))
             } else {
                 None
             }
@@ -1098,17 +1463,24 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
         self.get_preproc_keyword(pos)
     }
 
+    /// Get the physical location (in the source before the removal of the line splices)
     pub fn location(&self) -> Location {
-        Location {
-            pos: self.buf.pos(),
-            line: self.buf.get_line(),
-            column: self.buf.get_column(),
+        self.buf.location()
+    }
+
+    /// Get the span between two positions on the current line
+    pub(crate) fn span_of(&self, start: usize, end: usize) -> Span {
+        Span {
+            file: self.buf.get_source_id(),
+            start: self.buf.location_at(start),
+            end: self.buf.location_at(end),
         }
     }
 
     pub fn next_token(&mut self) -> Token {
         loop {
             self.start = self.location();
+            self.start_pos = self.buf.pos();
             if self.buf.check_char() {
                 let c = self.buf.next_char();
                 self.buf.inc();
@@ -1169,7 +1541,7 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                         return self.get_slash();
                     }
                     b'0'..=b'9' => {
-                        return self.get_number(u64::from(c - b'0'));
+                        return self.get_number();
                     }
                     b':' => {
                         return get_operator!(self, b':', Colon, ColonColon);
@@ -1266,7 +1638,11 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                             return tok;
                         }
                     }
-                    _ => {}
+                    b'\r' | b'\x0B' | b'\x0C' => {}
+                    _ => {
+                        let sp = self.span();
+                        self.errors.push(LexerError::StrayCharacter { sp, c });
+                    }
                 }
             } else {
                 return Token::Eof;
@@ -1286,11 +1662,11 @@ mod tests {
     fn test_keywords() {
         let mut p = Lexer::<DefaultContext>::new(b"while foa whila for While For static_cast");
         assert_eq!(p.next_token(), Token::While);
-        assert_eq!(p.next_token(), Token::Identifier("foa".to_string()));
-        assert_eq!(p.next_token(), Token::Identifier("whila".to_string()));
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("foa")));
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("whila")));
         assert_eq!(p.next_token(), Token::For);
-        assert_eq!(p.next_token(), Token::Identifier("While".to_string()));
-        assert_eq!(p.next_token(), Token::Identifier("For".to_string()));
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("While")));
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("For")));
         assert_eq!(p.next_token(), Token::StaticCast);
     }
 
@@ -1299,38 +1675,63 @@ mod tests {
         let mut p = Lexer::<DefaultContext>::new(
             b"hello world whilee Roo Lar uoo Uar u851 hello_world_WORLD_HELLO123",
         );
-        assert_eq!(p.next_token(), Token::Identifier("hello".to_string()));
-        assert_eq!(p.next_token(), Token::Identifier("world".to_string()));
-        assert_eq!(p.next_token(), Token::Identifier("whilee".to_string()));
-        assert_eq!(p.next_token(), Token::Identifier("Roo".to_string()));
-        assert_eq!(p.next_token(), Token::Identifier("Lar".to_string()));
-        assert_eq!(p.next_token(), Token::Identifier("uoo".to_string()));
-        assert_eq!(p.next_token(), Token::Identifier("Uar".to_string()));
-        assert_eq!(p.next_token(), Token::Identifier("u851".to_string()));
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("hello")));
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("world")));
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("whilee")));
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("Roo")));
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("Lar")));
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("uoo")));
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("Uar")));
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("u851")));
         assert_eq!(
             p.next_token(),
-            Token::Identifier("hello_world_WORLD_HELLO123".to_string())
+            Token::Identifier(Symbol::intern("hello_world_WORLD_HELLO123"))
         );
     }
 
     #[test]
     fn test_identifiers_utf8() {
         let mut p = Lexer::<DefaultContext>::new("🌹 🌵 🌻 🌷🌷🌷🌷🌷🌷".as_bytes());
-        assert_eq!(p.next_token(), Token::Identifier("🌹".to_string()));
-        assert_eq!(p.next_token(), Token::Identifier("🌵".to_string()));
-        assert_eq!(p.next_token(), Token::Identifier("🌻".to_string()));
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("🌹")));
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("🌵")));
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("🌻")));
         assert_eq!(
             p.next_token(),
-            Token::Identifier("🌷🌷🌷🌷🌷🌷".to_string())
+            Token::Identifier(Symbol::intern("🌷🌷🌷🌷🌷🌷"))
+        );
+    }
+
+    #[test]
+    fn test_stray() {
+        let mut p = Lexer::<DefaultContext>::new(b"a ` b\r\n\x01c");
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("a")));
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("b")));
+        assert_eq!(p.next_token(), Token::Eol);
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("c")));
+
+        let errors: Vec<_> = p
+            .errors
+            .iter()
+            .map(|e| {
+                let e = e.stringly();
+                (e.message, e.sp.start.pos)
+            })
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                ("stray '`' in program".to_string(), 2),
+                ("stray '\\x01' in program".to_string(), 7),
+            ]
         );
     }
 
     #[test]
     fn test_divide() {
         let mut p = Lexer::<DefaultContext>::new(b"a / b");
-        assert_eq!(p.next_token(), Token::Identifier("a".to_string()));
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("a")));
         assert_eq!(p.next_token(), Token::Divide);
-        assert_eq!(p.next_token(), Token::Identifier("b".to_string()));
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("b")));
     }
 
     #[test]
@@ -1378,9 +1779,165 @@ mod tests {
 
     #[test]
     fn test_for_cpp_kw() {
-        for (s, tok) in test_cpp_kw().drain(..) {
+        for (s, tok, _) in test_kw().drain(..) {
             let mut p = Lexer::<DefaultContext>::new(s.as_bytes());
             assert_eq!(p.next_token(), tok);
         }
     }
+
+    #[test]
+    fn test_dialect() {
+        fn kinds(src: &str, dialect: Dialect) -> Vec<TokenKind> {
+            let mut p = Lexer::<DefaultContext>::new(src.as_bytes());
+            p.set_dialect(dialect);
+            std::iter::from_fn(|| Some(p.next_token()))
+                .take_while(|t| *t != Token::Eof)
+                .map(|t| t.kind())
+                .collect()
+        }
+
+        use self::TokenKind::{Identifier as I, Keyword as K};
+        let src = "class new this template export restrict _Alignas _Atomic _Thread_local _Noreturn _Generic _Bool";
+        let c11 = Dialect::from_standard(args::Standard::C11);
+        assert_eq!(kinds(src, c11), vec![I, I, I, I, I, K, K, K, K, K, K, K]);
+        let cpp17 = Dialect::from_standard(args::Standard::Cpp17);
+        assert_eq!(kinds(src, cpp17), vec![K, K, K, K, K, I, I, I, I, I, I, I]);
+
+        let src = "bool nullptr static_assert char8_t concept";
+        assert_eq!(kinds(src, c11), vec![I, I, I, I, I]);
+        let c2x = Dialect::from_standard(args::Standard::C2x);
+        assert_eq!(kinds(src, c2x), vec![K, K, K, I, I]);
+        assert_eq!(kinds(src, cpp17), vec![K, K, K, I, I]);
+        let cpp20 = Dialect::from_standard(args::Standard::Cpp20);
+        assert_eq!(kinds(src, cpp20), vec![K, K, K, K, K]);
+
+        // The extensions
+        let src = "__uptr __cdecl __asm__ __inline __global__";
+        assert_eq!(kinds(src, cpp17), vec![I, I, I, I, I]);
+        assert_eq!(kinds(src, cpp17 | Dialect::MS), vec![K, K, I, K, I]);
+        assert_eq!(kinds(src, c11 | Dialect::GNU), vec![I, I, K, K, I]);
+        assert_eq!(kinds(src, cpp17 | Dialect::CUDA), vec![I, I, I, I, K]);
+
+        let opt = args::PreprocOptions {
+            lang: args::Language::C,
+            std: Some(args::Standard::C99),
+            ..Default::default()
+        };
+        assert_eq!(
+            Dialect::from_options(&opt),
+            Dialect::C89 | Dialect::C99 | Dialect::GNU
+        );
+    }
+
+    #[test]
+    fn test_token_kind() {
+        for (_, tok, _) in test_kw() {
+            assert_eq!(tok.kind(), TokenKind::Keyword);
+        }
+        for (_, tok) in test_preproc_kw() {
+            assert_eq!(tok.kind(), TokenKind::PreprocKeyword);
+        }
+
+        let mut p = Lexer::<DefaultContext>::new(b"a 'b' 1.5 u8R\"(c)\"_x -> ; // d\n");
+        let kinds: Vec<_> = std::iter::from_fn(|| Some(p.next_token()))
+            .take_while(|t| *t != Token::Eof)
+            .map(|t| t.kind())
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Identifier,
+                TokenKind::Literal,
+                TokenKind::Literal,
+                TokenKind::Literal,
+                TokenKind::Punctuator,
+                TokenKind::Punctuator,
+                TokenKind::Comment,
+                TokenKind::Other,
+            ]
+        );
+        assert!(Token::Nullptr.is_keyword());
+        assert!(Token::from_number("0x1p3").is_literal());
+        assert!(Token::ColonColon.is_punctuator());
+    }
+
+    fn useful_tokens(src: &[u8]) -> Vec<(Token, Location)> {
+        let mut p = Lexer::<DefaultContext>::new(src);
+        let mut toks = Vec::new();
+        loop {
+            let tok = p.next_useful();
+            if tok == Token::Eof {
+                return toks;
+            }
+            toks.push((tok, p.span().start));
+        }
+    }
+
+    fn at(pos: usize, line: u32, column: u32) -> Location {
+        Location { pos, line, column }
+    }
+
+    #[test]
+    fn test_newlines() {
+        let toks: Vec<_> = useful_tokens(b"\xEF\xBB\xBFa\r\nb\rc\n  d\r\n")
+            .into_iter()
+            .map(|(tok, loc)| (tok, loc.pos, loc.line))
+            .collect();
+        assert_eq!(
+            toks,
+            vec![
+                (Token::Identifier(Symbol::intern("a")), 3, 1),
+                (Token::Identifier(Symbol::intern("b")), 6, 2),
+                (Token::Identifier(Symbol::intern("c")), 8, 3),
+                (Token::Identifier(Symbol::intern("d")), 12, 4),
+            ]
+        );
+
+        let toks = useful_tokens(b"#if 0\r\nint a;\r\n#else\rint b;\r\n#endif\r\nint c;");
+        let ids: Vec<_> = toks
+            .iter()
+            .filter_map(|(tok, loc)| match tok {
+                Token::Identifier(id) => Some((id.as_str(), loc.line)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ids, vec![("b", 4), ("c", 6)]);
+    }
+
+    #[test]
+    fn test_splices() {
+        let toks = useful_tokens(b"in\\\nt a +\\\r\n= \"b\\\nc\"; /* d *\\\n/ e");
+        assert_eq!(
+            toks,
+            vec![
+                (Token::Int, at(0, 1, 1)),
+                (Token::Identifier(Symbol::intern("a")), at(6, 2, 3)),
+                (Token::PlusEqual, at(8, 2, 5)),
+                (Token::LiteralString(Symbol::intern("bc")), at(14, 3, 3)),
+                (Token::SemiColon, at(20, 4, 3)),
+                (Token::Identifier(Symbol::intern("e")), at(32, 5, 3)),
+            ]
+        );
+
+        let toks: Vec<_> = useful_tokens(b"#def\\\nine X \\\r\n 1\r\nX\n")
+            .into_iter()
+            .map(|(tok, _)| tok)
+            .collect();
+        assert_eq!(toks, vec![Token::PreprocDefine, Token::from_number("1")]);
+
+        // The line splices are kept in the raw strings
+        let toks: Vec<_> = useful_tokens(b"R\"(a\\\r\nb\r\nc)\"")
+            .into_iter()
+            .map(|(tok, _)| tok)
+            .collect();
+        assert_eq!(
+            toks,
+            vec![Token::LiteralRString(Symbol::intern("a\\\nb\nc"))]
+        );
+    }
+
+    #[test]
+    fn test_token_size() {
+        assert_eq!(std::mem::size_of::<Token>(), 12);
+    }
 }
@@ -0,0 +1,289 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use hashbrown::HashMap;
+use memchr::memchr2;
+
+use super::charset::OffsetMap;
+
+/// The unit used to count the columns
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    /// Bytes
+    Utf8,
+    /// UTF-16 code units (as in LSP)
+    Utf16,
+    /// Unicode code points
+    Char,
+}
+
+/// A line and a column, both 0-based
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct LineCol {
+    pub line: u32,
+    pub column: u32,
+}
+
+/// A multibyte character in a line
+#[derive(Clone, Copy, Debug)]
+struct Wide {
+    /// The byte offset in the line
+    start: u32,
+    len: u8,
+}
+
+impl Wide {
+    fn end(self) -> u32 {
+        self.start + self.len as u32
+    }
+
+    fn units(self, enc: Encoding) -> u32 {
+        match enc {
+            Encoding::Utf8 => self.len as u32,
+            Encoding::Utf16 if self.len == 4 => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// The lines of a file to convert the byte offsets used in the spans into
+/// lines and columns in any encoding (and back).
+/// The newlines are \n, \r\n and \r (as in the lexer) and an invalid UTF-8 byte
+/// counts for one character.
+/// For a transcoded source, the lines are the ones of the text in UTF-8 and the
+/// offsets are the ones in the source.
+#[derive(Debug, Default)]
+pub struct LineIndex {
+    /// The offsets of the beginnings of the lines
+    starts: Vec<usize>,
+    /// The offsets of the ends of the lines (before the newlines)
+    ends: Vec<usize>,
+    /// The multibyte characters of the non-ASCII lines
+    wide: HashMap<u32, Vec<Wide>>,
+    /// To go from the source to the transcoded text
+    offsets: OffsetMap,
+}
+
+/// The length of the UTF-8 sequence starting at the beginning of buf
+/// (1 for an invalid byte)
+fn char_len(buf: &[u8]) -> usize {
+    let n = match buf[0] {
+        0xC2..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF4 => 4,
+        _ => return 1,
+    };
+    if buf.len() >= n && buf[1..n].iter().all(|c| c & 0xC0 == 0x80) {
+        n
+    } else {
+        1
+    }
+}
+
+impl LineIndex {
+    pub fn new(text: &[u8]) -> Self {
+        const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+        // The columns on the first line are counted after the BOM
+        let first = if text.starts_with(BOM) { BOM.len() } else { 0 };
+        let mut starts = vec![first];
+        let mut ends = Vec::new();
+        let mut pos = first;
+        while let Some(i) = memchr2(b'\n', b'\r', &text[pos..]) {
+            let i = pos + i;
+            ends.push(i);
+            pos = if text[i] == b'\r' && text.get(i + 1) == Some(&b'\n') {
+                i + 2
+            } else {
+                i + 1
+            };
+            starts.push(pos);
+        }
+        ends.push(text.len());
+
+        let mut wide = HashMap::default();
+        for (line, &start) in starts.iter().enumerate() {
+            let buf = &text[start..ends[line]];
+            if buf.is_ascii() {
+                continue;
+            }
+
+            let mut chars = Vec::new();
+            let mut i = 0;
+            while i < buf.len() {
+                if buf[i] < 0x80 {
+                    i += 1;
+                    continue;
+                }
+                let len = char_len(&buf[i..]);
+                if len > 1 {
+                    chars.push(Wide {
+                        start: i as u32,
+                        len: len as u8,
+                    });
+                }
+                i += len;
+            }
+            if !chars.is_empty() {
+                wide.insert(line as u32, chars);
+            }
+        }
+
+        Self {
+            starts,
+            ends,
+            wide,
+            offsets: OffsetMap::default(),
+        }
+    }
+
+    pub(crate) fn with_offsets(text: &[u8], offsets: OffsetMap) -> Self {
+        Self {
+            offsets,
+            ..Self::new(text)
+        }
+    }
+
+    /// The number of lines
+    pub fn len(&self) -> usize {
+        self.starts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.starts.is_empty()
+    }
+
+    /// Get the line and the column of a byte offset
+    /// (an offset in the middle of a character gives the column of this character)
+    pub fn line_col(&self, pos: usize, enc: Encoding) -> LineCol {
+        let pos = if self.offsets.is_empty() {
+            pos
+        } else {
+            self.offsets.transcoded(pos)
+        };
+        let line = self.starts.partition_point(|&s| s <= pos).saturating_sub(1);
+        let col = pos.saturating_sub(self.starts[line]) as u32;
+        let line = line as u32;
+
+        let column = match self.wide.get(&line) {
+            Some(chars) if enc != Encoding::Utf8 => {
+                let mut column = col;
+                for c in chars.iter().take_while(|c| c.start < col) {
+                    if col < c.end() {
+                        // In the middle of a character
+                        column -= col - c.start;
+                        break;
+                    }
+                    column = column - c.len as u32 + c.units(enc);
+                }
+                column
+            }
+            _ => col,
+        };
+
+        LineCol { line, column }
+    }
+
+    /// Get the byte offset of a line and a column
+    /// (None when the line doesn't exist and the column is clamped to the end of the line)
+    pub fn offset(&self, lc: LineCol, enc: Encoding) -> Option<usize> {
+        let line = lc.line as usize;
+        let start = *self.starts.get(line)?;
+        let end = self.ends[line];
+
+        // The number of bytes minus the number of units before the current char
+        let mut shift = 0;
+        if enc != Encoding::Utf8 {
+            for c in self.wide.get(&lc.line).into_iter().flatten() {
+                let cstart = c.start - shift;
+                if lc.column <= cstart {
+                    break;
+                }
+                if lc.column < cstart + c.units(enc) {
+                    // A surrogate pair can't be split
+                    return Some(self.original(start + c.start as usize));
+                }
+                shift += c.len as u32 - c.units(enc);
+            }
+        }
+
+        Some(self.original((start + (lc.column + shift) as usize).min(end)))
+    }
+
+    fn original(&self, pos: usize) -> usize {
+        if self.offsets.is_empty() {
+            pos
+        } else {
+            self.offsets.original(pos)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn lc(line: u32, column: u32) -> LineCol {
+        LineCol { line, column }
+    }
+
+    #[test]
+    fn test_line_col() {
+        let text = "int a;\r\nconst char* s = \"é𝄞x\";\rint b;\n";
+        let index = LineIndex::new(text.as_bytes());
+        assert_eq!(index.len(), 4);
+
+        let x = text.find('x').unwrap();
+        assert_eq!(index.line_col(x, Encoding::Utf8), lc(1, 23));
+        assert_eq!(index.line_col(x, Encoding::Utf16), lc(1, 20));
+        assert_eq!(index.line_col(x, Encoding::Char), lc(1, 19));
+
+        let b = text.find('b').unwrap();
+        for enc in [Encoding::Utf8, Encoding::Utf16, Encoding::Char] {
+            assert_eq!(index.line_col(0, enc), lc(0, 0));
+            assert_eq!(index.line_col(b, enc), lc(2, 4));
+        }
+
+        // In the middle of 𝄞
+        let clef = text.find('𝄞').unwrap();
+        assert_eq!(index.line_col(clef + 2, Encoding::Utf16), lc(1, 18));
+        assert_eq!(index.line_col(clef + 2, Encoding::Char), lc(1, 18));
+    }
+
+    #[test]
+    fn test_offset() {
+        let text = "int a;\r\nconst char* s = \"é𝄞x\";\rint b;\n";
+        let index = LineIndex::new(text.as_bytes());
+
+        for (pos, c) in text.char_indices() {
+            if c == '\n' && text[..pos].ends_with('\r') {
+                // The end of the line is before \r\n
+                continue;
+            }
+            for enc in [Encoding::Utf8, Encoding::Utf16, Encoding::Char] {
+                let lc = index.line_col(pos, enc);
+                assert_eq!(index.offset(lc, enc), Some(pos), "{} {:?}", pos, enc);
+            }
+        }
+
+        // The column is clamped and a surrogate pair can't be split
+        assert_eq!(index.offset(lc(0, 100), Encoding::Utf16), Some(6));
+        let clef = text.find('𝄞').unwrap();
+        assert_eq!(index.offset(lc(1, 19), Encoding::Utf16), Some(clef));
+        assert_eq!(index.offset(lc(4, 0), Encoding::Utf16), None);
+    }
+
+    #[test]
+    fn test_bom_and_invalid() {
+        let index = LineIndex::new(b"\xEF\xBB\xBFa\xFFb\xC3\xA9c");
+        assert_eq!(index.line_col(3, Encoding::Char), lc(0, 0));
+        assert_eq!(index.line_col(6, Encoding::Char), lc(0, 3));
+        assert_eq!(index.line_col(8, Encoding::Char), lc(0, 4));
+        assert_eq!(index.line_col(8, Encoding::Utf8), lc(0, 5));
+        assert_eq!(index.offset(lc(0, 4), Encoding::Utf16), Some(8));
+    }
+}
@@ -0,0 +1,248 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use memchr::memchr2;
+use std::borrow::Cow;
+
+use super::buffer::Position;
+use super::lexer::Location;
+
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Some bytes removed from the source
+#[derive(Clone, Copy, Debug)]
+struct Removed {
+    /// The position in the text of the first byte after the removed ones
+    at: usize,
+    /// The number of bytes removed until there
+    shift: usize,
+    /// The number of line splices until there
+    lines: u32,
+    /// The removed bytes are a line splice
+    splice: bool,
+}
+
+/// The lexer works on the text got from the source without the BOM, the line splices
+/// and with \n as the only newline.
+/// The map gives back the positions in the source to have physical lines and columns.
+#[derive(Debug, Default)]
+pub(crate) struct LineMap {
+    removed: Vec<Removed>,
+}
+
+/// Get the text to lex and its map (None when the source can be lexed as is)
+pub(crate) fn clean(src: &[u8]) -> Option<(Vec<u8>, LineMap)> {
+    let mut text = Vec::new();
+    let mut map = LineMap::default();
+    let mut changed = false;
+    let mut shift = 0;
+    let mut lines = 0;
+    // The source is copied only when something has to be changed
+    let mut last = 0;
+    let mut pos = 0;
+
+    if src.starts_with(BOM) {
+        changed = true;
+        shift = BOM.len();
+        last = shift;
+        pos = shift;
+        map.removed.push(Removed {
+            at: 0,
+            shift,
+            lines,
+            splice: false,
+        });
+    }
+
+    while let Some(i) = memchr2(b'\r', b'\\', &src[pos..]) {
+        let i = pos + i;
+        if src[i] == b'\r' {
+            changed = true;
+            text.extend_from_slice(&src[last..i]);
+            if src.get(i + 1) == Some(&b'\n') {
+                // The \n is at the place of the \r so a newline token is still \r\n
+                shift += 1;
+                map.removed.push(Removed {
+                    at: text.len() + 1,
+                    shift,
+                    lines,
+                    splice: false,
+                });
+            } else {
+                text.push(b'\n');
+            }
+            last = i + 1;
+            pos = i + 1;
+        } else {
+            let n = match (src.get(i + 1), src.get(i + 2)) {
+                (Some(b'\r'), Some(b'\n')) => 3,
+                (Some(b'\n'), _) | (Some(b'\r'), _) => 2,
+                _ => {
+                    pos = i + 1;
+                    continue;
+                }
+            };
+            changed = true;
+            text.extend_from_slice(&src[last..i]);
+            shift += n;
+            lines += 1;
+            map.removed.push(Removed {
+                at: text.len(),
+                shift,
+                lines,
+                splice: true,
+            });
+            last = i + n;
+            pos = i + n;
+        }
+    }
+
+    if changed {
+        text.extend_from_slice(&src[last..]);
+        Some((text, map))
+    } else {
+        None
+    }
+}
+
+impl LineMap {
+    #[inline(always)]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.removed.is_empty()
+    }
+
+    /// The number of removals before pos
+    fn count(&self, pos: usize) -> usize {
+        self.removed.partition_point(|r| r.at <= pos)
+    }
+
+    fn find(&self, pos: usize) -> Option<&Removed> {
+        self.count(pos).checked_sub(1).map(|i| &self.removed[i])
+    }
+
+    fn physical(&self, pos: usize) -> usize {
+        pos + self.find(pos).map_or(0, |r| r.shift)
+    }
+
+    /// Get the number of line splices before pos
+    pub(crate) fn lines(&self, pos: usize) -> u32 {
+        self.find(pos).map_or(0, |r| r.lines)
+    }
+
+    /// Get the location in the source of a position in the text
+    /// (line is the line in the text and lpos the position of its beginning)
+    pub(crate) fn location(&self, pos: usize, line: u32, lpos: usize) -> Location {
+        let n = self.count(pos);
+        let (ppos, lines, start) = match n.checked_sub(1).map(|i| &self.removed[i]) {
+            Some(r) => {
+                // A line splice after the beginning of the line starts a new physical line
+                let start = self.removed[..n]
+                    .iter()
+                    .rev()
+                    .take_while(|r| r.at > lpos)
+                    .find(|r| r.splice)
+                    .map_or_else(|| self.physical(lpos), |r| r.at + r.shift);
+                (pos + r.shift, r.lines, start)
+            }
+            None => (pos, 0, lpos),
+        };
+        Location {
+            pos: ppos,
+            line: line + lines,
+            column: (ppos + 1).saturating_sub(start) as u32,
+        }
+    }
+
+    /// Get the position in the text of a location in the source
+    pub(crate) fn position(&self, loc: Location) -> Position {
+        let i = self.removed.partition_point(|r| r.at + r.shift <= loc.pos);
+        let (shift, lines) = i
+            .checked_sub(1)
+            .map_or((0, 0), |i| (self.removed[i].shift, self.removed[i].lines));
+        let pos = loc.pos.saturating_sub(shift);
+        Position {
+            pos,
+            line: loc.line.saturating_sub(lines),
+            lpos: (pos + 1).saturating_sub(loc.column as usize),
+        }
+    }
+
+    /// Put back the line splices removed from text[start..end]
+    /// (they're reverted in the raw strings)
+    pub(crate) fn unsplice<'a>(&self, text: &'a [u8], start: usize, end: usize) -> Cow<'a, [u8]> {
+        let first = self.removed.partition_point(|r| r.at < start);
+        let mut out = Cow::Borrowed(&text[start..end]);
+        let mut last = start;
+        for r in self.removed[first..].iter().take_while(|r| r.at <= end) {
+            if r.splice {
+                let out = out.to_mut();
+                if last == start {
+                    out.clear();
+                }
+                out.extend_from_slice(&text[last..r.at]);
+                out.extend_from_slice(b"\\\n");
+                last = r.at;
+            }
+        }
+        if last != start {
+            out.to_mut().extend_from_slice(&text[last..end]);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn loc(pos: usize, line: u32, column: u32) -> Location {
+        Location { pos, line, column }
+    }
+
+    #[test]
+    fn test_clean() {
+        assert!(clean(b"int a = '\\n';\n").is_none());
+
+        let (text, map) = clean(b"\xEF\xBB\xBFa\r\nb\rc\\\nd\\\r\ne\\\rf\\\\").unwrap();
+        assert_eq!(text, b"a\nb\ncdef\\\\");
+        assert_eq!(map.removed.len(), 5);
+        assert_eq!(map.lines(text.len()), 3);
+    }
+
+    #[test]
+    fn test_location() {
+        // The text is "abc\nde" and the second line starts at 4
+        let src = b"\xEF\xBB\xBFa\\\nbc\r\nd\\\r\ne";
+        let (text, map) = clean(src).unwrap();
+        assert_eq!(text, b"abc\nde");
+
+        assert_eq!(map.location(0, 1, 0), loc(3, 1, 1));
+        assert_eq!(map.location(1, 1, 0), loc(6, 2, 1));
+        assert_eq!(map.location(2, 1, 0), loc(7, 2, 2));
+        assert_eq!(map.location(3, 1, 0), loc(8, 2, 3));
+        assert_eq!(map.location(4, 2, 4), loc(10, 3, 1));
+        assert_eq!(map.location(5, 2, 4), loc(14, 4, 1));
+        assert_eq!(&src[14..15], b"e");
+
+        for (pos, line, lpos) in [(0, 1, 0), (2, 1, 0), (4, 2, 4), (5, 2, 4)] {
+            let p = map.position(map.location(pos, line, lpos));
+            assert_eq!(
+                map.location(p.pos, p.line, p.lpos),
+                map.location(pos, line, lpos)
+            );
+            assert_eq!(p.pos, pos);
+        }
+    }
+
+    #[test]
+    fn test_unsplice() {
+        let (text, map) = clean(b"R\"(a\\\nb\r\nc\\\r\n)\"").unwrap();
+        assert_eq!(text, b"R\"(ab\nc)\"");
+        assert_eq!(map.unsplice(&text, 3, 7).as_ref(), b"a\\\nb\nc\\\n");
+        assert_eq!(map.unsplice(&text, 5, 6).as_ref(), b"\n");
+    }
+}
@@ -0,0 +1,420 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::VecDeque;
+
+use super::lexer::{Lexer, TLexer, Token};
+use super::preprocessor::context::PreprocContext;
+use super::source::FileId;
+use crate::errors::Span;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TriviaKind {
+    Whitespace,
+    Newline,
+    LineContinuation,
+    Comment,
+    /// A preprocessor directive line
+    Directive,
+    /// The lines skipped in a disabled #if/#elif/#else branch
+    Disabled,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trivia<'a> {
+    pub kind: TriviaKind,
+    pub text: &'a [u8],
+}
+
+#[derive(Clone, Debug)]
+pub struct LosslessToken<'a> {
+    pub tok: Token,
+    pub text: &'a [u8],
+    pub span: Span,
+    pub leading: Vec<Trivia<'a>>,
+    /// The trivia until the end of the line
+    pub trailing: Vec<Trivia<'a>>,
+}
+
+impl<'a> LosslessToken<'a> {
+    /// Write the token and its trivia as they were in the source
+    pub fn write(&self, out: &mut Vec<u8>) {
+        for t in self.leading.iter() {
+            out.extend_from_slice(t.text);
+        }
+        out.extend_from_slice(self.text);
+        for t in self.trailing.iter() {
+            out.extend_from_slice(t.text);
+        }
+    }
+}
+
+struct RawToken {
+    tok: Token,
+    start: usize,
+    end: usize,
+    span: Span,
+}
+
+/// Lexer which keeps everything in the source: the tokens are got with their trivia
+/// so writing all of them (Eof included) gives back the source.
+/// The #if conditions are evaluated (the disabled branches are trivia) but the macros
+/// aren't expanded and the includes aren't followed.
+pub struct LosslessLexer<'a, PC: PreprocContext> {
+    lexer: Lexer<'a, PC>,
+    src: &'a [u8],
+    last: usize,
+    pending: VecDeque<Trivia<'a>>,
+    next: Option<RawToken>,
+    done: bool,
+}
+
+impl<'a, PC: PreprocContext> LosslessLexer<'a, PC> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self::from_lexer(Lexer::new(buf), buf)
+    }
+
+    pub fn new_with_context(buf: &'a [u8], source_id: FileId, context: PC) -> Self {
+        Self::from_lexer(Lexer::new_with_context(buf, source_id, context), buf)
+    }
+
+    fn from_lexer(mut lexer: Lexer<'a, PC>, src: &'a [u8]) -> Self {
+        lexer.lossless = true;
+        Self {
+            lexer,
+            src,
+            last: 0,
+            pending: VecDeque::new(),
+            next: None,
+            done: false,
+        }
+    }
+
+    pub fn get_lexer(&self) -> &Lexer<'a, PC> {
+        &self.lexer
+    }
+
+    fn push(&mut self, kind: TriviaKind, start: usize, end: usize) {
+        if start < end {
+            self.pending.push_back(Trivia {
+                kind,
+                text: &self.src[start..end],
+            });
+        }
+    }
+
+    /// Split the bytes skipped by the lexer between two pieces
+    fn push_gap(&mut self, end: usize) {
+        let mut pos = self.last;
+        let mut start = pos;
+        while pos < end {
+            match self.src[pos] {
+                b'\\' => {
+                    let n = if self.src[pos + 1..end].starts_with(b"\r\n") {
+                        3
+                    } else if self.src[pos + 1..end].starts_with(b"\n")
+                        || self.src[pos + 1..end].starts_with(b"\r")
+                    {
+                        2
+                    } else {
+                        pos += 1;
+                        continue;
+                    };
+                    self.push(TriviaKind::Whitespace, start, pos);
+                    self.push(TriviaKind::LineContinuation, pos, pos + n);
+                    pos += n;
+                    start = pos;
+                }
+                b'\n' | b'\r' => {
+                    let n = if self.src[pos..end].starts_with(b"\r\n") {
+                        2
+                    } else {
+                        1
+                    };
+                    self.push(TriviaKind::Whitespace, start, pos);
+                    self.push(TriviaKind::Newline, pos, pos + n);
+                    pos += n;
+                    start = pos;
+                }
+                _ => {
+                    pos += 1;
+                }
+            }
+        }
+        self.push(TriviaKind::Whitespace, start, end);
+    }
+
+    /// Get the end of the line (newline included) starting at pos
+    fn line_end(text: &[u8], pos: usize) -> usize {
+        let mut pos = pos;
+        while pos < text.len() {
+            if text[pos] == b'\n'
+                && !text[..pos].ends_with(b"\\")
+                && !text[..pos].ends_with(b"\\\r")
+            {
+                return pos + 1;
+            }
+            pos += 1;
+        }
+        text.len()
+    }
+
+    /// A directive can be followed by a disabled branch and the directive ending it
+    fn push_directive(&mut self, start: usize, end: usize) {
+        let text = &self.src[start..end];
+        let first = Self::line_end(text, 0);
+        let last = text[..text.len() - 1]
+            .iter()
+            .rposition(|c| *c == b'\n')
+            .map_or(0, |p| p + 1);
+
+        if last > first {
+            self.push(TriviaKind::Directive, start, start + first);
+            self.push(TriviaKind::Disabled, start + first, start + last);
+            self.push(TriviaKind::Directive, start + last, end);
+        } else {
+            self.push(TriviaKind::Directive, start, end);
+        }
+    }
+
+    /// Get the next token and put the trivia before it in pending
+    fn next_raw(&mut self) -> RawToken {
+        loop {
+            let tok = self.lexer.next_token();
+            // The positions are the ones in the source (with the line splices, \r, ...)
+            let end = self.lexer.location().pos;
+            let (start, end) = if tok == Token::Eof && end >= self.src.len() {
+                (self.src.len(), self.src.len())
+            } else {
+                (self.lexer.start.pos, end)
+            };
+
+            self.push_gap(start);
+            self.last = end;
+
+            if start < self.src.len() && self.src[start] == b'#' {
+                self.push_directive(start, end);
+                continue;
+            }
+
+            match tok {
+                Token::Comment => self.push(TriviaKind::Comment, start, end),
+                Token::Eol => self.push(TriviaKind::Newline, start, end),
+                _ => {
+                    return RawToken {
+                        tok,
+                        start,
+                        end,
+                        span: self.lexer.span(),
+                    };
+                }
+            }
+        }
+    }
+
+    pub fn next_lossless(&mut self) -> Option<LosslessToken<'a>> {
+        if self.done {
+            return None;
+        }
+
+        let raw = if let Some(raw) = self.next.take() {
+            raw
+        } else {
+            self.next_raw()
+        };
+        let leading: Vec<_> = self.pending.drain(..).collect();
+
+        if raw.tok == Token::Eof {
+            self.done = true;
+            return Some(LosslessToken {
+                tok: raw.tok,
+                text: &[],
+                span: raw.span,
+                leading,
+                trailing: Vec::new(),
+            });
+        }
+
+        // The trivia until the end of the line are trailing ones
+        let next = self.next_raw();
+        let n = self
+            .pending
+            .iter()
+            .position(|t| t.kind != TriviaKind::Whitespace && t.kind != TriviaKind::Comment)
+            .unwrap_or(self.pending.len());
+        let trailing: Vec<_> = self.pending.drain(..n).collect();
+        self.next = Some(next);
+
+        Some(LosslessToken {
+            tok: raw.tok,
+            text: &self.src[raw.start..raw.end],
+            span: raw.span,
+            leading,
+            trailing,
+        })
+    }
+}
+
+impl<'a, PC: PreprocContext> Iterator for LosslessLexer<'a, PC> {
+    type Item = LosslessToken<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_lossless()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::lexer::preprocessor::context::DefaultContext;
+    use crate::lexer::symbol::Symbol;
+    use pretty_assertions::assert_eq;
+
+    fn roundtrip(src: &[u8]) -> Vec<LosslessToken> {
+        let toks: Vec<_> = LosslessLexer::<DefaultContext>::new(src).collect();
+        let mut out = Vec::new();
+        for tok in toks.iter() {
+            tok.write(&mut out);
+        }
+        assert_eq!(String::from_utf8_lossy(&out), String::from_utf8_lossy(src));
+        toks
+    }
+
+    #[test]
+    fn test_lossless_trivia() {
+        let toks = roundtrip(b"  int x = 1; // one\n/* two */ int y \\\n = 2;\n");
+        let toks: Vec<_> = toks
+            .iter()
+            .map(|t| {
+                (
+                    t.tok,
+                    t.leading.iter().map(|t| t.kind).collect::<Vec<_>>(),
+                    t.trailing.iter().map(|t| t.kind).collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+
+        use self::TriviaKind::*;
+        assert_eq!(
+            toks,
+            vec![
+                (Token::Int, vec![Whitespace], vec![Whitespace]),
+                (
+                    Token::Identifier(Symbol::intern("x")),
+                    vec![],
+                    vec![Whitespace]
+                ),
+                (Token::Equal, vec![], vec![Whitespace]),
+                (Token::from_number("1"), vec![], vec![]),
+                (Token::SemiColon, vec![], vec![Whitespace, Comment]),
+                (
+                    Token::Int,
+                    vec![Newline, Comment, Whitespace],
+                    vec![Whitespace]
+                ),
+                (
+                    Token::Identifier(Symbol::intern("y")),
+                    vec![],
+                    vec![Whitespace]
+                ),
+                (
+                    Token::Equal,
+                    vec![LineContinuation, Whitespace],
+                    vec![Whitespace]
+                ),
+                (Token::from_number("2"), vec![], vec![]),
+                (Token::SemiColon, vec![], vec![]),
+                (Token::Eof, vec![Newline], vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lossless_newlines() {
+        let toks =
+            roundtrip(b"\xEF\xBB\xBFin\\\r\nt x;\r\n#if 0\r\nint y;\r\n#endif\r\n int z; \\\r\n\r");
+        let toks: Vec<_> = toks
+            .iter()
+            .map(|t| {
+                (
+                    t.tok,
+                    t.text,
+                    t.leading.iter().map(|t| t.text).collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+
+        let x = Token::Identifier(Symbol::intern("x"));
+        let z = Token::Identifier(Symbol::intern("z"));
+        assert_eq!(
+            toks,
+            vec![
+                (Token::Int, &b"in\\\r\nt"[..], vec![&b"\xEF\xBB\xBF"[..]]),
+                (x, b"x", vec![]),
+                (Token::SemiColon, b";", vec![]),
+                (
+                    Token::Int,
+                    b"int",
+                    vec![
+                        &b"\r\n"[..],
+                        b"#if 0\r\n",
+                        b"int y;\r\n",
+                        b"#endif",
+                        b"\r\n",
+                        b" "
+                    ]
+                ),
+                (z, b"z", vec![]),
+                (Token::SemiColon, b";", vec![]),
+                (Token::Eof, b"", vec![&b"\\\r\n"[..], b"\r"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lossless_preproc() {
+        let toks = roundtrip(
+            b"#define FOO 1\n#include \"foo.h\"\nint a = FOO;\n#if FOO\nint b;\n#else\nint c;\n#endif // FOO\n#if 0\n  #if 1\nint d;\n  #endif\n#elif FOO\nint e;\n#endif\n\tint f;",
+        );
+
+        let ids: Vec<_> = toks
+            .iter()
+            .filter_map(|t| match &t.tok {
+                Token::Identifier(id) => Some(*id),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ids, vec!["a", "FOO", "b", "e", "f"]);
+
+        let e = toks
+            .iter()
+            .find(|t| t.tok == Token::Identifier(Symbol::intern("e")))
+            .unwrap();
+        let int_e = &toks[toks.iter().position(|t| t.text == e.text).unwrap() - 1];
+        let kinds: Vec<_> = int_e
+            .leading
+            .iter()
+            .map(|t| (t.kind, String::from_utf8_lossy(t.text).to_string()))
+            .collect();
+
+        use self::TriviaKind::*;
+        assert_eq!(
+            kinds,
+            vec![
+                (Newline, "\n".to_string()),
+                (Directive, "#else\n".to_string()),
+                (Disabled, "int c;\n".to_string()),
+                (Directive, "#endif".to_string()),
+                (Whitespace, " ".to_string()),
+                (Comment, "// FOO".to_string()),
+                (Newline, "\n".to_string()),
+                (Directive, "#if 0\n".to_string()),
+                (Disabled, "  #if 1\nint d;\n  #endif\n".to_string()),
+                (Directive, "#elif FOO\n".to_string()),
+            ]
+        );
+    }
+}
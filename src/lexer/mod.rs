@@ -3,17 +3,32 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+#[macro_use]
+pub mod symbol;
+pub use self::symbol::Symbol;
+
 pub mod lexer;
 pub use self::lexer::*;
 
 pub mod buffer;
+pub mod charset;
 pub mod errors;
 pub mod extra;
+pub mod highlight;
+pub mod line_index;
+pub mod lossless;
+pub mod persist;
 pub mod preprocessor;
 pub mod source;
 
 mod cchar;
 mod comment;
+mod lines;
 mod number;
 mod string;
 mod tools;
+
+pub use self::number::{
+    BigUint, FloatId, FloatKind, FloatLiteral, FloatSuffix, IntId, IntKind, IntLiteral, IntSuffix,
+    Radix,
+};
@@ -3,66 +3,18 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use std::fmt;
+use std::ops::Deref;
+
 use bitflags::bitflags;
 use hashbrown::HashMap;
 use lazy_static::lazy_static;
 
-use super::lexer::{self, Lexer, Token};
+use super::errors::LexerError;
+use super::lexer::{Dialect, Lexer, TLexer, Token};
 use super::preprocessor::context::PreprocContext;
-
-const POW_P_10: [f64; 309] = [
-    1., 1e1, 1e2, 1e3, 1e4, 1e5, 1e6, 1e7, 1e8, 1e9, 1e10, 1e11, 1e12, 1e13, 1e14, 1e15, 1e16,
-    1e17, 1e18, 1e19, 1e20, 1e21, 1e22, 1e23, 1e24, 1e25, 1e26, 1e27, 1e28, 1e29, 1e30, 1e31, 1e32,
-    1e33, 1e34, 1e35, 1e36, 1e37, 1e38, 1e39, 1e40, 1e41, 1e42, 1e43, 1e44, 1e45, 1e46, 1e47, 1e48,
-    1e49, 1e50, 1e51, 1e52, 1e53, 1e54, 1e55, 1e56, 1e57, 1e58, 1e59, 1e60, 1e61, 1e62, 1e63, 1e64,
-    1e65, 1e66, 1e67, 1e68, 1e69, 1e70, 1e71, 1e72, 1e73, 1e74, 1e75, 1e76, 1e77, 1e78, 1e79, 1e80,
-    1e81, 1e82, 1e83, 1e84, 1e85, 1e86, 1e87, 1e88, 1e89, 1e90, 1e91, 1e92, 1e93, 1e94, 1e95, 1e96,
-    1e97, 1e98, 1e99, 1e100, 1e101, 1e102, 1e103, 1e104, 1e105, 1e106, 1e107, 1e108, 1e109, 1e110,
-    1e111, 1e112, 1e113, 1e114, 1e115, 1e116, 1e117, 1e118, 1e119, 1e120, 1e121, 1e122, 1e123,
-    1e124, 1e125, 1e126, 1e127, 1e128, 1e129, 1e130, 1e131, 1e132, 1e133, 1e134, 1e135, 1e136,
-    1e137, 1e138, 1e139, 1e140, 1e141, 1e142, 1e143, 1e144, 1e145, 1e146, 1e147, 1e148, 1e149,
-    1e150, 1e151, 1e152, 1e153, 1e154, 1e155, 1e156, 1e157, 1e158, 1e159, 1e160, 1e161, 1e162,
-    1e163, 1e164, 1e165, 1e166, 1e167, 1e168, 1e169, 1e170, 1e171, 1e172, 1e173, 1e174, 1e175,
-    1e176, 1e177, 1e178, 1e179, 1e180, 1e181, 1e182, 1e183, 1e184, 1e185, 1e186, 1e187, 1e188,
-    1e189, 1e190, 1e191, 1e192, 1e193, 1e194, 1e195, 1e196, 1e197, 1e198, 1e199, 1e200, 1e201,
-    1e202, 1e203, 1e204, 1e205, 1e206, 1e207, 1e208, 1e209, 1e210, 1e211, 1e212, 1e213, 1e214,
-    1e215, 1e216, 1e217, 1e218, 1e219, 1e220, 1e221, 1e222, 1e223, 1e224, 1e225, 1e226, 1e227,
-    1e228, 1e229, 1e230, 1e231, 1e232, 1e233, 1e234, 1e235, 1e236, 1e237, 1e238, 1e239, 1e240,
-    1e241, 1e242, 1e243, 1e244, 1e245, 1e246, 1e247, 1e248, 1e249, 1e250, 1e251, 1e252, 1e253,
-    1e254, 1e255, 1e256, 1e257, 1e258, 1e259, 1e260, 1e261, 1e262, 1e263, 1e264, 1e265, 1e266,
-    1e267, 1e268, 1e269, 1e270, 1e271, 1e272, 1e273, 1e274, 1e275, 1e276, 1e277, 1e278, 1e279,
-    1e280, 1e281, 1e282, 1e283, 1e284, 1e285, 1e286, 1e287, 1e288, 1e289, 1e290, 1e291, 1e292,
-    1e293, 1e294, 1e295, 1e296, 1e297, 1e298, 1e299, 1e300, 1e301, 1e302, 1e303, 1e304, 1e305,
-    1e306, 1e307, 1e308,
-];
-
-const POW_M_10: [f64; 309] = [
-    1., 1e-1, 1e-2, 1e-3, 1e-4, 1e-5, 1e-6, 1e-7, 1e-8, 1e-9, 1e-10, 1e-11, 1e-12, 1e-13, 1e-14,
-    1e-15, 1e-16, 1e-17, 1e-18, 1e-19, 1e-20, 1e-21, 1e-22, 1e-23, 1e-24, 1e-25, 1e-26, 1e-27,
-    1e-28, 1e-29, 1e-30, 1e-31, 1e-32, 1e-33, 1e-34, 1e-35, 1e-36, 1e-37, 1e-38, 1e-39, 1e-40,
-    1e-41, 1e-42, 1e-43, 1e-44, 1e-45, 1e-46, 1e-47, 1e-48, 1e-49, 1e-50, 1e-51, 1e-52, 1e-53,
-    1e-54, 1e-55, 1e-56, 1e-57, 1e-58, 1e-59, 1e-60, 1e-61, 1e-62, 1e-63, 1e-64, 1e-65, 1e-66,
-    1e-67, 1e-68, 1e-69, 1e-70, 1e-71, 1e-72, 1e-73, 1e-74, 1e-75, 1e-76, 1e-77, 1e-78, 1e-79,
-    1e-80, 1e-81, 1e-82, 1e-83, 1e-84, 1e-85, 1e-86, 1e-87, 1e-88, 1e-89, 1e-90, 1e-91, 1e-92,
-    1e-93, 1e-94, 1e-95, 1e-96, 1e-97, 1e-98, 1e-99, 1e-100, 1e-101, 1e-102, 1e-103, 1e-104,
-    1e-105, 1e-106, 1e-107, 1e-108, 1e-109, 1e-110, 1e-111, 1e-112, 1e-113, 1e-114, 1e-115, 1e-116,
-    1e-117, 1e-118, 1e-119, 1e-120, 1e-121, 1e-122, 1e-123, 1e-124, 1e-125, 1e-126, 1e-127, 1e-128,
-    1e-129, 1e-130, 1e-131, 1e-132, 1e-133, 1e-134, 1e-135, 1e-136, 1e-137, 1e-138, 1e-139, 1e-140,
-    1e-141, 1e-142, 1e-143, 1e-144, 1e-145, 1e-146, 1e-147, 1e-148, 1e-149, 1e-150, 1e-151, 1e-152,
-    1e-153, 1e-154, 1e-155, 1e-156, 1e-157, 1e-158, 1e-159, 1e-160, 1e-161, 1e-162, 1e-163, 1e-164,
-    1e-165, 1e-166, 1e-167, 1e-168, 1e-169, 1e-170, 1e-171, 1e-172, 1e-173, 1e-174, 1e-175, 1e-176,
-    1e-177, 1e-178, 1e-179, 1e-180, 1e-181, 1e-182, 1e-183, 1e-184, 1e-185, 1e-186, 1e-187, 1e-188,
-    1e-189, 1e-190, 1e-191, 1e-192, 1e-193, 1e-194, 1e-195, 1e-196, 1e-197, 1e-198, 1e-199, 1e-200,
-    1e-201, 1e-202, 1e-203, 1e-204, 1e-205, 1e-206, 1e-207, 1e-208, 1e-209, 1e-210, 1e-211, 1e-212,
-    1e-213, 1e-214, 1e-215, 1e-216, 1e-217, 1e-218, 1e-219, 1e-220, 1e-221, 1e-222, 1e-223, 1e-224,
-    1e-225, 1e-226, 1e-227, 1e-228, 1e-229, 1e-230, 1e-231, 1e-232, 1e-233, 1e-234, 1e-235, 1e-236,
-    1e-237, 1e-238, 1e-239, 1e-240, 1e-241, 1e-242, 1e-243, 1e-244, 1e-245, 1e-246, 1e-247, 1e-248,
-    1e-249, 1e-250, 1e-251, 1e-252, 1e-253, 1e-254, 1e-255, 1e-256, 1e-257, 1e-258, 1e-259, 1e-260,
-    1e-261, 1e-262, 1e-263, 1e-264, 1e-265, 1e-266, 1e-267, 1e-268, 1e-269, 1e-270, 1e-271, 1e-272,
-    1e-273, 1e-274, 1e-275, 1e-276, 1e-277, 1e-278, 1e-279, 1e-280, 1e-281, 1e-282, 1e-283, 1e-284,
-    1e-285, 1e-286, 1e-287, 1e-288, 1e-289, 1e-290, 1e-291, 1e-292, 1e-293, 1e-294, 1e-295, 1e-296,
-    1e-297, 1e-298, 1e-299, 1e-300, 1e-301, 1e-302, 1e-303, 1e-304, 1e-305, 1e-306, 1e-307, 1e-308,
-];
+use super::symbol::Symbol;
+use crate::errors::Span;
 
 #[rustfmt::skip]
 const HEX: [u64; 256] = [
@@ -157,474 +109,697 @@ const NUMCHARS: [Nums; 256] = [
     Nums::NON, Nums::NON, Nums::NON, Nums::NON, Nums::NON, Nums::NON, Nums::NON, Nums::NON, //
 ];
 
-#[derive(Clone)]
-enum IntType {
+/// An arbitrary-precision unsigned integer (just what's needed for the literals)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BigUint {
+    /// The 32 bits digits, least significant first and without leading zeros
+    limbs: Vec<u32>,
+}
+
+impl BigUint {
+    fn from_digits(digits: &[u32], base: u32) -> Self {
+        let mut n = Self::default();
+        for d in digits {
+            n.mul_add(base, *d);
+        }
+        n
+    }
+
+    fn mul_add(&mut self, mul: u32, add: u32) {
+        let mut carry = u64::from(add);
+        for limb in self.limbs.iter_mut() {
+            let x = u64::from(*limb) * u64::from(mul) + carry;
+            *limb = x as u32;
+            carry = x >> 32;
+        }
+        if carry != 0 {
+            self.limbs.push(carry as u32);
+        }
+    }
+
+    fn div_rem(&mut self, div: u32) -> u32 {
+        let mut rem = 0;
+        for limb in self.limbs.iter_mut().rev() {
+            let x = rem << 32 | u64::from(*limb);
+            *limb = (x / u64::from(div)) as u32;
+            rem = x % u64::from(div);
+        }
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+        rem as u32
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    /// Get the number of significant bits
+    pub fn bits(&self) -> u64 {
+        self.limbs.last().map_or(0, |l| {
+            32 * self.limbs.len() as u64 - u64::from(l.leading_zeros())
+        })
+    }
+
+    pub fn to_u64(&self) -> Option<u64> {
+        match self.limbs[..] {
+            [] => Some(0),
+            [a] => Some(u64::from(a)),
+            [a, b] => Some(u64::from(a) | u64::from(b) << 32),
+            _ => None,
+        }
+    }
+
+    /// Get self * 2^exp as a f64
+    pub fn to_f64_exp2(&self, exp: i64) -> f64 {
+        // Keep the 64 most significant bits
+        let shift = self.bits().saturating_sub(64);
+        let i = (shift / 32) as usize;
+        let get = |j: usize| u128::from(self.limbs.get(j).copied().unwrap_or(0));
+        let top = ((get(i) | get(i + 1) << 32 | get(i + 2) << 64) >> (shift % 32)) as u64;
+
+        // The discarded bits are kept as a sticky bit (the last one of the 11 bits
+        // dropped when converting to f64) to round correctly the halfway cases
+        let low = get(i) & ((1 << (shift % 32)) - 1);
+        let sticky = low != 0 || self.limbs.iter().take(i).any(|l| *l != 0);
+        let top = top | sticky as u64;
+
+        // Multiply in two steps to avoid an intermediate overflow or underflow
+        let exp = exp.saturating_add(shift as i64).clamp(-4000, 4000) as i32;
+        let half = exp / 2;
+        (top as f64) * 2f64.powi(half) * 2f64.powi(exp - half)
+    }
+}
+
+impl From<u64> for BigUint {
+    fn from(n: u64) -> Self {
+        let mut limbs = vec![n as u32, (n >> 32) as u32];
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+        Self { limbs }
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(n) = self.to_u64() {
+            return write!(f, "{}", n);
+        }
+
+        let mut n = self.clone();
+        let mut chunks = Vec::new();
+        while !n.is_zero() {
+            chunks.push(n.div_rem(1_000_000_000));
+        }
+        write!(f, "{}", chunks.pop().unwrap())?;
+        for c in chunks.iter().rev() {
+            write!(f, "{:09}", c)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Radix {
+    Bin,
+    Oct,
+    Dec,
+    Hex,
+}
+
+impl Radix {
+    pub fn base(self) -> u32 {
+        match self {
+            Radix::Bin => 2,
+            Radix::Oct => 8,
+            Radix::Dec => 10,
+            Radix::Hex => 16,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IntSuffix {
+    None,
     U,
     L,
     UL,
     LL,
     ULL,
-    UserDefined(String),
+    /// size_t (C++23)
+    Z,
+    UZ,
+    /// _BitInt (C23)
+    WB,
+    UWB,
+    UserDefined(Symbol),
 }
 
-lazy_static! {
-    static ref INT_SUFFIXES: HashMap<&'static str, IntType> = {
-        let mut map = HashMap::with_capacity(32);
-        map.insert("u", IntType::U);
-        map.insert("U", IntType::U);
-        map.insert("l", IntType::L);
-        map.insert("L", IntType::L);
-        map.insert("ul", IntType::UL);
-        map.insert("Ul", IntType::UL);
-        map.insert("uL", IntType::UL);
-        map.insert("UL", IntType::UL);
-        map.insert("lu", IntType::UL);
-        map.insert("lU", IntType::UL);
-        map.insert("Lu", IntType::UL);
-        map.insert("LU", IntType::UL);
-        map.insert("ll", IntType::LL);
-        map.insert("LL", IntType::LL);
-        map.insert("llu", IntType::ULL);
-        map.insert("llU", IntType::ULL);
-        map.insert("LLu", IntType::ULL);
-        map.insert("LLU", IntType::ULL);
-        map.insert("ull", IntType::ULL);
-        map.insert("Ull", IntType::ULL);
-        map.insert("uLL", IntType::ULL);
-        map.insert("ULL", IntType::ULL);
-        map
-    };
+/// The type of an integer literal (with the LP64 data model)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IntKind {
+    Int,
+    UInt,
+    Long,
+    ULong,
+    LongLong,
+    ULongLong,
+    /// The signed type corresponding to size_t
+    SignedSize,
+    Size,
+    /// _BitInt(N) and unsigned _BitInt(N)
+    BitInt(u32),
+    UBitInt(u32),
 }
 
-#[derive(Clone)]
-enum FloatType {
-    F,
-    L,
-    UserDefined(String),
+impl IntKind {
+    /// The max value which fits in an u64 (u64::MAX for the wider _BitInt)
+    pub fn max_value(self) -> u64 {
+        match self {
+            IntKind::Int => i32::MAX as u64,
+            IntKind::UInt => u64::from(u32::MAX),
+            IntKind::Long | IntKind::LongLong | IntKind::SignedSize => i64::MAX as u64,
+            IntKind::ULong | IntKind::ULongLong | IntKind::Size => u64::MAX,
+            IntKind::BitInt(n) => u64::MAX.checked_shr(65u32.saturating_sub(n)).unwrap_or(0),
+            IntKind::UBitInt(n) => u64::MAX.checked_shr(64u32.saturating_sub(n)).unwrap_or(0),
+        }
+    }
+
+    pub fn is_signed(self) -> bool {
+        matches!(
+            self,
+            IntKind::Int
+                | IntKind::Long
+                | IntKind::LongLong
+                | IntKind::SignedSize
+                | IntKind::BitInt(_)
+        )
+    }
 }
 
-lazy_static! {
-    static ref FLOAT_SUFFIXES: HashMap<&'static str, FloatType> = {
-        let mut map = HashMap::with_capacity(4);
-        map.insert("f", FloatType::F);
-        map.insert("F", FloatType::F);
-        map.insert("l", FloatType::L);
-        map.insert("L", FloatType::L);
-        map
-    };
+#[derive(Clone, Debug, PartialEq)]
+pub struct IntLiteral {
+    /// The literal as written in the source
+    pub spelling: String,
+    pub radix: Radix,
+    pub value: BigUint,
+    pub suffix: IntSuffix,
+    /// The type of the literal (unsigned long long for a user-defined one)
+    pub kind: IntKind,
 }
 
-#[inline(always)]
-pub(crate) fn get_decimal(dec: u64, exp: i64) -> f64 {
-    if exp == 0 {
-        dec as f64
-    } else if exp < 0 {
-        let exp = (-exp) as usize;
-        if exp <= 308 {
-            (dec as f64) * unsafe { POW_M_10.get_unchecked(exp) }
-        } else if exp <= 327 {
-            ((dec as f64) * unsafe { POW_M_10.get_unchecked(exp - 19) })
-                * unsafe { POW_M_10.get_unchecked(19) }
-        } else {
-            0.
+impl IntLiteral {
+    /// Parse the spelling of an integer literal (None if it's a floating point one)
+    pub fn new(spelling: &str) -> Option<Self> {
+        match parse_number(spelling).0 {
+            Token::LiteralInt(lit) => Some(lit.get().clone()),
+            _ => None,
         }
-    } else if exp <= 308 {
-        let exp = exp as usize;
-        (dec as f64) * unsafe { POW_P_10.get_unchecked(exp) }
-    } else {
-        std::f64::INFINITY
     }
-}
 
-#[inline(always)]
-pub(crate) fn get_hex_decimal(dec: u64, exp: i64) -> f64 {
-    if exp == 0 {
-        dec as f64
-    } else {
-        (dec as f64) * (exp as f64).exp2()
+    pub fn to_u64(&self) -> Option<u64> {
+        self.value.to_u64()
     }
 }
 
-impl<'a, PC: PreprocContext> Lexer<'a, PC> {
-    #[inline(always)]
-    pub(crate) fn get_exponent(&mut self) -> i64 {
-        if self.buf.has_char() {
-            let c = self.buf.next_char();
-            let sign = c == b'-';
-            let c = if sign || c == b'+' {
-                self.buf.inc();
-                self.buf.next_char()
-            } else {
-                c
-            };
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FloatSuffix {
+    None,
+    F,
+    L,
+    UserDefined(Symbol),
+}
 
-            self.buf.inc();
-            let first = u64::from(c - b'0');
-            let num = self.get_int(first) as i64;
-            if sign {
-                -num
-            } else {
-                num
-            }
-        } else {
-            0
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FloatKind {
+    Float,
+    Double,
+    LongDouble,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FloatLiteral {
+    /// The literal as written in the source
+    pub spelling: String,
+    /// Dec or Hex
+    pub radix: Radix,
+    /// The exact value is significand * 10^exponent (or 2^exponent for an hexadecimal literal)
+    pub significand: BigUint,
+    pub exponent: i64,
+    pub suffix: FloatSuffix,
+    /// The type of the literal (long double for a user-defined one)
+    pub kind: FloatKind,
+    /// The nearest double to the value
+    pub value: f64,
+}
+
+impl FloatLiteral {
+    /// Parse the spelling of a floating point literal (None if it's an integer one)
+    pub fn new(spelling: &str) -> Option<Self> {
+        match parse_number(spelling).0 {
+            Token::LiteralFloat(lit) => Some(lit.get().clone()),
+            _ => None,
         }
     }
+}
 
-    #[inline(always)]
-    pub(crate) fn get_number_after_dot(&mut self, start: u64) -> (u64, i64) {
-        let spos = self.buf.pos();
-        let mut num = start;
-        loop {
-            if self.buf.has_char() {
-                let c = self.buf.next_char();
-                if b'0' <= c && c <= b'9' {
-                    self.buf.inc();
-                    if num > (std::u64::MAX >> 7) {
-                        let shift = (self.buf.pos() - spos) as i64;
-                        let exp = self.skip_and_get_exponent();
-                        return (num, exp + 1 - shift);
-                    }
-                    num = 10 * num + u64::from(c - b'0');
-                } else if c == b'e' || c == b'E' {
-                    let shift = (self.buf.pos() - spos) as i64;
-                    self.buf.inc();
-                    let exp = self.get_exponent();
-                    return (num, exp - shift);
+/// A preprocessing number can't start with a '!'
+const INVALID_KEY: char = '!';
+
+macro_rules! literal_id {
+    ($id: ident, $lit: ident, $interner: ident) => {
+        interner!($interner, $lit);
+
+        /// A handle on a literal held in a side table
+        #[derive(Clone, Copy)]
+        pub struct $id(u32);
+
+        impl $id {
+            /// An invalid literal must be reported each time it's met, so it's interned
+            /// under a key which can't be found when looking up a spelling
+            fn new(lit: $lit, valid: bool) -> Self {
+                let key = if valid {
+                    lit.spelling.clone()
                 } else {
-                    let shift = (self.buf.pos() - spos) as i64;
-                    return (num, -shift);
-                }
-            } else {
-                let shift = (self.buf.pos() - spos) as i64;
-                return (num, -shift);
+                    format!("{}{}", INVALID_KEY, lit.spelling)
+                };
+                Self($interner().intern(&key, || {
+                    let lit: &'static $lit = Box::leak(Box::new(lit));
+                    let key: &'static str = Box::leak(key.as_str().into());
+                    (lit, key)
+                }))
             }
-        }
-    }
 
-    #[inline(always)]
-    pub(crate) fn skip_and_get_exponent(&mut self) -> i64 {
-        loop {
-            if self.buf.has_char() {
-                let c = self.buf.next_char();
-                if b'0' <= c && c <= b'9' {
-                    self.buf.inc();
-                    continue;
-                } else if c == b'e' || c == b'E' {
-                    self.buf.inc();
-                    return self.get_exponent();
-                } else {
-                    return 0;
-                }
-            } else {
-                return 0;
+            pub fn get(self) -> &'static $lit {
+                $interner().get(self.0)
             }
         }
-    }
 
-    #[inline(always)]
-    pub(crate) fn get_dot_or_number(&mut self) -> Token {
-        if self.buf.has_char() {
-            let c = self.buf.next_char();
-            if b'0' <= c && c <= b'9' {
-                self.buf.inc();
-                let (dec, exp) = self.get_number_after_dot(u64::from(c - b'0'));
-                return self.get_typed_float(get_decimal(dec, exp - 1));
-            } else if c == b'.' {
-                self.buf.inc();
-                if self.buf.has_char() {
-                    let c = self.buf.next_char();
-                    if c == b'.' {
-                        self.buf.inc();
-                        return Token::Ellipsis;
-                    }
-                }
-            } else if c == b'*' {
-                self.buf.inc();
-                return Token::DotStar;
+        impl Deref for $id {
+            type Target = $lit;
+
+            fn deref(&self) -> &$lit {
+                self.get()
             }
         }
-        Token::Dot
-    }
 
-    #[inline(always)]
-    pub(crate) fn get_hex_digit(c: u8) -> u64 {
-        unsafe { *HEX.get_unchecked(c as usize) }
-    }
+        impl PartialEq for $id {
+            fn eq(&self, other: &Self) -> bool {
+                self.0 == other.0 || self.get() == other.get()
+            }
+        }
 
-    #[inline(always)]
-    pub(crate) fn get_base_hex(&mut self) -> u64 {
-        let mut num = 0;
-        loop {
-            if self.buf.has_char() {
-                let c = self.buf.next_char();
-                let n = Self::get_hex_digit(c);
-                if n < 16 {
-                    self.buf.inc();
-                    num = 16 * num + n;
-                } else if c == b'\'' {
-                    self.buf.inc();
-                } else {
-                    break;
-                }
-            } else {
-                break;
+        impl fmt::Debug for $id {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                fmt::Debug::fmt(self.get(), f)
             }
         }
-        num
+    };
+}
+
+literal_id!(IntId, IntLiteral, int_literals);
+literal_id!(FloatId, FloatLiteral, float_literals);
+
+impl Token {
+    /// Get the token for the spelling of a numeric literal
+    pub fn from_number(spelling: &str) -> Self {
+        parse_number(spelling).0
     }
+}
 
-    #[inline(always)]
-    pub(crate) fn get_hex_after_dot(&mut self, start: u64) -> (u64, i64) {
-        let spos = self.buf.pos();
-        let mut num = start;
-        loop {
-            if self.buf.has_char() {
-                let c = self.buf.next_char();
-                self.buf.inc();
-                let d = Self::get_hex_digit(c);
-                if num <= (std::u64::MAX / 16) && d < 16 {
-                    num = 16 * num + d;
-                } else if c == b'p' || c == b'P' {
-                    let shift = (self.buf.pos() - spos) as i64;
-                    let exp = self.get_exponent();
-                    return (num, exp - 4 * shift);
-                } else {
-                    let shift = (self.buf.pos() - spos) as i64;
-                    return (num, -4 * shift);
-                }
-            } else {
-                let shift = (self.buf.pos() - spos) as i64;
-                return (num, -4 * shift);
-            }
+lazy_static! {
+    static ref INT_SUFFIXES: HashMap<&'static str, IntSuffix> = {
+        let mut map = HashMap::with_capacity(32);
+        map.insert("", IntSuffix::None);
+        map.insert("u", IntSuffix::U);
+        map.insert("U", IntSuffix::U);
+        map.insert("l", IntSuffix::L);
+        map.insert("L", IntSuffix::L);
+        map.insert("ul", IntSuffix::UL);
+        map.insert("Ul", IntSuffix::UL);
+        map.insert("uL", IntSuffix::UL);
+        map.insert("UL", IntSuffix::UL);
+        map.insert("lu", IntSuffix::UL);
+        map.insert("lU", IntSuffix::UL);
+        map.insert("Lu", IntSuffix::UL);
+        map.insert("LU", IntSuffix::UL);
+        map.insert("ll", IntSuffix::LL);
+        map.insert("LL", IntSuffix::LL);
+        map.insert("llu", IntSuffix::ULL);
+        map.insert("llU", IntSuffix::ULL);
+        map.insert("LLu", IntSuffix::ULL);
+        map.insert("LLU", IntSuffix::ULL);
+        map.insert("ull", IntSuffix::ULL);
+        map.insert("Ull", IntSuffix::ULL);
+        map.insert("uLL", IntSuffix::ULL);
+        map.insert("ULL", IntSuffix::ULL);
+        for z in &["z", "Z"] {
+            map.insert(z, IntSuffix::Z);
         }
+        for uz in &["uz", "uZ", "Uz", "UZ", "zu", "zU", "Zu", "ZU"] {
+            map.insert(uz, IntSuffix::UZ);
+        }
+        for wb in &["wb", "WB"] {
+            map.insert(wb, IntSuffix::WB);
+        }
+        for uwb in &["uwb", "uWB", "Uwb", "UWB", "wbu", "wbU", "WBu", "WBU"] {
+            map.insert(uwb, IntSuffix::UWB);
+        }
+        map
+    };
+}
+
+/// The suffixes without an underscore are reserved to the standard library
+fn is_ud_suffix(suffix: &str) -> bool {
+    suffix.starts_with('_')
+        || ["h", "min", "s", "ms", "us", "ns", "d", "y", "i", "il", "if"].contains(&suffix)
+}
+
+/// Get the first type in the list for the suffix and the radix where the value fits
+fn get_int_kind(value: &BigUint, radix: Radix, suffix: &IntSuffix) -> Option<IntKind> {
+    use IntKind::*;
+
+    // The width of a _BitInt is the min one to hold the value (with the sign bit)
+    let bits = value.bits() as u32;
+    match suffix {
+        IntSuffix::WB => return Some(BitInt((bits + 1).max(2))),
+        IntSuffix::UWB => return Some(UBitInt(bits.max(1))),
+        _ => {}
     }
 
-    #[inline(always)]
-    pub(crate) fn get_hex(&mut self) -> Token {
-        let num = self.get_base_hex();
+    let value = value.to_u64()?;
+    let dec = radix == Radix::Dec;
+    let kinds: &[IntKind] = match suffix {
+        IntSuffix::None if dec => &[Int, Long, LongLong],
+        IntSuffix::None => &[Int, UInt, Long, ULong, LongLong, ULongLong],
+        IntSuffix::U => &[UInt, ULong, ULongLong],
+        IntSuffix::L if dec => &[Long, LongLong],
+        IntSuffix::L => &[Long, ULong, LongLong, ULongLong],
+        IntSuffix::UL => &[ULong, ULongLong],
+        IntSuffix::LL if dec => &[LongLong],
+        IntSuffix::LL => &[LongLong, ULongLong],
+        IntSuffix::ULL => &[ULongLong],
+        IntSuffix::Z if dec => &[SignedSize],
+        IntSuffix::Z => &[SignedSize, Size],
+        IntSuffix::UZ => &[Size],
+        IntSuffix::WB | IntSuffix::UWB => unreachable!(),
+        IntSuffix::UserDefined(_) => &[ULongLong],
+    };
+    kinds.iter().copied().find(|k| value <= k.max_value())
+}
 
-        let c = self.buf.next_char();
-        if c == b'.' {
-            self.buf.inc();
-            let c = self.buf.next_char();
-            self.buf.inc();
-            let d = Self::get_hex_digit(c);
-            if d < 16 {
-                if num > (std::u64::MAX / 16) {
-                    let _ = self.get_hex_after_dot(0);
-                    return self.get_typed_float(num as f64);
-                }
-                let num = 16 * num + d;
-                let (dec, exp) = self.get_hex_after_dot(num);
-                self.get_typed_float(get_hex_decimal(dec, exp))
-            } else if c == b'p' || c == b'P' {
-                let exp = self.get_exponent();
-                self.get_typed_float(get_hex_decimal(num, exp))
-            } else {
-                self.get_typed_float_suf(c, num as f64)
-            }
-        } else if c == b'p' || c == b'P' {
-            self.buf.inc();
-            let exp = self.get_exponent();
-            self.get_typed_float(get_hex_decimal(num, exp))
-        } else {
-            self.get_typed_int(num)
-        }
+/// Check (approximately) if the value is greater than the max of a 80 bits long double
+fn is_long_double_overflow(significand: &BigUint, exponent: i64, radix: Radix) -> bool {
+    if significand.is_zero() {
+        false
+    } else if radix == Radix::Hex {
+        significand.bits() as i64 - 1 + exponent >= 16384
+    } else {
+        significand.to_string().len() as i64 - 1 + exponent > 4932
     }
+}
 
-    #[inline(always)]
-    pub(crate) fn get_oct(&mut self, start: u64) -> Token {
-        let mut num = start;
-        loop {
-            if self.buf.has_char() {
-                let c = self.buf.next_char();
-                if b'0' <= c && c <= b'7' {
-                    self.buf.inc();
-                    num = 8 * num + u64::from(c - b'0');
-                } else if c == b'\'' {
-                    self.buf.inc();
-                } else {
-                    break;
-                }
-            } else {
-                break;
+#[derive(Debug, PartialEq)]
+pub(crate) enum NumberError {
+    IntegerTooLarge,
+    FloatTooLarge,
+    InvalidSuffix(String),
+    /// An invalid digit (at offset in the spelling) in an octal or binary literal
+    InvalidDigit {
+        offset: usize,
+        digit: char,
+        radix: Radix,
+    },
+    /// No digits after the 0x or 0b prefix
+    MissingDigits(Radix),
+}
+
+impl NumberError {
+    fn into_lexer_error(self, sp: Span, literal: String) -> LexerError {
+        match self {
+            NumberError::IntegerTooLarge => LexerError::IntegerTooLarge { sp, literal },
+            NumberError::FloatTooLarge => LexerError::FloatTooLarge { sp, literal },
+            NumberError::InvalidSuffix(suffix) => LexerError::InvalidNumberSuffix {
+                sp,
+                literal,
+                suffix,
+            },
+            NumberError::InvalidDigit { digit, radix, .. } => {
+                LexerError::InvalidDigit { sp, digit, radix }
             }
+            NumberError::MissingDigits(radix) => LexerError::MissingDigits { sp, literal, radix },
         }
-        self.get_typed_int(num)
     }
+}
 
-    #[inline(always)]
-    fn get_bin(&mut self) -> Token {
-        let mut num = 0;
-        loop {
-            if self.buf.has_char() {
-                let c = self.buf.next_char();
-                if b'0' <= c && c <= b'1' {
-                    self.buf.inc();
-                    num = 2 * num + u64::from(c - b'0');
-                } else if c == b'\'' {
-                    self.buf.inc();
-                } else {
-                    break;
-                }
-            } else {
-                break;
+/// Parse the spelling of a preprocessing number
+pub(crate) fn parse_number(spelling: &str) -> (Token, Option<NumberError>) {
+    if let Some(id) = int_literals().lookup(spelling) {
+        return (Token::LiteralInt(IntId(id)), None);
+    }
+    if let Some(id) = float_literals().lookup(spelling) {
+        return (Token::LiteralFloat(FloatId(id)), None);
+    }
+
+    let s = spelling.as_bytes();
+    let (mut radix, mut pos) = match s {
+        [b'0', b'x', ..] | [b'0', b'X', ..] => (Radix::Hex, 2),
+        [b'0', b'b', ..] | [b'0', b'B', ..] => (Radix::Bin, 2),
+        _ => (Radix::Dec, 0),
+    };
+    // The octal and binary digits are scanned as decimal ones to catch the invalid ones
+    let base = if radix == Radix::Hex { 16 } else { 10 };
+    let max_digit = if radix == Radix::Bin { 2 } else { 8 };
+
+    let mut digits = Vec::with_capacity(s.len());
+    let mut frac = 0;
+    let mut float = false;
+    // The position of the first invalid digit in case of an octal or binary literal
+    let mut bad_digit = None;
+
+    while pos < s.len() {
+        let c = s[pos];
+        let d = HEX[c as usize] as u32;
+        if d < base {
+            if base == 10 && d >= max_digit && bad_digit.is_none() {
+                bad_digit = Some(pos);
             }
+            digits.push(d);
+            if float {
+                frac += 1;
+            }
+        } else if c == b'.' && !float && radix != Radix::Bin {
+            float = true;
+        } else if c != b'\'' {
+            break;
         }
-        self.get_typed_int(num)
+        pos += 1;
     }
 
-    #[inline(always)]
-    pub(crate) fn get_int(&mut self, start: u64) -> u64 {
-        let mut num = start;
-        loop {
-            if self.buf.has_char() {
-                let c = self.buf.next_char();
-                if b'0' <= c && c <= b'9' {
-                    self.buf.inc();
-                    // TODO: not correct here... we should handle number differently (using biguint or something similar
-                    if num <= std::u64::MAX / 10 {
-                        num = 10 * num + u64::from(c - b'0');
-                    }
-                } else if c == b'\'' {
-                    self.buf.inc();
-                } else {
-                    break;
+    let missing_digits = if digits.is_empty() && radix != Radix::Dec {
+        Some(NumberError::MissingDigits(radix))
+    } else {
+        None
+    };
+
+    let mut exp = 0i64;
+    let marker = if radix == Radix::Hex { b'p' } else { b'e' };
+    if radix != Radix::Bin && pos < s.len() && s[pos].to_ascii_lowercase() == marker {
+        let mut p = pos + 1;
+        let neg = s.get(p) == Some(&b'-');
+        if neg || s.get(p) == Some(&b'+') {
+            p += 1;
+        }
+        if s.get(p).is_some_and(u8::is_ascii_digit) {
+            while p < s.len() && (s[p].is_ascii_digit() || s[p] == b'\'') {
+                if s[p] != b'\'' {
+                    exp = exp
+                        .saturating_mul(10)
+                        .saturating_add(i64::from(s[p] - b'0'));
                 }
-            } else {
-                break;
+                p += 1;
+            }
+            if neg {
+                exp = -exp;
             }
+            float = true;
+            pos = p;
         }
-        num
     }
 
-    #[inline(always)]
-    pub(crate) fn get_typed_int(&mut self, num: u64) -> Token {
-        if let Some(suf) = self.get_int_type() {
-            match suf {
-                IntType::U => Token::LiteralUInt(num),
-                IntType::L => Token::LiteralLong(num),
-                IntType::UL => Token::LiteralULong(num),
-                IntType::LL => Token::LiteralLongLong(num),
-                IntType::ULL => Token::LiteralULongLong(num),
-                IntType::UserDefined(suf) => Token::LiteralIntUD(Box::new((num, suf))),
+    if float {
+        let suffix = &spelling[pos..];
+        let (suffix, kind, error) = match suffix {
+            "" => (FloatSuffix::None, FloatKind::Double, None),
+            "f" | "F" => (FloatSuffix::F, FloatKind::Float, None),
+            "l" | "L" => (FloatSuffix::L, FloatKind::LongDouble, None),
+            _ => (
+                FloatSuffix::UserDefined(Symbol::intern(suffix)),
+                FloatKind::LongDouble,
+                if is_ud_suffix(suffix) {
+                    None
+                } else {
+                    Some(NumberError::InvalidSuffix(suffix.to_string()))
+                },
+            ),
+        };
+
+        let significand = BigUint::from_digits(&digits, base);
+        let (exponent, value) = if radix == Radix::Hex {
+            let exponent = exp.saturating_sub(4 * frac);
+            (exponent, significand.to_f64_exp2(exponent))
+        } else {
+            let text: String = spelling[..pos].chars().filter(|c| *c != '\'').collect();
+            (exp.saturating_sub(frac), text.parse().unwrap_or(0.))
+        };
+
+        let too_large = match (&suffix, kind) {
+            (FloatSuffix::UserDefined(_), _) => false,
+            (_, FloatKind::Float) => (value as f32).is_infinite(),
+            (_, FloatKind::Double) => value.is_infinite(),
+            (_, FloatKind::LongDouble) => {
+                value.is_infinite() && is_long_double_overflow(&significand, exponent, radix)
             }
+        };
+        let error = missing_digits.or(error).or(if too_large {
+            Some(NumberError::FloatTooLarge)
         } else {
-            Token::LiteralInt(num)
-        }
+            None
+        });
+
+        let lit = FloatLiteral {
+            spelling: spelling.to_string(),
+            radix,
+            significand,
+            exponent,
+            suffix,
+            kind,
+            value,
+        };
+        return (
+            Token::LiteralFloat(FloatId::new(lit, error.is_none())),
+            error,
+        );
     }
 
+    if radix == Radix::Dec && s[0] == b'0' && digits.len() > 1 {
+        radix = Radix::Oct;
+    }
+
+    let mut error = missing_digits.or(match (radix, bad_digit) {
+        (Radix::Oct, Some(offset)) | (Radix::Bin, Some(offset)) => {
+            Some(NumberError::InvalidDigit {
+                offset,
+                digit: char::from(s[offset]),
+                radix,
+            })
+        }
+        _ => None,
+    });
+
+    let suffix = &spelling[pos..];
+    let suffix = if let Some(suffix) = INT_SUFFIXES.get(suffix) {
+        *suffix
+    } else {
+        if !is_ud_suffix(suffix) {
+            error = error.or_else(|| Some(NumberError::InvalidSuffix(suffix.to_string())));
+        }
+        IntSuffix::UserDefined(Symbol::intern(suffix))
+    };
+
+    let value = BigUint::from_digits(&digits, radix.base());
+    let kind = if let Some(kind) = get_int_kind(&value, radix, &suffix) {
+        kind
+    } else {
+        error = error.or(Some(NumberError::IntegerTooLarge));
+        IntKind::ULongLong
+    };
+
+    let lit = IntLiteral {
+        spelling: spelling.to_string(),
+        radix,
+        value,
+        suffix,
+        kind,
+    };
+    (Token::LiteralInt(IntId::new(lit, error.is_none())), error)
+}
+
+impl<'a, PC: PreprocContext> Lexer<'a, PC> {
     #[inline(always)]
-    fn get_int_type(&mut self) -> Option<IntType> {
+    pub(crate) fn get_dot_or_number(&mut self) -> Token {
         if self.buf.has_char() {
             let c = self.buf.next_char();
-            let kind = unsafe { lexer::CHARS.get_unchecked(c as usize) };
-            if *kind != lexer::Kind::NON {
-                // we've a suffix
+            if c.is_ascii_digit() {
+                return self.get_number();
+            } else if c == b'.' {
                 self.buf.inc();
-                let id = self.get_identifier_str();
-                if let Some(suf) = INT_SUFFIXES.get(id) {
-                    return Some(suf.clone());
-                } else {
-                    return Some(IntType::UserDefined(id.to_string()));
+                if self.buf.has_char() {
+                    let c = self.buf.next_char();
+                    if c == b'.' {
+                        self.buf.inc();
+                        return Token::Ellipsis;
+                    }
                 }
+            } else if c == b'*' {
+                self.buf.inc();
+                return Token::DotStar;
             }
         }
-        None
+        Token::Dot
     }
 
     #[inline(always)]
-    pub(crate) fn get_typed_float(&mut self, num: f64) -> Token {
-        if self.buf.has_char() {
-            let c = self.buf.next_char();
-            self.get_typed_float_suf(c, num)
-        } else {
-            Token::LiteralDouble(num)
-        }
+    pub(crate) fn get_hex_digit(c: u8) -> u64 {
+        unsafe { *HEX.get_unchecked(c as usize) }
     }
 
+    /// Skip the rest of a preprocessing number (digits, letters, ', e+, p-, ...)
     #[inline(always)]
-    fn get_float_type(&mut self, suf: u8) -> Option<FloatType> {
-        let kind = unsafe { lexer::CHARS.get_unchecked(suf as usize) };
-        if *kind != lexer::Kind::NON {
-            // we've a suffix
-            self.buf.inc();
-            let id = self.get_identifier_str();
-            if let Some(suf) = FLOAT_SUFFIXES.get(id) {
-                Some(suf.clone())
+    fn skip_pp_number(&mut self) {
+        // The digit separators are in C++14 and C23
+        let quote = !self.dialect.is_c() || self.dialect.contains(Dialect::C23);
+        while self.buf.has_char() {
+            let c = self.buf.next_char();
+            if c.is_ascii_alphanumeric() || c == b'_' || c == b'.' {
+                self.buf.inc();
+                if matches!(c, b'e' | b'E' | b'p' | b'P')
+                    && self.buf.has_char()
+                    && matches!(self.buf.next_char(), b'+' | b'-')
+                {
+                    self.buf.inc();
+                }
+            } else if c == b'\''
+                && quote
+                && self.buf.has_char_n(1)
+                && (self.buf.next_char_n(1).is_ascii_alphanumeric()
+                    || self.buf.next_char_n(1) == b'_')
+            {
+                self.buf.inc_n(2);
             } else {
-                Some(FloatType::UserDefined(id.to_string()))
+                break;
             }
-        } else {
-            None
         }
     }
 
-    #[inline(always)]
-    pub(crate) fn get_typed_float_suf(&mut self, suf: u8, num: f64) -> Token {
-        if let Some(suf) = self.get_float_type(suf) {
-            self.buf.inc();
-            match suf {
-                FloatType::F => Token::LiteralFloat(num),
-                FloatType::L => Token::LiteralLongDouble(num),
-                FloatType::UserDefined(suf) => Token::LiteralFloatUD(Box::new((num, suf))),
-            }
-        } else {
-            Token::LiteralDouble(num)
-        }
-    }
+    /// Get a numeric literal: its first char has already been consumed
+    pub(crate) fn get_number(&mut self) -> Token {
+        let start = self.buf.pos() - 1;
+        self.skip_pp_number();
 
-    #[inline(always)]
-    pub(crate) fn get_number(&mut self, start: u64) -> Token {
-        let num = start;
-        if self.buf.has_char() {
-            if num == 0 {
-                let c = self.buf.next_char();
-                if c == b'x' || c == b'X' {
-                    // hex
-                    self.buf.inc();
-                    return self.get_hex();
-                } else if c == b'b' || c == b'B' {
-                    // binary
-                    self.buf.inc();
-                    return self.get_bin();
-                } else if b'0' <= c && c <= b'9' {
-                    // octal
-                    self.buf.inc();
-                    return self.get_oct(u64::from(c - b'0'));
-                } else if c == b'e' || c == b'E' {
-                    // We've 0e....: useless so just consume exponent and return 0.
-                    self.buf.inc();
-                    let _ = self.get_exponent();
-                    return self.get_typed_float(0.);
-                } else if c == b'.' {
-                    self.buf.inc();
-                    let (dec, exp) = self.get_number_after_dot(0);
-                    return self.get_typed_float(get_decimal(dec, exp));
-                }
+        let spelling = String::from_utf8_lossy(self.buf.slice(start));
+        let (tok, error) = parse_number(&spelling);
+        if let Some(error) = error {
+            let sp = if let NumberError::InvalidDigit { offset, .. } = error {
+                self.span_of(start + offset, start + offset + 1)
             } else {
-                let num = self.get_int(num);
-                if self.buf.has_char() {
-                    let c = self.buf.next_char();
-                    if c == b'.' {
-                        self.buf.inc();
-                        let (dec, exp) = self.get_number_after_dot(num);
-                        return self.get_typed_float(get_decimal(dec, exp));
-                    } else if c == b'e' || c == b'E' {
-                        self.buf.inc();
-                        let exp = self.get_exponent();
-                        return self.get_typed_float(get_decimal(num, exp));
-                    } else {
-                        return self.get_typed_int(num);
-                    }
-                } else {
-                    return Token::LiteralInt(num);
-                }
-            }
+                self.span()
+            };
+            self.errors
+                .push(error.into_lexer_error(sp, spelling.into_owned()));
         }
-
-        Token::LiteralInt(num)
+        tok
     }
 
     #[inline(always)]
@@ -761,142 +936,490 @@ mod tests {
     use crate::lexer::preprocessor::context::DefaultContext;
     use pretty_assertions::assert_eq;
 
+    fn get_int(tok: Token) -> (String, Radix, String, IntSuffix, IntKind) {
+        match tok {
+            Token::LiteralInt(x) => (
+                x.spelling.clone(),
+                x.radix,
+                x.value.to_string(),
+                x.suffix,
+                x.kind,
+            ),
+            _ => panic!("Not an integer: {:?}", tok),
+        }
+    }
+
+    fn get_float(tok: Token) -> (String, Radix, f64, FloatSuffix, FloatKind) {
+        match tok {
+            Token::LiteralFloat(x) => (x.spelling.clone(), x.radix, x.value, x.suffix, x.kind),
+            _ => panic!("Not a float: {:?}", tok),
+        }
+    }
+
     #[test]
     fn test_number_hex() {
         let mut p = Lexer::<DefaultContext>::new(b"0x12345 0xabcdef 0XA'1b2'C3D'4e5 0xaB1ul");
-        assert_eq!(p.next_token(), Token::LiteralInt(0x12345));
-        assert_eq!(p.next_token(), Token::LiteralInt(0xabcdef));
-        assert_eq!(p.next_token(), Token::LiteralInt(0xa1b2c3d4e5));
-        assert_eq!(p.next_token(), Token::LiteralULong(0xab1));
+        assert_eq!(
+            get_int(p.next_token()),
+            (
+                "0x12345".to_string(),
+                Radix::Hex,
+                "74565".to_string(),
+                IntSuffix::None,
+                IntKind::Int
+            )
+        );
+        assert_eq!(p.next_token(), Token::from_number("0xabcdef"));
+        assert_eq!(
+            get_int(p.next_token()),
+            (
+                "0XA'1b2'C3D'4e5".to_string(),
+                Radix::Hex,
+                0xa1b2c3d4e5u64.to_string(),
+                IntSuffix::None,
+                IntKind::Long
+            )
+        );
+        assert_eq!(
+            get_int(p.next_token()),
+            (
+                "0xaB1ul".to_string(),
+                Radix::Hex,
+                "2737".to_string(),
+                IntSuffix::UL,
+                IntKind::ULong
+            )
+        );
     }
 
     #[test]
     fn test_number_oct() {
-        let mut p = Lexer::<DefaultContext>::new(b"012345 01357 012'34ul");
-        assert_eq!(p.next_token(), Token::LiteralInt(0o12345));
-        assert_eq!(p.next_token(), Token::LiteralInt(0o1357));
-        assert_eq!(p.next_token(), Token::LiteralULong(0o1234));
+        let mut p = Lexer::<DefaultContext>::new(b"012345 01357 012'34ul 0");
+        assert_eq!(
+            get_int(p.next_token()),
+            (
+                "012345".to_string(),
+                Radix::Oct,
+                0o12345.to_string(),
+                IntSuffix::None,
+                IntKind::Int
+            )
+        );
+        assert_eq!(IntLiteral::new("01357").unwrap().to_u64(), Some(0o1357));
+        assert_eq!(p.next_token(), Token::from_number("01357"));
+        assert_eq!(
+            get_int(p.next_token()),
+            (
+                "012'34ul".to_string(),
+                Radix::Oct,
+                0o1234.to_string(),
+                IntSuffix::UL,
+                IntKind::ULong
+            )
+        );
+        assert_eq!(IntLiteral::new("0").unwrap().radix, Radix::Dec);
     }
 
     #[test]
     fn test_number_bin() {
         let mut p = Lexer::<DefaultContext>::new(b"0b110'001'110'010'010'110'011'101 0b1001ul");
         assert_eq!(
-            p.next_token(),
-            Token::LiteralInt(0b110001110010010110011101)
+            get_int(p.next_token()),
+            (
+                "0b110'001'110'010'010'110'011'101".to_string(),
+                Radix::Bin,
+                0b110001110010010110011101.to_string(),
+                IntSuffix::None,
+                IntKind::Int
+            )
+        );
+        assert_eq!(
+            get_int(p.next_token()),
+            (
+                "0b1001ul".to_string(),
+                Radix::Bin,
+                "9".to_string(),
+                IntSuffix::UL,
+                IntKind::ULong
+            )
         );
-        assert_eq!(p.next_token(), Token::LiteralULong(0b1001));
     }
 
     #[test]
     fn test_number_dec() {
         let mut p = Lexer::<DefaultContext>::new(b"123 123e45 123e+45 123e-45");
-        assert_eq!(p.next_token(), Token::LiteralInt(123));
-        assert_eq!(p.next_token(), Token::LiteralDouble(123e45));
-        assert_eq!(p.next_token(), Token::LiteralDouble(123e45));
-        assert_eq!(p.next_token(), Token::LiteralDouble(123e-45));
+        assert_eq!(p.next_token(), Token::from_number("123"));
+        assert_eq!(get_float(p.next_token()).2, 123e45);
+        assert_eq!(get_float(p.next_token()).2, 123e45);
+        assert_eq!(get_float(p.next_token()).2, 123e-45);
 
         let mut p = Lexer::<DefaultContext>::new(b"123. 123.e45 123.e+45 123.e-45");
-        assert_eq!(p.next_token(), Token::LiteralDouble(123.));
-        assert_eq!(p.next_token(), Token::LiteralDouble(123e45));
-        assert_eq!(p.next_token(), Token::LiteralDouble(123e45));
-        assert_eq!(p.next_token(), Token::LiteralDouble(123e-45));
+        assert_eq!(
+            get_float(p.next_token()),
+            (
+                "123.".to_string(),
+                Radix::Dec,
+                123.,
+                FloatSuffix::None,
+                FloatKind::Double
+            )
+        );
+        assert_eq!(get_float(p.next_token()).2, 123e45);
+        assert_eq!(get_float(p.next_token()).2, 123e45);
+        assert_eq!(get_float(p.next_token()).2, 123e-45);
 
         let mut p = Lexer::<DefaultContext>::new(b"123.f 123.e45F 123.e+45L 123.e-45l");
-        assert_eq!(p.next_token(), Token::LiteralFloat(123.));
-        assert_eq!(p.next_token(), Token::LiteralFloat(123e45));
-        assert_eq!(p.next_token(), Token::LiteralLongDouble(123e45));
-        assert_eq!(p.next_token(), Token::LiteralLongDouble(123e-45));
+        assert_eq!(
+            get_float(p.next_token()),
+            (
+                "123.f".to_string(),
+                Radix::Dec,
+                123.,
+                FloatSuffix::F,
+                FloatKind::Float
+            )
+        );
+        assert_eq!(get_float(p.next_token()).4, FloatKind::Float);
+        assert_eq!(
+            get_float(p.next_token()),
+            (
+                "123.e+45L".to_string(),
+                Radix::Dec,
+                123e45,
+                FloatSuffix::L,
+                FloatKind::LongDouble
+            )
+        );
+        assert_eq!(get_float(p.next_token()).2, 123e-45);
 
         let mut p = Lexer::<DefaultContext>::new(b"123.456 123.456e78 123.456e+78 123.456e-78 1.79769313486231570814527423731704357e+308L 2.2250738585072014e-308F");
-        assert_eq!(p.next_token(), Token::LiteralDouble(123.456));
-        assert_eq!(p.next_token(), Token::LiteralDouble(123.456e78));
-        assert_eq!(p.next_token(), Token::LiteralDouble(123.456e78));
-        assert_eq!(p.next_token(), Token::LiteralDouble(123.456e-78));
+        assert_eq!(get_float(p.next_token()).2, 123.456);
+        assert_eq!(get_float(p.next_token()).2, 123.456e78);
+        assert_eq!(get_float(p.next_token()).2, 123.456e78);
+        assert_eq!(get_float(p.next_token()).2, 123.456e-78);
         assert_eq!(
-            p.next_token(),
-            Token::LiteralLongDouble(1.79769313486231570814527423731704357e+308)
+            get_float(p.next_token()).2,
+            1.79769313486231570814527423731704357e+308
         );
-        assert_eq!(p.next_token(), Token::LiteralFloat(2.2250738585072014e-308));
+        assert_eq!(get_float(p.next_token()).2, 2.2250738585072014e-308);
 
         let mut p = Lexer::<DefaultContext>::new(b"0.123 0.123e45 0.123e+45 0.123e-45");
-        assert_eq!(p.next_token(), Token::LiteralDouble(0.123));
-        assert_eq!(p.next_token(), Token::LiteralDouble(0.123e45));
-        assert_eq!(p.next_token(), Token::LiteralDouble(0.123e45));
-        assert_eq!(p.next_token(), Token::LiteralDouble(0.123e-45));
+        assert_eq!(get_float(p.next_token()).2, 0.123);
+        assert_eq!(get_float(p.next_token()).2, 0.123e45);
+        assert_eq!(get_float(p.next_token()).2, 0.123e45);
+        assert_eq!(get_float(p.next_token()).2, 0.123e-45);
 
         let mut p = Lexer::<DefaultContext>::new(b".123 .123e45 .123e+45 .123e-45");
-        assert_eq!(p.next_token(), Token::LiteralDouble(0.123));
-        assert_eq!(p.next_token(), Token::LiteralDouble(0.123e45));
-        assert_eq!(p.next_token(), Token::LiteralDouble(0.123e45));
-        assert_eq!(p.next_token(), Token::LiteralDouble(0.123e-45));
+        assert_eq!(get_float(p.next_token()).2, 0.123);
+        assert_eq!(get_float(p.next_token()).2, 0.123e45);
+        assert_eq!(get_float(p.next_token()).2, 0.123e45);
+        assert_eq!(get_float(p.next_token()).2, 0.123e-45);
 
         let mut p = Lexer::<DefaultContext>::new(b"0 0. .0 0.0");
-        assert_eq!(p.next_token(), Token::LiteralInt(0));
-        assert_eq!(p.next_token(), Token::LiteralDouble(0.));
-        assert_eq!(p.next_token(), Token::LiteralDouble(0.));
-        assert_eq!(p.next_token(), Token::LiteralDouble(0.));
-
-        let mut p = Lexer::<DefaultContext>::new(b"123 123u 123U 123llu 123LLu 123llU 123LLU 123ull 123Ull 123ULL 123lu 123ul 123uL 123L");
-        assert_eq!(p.next_token(), Token::LiteralInt(123));
-        assert_eq!(p.next_token(), Token::LiteralUInt(123));
-        assert_eq!(p.next_token(), Token::LiteralUInt(123));
-        assert_eq!(p.next_token(), Token::LiteralULongLong(123));
-        assert_eq!(p.next_token(), Token::LiteralULongLong(123));
-        assert_eq!(p.next_token(), Token::LiteralULongLong(123));
-        assert_eq!(p.next_token(), Token::LiteralULongLong(123));
-        assert_eq!(p.next_token(), Token::LiteralULongLong(123));
-        assert_eq!(p.next_token(), Token::LiteralULongLong(123));
-        assert_eq!(p.next_token(), Token::LiteralULongLong(123));
-        assert_eq!(p.next_token(), Token::LiteralULong(123));
-        assert_eq!(p.next_token(), Token::LiteralULong(123));
-        assert_eq!(p.next_token(), Token::LiteralULong(123));
-        assert_eq!(p.next_token(), Token::LiteralLong(123));
+        assert_eq!(p.next_token(), Token::from_number("0"));
+        assert_eq!(get_float(p.next_token()).2, 0.);
+        assert_eq!(get_float(p.next_token()).2, 0.);
+        assert_eq!(get_float(p.next_token()).2, 0.);
+
+        let mut p = Lexer::<DefaultContext>::new(b"123 123u 123U 123llu 123LLu 123llU 123LLU 123ull 123Ull 123ULL 123lu 123ul 123uL 123L 123ll");
+        use self::IntKind::*;
+        let kinds = vec![
+            Int, UInt, UInt, ULongLong, ULongLong, ULongLong, ULongLong, ULongLong, ULongLong,
+            ULongLong, ULong, ULong, ULong, Long, LongLong,
+        ];
+        for kind in kinds {
+            let (_, _, value, _, k) = get_int(p.next_token());
+            assert_eq!(value, "123");
+            assert_eq!(k, kind);
+        }
 
         let mut p = Lexer::<DefaultContext>::new(b"0x1.2p3 0x1.2p3F 0xA.Bp-1 0XAB1P-3");
-        assert_eq!(p.next_token(), Token::LiteralDouble(9.0));
-        assert_eq!(p.next_token(), Token::LiteralFloat(9.0));
-        assert_eq!(p.next_token(), Token::LiteralDouble(5.34375));
-        assert_eq!(p.next_token(), Token::LiteralDouble(342.125));
+        assert_eq!(
+            get_float(p.next_token()),
+            (
+                "0x1.2p3".to_string(),
+                Radix::Hex,
+                9.,
+                FloatSuffix::None,
+                FloatKind::Double
+            )
+        );
+        assert_eq!(get_float(p.next_token()).2, 9.);
+        assert_eq!(get_float(p.next_token()).2, 5.34375);
+        assert_eq!(get_float(p.next_token()).2, 342.125);
 
         let mut p = Lexer::<DefaultContext>::new(b"3.14");
-        assert_eq!(p.next_token(), Token::LiteralDouble(3.14));
+        assert_eq!(get_float(p.next_token()).2, 3.14);
+        assert!(p.errors.is_empty());
     }
 
     #[test]
     fn test_number_ud() {
-        let mut p = Lexer::<DefaultContext>::new(b"12_km 12.34_km");
+        let mut p = Lexer::<DefaultContext>::new(b"12_km 12.34_km 10ms 1.5if");
+        assert_eq!(
+            get_int(p.next_token()),
+            (
+                "12_km".to_string(),
+                Radix::Dec,
+                "12".to_string(),
+                IntSuffix::UserDefined(Symbol::intern("_km")),
+                IntKind::ULongLong
+            )
+        );
         assert_eq!(
-            p.next_token(),
-            Token::LiteralIntUD(Box::new((12, "_km".to_string())))
+            get_float(p.next_token()),
+            (
+                "12.34_km".to_string(),
+                Radix::Dec,
+                12.34,
+                FloatSuffix::UserDefined(Symbol::intern("_km")),
+                FloatKind::LongDouble
+            )
         );
         assert_eq!(
-            p.next_token(),
-            Token::LiteralFloatUD(Box::new((12.34, "_km".to_string())))
+            get_int(p.next_token()).3,
+            IntSuffix::UserDefined(Symbol::intern("ms"))
         );
+        assert_eq!(
+            get_float(p.next_token()).3,
+            FloatSuffix::UserDefined(Symbol::intern("if"))
+        );
+        assert!(p.errors.is_empty());
     }
 
     #[test]
     fn test_number_just_one() {
         let mut p = Lexer::<DefaultContext>::new(b"123.");
-        assert_eq!(p.next_token(), Token::LiteralDouble(123.));
+        assert_eq!(get_float(p.next_token()).2, 123.);
 
         let mut p = Lexer::<DefaultContext>::new(b"3.14");
-        assert_eq!(p.next_token(), Token::LiteralDouble(3.14));
+        assert_eq!(get_float(p.next_token()).2, 3.14);
 
         let mut p = Lexer::<DefaultContext>::new(b"123.e1");
-        assert_eq!(p.next_token(), Token::LiteralDouble(123e1));
+        assert_eq!(get_float(p.next_token()).2, 123e1);
 
         let mut p = Lexer::<DefaultContext>::new(b"1.2e+34");
-        assert_eq!(p.next_token(), Token::LiteralDouble(1.2e34));
+        assert_eq!(get_float(p.next_token()).2, 1.2e34);
 
         let mut p = Lexer::<DefaultContext>::new(b"1.79769313486231570814527423731704357e+308");
         assert_eq!(
-            p.next_token(),
-            Token::LiteralDouble(1.79769313486231570814527423731704357e+308)
+            get_float(p.next_token()).2,
+            1.79769313486231570814527423731704357e+308
         );
 
         let mut p = Lexer::<DefaultContext>::new(b"0.123");
-        assert_eq!(p.next_token(), Token::LiteralDouble(0.123));
+        assert_eq!(get_float(p.next_token()).2, 0.123);
+    }
+
+    #[test]
+    fn test_number_exact() {
+        let a = IntLiteral::new("0x10").unwrap();
+        let b = IntLiteral::new("16").unwrap();
+        assert_eq!(a.value, b.value);
+        assert_ne!(a, b);
+
+        let x = IntLiteral::new("0xffff'ffff'ffff'ffff'ffff").unwrap();
+        assert_eq!(x.value.to_string(), "1208925819614629174706175");
+        assert_eq!(x.to_u64(), None);
+
+        let x = FloatLiteral::new("1e400").unwrap();
+        assert!(x.value.is_infinite());
+        assert_eq!(x.significand.to_u64(), Some(1));
+        assert_eq!(x.exponent, 400);
+
+        let x = FloatLiteral::new("12.345e-2L").unwrap();
+        assert_eq!(x.significand.to_u64(), Some(12345));
+        assert_eq!(x.exponent, -5);
+
+        let x = FloatLiteral::new("0x1.8p1").unwrap();
+        assert_eq!(x.significand.to_u64(), Some(0x18));
+        assert_eq!(x.exponent, -3);
+    }
+
+    #[test]
+    fn test_number_long_hex_float() {
+        // Just halfway between 1 and the next double: rounded to even
+        let x = FloatLiteral::new("0x1.00000000000008p0").unwrap();
+        assert_eq!(x.value, 1.);
+        // Just above halfway with a bit far after the 64 first ones
+        let x = FloatLiteral::new("0x1.0000000000000800000000000000001p0").unwrap();
+        assert_eq!(x.value, 1. + f64::EPSILON);
+        let x = FloatLiteral::new("0x1.0000000000000800000000000000000p0").unwrap();
+        assert_eq!(x.value, 1.);
+        // Just below halfway
+        let x = FloatLiteral::new("0x1.00000000000007ffffffffffffffffffp0").unwrap();
+        assert_eq!(x.value, 1.);
+    }
+
+    #[test]
+    fn test_number_kind() {
+        let kind = |s| IntLiteral::new(s).unwrap().kind;
+        assert_eq!(kind("2147483647"), IntKind::Int);
+        assert_eq!(kind("2147483648"), IntKind::Long);
+        assert_eq!(kind("0x7fffffff"), IntKind::Int);
+        assert_eq!(kind("0x80000000"), IntKind::UInt);
+        assert_eq!(kind("4294967296u"), IntKind::ULong);
+        assert_eq!(kind("0x8000000000000000"), IntKind::ULong);
+        assert_eq!(kind("0x8000000000000000ll"), IntKind::ULongLong);
+        assert_eq!(kind("1ll"), IntKind::LongLong);
+    }
+
+    #[test]
+    fn test_number_size_and_bitint() {
+        let mut p = Lexer::<DefaultContext>::new(b"12z 12uz 12ZU 0xffff'ffff'ffff'ffffz");
+        assert_eq!(get_int(p.next_token()).4, IntKind::SignedSize);
+        assert_eq!(get_int(p.next_token()).3, IntSuffix::UZ);
+        assert_eq!(get_int(p.next_token()).4, IntKind::Size);
+        assert_eq!(get_int(p.next_token()).4, IntKind::Size);
+
+        let mut p =
+            Lexer::<DefaultContext>::new(b"0wb 0uwb 255wb 255uwb 0x1'0000'0000'0000'0000WBu");
+        assert_eq!(get_int(p.next_token()).4, IntKind::BitInt(2));
+        assert_eq!(get_int(p.next_token()).4, IntKind::UBitInt(1));
+        assert_eq!(get_int(p.next_token()).4, IntKind::BitInt(9));
+        assert_eq!(
+            get_int(p.next_token()),
+            (
+                "255uwb".to_string(),
+                Radix::Dec,
+                "255".to_string(),
+                IntSuffix::UWB,
+                IntKind::UBitInt(8)
+            )
+        );
+        assert_eq!(get_int(p.next_token()).4, IntKind::UBitInt(65));
+        assert!(p.errors.is_empty());
+
+        assert_eq!(IntKind::BitInt(9).max_value(), 255);
+        assert_eq!(IntKind::UBitInt(64).max_value(), u64::MAX);
+
+        let mut p = Lexer::<DefaultContext>::new(b"9223372036854775808z");
+        p.next_token();
+        assert_eq!(p.errors.len(), 1);
+    }
+
+    #[test]
+    fn test_number_c_separator() {
+        // Before C23, 1'2' is the number 1 followed by the char '2'
+        let mut p = Lexer::<DefaultContext>::new(b"1'2' 0b10");
+        p.set_dialect(Dialect::from_standard(crate::args::Standard::C11));
+        assert_eq!(p.next_token(), Token::from_number("1"));
+        assert_eq!(p.next_token(), Token::LiteralChar(u32::from('2')));
+        assert_eq!(p.next_token(), Token::from_number("0b10"));
+
+        let mut p = Lexer::<DefaultContext>::new(b"1'2 0b1'0");
+        p.set_dialect(Dialect::from_standard(crate::args::Standard::C2x));
+        assert_eq!(get_int(p.next_token()).2, "12");
+        assert_eq!(get_int(p.next_token()).2, "2");
+        assert!(p.errors.is_empty());
+    }
+
+    #[test]
+    fn test_number_errors() {
+        let mut p = Lexer::<DefaultContext>::new(
+            b"18446744073709551615u 18446744073709551616 9223372036854775808 1e400 1e400L 1e40f 1e5000L 0x1p16384L 12km 1.5x",
+        );
+        let toks: Vec<_> = std::iter::from_fn(|| match p.next_token() {
+            Token::Eof => None,
+            tok => Some(tok),
+        })
+        .collect();
+        assert_eq!(toks.len(), 10);
+
+        let errors: Vec<_> = p
+            .errors
+            .iter()
+            .map(|e| match e {
+                LexerError::IntegerTooLarge { literal, .. } => format!("int {}", literal),
+                LexerError::FloatTooLarge { literal, .. } => format!("float {}", literal),
+                LexerError::InvalidNumberSuffix {
+                    literal, suffix, ..
+                } => format!("suffix {} {}", literal, suffix),
+                _ => panic!(),
+            })
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "int 18446744073709551616",
+                "int 9223372036854775808",
+                "float 1e400",
+                "float 1e40f",
+                "float 1e5000L",
+                "float 0x1p16384L",
+                "suffix 12km km",
+                "suffix 1.5x x",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_number_invalid_shared() {
+        let mut p = Lexer::<DefaultContext>::new(b"12km 12km 12");
+        let (a, b, c) = match (p.next_token(), p.next_token(), p.next_token()) {
+            (Token::LiteralInt(a), Token::LiteralInt(b), Token::LiteralInt(c)) => (a, b, c),
+            _ => panic!(),
+        };
+        assert_eq!(a.0, b.0);
+        assert_ne!(a.0, c.0);
+        assert_eq!(p.errors.len(), 2);
+    }
+
+    #[test]
+    fn test_number_pp() {
+        let mut p = Lexer::<DefaultContext>::new(b"1.e+5f+0x1p-2 1'2 x.1");
+        assert_eq!(p.next_token(), Token::from_number("1.e+5f"));
+        assert_eq!(p.next_token(), Token::Plus);
+        assert_eq!(p.next_token(), Token::from_number("0x1p-2"));
+        assert_eq!(p.next_token(), Token::from_number("1'2"));
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("x")));
+        assert_eq!(p.next_token(), Token::from_number(".1"));
+    }
+
+    #[test]
+    fn test_number_invalid_digit() {
+        let mut p = Lexer::<DefaultContext>::new(b"0789 0b102u 089.5 0x9f");
+        assert_eq!(get_int(p.next_token()).1, Radix::Oct);
+        assert_eq!(get_int(p.next_token()).3, IntSuffix::U);
+        assert_eq!(get_float(p.next_token()).2, 89.5);
+        assert_eq!(p.next_token(), Token::from_number("0x9f"));
+
+        let errors: Vec<_> = p
+            .errors
+            .iter()
+            .map(|e| {
+                let e = e.stringly();
+                (e.message, e.sp.start.pos, e.sp.end.pos)
+            })
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                ("invalid digit '8' in octal literal".to_string(), 2, 3),
+                ("invalid digit '2' in binary literal".to_string(), 9, 10),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_number_missing_digits() {
+        let mut p = Lexer::<DefaultContext>::new(b"0x 0b 0xu 0x.p1 0x1 0b1");
+        let toks: Vec<_> = std::iter::from_fn(|| match p.next_token() {
+            Token::Eof => None,
+            tok => Some(tok),
+        })
+        .collect();
+        assert_eq!(toks.len(), 6);
+
+        let errors: Vec<_> = p.errors.iter().map(|e| e.stringly().message).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "no digits in hexadecimal literal 0x",
+                "no digits in binary literal 0b",
+                "no digits in hexadecimal literal 0xu",
+                "no digits in hexadecimal literal 0x.p1",
+            ]
+        );
     }
 }
@@ -0,0 +1,174 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use super::buffer::Position;
+use super::preprocessor::cache::IfCache;
+use super::source::{FileId, SourceLocator};
+
+/// Must be bumped each time the format or the meaning of the data changes
+pub const CACHE_VERSION: u32 = 1;
+
+const CACHE_FILE: &str = "cpp-parser-cache.json";
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+struct FileStamp {
+    size: u64,
+    mtime: (u64, u32),
+}
+
+impl FileStamp {
+    fn new(path: &Path) -> Option<Self> {
+        let meta = fs::metadata(path).ok()?;
+        let mtime = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Self {
+            size: meta.len(),
+            mtime: (mtime.as_secs(), mtime.subsec_nanos()),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct FileEntry {
+    path: PathBuf,
+    stamp: Option<FileStamp>,
+    ifs: Vec<(usize, Position)>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct CacheData {
+    version: u32,
+    files: Vec<FileEntry>,
+}
+
+fn get_cache_file(dir: &Path) -> PathBuf {
+    dir.join(CACHE_FILE)
+}
+
+/// Save the source table and the if cache in the directory dir
+pub fn save_caches(dir: &Path, source: &SourceLocator, if_cache: &IfCache) -> io::Result<()> {
+    let mut files: Vec<FileEntry> = source
+        .get_paths()
+        .iter()
+        .map(|path| FileEntry {
+            path: path.clone(),
+            stamp: FileStamp::new(path),
+            ifs: Vec::new(),
+        })
+        .collect();
+
+    for (file_id, pos, next) in if_cache.entries() {
+        // The file 0 is the command line buffer which is different for each TU
+        if file_id.0 != 0 {
+            if let Some(entry) = files.get_mut(file_id.0 as usize) {
+                entry.ifs.push((pos, next));
+            }
+        }
+    }
+
+    let data = CacheData {
+        version: CACHE_VERSION,
+        files,
+    };
+
+    fs::create_dir_all(dir)?;
+    let file = File::create(get_cache_file(dir))?;
+    serde_json::to_writer(BufWriter::new(file), &data)?;
+
+    Ok(())
+}
+
+/// Load the caches saved in the directory dir.
+/// The ids are kept from one run to another and the if cache entries for the files
+/// which have been modified since the last save are dropped.
+/// If the cache is missing or has been written by another version, new caches are returned.
+pub fn load_caches(dir: &Path) -> (SourceLocator, IfCache) {
+    let mut source = SourceLocator::default();
+    let if_cache = IfCache::default();
+
+    let data: CacheData = if let Some(data) = File::open(get_cache_file(dir))
+        .ok()
+        .and_then(|file| serde_json::from_reader(BufReader::new(file)).ok())
+    {
+        data
+    } else {
+        return (source, if_cache);
+    };
+
+    if data.version != CACHE_VERSION {
+        return (source, if_cache);
+    }
+
+    for (i, entry) in data.files.into_iter().enumerate() {
+        let file_id = source.get_id(&entry.path);
+        if file_id.0 as usize != i {
+            // Corrupted data
+            return (SourceLocator::default(), IfCache::default());
+        }
+
+        if file_id.0 != 0 && entry.stamp.is_some() && entry.stamp == FileStamp::new(&entry.path) {
+            for (pos, next) in entry.ifs {
+                if_cache.save_next(FileId(i as u32), pos, next);
+            }
+        }
+    }
+
+    (source, if_cache)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+    use tempdir::TempDir;
+
+    fn mk_file(dir: &Path, name: &str, content: &str) -> PathBuf {
+        let path = dir.join(name);
+        let mut file = File::create(&path).unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_persist_caches() {
+        let tmp_dir = TempDir::new("test_persist").unwrap();
+        let a = mk_file(tmp_dir.path(), "a.h", "#if 0\n#endif\n");
+        let b = mk_file(tmp_dir.path(), "b.h", "#if 0\n#endif\n");
+
+        let mut source = SourceLocator::default();
+        let if_cache = IfCache::default();
+        let id_a = source.get_id(&a);
+        let id_b = source.get_id(&b);
+        if_cache.save_next(id_a, 3, Position::default());
+        if_cache.save_next(id_b, 3, Position::default());
+
+        let cache_dir = tmp_dir.path().join("cache");
+        save_caches(&cache_dir, &source, &if_cache).unwrap();
+
+        mk_file(tmp_dir.path(), "b.h", "#if 0\n#else\n#endif\n");
+
+        let (mut source, if_cache) = load_caches(&cache_dir);
+        assert_eq!(source.get_id(&a), id_a);
+        assert_eq!(source.get_id(&b), id_b);
+        assert!(if_cache.get_next(id_a, 3).is_some());
+        assert!(if_cache.get_next(id_b, 3).is_none());
+    }
+
+    #[test]
+    fn test_persist_no_cache() {
+        let tmp_dir = TempDir::new("test_persist").unwrap();
+        let (source, if_cache) = load_caches(tmp_dir.path());
+
+        assert_eq!(source.get_paths().len(), 1);
+        assert!(if_cache.entries().is_empty());
+    }
+}
@@ -4,10 +4,13 @@
 // copied, modified, or distributed except according to those terms.
 
 use hashbrown::HashMap;
-use std::sync::Mutex;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
 
-use crate::lexer::buffer::Position;
-use crate::lexer::source::FileId;
+use crate::lexer::buffer::{Position, SourceText};
+use crate::lexer::charset::Charset;
+use crate::lexer::source::{FileData, FileId};
 
 #[derive(Debug, PartialEq, Hash)]
 struct Key {
@@ -32,4 +35,401 @@ impl IfCache {
         let mut cache = self.cache.lock().unwrap();
         cache.insert(Key { file_id, pos }, next);
     }
+
+    pub(crate) fn entries(&self) -> Vec<(FileId, usize, Position)> {
+        let cache = self.cache.lock().unwrap();
+        cache
+            .iter()
+            .map(|(k, v)| (k.file_id, k.pos, v.clone()))
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FileCacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    pub evictions: usize,
+    pub bytes: usize,
+}
+
+#[derive(Debug)]
+struct CachedFile {
+    data: Arc<FileData>,
+    /// The text to lex got from the data (for the charset used the last time)
+    text: Option<Arc<SourceText>>,
+    last_use: u64,
+}
+
+impl CachedFile {
+    fn bytes(&self) -> usize {
+        self.data.len() + self.text.as_ref().map_or(0, |t| t.extra_bytes())
+    }
+}
+
+#[derive(Debug, Default)]
+struct FileCacheInner {
+    files: HashMap<FileId, CachedFile>,
+    /// The files ordered by last use (the least recently used first)
+    lru: BTreeMap<u64, FileId>,
+    tick: u64,
+    stats: FileCacheStats,
+}
+
+impl FileCacheInner {
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    /// Evict the least recently used files (except keep) until size bytes can be added
+    fn make_room(&mut self, size: usize, max_bytes: usize, keep: Option<FileId>) -> bool {
+        while self.stats.bytes + size > max_bytes {
+            let oldest = self
+                .lru
+                .iter()
+                .find(|(_, id)| Some(**id) != keep)
+                .map(|(tick, _)| *tick);
+            if let Some(oldest) = oldest {
+                let id = self.lru.remove(&oldest).unwrap();
+                let file = self.files.remove(&id).unwrap();
+                self.stats.bytes -= file.bytes();
+                self.stats.evictions += 1;
+            } else {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Cache of the lexer input for each file, shared between the lexers.
+/// It's not a cache of tokens: the lexer runs the directives while lexing so the
+/// tokens of a header depend on the macros defined where it's included, and each
+/// inclusion of a header is still lexed.
+/// So the file contents are what is cached with the text got from them (transcoded
+/// and cleaned): the lexers borrow them through the Arc so a header is never read,
+/// transcoded or cleaned twice.
+#[derive(Debug)]
+pub struct FileCache {
+    inner: Mutex<FileCacheInner>,
+    max_bytes: usize,
+    map_files: bool,
+}
+
+impl Default for FileCache {
+    fn default() -> Self {
+        Self::new(usize::MAX)
+    }
+}
+
+impl FileCache {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            inner: Mutex::new(FileCacheInner::default()),
+            max_bytes,
+            map_files: false,
+        }
+    }
+
+    /// Map the big files instead of reading them: they mustn't be modified
+    /// while they're in the cache (see FileData::read_or_map)
+    pub fn set_map_files(&mut self, map_files: bool) {
+        self.map_files = map_files;
+    }
+
+    /// Read a file to put in the cache
+    pub fn read_file<P: AsRef<Path>>(&self, path: P) -> Option<FileData> {
+        if self.map_files {
+            FileData::read_or_map(path)
+        } else {
+            FileData::read(path)
+        }
+    }
+
+    pub fn get(&self, file_id: FileId) -> Option<Arc<FileData>> {
+        let mut inner = self.inner.lock().unwrap();
+        let tick = inner.next_tick();
+        if let Some(file) = inner.files.get_mut(&file_id) {
+            let last_use = std::mem::replace(&mut file.last_use, tick);
+            let data = Arc::clone(&file.data);
+            inner.lru.remove(&last_use);
+            inner.lru.insert(tick, file_id);
+            inner.stats.hits += 1;
+            Some(data)
+        } else {
+            inner.stats.misses += 1;
+            None
+        }
+    }
+
+    pub fn insert(&self, file_id: FileId, data: FileData) -> Arc<FileData> {
+        let data = Arc::new(data);
+        let size = data.len();
+        if size > self.max_bytes {
+            return data;
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(file) = inner.files.get(&file_id) {
+            // Another thread has been faster
+            return Arc::clone(&file.data);
+        }
+
+        inner.make_room(size, self.max_bytes, None);
+
+        let last_use = inner.next_tick();
+        inner.lru.insert(last_use, file_id);
+        inner.stats.bytes += size;
+        inner.files.insert(
+            file_id,
+            CachedFile {
+                data: Arc::clone(&data),
+                text: None,
+                last_use,
+            },
+        );
+        data
+    }
+
+    pub fn get_or_load<F: FnOnce() -> Option<FileData>>(
+        &self,
+        file_id: FileId,
+        load: F,
+    ) -> Option<Arc<FileData>> {
+        if let Some(data) = self.get(file_id) {
+            Some(data)
+        } else {
+            // Read the file without holding the lock
+            load().map(|data| self.insert(file_id, data))
+        }
+    }
+
+    /// Get the text to lex for a file: it's transcoded and cleaned once
+    /// (again when the file is wanted with another charset)
+    pub fn get_text<F: FnOnce() -> Option<FileData>>(
+        &self,
+        file_id: FileId,
+        charset: Charset,
+        load: F,
+    ) -> Option<Arc<SourceText>> {
+        let data = self.get_or_load(file_id, load)?;
+        let cached = |inner: &FileCacheInner| {
+            let file = inner.files.get(&file_id)?;
+            let text = file.text.as_ref()?;
+            if Arc::ptr_eq(&file.data, &data) && text.charset() == charset {
+                Some(Arc::clone(text))
+            } else {
+                None
+            }
+        };
+        if let Some(text) = cached(&self.inner.lock().unwrap()) {
+            return Some(text);
+        }
+
+        // Prepare the text without holding the lock
+        let text = Arc::new(SourceText::from_data(Arc::clone(&data), charset));
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(cached) = cached(&inner) {
+            // Another thread has been faster
+            return Some(cached);
+        }
+        let old = match inner.files.get(&file_id) {
+            Some(file) if Arc::ptr_eq(&file.data, &data) => {
+                file.text.as_ref().map_or(0, |t| t.extra_bytes())
+            }
+            _ => return Some(text),
+        };
+        inner.stats.bytes -= old;
+        let size = text.extra_bytes();
+        let file = inner.files.get_mut(&file_id).unwrap();
+        file.text = None;
+        if inner.make_room(size, self.max_bytes, Some(file_id)) {
+            inner.stats.bytes += size;
+            inner.files.get_mut(&file_id).unwrap().text = Some(Arc::clone(&text));
+        }
+        Some(text)
+    }
+
+    pub fn stats(&self) -> FileCacheStats {
+        self.inner.lock().unwrap().stats
+    }
+}
+
+#[derive(Debug, Hash, PartialEq, Eq)]
+pub(crate) struct PrefixKey {
+    pub(crate) cl: Vec<u8>,
+    pub(crate) sys_paths: Vec<String>,
+    pub(crate) charset: Charset,
+}
+
+/// The snapshot for a prefix: it's made once and the callers wanting it meanwhile wait.
+/// The context is only locked while it's cloned (it isn't Sync).
+type PrefixSlot<PC> = Arc<OnceLock<Mutex<PC>>>;
+
+/// Snapshots of the preprocessor contexts got after having processed
+/// the command line definitions and the forced includes.
+/// The macros of a snapshot are shared (see PreprocContext::share_macros) so a clone is cheap.
+#[derive(Debug)]
+pub struct PrefixCache<PC: Clone> {
+    cache: Mutex<HashMap<PrefixKey, PrefixSlot<PC>>>,
+}
+
+impl<PC: Clone> Default for PrefixCache<PC> {
+    fn default() -> Self {
+        Self {
+            cache: Mutex::new(HashMap::default()),
+        }
+    }
+}
+
+impl<PC: Clone> PrefixCache<PC> {
+    pub(crate) fn get_or_insert<F: FnOnce() -> PC>(&self, key: PrefixKey, make: F) -> PC {
+        // The global lock is only kept to get the slot: only the callers wanting
+        // the same prefix wait while it's processed
+        let slot = Arc::clone(self.cache.lock().unwrap().entry(key).or_default());
+        let snapshot = slot.get_or_init(|| Mutex::new(make()));
+        let context = snapshot.lock().unwrap().clone();
+        context
+    }
+
+    pub fn len(&self) -> usize {
+        self.cache.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::lexer::buffer::FileInfo;
+    use crate::lexer::preprocessor::context::{DefaultContext, PreprocContext};
+    use crate::lexer::preprocessor::macros::MacroObject;
+    use crate::lexer::Lexer;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_file_cache_hit_miss() {
+        let cache = FileCache::default();
+
+        let data = cache.get_or_load(FileId(1), || Some(b"int x;".to_vec().into()));
+        assert_eq!(&data.unwrap()[..], b"int x;");
+
+        let data = cache.get_or_load(FileId(1), || panic!("Must be in the cache"));
+        assert_eq!(&data.unwrap()[..], b"int x;");
+
+        assert!(cache.get_or_load(FileId(2), || None).is_none());
+
+        assert_eq!(
+            cache.stats(),
+            FileCacheStats {
+                hits: 1,
+                misses: 2,
+                evictions: 0,
+                bytes: 6,
+            }
+        );
+    }
+
+    #[test]
+    fn test_file_cache_eviction() {
+        let cache = FileCache::new(10);
+
+        cache.insert(FileId(1), vec![0; 4].into());
+        cache.insert(FileId(2), vec![0; 4].into());
+        cache.get(FileId(1));
+        cache.insert(FileId(3), vec![0; 4].into());
+
+        assert!(cache.get(FileId(1)).is_some());
+        assert!(cache.get(FileId(2)).is_none());
+        assert!(cache.get(FileId(3)).is_some());
+
+        // Too big to be cached
+        cache.insert(FileId(4), vec![0; 11].into());
+        assert!(cache.get(FileId(4)).is_none());
+
+        let stats = cache.stats();
+        assert_eq!(stats.evictions, 1);
+        assert_eq!(stats.bytes, 8);
+
+        // The least recently used is evicted first
+        cache.get(FileId(3));
+        cache.get(FileId(1));
+        cache.insert(FileId(5), vec![0; 4].into());
+        assert!(cache.get(FileId(3)).is_none());
+        assert!(cache.get(FileId(1)).is_some());
+        assert_eq!(cache.stats().evictions, 2);
+    }
+
+    #[test]
+    fn test_file_cache_text() {
+        let cache = FileCache::default();
+        let load = || Some(b"int \xE9;\r\n".to_vec().into());
+
+        // The cleaned text is shared
+        let a = cache.get_text(FileId(1), Charset::Utf8, load).unwrap();
+        let b = cache
+            .get_text(FileId(1), Charset::Utf8, || panic!("Must be in the cache"))
+            .unwrap();
+        assert!(Arc::ptr_eq(&a, &b));
+        assert_eq!(cache.stats().bytes, 8 + 7);
+
+        // Another charset
+        let c = cache.get_text(FileId(1), Charset::Latin1, load).unwrap();
+        assert!(!Arc::ptr_eq(&a, &c));
+        assert_eq!(c.charset(), Charset::Latin1);
+        assert!(Arc::ptr_eq(a.data().unwrap(), c.data().unwrap()));
+        assert_eq!(cache.stats().bytes, 8 + 9 + 8);
+
+        // The text is evicted with the data
+        let cache = FileCache::new(20);
+        cache.get_text(FileId(1), Charset::Utf8, load);
+        cache.insert(FileId(2), vec![0; 8].into());
+        assert!(cache.get(FileId(1)).is_none());
+        assert_eq!(cache.stats().bytes, 8);
+    }
+
+    #[test]
+    fn test_prefix_cache() {
+        let cache = PrefixCache::<DefaultContext>::default();
+        let key = |cl: &[u8]| PrefixKey {
+            cl: cl.to_vec(),
+            sys_paths: Vec::new(),
+            charset: Charset::Utf8,
+        };
+        let make = |cl: &'static [u8]| {
+            move || {
+                let mut p = Lexer::<DefaultContext>::new(cl);
+                p.consume_all();
+                p.context.share_macros();
+                p.context
+            }
+        };
+
+        // Another prefix can be processed while one is made
+        let mut a = cache.get_or_insert(key(b"A"), || {
+            let b = cache.get_or_insert(key(b"B"), make(b"#define B 2\n"));
+            assert!(b.get("B").is_some());
+            make(b"#define A 1\n#define C 3\n")()
+        });
+        assert_eq!(cache.len(), 2);
+
+        // The changes in a snapshot aren't seen by the other ones
+        a.undef("A");
+        a.add_object(
+            "D".to_string(),
+            MacroObject::new(Vec::new(), false, FileInfo::default()),
+        );
+        assert!(a.get("A").is_none());
+        assert!(a.get("C").is_some());
+
+        let a = cache.get_or_insert(key(b"A"), || panic!("Must be in the cache"));
+        assert!(a.get("A").is_some());
+        assert!(a.get("D").is_none());
+        assert!(a.get("B").is_none());
+    }
 }
@@ -730,13 +730,9 @@ impl<'a, 'b, PC: PreprocContext> Condition<'a, 'b, PC> {
                 Token::RightParen => {
                     self.flush_until_paren();
                 }
-                Token::LiteralInt(x)
-                | Token::LiteralUInt(x)
-                | Token::LiteralLong(x)
-                | Token::LiteralLongLong(x)
-                | Token::LiteralULong(x)
-                | Token::LiteralULongLong(x) => {
-                    self.operands.push(Int::Unsigned(x));
+                Token::LiteralInt(x) => {
+                    self.operands
+                        .push(Int::Unsigned(x.to_u64().unwrap_or(u64::MAX)));
                     self.last = LastKind::Operand;
                 }
                 Token::Identifier(id) => {
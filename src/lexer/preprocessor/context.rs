@@ -3,7 +3,8 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use hashbrown::HashMap;
+use hashbrown::{HashMap, HashSet};
+use std::cell::{Cell, RefCell};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
@@ -13,6 +14,7 @@ use super::macros::{
     Macro, MacroCounter, MacroFile, MacroFunction, MacroLine, MacroObject, MacroType,
 };
 use crate::lexer::buffer::{BufferData, Position};
+use crate::lexer::charset::Charset;
 use crate::lexer::source::{FileId, SourceMutex};
 
 /// Indicate the state of the if statement
@@ -68,7 +70,22 @@ pub trait PreprocContext: Default + IncludeLocator {
     /// Save the position of matching #if/#else|#endif
     fn save_switch(&self, file: FileId, pos: usize, next: Position);
 
+    /// Mark the macro with the given id as being expanded (or not):
+    /// a macro isn't expanded again in its own expansion
+    fn set_in_use(&self, id: usize, in_use: bool);
+
+    /// Get the value of __COUNTER__ and increment it
+    fn next_counter(&self) -> u64;
+
+    /// Move the macros defined until now in a table shared by the clones of the context
+    /// (see PrefixCache)
+    fn share_macros(&mut self);
+
     fn new_with_if_cache(if_cache: Arc<IfCache>) -> Self;
+
+    /// Called for each token given by the lexer with the file containing it
+    /// (the macros used to get the token have been got just before)
+    fn on_token(&mut self, _file: Option<FileId>) {}
 }
 
 #[derive(Default)]
@@ -108,6 +125,14 @@ impl PreprocContext for EmptyContext {
 
     fn save_switch(&self, _file: FileId, _pos: usize, _next: Position) {}
 
+    fn set_in_use(&self, _id: usize, _in_use: bool) {}
+
+    fn next_counter(&self) -> u64 {
+        0
+    }
+
+    fn share_macros(&mut self) {}
+
     fn new_with_if_cache(_if_cache: Arc<IfCache>) -> Self {
         Self {}
     }
@@ -136,6 +161,8 @@ impl IncludeLocator for EmptyContext {
     fn set_source(&mut self, _source: SourceMutex) {}
 
     fn set_sys_paths<P: AsRef<Path>>(&mut self, _paths: &[P]) {}
+
+    fn set_charset(&mut self, _charset: Charset) {}
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -147,7 +174,15 @@ pub enum IfKind {
 
 #[derive(Clone, Debug)]
 pub struct Context<IL: IncludeLocator> {
+    /// The macros shared with the other clones of the context (see share_macros)
+    shared: Arc<HashMap<String, Macro>>,
+    /// The macros defined after the shared ones (they hide them)
     macros: HashMap<String, Macro>,
+    /// The shared macros which have been undefined
+    undefs: HashSet<String>,
+    /// The ids of the macros being expanded
+    in_use: RefCell<Vec<usize>>,
+    counter: Cell<u64>,
     if_stack: Vec<IfState>,
     if_cache: Arc<IfCache>,
     include: IL,
@@ -169,10 +204,7 @@ impl<IL: IncludeLocator> Default for Context<IL> {
                 );
                 map
             },
-            if_stack: Vec::new(),
-            if_cache: Arc::new(IfCache::default()),
-            include: IL::default(),
-            buffer: None,
+            ..Self::new(IL::default())
         }
     }
 }
@@ -180,13 +212,31 @@ impl<IL: IncludeLocator> Default for Context<IL> {
 impl<IL: IncludeLocator> Context<IL> {
     pub fn new(include: IL) -> Self {
         Self {
+            shared: Arc::new(HashMap::default()),
             macros: HashMap::default(),
+            undefs: HashSet::default(),
+            in_use: RefCell::new(Vec::new()),
+            counter: Cell::new(0),
             if_stack: Vec::new(),
             if_cache: Arc::new(IfCache::default()),
             include,
             buffer: None,
         }
     }
+
+    fn find_macro(&self, name: &str) -> Option<&Macro> {
+        self.macros.get(name).or_else(|| {
+            if self.undefs.contains(name) {
+                None
+            } else {
+                self.shared.get(name)
+            }
+        })
+    }
+
+    fn is_in_use(&self, id: usize) -> bool {
+        self.in_use.borrow().contains(&id)
+    }
 }
 
 impl<IL: IncludeLocator> PreprocContext for Context<IL> {
@@ -216,24 +266,27 @@ impl<IL: IncludeLocator> PreprocContext for Context<IL> {
 
     fn undef(&mut self, name: &str) {
         self.macros.remove(name);
+        if self.shared.contains_key(name) {
+            self.undefs.insert(name.to_string());
+        }
     }
 
     fn defined(&mut self, name: &str) -> bool {
-        self.macros.contains_key(name)
+        self.find_macro(name).is_some()
     }
 
     fn get(&self, name: &str) -> Option<&Macro> {
-        if let Some(mac) = self.macros.get(name) {
+        if let Some(mac) = self.find_macro(name) {
             match mac {
                 Macro::Object(m) => {
-                    if m.in_use.get() {
+                    if self.is_in_use(m.id()) {
                         None
                     } else {
                         Some(mac)
                     }
                 }
                 Macro::Function(m) => {
-                    if m.in_use.get() {
+                    if self.is_in_use(m.id()) {
                         None
                     } else {
                         Some(mac)
@@ -268,13 +321,37 @@ impl<IL: IncludeLocator> PreprocContext for Context<IL> {
         self.if_cache.save_next(file, pos, next);
     }
 
+    fn set_in_use(&self, id: usize, in_use: bool) {
+        let mut ids = self.in_use.borrow_mut();
+        if in_use {
+            ids.push(id);
+        } else if let Some(i) = ids.iter().rposition(|x| *x == id) {
+            ids.remove(i);
+        }
+    }
+
+    fn next_counter(&self) -> u64 {
+        let n = self.counter.get();
+        self.counter.set(n + 1);
+        n
+    }
+
+    fn share_macros(&mut self) {
+        if self.macros.is_empty() && self.undefs.is_empty() {
+            return;
+        }
+        let mut shared = (*self.shared).clone();
+        for name in self.undefs.drain() {
+            shared.remove(&name);
+        }
+        shared.extend(self.macros.drain());
+        self.shared = Arc::new(shared);
+    }
+
     fn new_with_if_cache(if_cache: Arc<IfCache>) -> Self {
         Self {
-            macros: HashMap::default(),
-            if_stack: Vec::new(),
             if_cache,
-            include: IL::default(),
-            buffer: None,
+            ..Self::new(IL::default())
         }
     }
 }
@@ -306,4 +383,8 @@ impl<IL: IncludeLocator> IncludeLocator for Context<IL> {
     fn set_sys_paths<P: AsRef<Path>>(&mut self, paths: &[P]) {
         self.include.set_sys_paths(paths);
     }
+
+    fn set_charset(&mut self, charset: Charset) {
+        self.include.set_charset(charset);
+    }
 }
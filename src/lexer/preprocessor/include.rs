@@ -3,15 +3,15 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use std::fs::{self, File};
-use std::io::Read;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use crate::lexer::buffer::BufferData;
+use crate::lexer::charset::Charset;
 use crate::lexer::errors::LexerError;
 use crate::lexer::lexer::{Lexer, TLexer};
 use crate::lexer::preprocessor::PreprocContext;
-use crate::lexer::source::{FileId, SourceMutex};
+use crate::lexer::source::{FileData, FileId, SourceMutex};
 
 #[derive(Debug, Clone, Copy, Default)]
 pub struct PathIndex(pub usize);
@@ -36,12 +36,14 @@ pub trait IncludeLocator: Default {
     fn get_path(&self, id: FileId) -> PathBuf;
     fn set_source(&mut self, source: SourceMutex);
     fn set_sys_paths<P: AsRef<Path>>(&mut self, paths: &[P]);
+    fn set_charset(&mut self, charset: Charset);
 }
 
 #[derive(Clone, Debug, Default)]
 pub struct DefaultIncludeLocator {
     sys: Vec<PathBuf>,
     source: Option<SourceMutex>,
+    charset: Charset,
 }
 
 impl DefaultIncludeLocator {
@@ -51,35 +53,31 @@ impl DefaultIncludeLocator {
         Self {
             sys,
             source: Some(source),
+            charset: Charset::default(),
         }
     }
 
-    fn read_file(path: &PathBuf) -> Option<Vec<u8>> {
-        //eprintln!("PATH {:?}", path);
-        let file_size = fs::metadata(&path).map_or(1024 * 1024, |m| m.len() as usize);
-        if let Ok(mut file) = File::open(path) {
-            let mut data = Vec::with_capacity(file_size + 1);
-            file.read_to_end(&mut data).unwrap();
-            Some(data)
-        } else {
-            None
-        }
-    }
-
-    fn get_file(file: PathBuf, source: Option<&SourceMutex>, path_index: PathIndex) -> BufferData {
+    fn get_file(&self, file: PathBuf, path_index: PathIndex) -> BufferData {
         // TODO: how to deal with that
         //let file = std::fs::canonicalize(file).unwrap();
-        let id = source
-            .as_ref()
-            .map_or(FileId(0), |s| s.lock().unwrap().get_id(&file));
-        BufferData::new(Self::read_file(&file).unwrap(), id, path_index)
+        let (id, cache) = self.source.as_ref().map_or((FileId(0), None), |s| {
+            let mut s = s.lock().unwrap();
+            (s.get_id(&file), s.get_file_cache())
+        });
+        if let Some(cache) = cache {
+            let text = cache.get_text(id, self.charset, || cache.read_file(&file));
+            BufferData::with_text(text.unwrap(), id, path_index)
+        } else {
+            let data = FileData::read(&file).map(Arc::new);
+            BufferData::with_charset(data.unwrap(), id, path_index, self.charset)
+        }
     }
 
     fn find_angle(&self, path: &str, next: bool, path_index: PathIndex) -> Option<BufferData> {
         let path = PathBuf::from(path);
 
         if path.is_absolute() {
-            return Some(Self::get_file(path, self.source.as_ref(), PathIndex(0)));
+            return Some(self.get_file(path, PathIndex(0)));
         }
 
         let index = if next { path_index.0 + 1 } else { 0 };
@@ -87,11 +85,7 @@ impl DefaultIncludeLocator {
         for (n, dir) in self.sys.get(index..).unwrap().iter().enumerate() {
             let file = dir.join(&path);
             if file.is_file() {
-                return Some(Self::get_file(
-                    file,
-                    self.source.as_ref(),
-                    PathIndex(index + n + 1),
-                ));
+                return Some(self.get_file(file, PathIndex(index + n + 1)));
             }
         }
 
@@ -108,7 +102,7 @@ impl DefaultIncludeLocator {
         let path = PathBuf::from(path);
 
         if path.is_absolute() {
-            return Some(Self::get_file(path, self.source.as_ref(), PathIndex(0)));
+            return Some(self.get_file(path, PathIndex(0)));
         }
 
         let index = if next { path_index.0 + 1 } else { 0 };
@@ -119,7 +113,7 @@ impl DefaultIncludeLocator {
                 let current = current.parent().unwrap().to_path_buf();
                 let file = current.join(&path);
                 if file.is_file() {
-                    return Some(Self::get_file(file, self.source.as_ref(), PathIndex(0)));
+                    return Some(self.get_file(file, PathIndex(0)));
                 }
             }
             1
@@ -130,11 +124,7 @@ impl DefaultIncludeLocator {
         for (n, dir) in self.sys.get(index - 1..).unwrap().iter().enumerate() {
             let file = dir.join(&path);
             if file.is_file() {
-                return Some(Self::get_file(
-                    file,
-                    self.source.as_ref(),
-                    PathIndex(index + n),
-                ));
+                return Some(self.get_file(file, PathIndex(index + n)));
             }
         }
 
@@ -178,6 +168,10 @@ impl IncludeLocator for DefaultIncludeLocator {
     fn set_sys_paths<P: AsRef<Path>>(&mut self, paths: &[P]) {
         self.sys = paths.iter().map(|s| s.as_ref().to_path_buf()).collect();
     }
+
+    fn set_charset(&mut self, charset: Charset) {
+        self.charset = charset;
+    }
 }
 
 impl<'a, PC: PreprocContext> Lexer<'a, PC> {
@@ -309,11 +303,15 @@ mod tests {
     use tempdir::TempDir;
 
     use super::*;
+    use crate::args::{Driver, Language, PreprocOptions};
     use crate::lexer::buffer::OutBuf;
     use crate::lexer::lexer::Token;
+    use crate::lexer::line_index::{Encoding, LineCol};
+    use crate::lexer::preprocessor::cache::{IfCache, PrefixCache};
     use crate::lexer::preprocessor::context::{Context, DefaultContext};
     use crate::lexer::preprocessor::macros::Macro;
-    use crate::lexer::source::SourceLocator;
+    use crate::lexer::source::{get_offset, get_range, SourceLocator};
+    use crate::lexer::symbol::Symbol;
     use pretty_assertions::assert_eq;
 
     macro_rules! eval {
@@ -335,9 +333,7 @@ mod tests {
     macro_rules! lexer_for_file {
         ( $name: ident, $code: expr, $file: expr, $id: expr, $context: expr) => {
             std::fs::write($file, $code).unwrap();
-            let mut file = File::open($file).unwrap();
-            let mut data = Vec::new();
-            file.read_to_end(&mut data).unwrap();
+            let data = FileData::read($file).unwrap();
 
             let mut $name = Lexer::new_with_context(&data, $id, $context);
         };
@@ -407,6 +403,8 @@ mod tests {
         fn set_source(&mut self, _source: SourceMutex) {}
 
         fn set_sys_paths<P: AsRef<Path>>(&mut self, _paths: &[P]) {}
+
+        fn set_charset(&mut self, _charset: Charset) {}
     }
 
     #[test]
@@ -526,13 +524,13 @@ mod tests {
         assert_eq!(p.next_token(), Token::PreprocInclude);
         assert_eq!(p.next_token(), Token::PreprocDefine);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::Identifier("inc_foo".to_string()));
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("inc_foo")));
 
         lexer_for_file!(p, "#include \"foo.h\"\ntest", &foo, id, context.clone());
         assert_eq!(p.next_token(), Token::PreprocInclude);
         assert_eq!(p.next_token(), Token::PreprocDefine);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::Identifier("cur_foo".to_string()));
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("cur_foo")));
 
         lexer_for_file!(
             p,
@@ -544,13 +542,13 @@ mod tests {
         assert_eq!(p.next_token(), Token::PreprocIncludeNext);
         assert_eq!(p.next_token(), Token::PreprocDefine);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::Identifier("inc_foo".to_string()));
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("inc_foo")));
 
         lexer_for_file!(p, "#include_next <foo.h>\ntest", &foo, id, context.clone());
         assert_eq!(p.next_token(), Token::PreprocIncludeNext);
         assert_eq!(p.next_token(), Token::PreprocDefine);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::Identifier("sys_foo".to_string()));
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("sys_foo")));
 
         lexer_for_file!(
             p,
@@ -562,7 +560,7 @@ mod tests {
         assert_eq!(p.next_token(), Token::PreprocIncludeNext);
         assert_eq!(p.next_token(), Token::PreprocDefine);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::Identifier("inc_bar".to_string()));
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("inc_bar")));
 
         lexer_for_file!(p, "#include <oof.h>\ntest", &foo, id, context.clone());
         assert_eq!(p.next_token(), Token::PreprocInclude);
@@ -570,7 +568,7 @@ mod tests {
         assert_eq!(p.next_token(), Token::PreprocDefine);
         assert_eq!(p.next_token(), Token::Eol);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::Identifier("sys_foo".to_string()));
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("sys_foo")));
     }
 
     #[test]
@@ -604,4 +602,284 @@ mod tests {
             panic!("mismatch. Was: {:?}", p.errors[0]);
         }
     }
+
+    #[test]
+    fn test_include_prefix() {
+        let tmp = TempDir::new("test").unwrap();
+        let prefix = tmp.path().join("prefix.h");
+        let foo = tmp.path().join("foo.c");
+        let bar = tmp.path().join("bar.c");
+
+        std::fs::write(&prefix, "#define FOO foo\n").unwrap();
+        std::fs::write(&foo, "FOO BAR\n").unwrap();
+        std::fs::write(&bar, "BAR FOO\n").unwrap();
+
+        let source = Arc::new(Mutex::new(SourceLocator::default()));
+        let if_cache = Arc::new(IfCache::default());
+        let prefix_cache = PrefixCache::<DefaultContext>::default();
+        let opt = PreprocOptions {
+            def: vec![crate::args::Macro::Defined((
+                "BAR".to_string(),
+                "bar".to_string(),
+            ))],
+            sys_paths: vec![],
+            includes: vec![prefix.to_str().unwrap().to_string()],
+            current_dir: tmp.path().to_path_buf(),
+            file: PathBuf::from(""),
+            lang: Language::C,
+            std: None,
+            driver: Driver::GCC,
+            charset: Charset::Utf8,
+            cuda: false,
+        };
+
+        let mut p = Lexer::<DefaultContext>::new_from_file_with_prefix(
+            foo.to_str().unwrap(),
+            Arc::clone(&source),
+            Arc::clone(&if_cache),
+            &prefix_cache,
+            opt.clone(),
+        );
+        assert_eq!(p.next_useful(), Token::Identifier(Symbol::intern("foo")));
+        assert_eq!(p.next_useful(), Token::Identifier(Symbol::intern("bar")));
+        assert_eq!(p.next_useful(), Token::Eof);
+
+        // The prefix is not read again
+        std::fs::write(&prefix, "#define FOO oof\n").unwrap();
+
+        let mut p = Lexer::<DefaultContext>::new_from_file_with_prefix(
+            bar.to_str().unwrap(),
+            Arc::clone(&source),
+            Arc::clone(&if_cache),
+            &prefix_cache,
+            opt,
+        );
+        assert_eq!(p.next_useful(), Token::Identifier(Symbol::intern("bar")));
+        assert_eq!(p.next_useful(), Token::Identifier(Symbol::intern("foo")));
+        assert_eq!(p.next_useful(), Token::Eof);
+
+        assert_eq!(prefix_cache.len(), 1);
+    }
+
+    #[test]
+    fn test_include_standard_version() {
+        let tmp = TempDir::new("test").unwrap();
+        let foo = tmp.path().join("foo.c");
+        std::fs::write(&foo, "__STDC_VERSION__ __cplusplus\n").unwrap();
+
+        let source = Arc::new(Mutex::new(SourceLocator::default()));
+        let if_cache = Arc::new(IfCache::default());
+        let prefix_cache = PrefixCache::<DefaultContext>::default();
+        let mut opt = PreprocOptions {
+            file: foo.clone(),
+            lang: Language::C,
+            std: Some(crate::args::Standard::C99),
+            ..Default::default()
+        };
+
+        let mut p = Lexer::<DefaultContext>::new_from_file_with_prefix(
+            foo.to_str().unwrap(),
+            Arc::clone(&source),
+            Arc::clone(&if_cache),
+            &prefix_cache,
+            opt.clone(),
+        );
+        assert_eq!(p.next_useful(), Token::from_number("199901L"));
+        assert_eq!(
+            p.next_useful(),
+            Token::Identifier(Symbol::intern("__cplusplus"))
+        );
+
+        opt.lang = Language::CPP;
+        opt.std = Some(crate::args::Standard::Cpp20);
+        let mut p = Lexer::<DefaultContext>::new_from_file_with_prefix(
+            foo.to_str().unwrap(),
+            Arc::clone(&source),
+            Arc::clone(&if_cache),
+            &prefix_cache,
+            opt,
+        );
+        assert_eq!(p.get_standard(), crate::args::Standard::Cpp20);
+        assert_eq!(
+            p.next_useful(),
+            Token::Identifier(Symbol::intern("__STDC_VERSION__"))
+        );
+        assert_eq!(p.next_useful(), Token::from_number("202002L"));
+    }
+
+    #[test]
+    fn test_include_dialect() {
+        let tmp = TempDir::new("test").unwrap();
+        let foo = tmp.path().join("foo.c");
+        std::fs::write(&foo, "class __uptr _Alignas\n").unwrap();
+
+        let source = Arc::new(Mutex::new(SourceLocator::default()));
+        let if_cache = Arc::new(IfCache::default());
+        let prefix_cache = PrefixCache::<DefaultContext>::default();
+        let mut opt = PreprocOptions {
+            file: foo.clone(),
+            lang: Language::C,
+            ..Default::default()
+        };
+
+        let mut p = Lexer::<DefaultContext>::new_from_file_with_prefix(
+            foo.to_str().unwrap(),
+            Arc::clone(&source),
+            Arc::clone(&if_cache),
+            &prefix_cache,
+            opt.clone(),
+        );
+        assert_eq!(p.next_useful(), Token::Identifier(Symbol::intern("class")));
+        assert_eq!(p.next_useful(), Token::Identifier(Symbol::intern("__uptr")));
+        assert_eq!(p.next_useful(), Token::Alignas);
+
+        opt.lang = Language::CPP;
+        opt.driver = Driver::MSVC;
+        let mut p = Lexer::<DefaultContext>::new_from_file_with_prefix(
+            foo.to_str().unwrap(),
+            Arc::clone(&source),
+            Arc::clone(&if_cache),
+            &prefix_cache,
+            opt,
+        );
+        assert_eq!(p.next_useful(), Token::Class);
+        assert_eq!(p.next_useful(), Token::MSUptr);
+        assert_eq!(
+            p.next_useful(),
+            Token::Identifier(Symbol::intern("_Alignas"))
+        );
+    }
+
+    #[test]
+    fn test_include_charset() {
+        let tmp = TempDir::new("test").unwrap();
+        let foo = tmp.path().join("foo.c");
+        let bar = tmp.path().join("bar.h");
+        let baz = tmp.path().join("baz.h");
+        std::fs::write(
+            &foo,
+            b"// caf\xE9\n#include \"bar.h\"\n#include \"baz.h\"\nc\n",
+        )
+        .unwrap();
+        std::fs::write(&bar, b"/* \xE9\xE9 */ a\n").unwrap();
+        // A UTF-16 BOM takes precedence over -finput-charset
+        let utf16: Vec<u8> = b"\xFF\xFE"
+            .iter()
+            .copied()
+            .chain("/* é */ b\n".encode_utf16().flat_map(|u| u.to_le_bytes()))
+            .collect();
+        std::fs::write(&baz, utf16).unwrap();
+
+        let source = Arc::new(Mutex::new(SourceLocator::default()));
+        let if_cache = Arc::new(IfCache::default());
+        let prefix_cache = PrefixCache::<DefaultContext>::default();
+        let opt = PreprocOptions {
+            file: foo.clone(),
+            lang: Language::C,
+            charset: Charset::Latin1,
+            ..Default::default()
+        };
+
+        let mut p = Lexer::<DefaultContext>::new_from_file_with_prefix(
+            foo.to_str().unwrap(),
+            Arc::clone(&source),
+            Arc::clone(&if_cache),
+            &prefix_cache,
+            opt,
+        );
+
+        // The spans are in bytes in the original files
+        assert_eq!(p.next_useful(), Token::PreprocInclude);
+        assert_eq!(p.next_useful(), Token::Identifier(Symbol::intern("a")));
+        assert_eq!(p.span().start.pos, 9);
+        assert_eq!(p.span().start.column, 10);
+        assert_eq!(p.next_useful(), Token::PreprocInclude);
+        assert_eq!(p.next_useful(), Token::Identifier(Symbol::intern("b")));
+        assert_eq!(p.span().start.pos, 18);
+        assert_eq!(p.span().end.pos, 20);
+        // The BOM isn't counted in the columns
+        assert_eq!(p.span().start.column, 17);
+
+        // The lines and the columns are the ones of the transcoded text
+        let span = p.span();
+        let lc = |line, column| LineCol { line, column };
+        let range = get_range(&source, &span, Charset::Latin1, Encoding::Utf16);
+        assert_eq!(range, Some((lc(0, 8), lc(0, 9))));
+        let range = get_range(&source, &span, Charset::Latin1, Encoding::Utf8);
+        assert_eq!(range, Some((lc(0, 9), lc(0, 10))));
+        let offset = get_offset(
+            &source,
+            span.file.unwrap(),
+            lc(0, 8),
+            Charset::Latin1,
+            Encoding::Utf16,
+        );
+        assert_eq!(offset, Some(18));
+
+        assert_eq!(p.next_useful(), Token::Identifier(Symbol::intern("c")));
+        assert_eq!(p.span().start.pos, 42);
+        assert_eq!(p.next_useful(), Token::Eof);
+    }
+
+    #[test]
+    fn test_include_two_charsets() {
+        let tmp = TempDir::new("test").unwrap();
+        let foo = tmp.path().join("foo.c");
+        let bar = tmp.path().join("bar.c");
+        let header = tmp.path().join("a.h");
+        std::fs::write(&foo, b"#include \"a.h\"\n").unwrap();
+        std::fs::write(&bar, b"#include \"a.h\"\n").unwrap();
+        std::fs::write(&header, b"/* \xC3\xA9 */ x\n").unwrap();
+
+        let source = Arc::new(Mutex::new(SourceLocator::default()));
+        let if_cache = Arc::new(IfCache::default());
+        let lex = |file: &PathBuf, charset| {
+            let opt = PreprocOptions {
+                file: file.clone(),
+                lang: Language::C,
+                charset,
+                ..Default::default()
+            };
+            let mut p = Lexer::<DefaultContext>::new_from_file(
+                file.to_str().unwrap(),
+                Arc::clone(&source),
+                Arc::clone(&if_cache),
+                opt,
+            );
+            // Skip the definitions from the command line
+            let x = Token::Identifier(Symbol::intern("x"));
+            loop {
+                let tok = p.next_useful();
+                if tok == x {
+                    break;
+                }
+                assert_ne!(tok, Token::Eof);
+            }
+            p.span()
+        };
+
+        // The same header is in UTF-8 for foo.c and in Latin-1 for bar.c
+        let utf8 = lex(&foo, Charset::Utf8);
+        let latin1 = lex(&bar, Charset::Latin1);
+        assert_eq!(utf8, latin1);
+
+        let lc = |line, column| LineCol { line, column };
+        assert_eq!(
+            get_range(&source, &latin1, Charset::Latin1, Encoding::Utf16),
+            Some((lc(0, 9), lc(0, 10)))
+        );
+        assert_eq!(
+            get_range(&source, &utf8, Charset::Utf8, Encoding::Utf16),
+            Some((lc(0, 8), lc(0, 9)))
+        );
+        let id = utf8.file.unwrap();
+        assert_eq!(
+            get_offset(&source, id, lc(0, 9), Charset::Latin1, Encoding::Utf16),
+            Some(9)
+        );
+        assert_eq!(
+            get_offset(&source, id, lc(0, 8), Charset::Utf8, Encoding::Utf16),
+            Some(9)
+        );
+    }
 }
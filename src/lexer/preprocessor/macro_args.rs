@@ -497,7 +497,7 @@ impl<'a> MacroNode<'a> {
                                 mac.eval(out, context, info);
                             }
                             Macro::Counter(mac) => {
-                                mac.eval(out);
+                                mac.eval(out, context);
                             }
                         }
                     } else {
@@ -3,7 +3,6 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use std::cell::Cell;
 use std::fmt;
 
 use super::context::{EmptyContext, PreprocContext};
@@ -15,7 +14,6 @@ use crate::lexer::{tools, Lexer};
 pub struct MacroObject {
     out: Vec<u8>,
     has_id: bool,
-    pub(crate) in_use: Cell<bool>,
     pub(crate) file_info: FileInfo,
 }
 
@@ -24,7 +22,6 @@ pub struct MacroFunction {
     out: Vec<u8>,
     actions: Vec<Action>,
     pub(crate) n_args: usize,
-    pub(crate) in_use: Cell<bool>,
     pub(crate) va_args: Option<usize>,
     pub(crate) file_info: FileInfo,
 }
@@ -34,8 +31,8 @@ impl fmt::Debug for MacroFunction {
         let out: Vec<_> = self.out.iter().map(|x| *x as char).enumerate().collect();
         write!(
             f,
-            "Macro Function: {:?}\n{:?}\nn_args: {}\nin_use: {:?}",
-            out, self.actions, self.n_args, self.in_use
+            "Macro Function: {:?}\n{:?}\nn_args: {}",
+            out, self.actions, self.n_args
         )
     }
 }
@@ -43,7 +40,7 @@ impl fmt::Debug for MacroFunction {
 impl fmt::Debug for MacroObject {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let out: Vec<_> = self.out.iter().map(|x| *x as char).enumerate().collect();
-        write!(f, "Macro Object: {:?}\nin_use: {:?}", out, self.in_use)
+        write!(f, "Macro Object: {:?}", out)
     }
 }
 
@@ -87,7 +84,6 @@ impl MacroFunction {
             out,
             actions,
             n_args,
-            in_use: Cell::new(false),
             va_args,
             file_info,
         }
@@ -97,6 +93,11 @@ impl MacroFunction {
         &self.file_info
     }
 
+    /// The id used to mark the macro as being expanded (see PreprocContext::set_in_use)
+    pub(crate) fn id(&self) -> usize {
+        self as *const Self as usize
+    }
+
     #[inline(always)]
     pub(crate) fn eval_parsed_args<'a, PC: PreprocContext>(
         &self,
@@ -148,10 +149,10 @@ impl MacroFunction {
             .buf
             .extend_from_slice(unsafe { &self.out.get_unchecked(out_pos..) });
 
-        let mut lexer = Lexer::<EmptyContext>::new(&output.buf);
-        self.in_use.set(true);
+        let mut lexer = Lexer::<EmptyContext>::new_expansion(&output.buf);
+        context.set_in_use(self.id(), true);
         lexer.macro_final_eval(out, context, info);
-        self.in_use.set(false);
+        context.set_in_use(self.id(), false);
     }
 
     #[inline(always)]
@@ -180,11 +181,15 @@ impl MacroObject {
         Self {
             out,
             has_id,
-            in_use: Cell::new(false),
             file_info,
         }
     }
 
+    /// The id used to mark the macro as being expanded (see PreprocContext::set_in_use)
+    pub(crate) fn id(&self) -> usize {
+        self as *const Self as usize
+    }
+
     #[inline(always)]
     pub(crate) fn eval<'a, PC: PreprocContext>(
         &'a self,
@@ -199,12 +204,12 @@ impl MacroObject {
         }
 
         if self.has_id {
-            let mut lexer = Lexer::<EmptyContext>::new(&self.out);
-            self.in_use.set(true);
+            let mut lexer = Lexer::<EmptyContext>::new_expansion(&self.out);
+            context.set_in_use(self.id(), true);
 
             lexer.macro_final_eval(out, context, info);
 
-            self.in_use.set(false);
+            context.set_in_use(self.id(), false);
         } else {
             out.buf.extend_from_slice(&self.out);
         }
@@ -266,30 +271,25 @@ impl MacroFile {
     }
 }
 
+/// The value of the counter is in the context
 #[derive(Debug, Clone)]
-pub struct MacroCounter {
-    value: Cell<u64>,
-}
+pub struct MacroCounter {}
 
 impl MacroCounter {
     #[inline(always)]
     pub(crate) fn new() -> Self {
-        Self {
-            value: Cell::new(0),
-        }
+        Self {}
     }
 
     #[inline(always)]
-    pub(crate) fn eval<'a>(&'a self, out: &mut OutBuf) {
+    pub(crate) fn eval<PC: PreprocContext>(&self, out: &mut OutBuf, context: &PC) {
         if let Some(last) = out.buf.last() {
             if *last != b' ' {
                 out.buf.push(b' ');
             }
         }
 
-        let n = self.value.get();
-        tools::extend_with_u64(&mut out.buf, n);
-        self.value.set(n + 1);
+        tools::extend_with_u64(&mut out.buf, context.next_counter());
 
         out.buf.push(b' ');
     }
@@ -300,7 +300,7 @@ mod tests {
 
     use super::*;
     use crate::lexer::preprocessor::context::DefaultContext;
-    use crate::lexer::Token;
+    use crate::lexer::{Symbol, Token};
     use pretty_assertions::assert_eq;
 
     macro_rules! eval {
@@ -411,11 +411,11 @@ mod tests {
         assert_eq!(p.next_token(), Token::While);
         assert_eq!(p.next_token(), Token::Eol);
         assert_eq!(p.next_token(), Token::PreprocDefine);
-        assert_eq!(p.next_token(), Token::Identifier("lewhi".to_string()));
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("lewhi")));
         assert_eq!(p.next_token(), Token::Eol);
         assert_eq!(p.next_token(), Token::PreprocDefine);
-        assert_eq!(p.next_token(), Token::Identifier("bar".to_string()));
-        assert_eq!(p.next_token(), Token::Identifier("foo".to_string()));
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("bar")));
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("foo")));
         assert_eq!(p.next_token(), Token::Eol);
     }
 
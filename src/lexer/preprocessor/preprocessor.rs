@@ -12,6 +12,7 @@ use crate::lexer::buffer::{FileInfo, OutBuf, Position};
 use crate::lexer::errors::LexerError;
 use crate::lexer::lexer::{Lexer, TLexer, Token};
 use crate::lexer::string::StringType;
+use crate::lexer::tools::find_skip_stop;
 
 #[derive(Clone, Debug, Copy, PartialEq, PartialOrd)]
 #[repr(u8)]
@@ -109,6 +110,9 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
         // https://docs.freebsd.org/info/cpp/cpp.pdf
         skip_whites!(self);
         match instr {
+            Token::PreprocInclude | Token::PreprocIncludeNext if self.lossless => {
+                skip_until!(self, b'\n');
+            }
             Token::PreprocInclude => {
                 self.get_include(false)?;
             }
@@ -565,7 +569,7 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                             }
                             Macro::Counter(mac) => {
                                 fake = None;
-                                mac.eval(out);
+                                mac.eval(out, context);
                             }
                         }
                     } else {
@@ -629,7 +633,7 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                 mac.eval(self.buf.get_preproc_buf(), &self.context, &info);
             }
             MacroType::Counter(mac) => {
-                mac.eval(self.buf.get_preproc_buf());
+                mac.eval(self.buf.get_preproc_buf(), &self.context);
             }
         }
         true
@@ -652,42 +656,36 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
             }
         }
 
-        loop {
-            if self.buf.has_char() {
-                let c = self.buf.next_char();
-                self.buf.inc();
-                let kind = unsafe { *PPCHARS.get_unchecked(c as usize) };
-                match kind {
-                    #[cold]
-                    Kind::QUO => {
-                        // we've a string or char literal
-                        self.skip_by_delim(c);
-                    }
-                    #[cold]
-                    Kind::RET => {
-                        self.buf.add_new_line();
-                        // we've a new line so check if it starts with preproc directive
-                        loop {
-                            let spos = self.buf.pos();
-                            skip_whites!(self);
-                            if self.stop_skipping()? {
-                                return Ok(());
-                            }
-                            if spos == self.buf.pos() || self.buf.prev_char() != b'\n' {
-                                break;
-                            }
+        // jump directly to the next newline, quote or slash: the rest doesn't matter
+        while let Some(i) = find_skip_stop(self.buf.remainder()) {
+            self.buf.inc_n(i);
+            let c = self.buf.next_char();
+            self.buf.inc();
+            match c {
+                b'\n' => {
+                    self.buf.add_new_line();
+                    // we've a new line so check if it starts with preproc directive
+                    loop {
+                        let spos = self.buf.pos();
+                        skip_whites!(self);
+                        if self.stop_skipping()? {
+                            return Ok(());
+                        }
+                        if spos == self.buf.pos() || self.buf.prev_char() != b'\n' {
+                            break;
                         }
                     }
-                    #[cold]
-                    Kind::SLA => {
-                        self.skip_slash_or_not();
-                    }
-                    _ => {}
                 }
-            } else {
-                break;
+                b'/' => {
+                    self.skip_slash_or_not();
+                }
+                _ => {
+                    // we've a string or char literal
+                    self.skip_by_delim(c);
+                }
             }
         }
+        self.buf.inc_n(self.buf.rem());
         Ok(())
     }
 
@@ -743,8 +741,15 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
             skip_whites!(self);
             let condition = match kind {
                 IfKind::If => {
+                    // The macros must be expanded in the condition even in lossless mode
+                    let lossless = std::mem::replace(&mut self.lossless, false);
+                    let (start, start_pos) = (self.start, self.start_pos);
                     let mut condition = Condition::new(self);
-                    condition.eval_as_bool()
+                    let condition = condition.eval_as_bool();
+                    self.lossless = lossless;
+                    self.start = start;
+                    self.start_pos = start_pos;
+                    condition
                 }
                 IfKind::Ifdef => {
                     let id = self.get_preproc_identifier();
@@ -925,6 +930,7 @@ mod tests {
     use crate::lexer::preprocessor::cache::IfCache;
     use crate::lexer::preprocessor::context::DefaultContext;
     use crate::lexer::source::FileId;
+    use crate::lexer::Symbol;
     use pretty_assertions::assert_eq;
     use std::sync::Arc;
 
@@ -973,7 +979,7 @@ mod tests {
         assert_eq!(p.next_token(), Token::PreprocDefine);
         assert_eq!(p.next_token(), Token::PreprocEndif);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(56));
+        assert_eq!(p.next_token(), Token::from_number("56"));
 
         let mut p = Lexer::<DefaultContext>::new(
             concat!(
@@ -989,7 +995,7 @@ mod tests {
         assert_eq!(p.next_token(), Token::PreprocDefine);
         assert_eq!(p.next_token(), Token::PreprocIf);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(37));
+        assert_eq!(p.next_token(), Token::from_number("37"));
 
         let mut p = Lexer::<DefaultContext>::new(
             concat!(
@@ -1010,7 +1016,7 @@ mod tests {
         assert_eq!(p.next_token(), Token::PreprocDefine);
         assert_eq!(p.next_token(), Token::PreprocEndif);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(78));
+        assert_eq!(p.next_token(), Token::from_number("78"));
 
         let mut p = Lexer::<DefaultContext>::new(
             concat!(
@@ -1030,7 +1036,7 @@ mod tests {
         assert_eq!(p.next_token(), Token::PreprocDefine);
         assert_eq!(p.next_token(), Token::PreprocElse);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(56));
+        assert_eq!(p.next_token(), Token::from_number("56"));
     }
 
     #[test]
@@ -1074,8 +1080,8 @@ mod tests {
         assert_eq!(p.next_token(), Token::Eol);
         assert_eq!(p.next_token(), Token::PreprocEndif);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(56));
-        assert_eq!(p.next_token(), Token::LiteralInt(910));
+        assert_eq!(p.next_token(), Token::from_number("56"));
+        assert_eq!(p.next_token(), Token::from_number("910"));
 
         let mut p = Lexer::<DefaultContext>::new(
             concat!(
@@ -1112,8 +1118,8 @@ mod tests {
         assert_eq!(p.next_token(), Token::Eol);
         assert_eq!(p.next_token(), Token::PreprocEndif);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(56));
-        assert_eq!(p.next_token(), Token::LiteralInt(78));
+        assert_eq!(p.next_token(), Token::from_number("56"));
+        assert_eq!(p.next_token(), Token::from_number("78"));
 
         let mut p = Lexer::<DefaultContext>::new(
             concat!(
@@ -1151,8 +1157,8 @@ mod tests {
         assert_eq!(p.next_token(), Token::Eol);
         assert_eq!(p.next_token(), Token::PreprocEndif);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(56));
-        assert_eq!(p.next_token(), Token::LiteralInt(1112));
+        assert_eq!(p.next_token(), Token::from_number("56"));
+        assert_eq!(p.next_token(), Token::from_number("1112"));
     }
 
     #[test]
@@ -1179,7 +1185,7 @@ mod tests {
         assert_eq!(p.next_token(), Token::PreprocEndif);
         assert_eq!(p.next_token(), Token::Eol);
 
-        assert_eq!(p.next_token(), Token::LiteralInt(56));
+        assert_eq!(p.next_token(), Token::from_number("56"));
     }
 
     #[test]
@@ -1202,7 +1208,7 @@ mod tests {
         assert_eq!(p.next_token(), Token::PreprocDefine);
         assert_eq!(p.next_token(), Token::PreprocEndif);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(56));
+        assert_eq!(p.next_token(), Token::from_number("56"));
     }
 
     #[test]
@@ -1224,7 +1230,7 @@ mod tests {
         assert_eq!(p.next_token(), Token::PreprocDefine);
         assert_eq!(p.next_token(), Token::PreprocIf);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(123));
+        assert_eq!(p.next_token(), Token::from_number("123"));
     }
 
     #[test]
@@ -1247,7 +1253,7 @@ mod tests {
         assert_eq!(p.next_token(), Token::PreprocDefine);
         assert_eq!(p.next_token(), Token::PreprocIf);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(123));
+        assert_eq!(p.next_token(), Token::from_number("123"));
     }
 
     #[test]
@@ -1275,7 +1281,7 @@ mod tests {
         assert_eq!(p.next_token(), Token::PreprocDefine);
         assert_eq!(p.next_token(), Token::PreprocEndif);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(789));
+        assert_eq!(p.next_token(), Token::from_number("789"));
     }
 
     #[test]
@@ -1296,15 +1302,15 @@ mod tests {
         );
 
         assert_eq!(p.next_token(), Token::PreprocDefine);
-        assert_eq!(p.next_token(), Token::LiteralInt(2));
+        assert_eq!(p.next_token(), Token::from_number("2"));
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(3));
+        assert_eq!(p.next_token(), Token::from_number("3"));
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(4));
+        assert_eq!(p.next_token(), Token::from_number("4"));
         assert_eq!(p.next_token(), Token::Eol);
         assert_eq!(p.next_token(), Token::Comment);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(9));
+        assert_eq!(p.next_token(), Token::from_number("9"));
     }
 
     #[test]
@@ -1321,13 +1327,13 @@ mod tests {
         );
 
         assert_eq!(p.next_token(), Token::PreprocDefine);
-        assert_eq!(p.next_token(), Token::LiteralInt(0));
+        assert_eq!(p.next_token(), Token::from_number("0"));
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(1));
+        assert_eq!(p.next_token(), Token::from_number("1"));
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(2));
+        assert_eq!(p.next_token(), Token::from_number("2"));
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(3));
+        assert_eq!(p.next_token(), Token::from_number("3"));
     }
 
     #[test]
@@ -1383,9 +1389,9 @@ mod tests {
             assert_eq!(p.next_token(), Token::Eol);
             assert_eq!(p.next_token(), Token::PreprocIf);
             assert_eq!(p.next_token(), Token::Eol);
-            assert_eq!(p.next_token(), Token::LiteralInt(3));
+            assert_eq!(p.next_token(), Token::from_number("3"));
             assert_eq!(p.next_token(), Token::Eol);
-            assert_eq!(p.next_token(), Token::LiteralInt(4));
+            assert_eq!(p.next_token(), Token::from_number("4"));
             assert_eq!(p.next_token(), Token::Eol);
 
             assert_eq!(cache.get_next(FileId(0), 1).map(|p| p.pos), Some(16));
@@ -1413,7 +1419,7 @@ mod tests {
             assert_eq!(p.next_token(), Token::Eol);
             assert_eq!(p.next_token(), Token::PreprocIf);
             assert_eq!(p.next_token(), Token::Eol);
-            assert_eq!(p.next_token(), Token::LiteralInt(5));
+            assert_eq!(p.next_token(), Token::from_number("5"));
 
             assert_eq!(cache.get_next(FileId(0), 1).map(|p| p.pos), Some(17));
         }
@@ -1440,11 +1446,11 @@ mod tests {
 
             assert_eq!(p.next_token(), Token::Eol);
             assert_eq!(p.next_token(), Token::PreprocIf);
-            assert_eq!(p.next_token(), Token::LiteralInt(0));
+            assert_eq!(p.next_token(), Token::from_number("0"));
             assert_eq!(p.next_token(), Token::Eol);
-            assert_eq!(p.next_token(), Token::LiteralInt(1));
+            assert_eq!(p.next_token(), Token::from_number("1"));
             assert_eq!(p.next_token(), Token::Eol);
-            assert_eq!(p.next_token(), Token::LiteralInt(2));
+            assert_eq!(p.next_token(), Token::from_number("2"));
             assert_eq!(p.next_token(), Token::Eol);
             assert_eq!(p.next_token(), Token::PreprocElse);
             assert_eq!(p.next_token(), Token::Eol);
@@ -1454,4 +1460,14 @@ mod tests {
             assert_eq!(cache.get_next(FileId(0), 13).map(|p| p.pos), Some(25));
         }
     }
+
+    #[test]
+    fn test_skip_lines() {
+        let mut p = Lexer::<DefaultContext>::new(
+            b"#if 0\n/* #endif\n */ 'a' \"b\\\n#endif\" // c\n#endif\nx",
+        );
+        assert_eq!(p.next_useful(), Token::PreprocIf);
+        assert_eq!(p.next_useful(), Token::Identifier(Symbol::intern("x")));
+        assert_eq!(p.span().start.line, 6);
+    }
 }
@@ -4,16 +4,87 @@
 // copied, modified, or distributed except according to those terms.
 
 use hashbrown::{hash_map, HashMap};
-use std::path::PathBuf;
+use memmap2::Mmap;
+use std::fs::File;
+use std::io::Read;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
-#[derive(Debug, Clone, Copy, Default, Hash, PartialEq)]
+use crate::errors::Span;
+use crate::lexer::buffer::SourceText;
+use crate::lexer::charset::Charset;
+use crate::lexer::line_index::{Encoding, LineCol, LineIndex};
+use crate::lexer::preprocessor::cache::FileCache;
+
+#[derive(Debug, Clone, Copy, Default, Hash, PartialEq, Eq)]
 pub struct FileId(pub u32);
 
+/// The contents of a source file: read in memory or mapped (see read_or_map)
+#[derive(Debug)]
+pub enum FileData {
+    Heap(Vec<u8>),
+    Mapped(Mmap),
+}
+
+impl FileData {
+    /// The files smaller than that are read (mapping them costs more than reading them)
+    const MAP_THRESHOLD: u64 = 16 * 1024;
+
+    /// Read a file in memory
+    pub fn read<P: AsRef<Path>>(path: P) -> Option<Self> {
+        Self::load(path, false)
+    }
+
+    /// Map the big files and read the other ones.
+    /// A mapped file mustn't be truncated or rewritten while its data is alive
+    /// (e.g. in a FileCache): the process would get a SIGBUS when reading it.
+    pub fn read_or_map<P: AsRef<Path>>(path: P) -> Option<Self> {
+        Self::load(path, true)
+    }
+
+    fn load<P: AsRef<Path>>(path: P, map: bool) -> Option<Self> {
+        let mut file = File::open(path).ok()?;
+        let len = file.metadata().map_or(0, |m| m.len());
+        if map && len >= Self::MAP_THRESHOLD {
+            // See read_or_map for the files modified while they're mapped
+            if let Ok(map) = unsafe { Mmap::map(&file) } {
+                return Some(FileData::Mapped(map));
+            }
+        }
+
+        let mut data = Vec::with_capacity(len as usize);
+        file.read_to_end(&mut data).ok()?;
+        Some(FileData::Heap(data))
+    }
+}
+
+impl Deref for FileData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            FileData::Heap(data) => data,
+            FileData::Mapped(map) => map,
+        }
+    }
+}
+
+impl From<Vec<u8>> for FileData {
+    fn from(data: Vec<u8>) -> Self {
+        FileData::Heap(data)
+    }
+}
+
 #[derive(Debug)]
 pub struct SourceLocator {
     file2id: HashMap<PathBuf, FileId>,
     id2file: Vec<PathBuf>,
+    cache: Option<Arc<FileCache>>,
+    /// The lines of the files for the charsets they've been lexed with
+    lines: HashMap<(FileId, Charset), Arc<LineIndex>>,
+    /// The lines of the texts which have been set
+    texts: HashMap<FileId, Arc<LineIndex>>,
 }
 
 impl Default for SourceLocator {
@@ -25,6 +96,9 @@ impl Default for SourceLocator {
                 map
             },
             id2file: vec![PathBuf::from("")],
+            cache: None,
+            lines: HashMap::default(),
+            texts: HashMap::default(),
         }
     }
 }
@@ -35,6 +109,12 @@ pub fn get_source_mutex() -> SourceMutex {
     Arc::new(Mutex::new(SourceLocator::default()))
 }
 
+pub fn get_source_mutex_with_cache(cache: Arc<FileCache>) -> SourceMutex {
+    let mut source = SourceLocator::default();
+    source.set_file_cache(cache);
+    Arc::new(Mutex::new(source))
+}
+
 impl SourceLocator {
     pub fn get_id(&mut self, path: &PathBuf) -> FileId {
         match self.file2id.entry(path.clone()) {
@@ -51,4 +131,174 @@ impl SourceLocator {
     pub fn get_path(&self, id: FileId) -> PathBuf {
         unsafe { self.id2file.get_unchecked(id.0 as usize).clone() }
     }
+
+    pub(crate) fn get_paths(&self) -> &[PathBuf] {
+        &self.id2file
+    }
+
+    pub fn set_file_cache(&mut self, cache: Arc<FileCache>) {
+        self.cache = Some(cache);
+    }
+
+    pub fn get_file_cache(&self) -> Option<Arc<FileCache>> {
+        self.cache.clone()
+    }
+
+    /// Set the text of a file (for the ones which aren't on the disk or have been modified)
+    pub fn set_text(&mut self, id: FileId, text: &[u8]) {
+        self.texts.insert(id, Arc::new(LineIndex::new(text)));
+    }
+}
+
+/// Get the lines of a file transcoded with the given charset: they're computed from the
+/// file the first time (the file is read without holding the lock on the locator)
+pub fn get_line_index(
+    source: &SourceMutex,
+    id: FileId,
+    charset: Charset,
+) -> Option<Arc<LineIndex>> {
+    let (path, cache) = {
+        let source = source.lock().unwrap();
+        if let Some(index) = source
+            .texts
+            .get(&id)
+            .or_else(|| source.lines.get(&(id, charset)))
+        {
+            return Some(Arc::clone(index));
+        }
+        (
+            source.id2file.get(id.0 as usize)?.clone(),
+            source.cache.clone(),
+        )
+    };
+
+    let text = match cache {
+        Some(cache) => cache.get_text(id, charset, || cache.read_file(&path))?,
+        None => Arc::new(SourceText::from_data(
+            Arc::new(FileData::read(&path)?),
+            charset,
+        )),
+    };
+    let index = Arc::new(text.line_index());
+    let mut source = source.lock().unwrap();
+    Some(Arc::clone(
+        source.lines.entry((id, charset)).or_insert(index),
+    ))
+}
+
+/// Get the lines and the columns of the beginning and of the end of a span
+pub fn get_range(
+    source: &SourceMutex,
+    span: &Span,
+    charset: Charset,
+    enc: Encoding,
+) -> Option<(LineCol, LineCol)> {
+    let index = get_line_index(source, span.file?, charset)?;
+    Some((
+        index.line_col(span.start.pos, enc),
+        index.line_col(span.end.pos, enc),
+    ))
+}
+
+/// Get the position in a file of a line and a column
+pub fn get_offset(
+    source: &SourceMutex,
+    id: FileId,
+    lc: LineCol,
+    charset: Charset,
+    enc: Encoding,
+) -> Option<usize> {
+    get_line_index(source, id, charset)?.offset(lc, enc)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_file_data() {
+        let tmp_dir = TempDir::new("test_file_data").unwrap();
+        let small = tmp_dir.path().join("small.h");
+        let big = tmp_dir.path().join("big.h");
+        let code = "int x;\n".repeat(4096);
+        std::fs::write(&small, "int x;\n").unwrap();
+        std::fs::write(&big, &code).unwrap();
+
+        let data = FileData::read_or_map(&small).unwrap();
+        assert!(matches!(data, FileData::Heap(_)));
+        assert_eq!(&data[..], b"int x;\n");
+
+        let data = FileData::read_or_map(&big).unwrap();
+        assert!(matches!(data, FileData::Mapped(_)));
+        assert_eq!(&data[..], code.as_bytes());
+
+        // The files are only mapped on demand
+        let data = FileData::read(&big).unwrap();
+        assert!(matches!(data, FileData::Heap(_)));
+        assert_eq!(&data[..], code.as_bytes());
+
+        assert!(FileData::read(tmp_dir.path().join("foo.h")).is_none());
+    }
+
+    #[test]
+    fn test_range() {
+        use crate::lexer::lexer::Location;
+
+        let tmp_dir = TempDir::new("test_range").unwrap();
+        let path = tmp_dir.path().join("a.h");
+        std::fs::write(&path, "// é\nint 𝄞x;\n").unwrap();
+
+        let source = get_source_mutex();
+        let id = source.lock().unwrap().get_id(&path);
+        let span = Span {
+            file: Some(id),
+            start: Location {
+                pos: 10,
+                line: 2,
+                column: 5,
+            },
+            end: Location {
+                pos: 15,
+                line: 2,
+                column: 10,
+            },
+        };
+        let lc = |line, column| LineCol { line, column };
+
+        assert_eq!(
+            get_range(&source, &span, Charset::Utf8, Encoding::Utf8),
+            Some((lc(1, 4), lc(1, 9)))
+        );
+        assert_eq!(
+            get_range(&source, &span, Charset::Utf8, Encoding::Utf16),
+            Some((lc(1, 4), lc(1, 7)))
+        );
+        assert_eq!(
+            get_range(&source, &span, Charset::Utf8, Encoding::Char),
+            Some((lc(1, 4), lc(1, 6)))
+        );
+        assert_eq!(
+            get_offset(&source, id, lc(1, 7), Charset::Utf8, Encoding::Utf16),
+            Some(15)
+        );
+
+        // A text without file
+        assert!(get_line_index(&source, FileId(0), Charset::Utf8).is_none());
+        source
+            .lock()
+            .unwrap()
+            .set_text(FileId(0), "é\na".as_bytes());
+        assert_eq!(
+            get_offset(
+                &source,
+                FileId(0),
+                lc(0, 1),
+                Charset::Latin1,
+                Encoding::Char
+            ),
+            Some(2)
+        );
+    }
 }
@@ -3,10 +3,13 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use super::lexer::{Lexer, Token};
+use super::errors::LexerError;
+use super::lexer::{Dialect, Lexer, TLexer, Token};
 use super::preprocessor::context::PreprocContext;
+use super::symbol::Symbol;
+use super::tools::find_delim_end;
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, PartialEq)]
 pub(crate) enum StringType {
     None,
     L,
@@ -69,7 +72,10 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                     if c == b'\"' {
                         self.buf.inc_n(2);
                         return Some(StringCharType::S(StringType::U8));
-                    } else if c == b'\'' {
+                    } else if c == b'\''
+                        && (!self.dialect.is_c() || self.dialect.contains(Dialect::C23))
+                    {
+                        // u8'a' is an identifier followed by a char before C23
                         self.buf.inc_n(2);
                         return Some(StringCharType::C(StringType::U8));
                     } else if c == b'R' {
@@ -101,91 +107,81 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
     pub(super) fn get_string(&mut self, typ: StringType) -> Token {
         match typ {
             StringType::L => {
-                let s = self.get_string_content();
+                let s = Symbol::intern(&self.get_string_content());
                 if let Some(suf) = self.get_suffix() {
-                    let s = Box::new((s, suf));
-                    Token::LiteralLStringUD(s)
+                    Token::LiteralLStringUD(s, suf)
                 } else {
                     Token::LiteralLString(s)
                 }
             }
             StringType::UU => {
-                let s = self.get_string_content();
+                let s = Symbol::intern(&self.get_string_content());
                 if let Some(suf) = self.get_suffix() {
-                    let s = Box::new((s, suf));
-                    Token::LiteralUUStringUD(s)
+                    Token::LiteralUUStringUD(s, suf)
                 } else {
                     Token::LiteralUUString(s)
                 }
             }
             StringType::R => {
-                let s = self.get_r_string_content();
+                let s = Symbol::intern(&self.get_r_string_content());
                 if let Some(suf) = self.get_suffix() {
-                    let s = Box::new((s, suf));
-                    Token::LiteralRStringUD(s)
+                    Token::LiteralRStringUD(s, suf)
                 } else {
                     Token::LiteralRString(s)
                 }
             }
             StringType::U => {
-                let s = self.get_string_content();
+                let s = Symbol::intern(&self.get_string_content());
                 if let Some(suf) = self.get_suffix() {
-                    let s = Box::new((s, suf));
-                    Token::LiteralUStringUD(s)
+                    Token::LiteralUStringUD(s, suf)
                 } else {
                     Token::LiteralUString(s)
                 }
             }
             StringType::U8 => {
-                let s = self.get_string_content();
+                let s = Symbol::intern(&self.get_string_content());
                 if let Some(suf) = self.get_suffix() {
-                    let s = Box::new((s, suf));
-                    Token::LiteralU8StringUD(s)
+                    Token::LiteralU8StringUD(s, suf)
                 } else {
                     Token::LiteralU8String(s)
                 }
             }
             StringType::LR => {
-                let s = self.get_r_string_content();
+                let s = Symbol::intern(&self.get_r_string_content());
                 if let Some(suf) = self.get_suffix() {
-                    let s = Box::new((s, suf));
-                    Token::LiteralLRStringUD(s)
+                    Token::LiteralLRStringUD(s, suf)
                 } else {
                     Token::LiteralLRString(s)
                 }
             }
             StringType::UUR => {
-                let s = self.get_r_string_content();
+                let s = Symbol::intern(&self.get_r_string_content());
                 if let Some(suf) = self.get_suffix() {
-                    let s = Box::new((s, suf));
-                    Token::LiteralUURStringUD(s)
+                    Token::LiteralUURStringUD(s, suf)
                 } else {
                     Token::LiteralUURString(s)
                 }
             }
             StringType::UR => {
-                let s = self.get_r_string_content();
+                let s = Symbol::intern(&self.get_r_string_content());
                 if let Some(suf) = self.get_suffix() {
-                    let s = Box::new((s, suf));
-                    Token::LiteralURStringUD(s)
+                    Token::LiteralURStringUD(s, suf)
                 } else {
                     Token::LiteralURString(s)
                 }
             }
             StringType::U8R => {
-                let s = self.get_r_string_content();
+                let s = Symbol::intern(&self.get_r_string_content());
                 if let Some(suf) = self.get_suffix() {
-                    let s = Box::new((s, suf));
-                    Token::LiteralU8RStringUD(s)
+                    Token::LiteralU8RStringUD(s, suf)
                 } else {
                     Token::LiteralU8RString(s)
                 }
             }
             StringType::None => {
-                let s = self.get_string_content();
+                let s = Symbol::intern(&self.get_string_content());
                 if let Some(suf) = self.get_suffix() {
-                    let s = Box::new((s, suf));
-                    Token::LiteralStringUD(s)
+                    Token::LiteralStringUD(s, suf)
                 } else {
                     Token::LiteralString(s)
                 }
@@ -193,46 +189,36 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
         }
     }
 
+    /// Get the spelling of a string: the escapes are checked but they're decoded
+    /// in the parser (see parser::literals)
     #[inline(always)]
     pub(crate) fn get_string_content(&mut self) -> String {
-        let mut buf = Vec::new();
-        let mut spos = self.buf.pos();
-        let mut ch = String::new();
+        let spos = self.buf.pos();
 
         loop {
             if self.buf.has_char() {
                 let c = self.buf.next_char();
                 if c == b'\\' {
-                    let pos = self.buf.pos();
                     self.buf.inc();
-                    if let Some(code) = self.get_escape() {
-                        buf.extend_from_slice(self.buf.slice_p(spos, pos));
-                        ch.push(std::char::from_u32(code).unwrap());
-                        buf.extend_from_slice(ch.as_bytes());
-                        ch.clear();
-                        spos = self.buf.pos();
-                    }
+                    self.get_escape();
                 } else if c == b'\"' {
-                    let s = if buf.is_empty() {
-                        String::from_utf8(self.buf.slice(spos).to_vec()).unwrap()
-                    } else {
-                        buf.extend_from_slice(self.buf.slice(spos));
-                        String::from_utf8(buf).unwrap()
-                    };
+                    let s = String::from_utf8(self.buf.slice(spos).to_vec()).unwrap();
                     self.buf.inc();
                     return s;
+                } else if c == b'\n' {
+                    // Stop at the end of the line to not eat the rest of the file
+                    break;
                 } else {
                     self.buf.inc();
                 }
             } else {
-                return if buf.is_empty() {
-                    String::from_utf8(self.buf.slice(spos).to_vec()).unwrap()
-                } else {
-                    buf.extend_from_slice(self.buf.slice(spos));
-                    String::from_utf8(buf).unwrap()
-                };
+                break;
             }
         }
+
+        let sp = self.span();
+        self.errors.push(LexerError::UnterminatedString { sp });
+        String::from_utf8(self.buf.slice(spos).to_vec()).unwrap()
     }
 
     #[inline(always)]
@@ -246,6 +232,8 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                     break;
                 }
             } else {
+                let sp = self.span();
+                self.errors.push(LexerError::UnterminatedString { sp });
                 return String::from_utf8(self.buf.slice(spos).to_vec()).unwrap();
             }
         }
@@ -276,7 +264,8 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                             delim_pos += 1;
                         } else if c == b'\"' {
                             self.buf.inc();
-                            return String::from_utf8(self.buf.slice_p(spos, rspos).to_vec())
+                            // The line splices are reverted in a raw string
+                            return String::from_utf8(self.buf.unspliced(spos, rspos).into_owned())
                                 .unwrap();
                         } else if c == b'\n' {
                             self.buf.add_new_line();
@@ -293,6 +282,8 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                     self.buf.inc();
                 }
             } else {
+                let sp = self.span();
+                self.errors.push(LexerError::UnterminatedString { sp });
                 return String::from_utf8(self.buf.slice(spos).to_vec()).unwrap();
             }
         }
@@ -300,30 +291,8 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
 
     #[inline(always)]
     pub(crate) fn skip_by_delim(&mut self, delim: u8) {
-        loop {
-            if self.buf.has_char() {
-                let c = self.buf.next_char();
-                if c == delim {
-                    self.buf.inc();
-                    break;
-                } else if c == b'\\' {
-                    self.buf.inc();
-                    if self.buf.has_char() {
-                        let c = self.buf.next_char();
-                        if c == b'\n' {
-                            self.buf.add_new_line();
-                        }
-                        self.buf.inc();
-                    } else {
-                        break;
-                    }
-                } else {
-                    self.buf.inc();
-                }
-            } else {
-                break;
-            }
-        }
+        let end = find_delim_end(self.buf.remainder(), delim);
+        self.buf.advance(end);
     }
 }
 
@@ -337,78 +306,125 @@ mod tests {
     #[test]
     fn test_string() {
         let mut p = Lexer::<DefaultContext>::new(b"\"foo\" \"foo\\\"bar\"");
-        assert_eq!(p.next_token(), Token::LiteralString("foo".to_string()));
-        assert_eq!(p.next_token(), Token::LiteralString("foo\"bar".to_string()));
+        assert_eq!(p.next_token(), Token::LiteralString(Symbol::intern("foo")));
+        assert_eq!(
+            p.next_token(),
+            Token::LiteralString(Symbol::intern("foo\\\"bar"))
+        );
 
         let mut p = Lexer::<DefaultContext>::new(b"u\"foo\" u\"foo\\\"bar\"");
-        assert_eq!(p.next_token(), Token::LiteralUString("foo".to_string()));
+        assert_eq!(p.next_token(), Token::LiteralUString(Symbol::intern("foo")));
         assert_eq!(
             p.next_token(),
-            Token::LiteralUString("foo\"bar".to_string())
+            Token::LiteralUString(Symbol::intern("foo\\\"bar"))
         );
 
         let mut p = Lexer::<DefaultContext>::new(b"U\"foo\" U\"foo\\\"bar\"");
-        assert_eq!(p.next_token(), Token::LiteralUUString("foo".to_string()));
         assert_eq!(
             p.next_token(),
-            Token::LiteralUUString("foo\"bar".to_string())
+            Token::LiteralUUString(Symbol::intern("foo"))
+        );
+        assert_eq!(
+            p.next_token(),
+            Token::LiteralUUString(Symbol::intern("foo\\\"bar"))
         );
 
         let mut p = Lexer::<DefaultContext>::new(b"u8\"foo\" u8\"foo\\\"bar\"");
-        assert_eq!(p.next_token(), Token::LiteralU8String("foo".to_string()));
         assert_eq!(
             p.next_token(),
-            Token::LiteralU8String("foo\"bar".to_string())
+            Token::LiteralU8String(Symbol::intern("foo"))
+        );
+        assert_eq!(
+            p.next_token(),
+            Token::LiteralU8String(Symbol::intern("foo\\\"bar"))
         );
 
         let mut p = Lexer::<DefaultContext>::new(b"L\"foo\" L\"foo\\\"bar\"");
-        assert_eq!(p.next_token(), Token::LiteralLString("foo".to_string()));
+        assert_eq!(p.next_token(), Token::LiteralLString(Symbol::intern("foo")));
         assert_eq!(
             p.next_token(),
-            Token::LiteralLString("foo\"bar".to_string())
+            Token::LiteralLString(Symbol::intern("foo\\\"bar"))
         );
 
         let mut p = Lexer::<DefaultContext>::new(
             b"R\"hello(foo)hello\" R\"world(foo\n\\\"bar)world\" R\"world(foo)world  )world\"",
         );
-        assert_eq!(p.next_token(), Token::LiteralRString("foo".to_string()));
+        assert_eq!(p.next_token(), Token::LiteralRString(Symbol::intern("foo")));
         assert_eq!(
             p.next_token(),
-            Token::LiteralRString("foo\n\\\"bar".to_string())
+            Token::LiteralRString(Symbol::intern("foo\n\\\"bar"))
         );
         assert_eq!(
             p.next_token(),
-            Token::LiteralRString("foo)world  ".to_string())
+            Token::LiteralRString(Symbol::intern("foo)world  "))
         );
 
         let mut p =
             Lexer::<DefaultContext>::new(b"LR\"hello(foo)hello\" UR\"world(foo\n\\\"bar)world\"");
-        assert_eq!(p.next_token(), Token::LiteralLRString("foo".to_string()));
         assert_eq!(
             p.next_token(),
-            Token::LiteralUURString("foo\n\\\"bar".to_string())
+            Token::LiteralLRString(Symbol::intern("foo"))
+        );
+        assert_eq!(
+            p.next_token(),
+            Token::LiteralUURString(Symbol::intern("foo\n\\\"bar"))
         );
 
         let mut p =
             Lexer::<DefaultContext>::new(b"uR\"hello(foo)hello\" u8R\"world(foo\n\\\"bar)world\"");
-        assert_eq!(p.next_token(), Token::LiteralURString("foo".to_string()));
         assert_eq!(
             p.next_token(),
-            Token::LiteralU8RString("foo\n\\\"bar".to_string())
+            Token::LiteralURString(Symbol::intern("foo"))
+        );
+        assert_eq!(
+            p.next_token(),
+            Token::LiteralU8RString(Symbol::intern("foo\n\\\"bar"))
         );
 
         let mut p = Lexer::<DefaultContext>::new(b"R\"(abc)\ndef)\n)\"");
         assert_eq!(
             p.next_token(),
-            Token::LiteralRString("abc)\ndef)\n".to_string())
+            Token::LiteralRString(Symbol::intern("abc)\ndef)\n"))
         );
 
         let mut p =
             Lexer::<DefaultContext>::new(b"\"test\\0\\\\\\\"\\t\\a\\b\\234\\u1234\\U0010ffff\"");
         assert_eq!(
             p.next_token(),
-            Token::LiteralString("test\u{0}\\\"\t\u{7}\u{8}\u{9c}\u{1234}\u{10ffff}".to_string())
+            Token::LiteralString(Symbol::intern(
+                "test\\0\\\\\\\"\\t\\a\\b\\234\\u1234\\U0010ffff"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        let mut p = Lexer::<DefaultContext>::new(
+            b"\"\\x{41}\\o{101}\\u{1F600}\\N{LATIN CAPITAL LETTER A}\" u8'a'",
+        );
+        assert_eq!(
+            p.next_token(),
+            Token::LiteralString(Symbol::intern(
+                "\\x{41}\\o{101}\\u{1F600}\\N{LATIN CAPITAL LETTER A}"
+            ))
         );
+        assert_eq!(p.next_token(), Token::LiteralU8Char(u32::from('a')));
+        assert!(p.errors.is_empty());
+    }
+
+    #[test]
+    fn test_u8_char_in_c() {
+        use crate::args::Standard;
+
+        let mut p = Lexer::<DefaultContext>::new(b"u8'a' u8\"b\"");
+        p.set_dialect(Dialect::from_standard(Standard::C11));
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("u8")));
+        assert_eq!(p.next_token(), Token::LiteralChar(u32::from('a')));
+        assert_eq!(p.next_token(), Token::LiteralU8String(Symbol::intern("b")));
+
+        let mut p = Lexer::<DefaultContext>::new(b"u8'a'");
+        p.set_dialect(Dialect::from_standard(Standard::C2x));
+        assert_eq!(p.next_token(), Token::LiteralU8Char(u32::from('a')));
     }
 
     #[test]
@@ -416,7 +432,39 @@ mod tests {
         let mut p = Lexer::<DefaultContext>::new(b"\"foo\"_abcde");
         assert_eq!(
             p.next_token(),
-            Token::LiteralStringUD(Box::new(("foo".to_string(), "_abcde".to_string())))
+            Token::LiteralStringUD(Symbol::intern("foo"), Symbol::intern("_abcde"))
+        );
+    }
+
+    #[test]
+    fn test_string_errors() {
+        let mut p = Lexer::<DefaultContext>::new(b"\"abc\\d\nint x;\nR\"x(abc");
+        assert_eq!(
+            p.next_token(),
+            Token::LiteralString(Symbol::intern("abc\\d"))
+        );
+        assert_eq!(p.next_token(), Token::Eol);
+        assert_eq!(p.next_token(), Token::Int);
+        assert_eq!(p.next_token(), Token::Identifier(Symbol::intern("x")));
+        assert_eq!(p.next_token(), Token::SemiColon);
+        assert_eq!(p.next_token(), Token::Eol);
+        assert_eq!(p.next_token(), Token::LiteralRString(Symbol::intern("abc")));
+
+        let errors: Vec<_> = p
+            .errors
+            .iter()
+            .map(|e| {
+                let e = e.stringly();
+                (e.message, e.sp.start.pos, e.sp.end.pos)
+            })
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                ("invalid escape sequence \\d".to_string(), 4, 6),
+                ("missing terminating \" character".to_string(), 0, 6),
+                ("missing terminating \" character".to_string(), 14, 21),
+            ]
         );
     }
 }
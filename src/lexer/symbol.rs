@@ -0,0 +1,196 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Mutex;
+use std::thread::LocalKey;
+
+use hashbrown::HashMap;
+
+/// The values of an interner: they're leaked so they can be handed out as `&'static`
+/// (the tables are never shrunk so the memory is bounded by the number of distinct values)
+pub(crate) struct Table<T: ?Sized + 'static> {
+    map: HashMap<&'static str, u32>,
+    values: Vec<&'static T>,
+}
+
+impl<T: ?Sized> Default for Table<T> {
+    fn default() -> Self {
+        Self {
+            map: HashMap::default(),
+            values: Vec::new(),
+        }
+    }
+}
+
+/// An append-only table shared by all the threads.
+/// Each thread has its own copy of the part it has already seen, so the lock
+/// is only taken when a new value is met.
+pub(crate) struct Interner<T: ?Sized + 'static> {
+    pub(crate) global: &'static Mutex<Table<T>>,
+    pub(crate) local: &'static LocalKey<RefCell<Table<T>>>,
+}
+
+macro_rules! interner {
+    ($name: ident, $typ: ty) => {
+        fn $name() -> $crate::lexer::symbol::Interner<$typ> {
+            use std::cell::RefCell;
+            use std::sync::Mutex;
+            use $crate::lexer::symbol::{Interner, Table};
+
+            lazy_static::lazy_static! {
+                static ref GLOBAL: Mutex<Table<$typ>> = Mutex::new(Table::default());
+            }
+            thread_local! {
+                static LOCAL: RefCell<Table<$typ>> = RefCell::new(Table::default());
+            }
+
+            Interner {
+                global: &GLOBAL,
+                local: &LOCAL,
+            }
+        }
+    };
+}
+
+impl<T: ?Sized + Sync + 'static> Interner<T> {
+    /// Get the id of the value with the given key
+    pub(crate) fn lookup(&self, key: &str) -> Option<u32> {
+        if let Some(id) = self.local.with(|l| l.borrow().map.get(key).copied()) {
+            return Some(id);
+        }
+
+        let (key, id) = {
+            let global = self.global.lock().unwrap();
+            let (key, id) = global.map.get_key_value(key)?;
+            (*key, *id)
+        };
+        self.local.with(|l| l.borrow_mut().map.insert(key, id));
+        Some(id)
+    }
+
+    /// Get the id of the value with the given key or add the one built by make
+    pub(crate) fn intern<F>(&self, key: &str, make: F) -> u32
+    where
+        F: FnOnce() -> (&'static T, &'static str),
+    {
+        if let Some(id) = self.local.with(|l| l.borrow().map.get(key).copied()) {
+            return id;
+        }
+
+        let (key, id) = {
+            let mut global = self.global.lock().unwrap();
+            if let Some((key, id)) = global.map.get_key_value(key) {
+                (*key, *id)
+            } else {
+                let (value, key) = make();
+                let id = global.values.len() as u32;
+                global.values.push(value);
+                global.map.insert(key, id);
+                (key, id)
+            }
+        };
+        self.local.with(|l| l.borrow_mut().map.insert(key, id));
+        id
+    }
+
+    pub(crate) fn get(&self, id: u32) -> &'static T {
+        let id = id as usize;
+        if let Some(value) = self.local.with(|l| l.borrow().values.get(id).copied()) {
+            return value;
+        }
+
+        let global = self.global.lock().unwrap();
+        self.local.with(|l| {
+            let values = &mut l.borrow_mut().values;
+            values.extend_from_slice(&global.values[values.len()..]);
+            values[id]
+        })
+    }
+}
+
+interner!(symbols, str);
+
+/// An interned string (identifier, literal suffix, ...)
+#[derive(Clone, Copy, Eq, Hash, PartialEq)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    pub fn intern(s: &str) -> Self {
+        Self(symbols().intern(s, || {
+            let s: &'static str = Box::leak(s.into());
+            (s, s)
+        }))
+    }
+
+    pub fn as_str(self) -> &'static str {
+        symbols().get(self.0)
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(s: &str) -> Self {
+        Self::intern(s)
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_symbol() {
+        let a = Symbol::intern("hello");
+        let b = Symbol::intern("world");
+        assert_ne!(a, b);
+        assert_eq!(a, Symbol::intern("hello"));
+        assert_eq!(a.as_str(), "hello");
+        assert_eq!(b, "world");
+
+        // The symbols are shared between the threads
+        let c = std::thread::spawn(|| (Symbol::intern("world"), Symbol::intern("foo")))
+            .join()
+            .unwrap();
+        assert_eq!(c.0, b);
+        assert_eq!(c.1.as_str(), "foo");
+        assert_eq!(Symbol::intern("foo"), c.1);
+    }
+}
@@ -3,6 +3,11 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+// The scanning functions below use memchr which picks at runtime the best
+// implementation for the cpu (AVX2/SSE2 on x86_64, NEON on aarch64) and has
+// a portable word-at-a-time fallback.
+use memchr::{memchr, memchr2, memchr3};
+
 pub fn extend_with_u64(buf: &mut Vec<u8>, n: u64) {
     // TODO: can be optimized using "Three Optimization Tips for C++" talk
     let mut n = n;
@@ -36,3 +41,91 @@ pub fn extend_with_u32(buf: &mut Vec<u8>, n: u32) {
 
     buf.extend_from_slice(&s.get(i..).unwrap());
 }
+
+/// Get the position of the '/' closing a multiline comment (buf starts just after the /*)
+#[inline(always)]
+pub(crate) fn find_comment_end(buf: &[u8]) -> Option<usize> {
+    // the first char can't end the comment: /*/ isn't a comment
+    let mut pos = 1;
+    while pos < buf.len() {
+        let i = pos + memchr(b'/', &buf[pos..])?;
+        if buf[i - 1] == b'*' {
+            return Some(i);
+        }
+        pos = i + 1;
+    }
+    None
+}
+
+/// Get the position of the newline ending a single line comment (or the length of buf).
+/// The line splices have been removed before lexing so they don't need to be handled here.
+#[inline(always)]
+pub(crate) fn find_line_end(buf: &[u8]) -> usize {
+    memchr(b'\n', buf).unwrap_or(buf.len())
+}
+
+/// Get the position after the delimiter ending a string or a char literal (or the length of buf)
+#[inline(always)]
+pub(crate) fn find_delim_end(buf: &[u8], delim: u8) -> usize {
+    let mut pos = 0;
+    while let Some(i) = memchr2(delim, b'\\', &buf[pos..]) {
+        let i = pos + i;
+        if buf[i] == delim {
+            return i + 1;
+        }
+        pos = i + 2;
+        if pos >= buf.len() {
+            break;
+        }
+    }
+    buf.len()
+}
+
+/// Get the position of the next char which matters when skipping a disabled block:
+/// a newline (where a directive can start), a quote or a slash (for the comments)
+#[inline(always)]
+pub(crate) fn find_skip_stop(buf: &[u8]) -> Option<usize> {
+    let end = memchr3(b'\n', b'"', b'\'', buf);
+    let slash = memchr(b'/', &buf[..end.unwrap_or(buf.len())]);
+    slash.or(end)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_find_comment_end() {
+        assert_eq!(find_comment_end(b" abc */ def"), Some(6));
+        assert_eq!(find_comment_end(b"*/"), Some(1));
+        assert_eq!(find_comment_end(b"/ */"), Some(3));
+        assert_eq!(find_comment_end(b"/ * / "), None);
+        assert_eq!(find_comment_end(b""), None);
+    }
+
+    #[test]
+    fn test_find_line_end() {
+        assert_eq!(find_line_end(b"abc\ndef"), 3);
+        assert_eq!(find_line_end(b"abc\\\ndef\nghi"), 4);
+        assert_eq!(find_line_end(b"abc\\"), 4);
+        assert_eq!(find_line_end(b"abc"), 3);
+    }
+
+    #[test]
+    fn test_find_delim_end() {
+        assert_eq!(find_delim_end(b"abc\" def", b'"'), 4);
+        assert_eq!(find_delim_end(b"a\\\"c\" def", b'"'), 5);
+        assert_eq!(find_delim_end(b"a\\", b'\''), 2);
+        assert_eq!(find_delim_end(b"abc", b'\''), 3);
+    }
+
+    #[test]
+    fn test_find_skip_stop() {
+        assert_eq!(find_skip_stop(b"abc\ndef"), Some(3));
+        assert_eq!(find_skip_stop(b"a / c \"d\""), Some(2));
+        assert_eq!(find_skip_stop(b"a 'c' / \"d\""), Some(2));
+        assert_eq!(find_skip_stop(b"abc"), None);
+    }
+}
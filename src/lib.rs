@@ -16,6 +16,7 @@ pub use self::macros::*;
 
 pub mod args;
 pub mod defaults;
+pub mod driver;
 pub mod errors;
 pub mod lexer;
 pub mod parser;
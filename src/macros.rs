@@ -54,17 +54,7 @@ macro_rules! skip_whites_back {
 #[macro_export]
 macro_rules! skip_until {
     ( $lexer: expr, $char: expr ) => {{
-        loop {
-            if $lexer.buf.has_char() {
-                let c = $lexer.buf.next_char();
-                if c == $char {
-                    break;
-                }
-                $lexer.buf.inc();
-            } else {
-                break;
-            }
-        }
+        $lexer.buf.skip_until($char);
     }};
 }
 
@@ -9,10 +9,14 @@ use hashbrown::HashMap;
 use std::cell::RefCell;
 use std::rc::Rc;
 
+use crate::args::{Language, Standard};
+use crate::errors::Span;
 use crate::lexer::extra::SavedLexer;
+use crate::lexer::source::FileId;
 use crate::parser::declarations::namespace::NsNames;
 use crate::parser::declarations::TypeDeclarator;
 use crate::parser::dump::Dump;
+use crate::parser::literals::WcharSize;
 use crate::parser::names::name::{Identifier, Name, Qualified};
 
 #[derive(Debug, Default, PartialEq)]
@@ -94,6 +98,7 @@ pub struct Scope {
     using: Vec<Rc<RefCell<Scope>>>,
     incomplete: Option<TypeToFix>,
     methods: Vec<(Rc<TypeDeclarator>, SavedLexer)>,
+    origins: HashMap<Name, FileId>,
 }
 
 impl Scope {
@@ -105,6 +110,7 @@ impl Scope {
             using: Vec::default(),
             incomplete: None,
             methods: Vec::new(),
+            origins: HashMap::default(),
         }
     }
 }
@@ -115,9 +121,89 @@ impl Default for Scope {
     }
 }
 
+/// A name used in a file and declared in another one
+#[derive(Clone, Debug, PartialEq)]
+pub struct NameUse {
+    pub name: String,
+    pub file: FileId,
+    pub origin: FileId,
+}
+
+/// A value in a scope before or after a change
+#[derive(Clone, Debug)]
+enum Entry {
+    Decl(Name, Option<Kind>),
+    Origin(Name, Option<FileId>),
+    Scope(String, Option<Rc<RefCell<Scope>>>),
+    Using(Vec<Rc<RefCell<Scope>>>),
+}
+
+/// A change in a scope which can be undone and redone
+#[derive(Clone, Debug)]
+pub(crate) struct Change {
+    scope: Rc<RefCell<Scope>>,
+    before: Entry,
+    after: Entry,
+}
+
+impl Change {
+    /// The identifier whose meaning is changed (None for a using directive or an operator)
+    pub(crate) fn name(&self) -> Option<&str> {
+        match &self.after {
+            Entry::Decl(Name::Identifier(id), _) | Entry::Origin(Name::Identifier(id), _) => {
+                Some(&id.val)
+            }
+            Entry::Scope(name, _) => Some(name),
+            _ => None,
+        }
+    }
+
+    fn set(&self, entry: &Entry) {
+        let mut scope = self.scope.borrow_mut();
+        match entry.clone() {
+            Entry::Decl(name, Some(kind)) => {
+                scope.decls.insert(name, kind);
+            }
+            Entry::Decl(name, None) => {
+                scope.decls.remove(&name);
+            }
+            Entry::Origin(name, Some(file)) => {
+                scope.origins.insert(name, file);
+            }
+            Entry::Origin(name, None) => {
+                scope.origins.remove(&name);
+            }
+            Entry::Scope(name, Some(sc)) => {
+                scope.scopes.insert(name, sc);
+            }
+            Entry::Scope(name, None) => {
+                scope.scopes.remove(&name);
+            }
+            Entry::Using(using) => {
+                scope.using = using;
+            }
+        }
+    }
+}
+
+/// The span of the first token of each declaration and the number of changes before it
+pub(crate) type Marks = Vec<(Span, usize)>;
+
+/// The changes made in the scopes and the positions of the top-level declarations
+#[derive(Clone, Debug, Default)]
+struct Journal {
+    changes: Vec<Change>,
+    marks: Marks,
+}
+
 #[derive(Clone, Debug)]
 pub struct Context {
     stack: Vec<Rc<RefCell<Scope>>>,
+    file: Option<FileId>,
+    name_uses: Option<RefCell<Vec<NameUse>>>,
+    journal: Option<RefCell<Journal>>,
+    std: Standard,
+    wchar: WcharSize,
 }
 
 #[derive(Clone, Debug)]
@@ -141,17 +227,24 @@ impl Default for Context {
     fn default() -> Self {
         Self {
             stack: vec![Rc::new(RefCell::new(Scope::default()))],
+            file: None,
+            name_uses: None,
+            journal: None,
+            std: Standard::default_for(&Language::CPP),
+            wchar: WcharSize::default(),
         }
     }
 }
 
+type Found = (SearchResult, Option<FileId>);
+
 trait Search {
-    fn search(&self, names: &[Name]) -> Option<SearchResult>;
+    fn search(&self, names: &[Name]) -> Option<Found>;
     fn search_scope<T: AsRef<str>>(self, names: &[T]) -> Self;
 }
 
 impl Search for Rc<RefCell<Scope>> {
-    fn search(&self, names: &[Name]) -> Option<SearchResult> {
+    fn search(&self, names: &[Name]) -> Option<Found> {
         let n_names = names.len();
         let last = &names[n_names - 1];
 
@@ -176,10 +269,11 @@ impl Search for Rc<RefCell<Scope>> {
             // a scope has been found so try to get the type/var
             let decl = sc.decls.get(&last);
             if let Some(decl) = decl {
-                Some(match decl {
+                let res = match decl {
                     Kind::Type(ty) => SearchResult::Type(Rc::clone(ty)),
                     Kind::Var(ty) => SearchResult::Var(Rc::clone(ty)),
-                })
+                };
+                Some((res, sc.origins.get(last).copied()))
             } else {
                 // self refering struct/enum
                 if let Some(sc) = sc.scopes.get(last.as_ref()) {
@@ -193,9 +287,9 @@ impl Search for Rc<RefCell<Scope>> {
                     };
 
                     if sc.kind == ScopeKind::Function {
-                        Some(SearchResult::IncompleteVar(inc))
+                        Some((SearchResult::IncompleteVar(inc), None))
                     } else {
-                        Some(SearchResult::IncompleteType(inc))
+                        Some((SearchResult::IncompleteType(inc), None))
                     }
                 } else {
                     // search in using
@@ -240,15 +334,113 @@ impl Context {
     pub fn search(&self, name: Option<&Qualified>) -> Option<SearchResult> {
         if let Some(name) = name {
             for scope in self.stack.iter().rev() {
-                let ty = scope.search(&name.names);
-                if ty.is_some() {
-                    return ty;
+                if let Some((res, origin)) = scope.search(&name.names) {
+                    self.add_name_use(name, origin);
+                    return Some(res);
                 }
             }
         }
         None
     }
 
+    pub fn set_standard(&mut self, std: Standard) {
+        self.std = std;
+    }
+
+    pub fn get_standard(&self) -> Standard {
+        self.std
+    }
+
+    pub fn set_wchar_size(&mut self, wchar: WcharSize) {
+        self.wchar = wchar;
+    }
+
+    pub fn get_wchar_size(&self) -> WcharSize {
+        self.wchar
+    }
+
+    /// Set the file where the next declarations come from
+    pub fn set_file(&mut self, file: Option<FileId>) {
+        self.file = file;
+    }
+
+    /// Keep the names used in a file but declared in another one
+    pub fn track_name_uses(&mut self) {
+        self.name_uses = Some(RefCell::new(Vec::new()));
+    }
+
+    pub fn get_name_uses(&self) -> Vec<NameUse> {
+        self.name_uses
+            .as_ref()
+            .map_or_else(Vec::new, |uses| uses.borrow().clone())
+    }
+
+    /// Keep the changes made in the scopes so they can be undone (see IncrementalParser)
+    pub fn track_changes(&mut self) {
+        self.journal = Some(RefCell::new(Journal::default()));
+    }
+
+    fn record(&self, scope: &Rc<RefCell<Scope>>, before: Entry, after: Entry) {
+        if let Some(journal) = self.journal.as_ref() {
+            journal.borrow_mut().changes.push(Change {
+                scope: Rc::clone(scope),
+                before,
+                after,
+            });
+        }
+    }
+
+    /// Mark the beginning of a top-level declaration
+    pub(crate) fn mark_declaration(&self, span: Span) {
+        if let Some(journal) = self.journal.as_ref() {
+            let mut journal = journal.borrow_mut();
+            let n = journal.changes.len();
+            journal.marks.push((span, n));
+        }
+    }
+
+    pub(crate) fn take_marks(&self) -> Marks {
+        self.journal
+            .as_ref()
+            .map_or_else(Vec::new, |j| std::mem::take(&mut j.borrow_mut().marks))
+    }
+
+    /// Undo the changes after the first n ones and get them back
+    pub(crate) fn rollback(&self, n: usize) -> Vec<Change> {
+        let changes = if let Some(journal) = self.journal.as_ref() {
+            journal.borrow_mut().changes.split_off(n)
+        } else {
+            return Vec::new();
+        };
+        for change in changes.iter().rev() {
+            change.set(&change.before);
+        }
+        changes
+    }
+
+    /// Redo some changes previously got from rollback
+    pub(crate) fn replay(&self, changes: Vec<Change>) {
+        for change in changes.iter() {
+            change.set(&change.after);
+        }
+        if let Some(journal) = self.journal.as_ref() {
+            journal.borrow_mut().changes.extend(changes);
+        }
+    }
+
+    fn add_name_use(&self, name: &Qualified, origin: Option<FileId>) {
+        if let (Some(uses), Some(file), Some(origin)) = (self.name_uses.as_ref(), self.file, origin)
+        {
+            if file != origin {
+                uses.borrow_mut().push(NameUse {
+                    name: name.to_string(),
+                    file,
+                    origin,
+                });
+            }
+        }
+    }
+
     pub fn set_current(&mut self, name: Option<&Qualified>, kind: ScopeKind) {
         if let Some(name) = name {
             if let Some((last, names)) = name.names.split_last() {
@@ -269,9 +461,19 @@ impl Context {
                 // then it probably means that the currect type is just
                 // a declaration and here we've a definition.
                 // So we should make the link between them here.
-                let mut sc = scope.borrow_mut();
                 let ns = Rc::new(RefCell::new(Scope::new(kind)));
-                sc.scopes.insert(last.as_ref().to_string(), Rc::clone(&ns));
+                let name = last.as_ref().to_string();
+                let before = scope
+                    .borrow_mut()
+                    .scopes
+                    .insert(name.clone(), Rc::clone(&ns));
+                if self.journal.is_some() {
+                    self.record(
+                        &scope,
+                        Entry::Scope(name.clone(), before),
+                        Entry::Scope(name, Some(Rc::clone(&ns))),
+                    );
+                }
                 self.stack.push(ns);
             }
         } else {
@@ -292,11 +494,22 @@ impl Context {
                     let ns = Rc::new(RefCell::new(Scope::default()));
                     sc.scopes.insert(name.as_ref().to_string(), Rc::clone(&ns));
                     self.stack.push(Rc::clone(&ns));
+                    drop(sc);
+                    self.record(
+                        &scope,
+                        Entry::Scope(name.as_ref().to_string(), None),
+                        Entry::Scope(name.as_ref().to_string(), Some(Rc::clone(&ns))),
+                    );
                     ns
                 }
             };
             if name.inline {
+                let before = scope.borrow().using.clone();
                 scope.borrow_mut().using.push(Rc::clone(&ns));
+                if self.journal.is_some() {
+                    let after = scope.borrow().using.clone();
+                    self.record(&scope, Entry::Using(before), Entry::Using(after));
+                }
             }
             scope = ns;
         }
@@ -336,10 +549,9 @@ impl Context {
                     if let Some((last, names)) = $field.names.split_last() {
                         let scope = Rc::clone(self.stack.last().unwrap());
                         let scope = scope.search_scope(&names);
-                        scope
-                            .borrow_mut()
-                            .decls
-                            .insert(last.clone(), Kind::$kind(Rc::clone(&typ)));
+                        let kind = Kind::$kind(Rc::clone(&typ));
+                        let before = scope.borrow_mut().decls.insert(last.clone(), kind.clone());
+                        self.record_decl(&scope, last, before, kind);
                     }
                 }
             }};
@@ -354,11 +566,40 @@ impl Context {
     }
 
     pub fn add_alias(&mut self, name: &str, typ: Rc<TypeDeclarator>) {
-        let scope = self.stack.last().unwrap();
+        let scope = Rc::clone(self.stack.last().unwrap());
         let name = Name::Identifier(Identifier {
             val: name.to_string(),
         });
-        scope.borrow_mut().decls.insert(name, Kind::Type(typ));
+        let kind = Kind::Type(typ);
+        let before = scope.borrow_mut().decls.insert(name.clone(), kind.clone());
+        self.record_decl(&scope, &name, before, kind);
+    }
+
+    /// Set the origin of a new declaration and record the change
+    fn record_decl(
+        &self,
+        scope: &Rc<RefCell<Scope>>,
+        name: &Name,
+        before: Option<Kind>,
+        kind: Kind,
+    ) {
+        let origin = self
+            .file
+            .and_then(|file| scope.borrow_mut().origins.insert(name.clone(), file));
+        if self.journal.is_some() {
+            self.record(
+                scope,
+                Entry::Decl(name.clone(), before),
+                Entry::Decl(name.clone(), Some(kind)),
+            );
+            if let Some(file) = self.file {
+                self.record(
+                    scope,
+                    Entry::Origin(name.clone(), origin),
+                    Entry::Origin(name.clone(), Some(file)),
+                );
+            }
+        }
     }
 
     pub fn add_method(&self, typ: Rc<TypeDeclarator>, saved: SavedLexer) {
@@ -523,4 +764,35 @@ unsigned factorial(unsigned n) {
         assert_eq!(context.stack.len(), 1);
         assert!(context.search(Some(&mk_id!("factorial"))).is_some());
     }
+
+    #[test]
+    fn test_context_name_uses() {
+        let mut context = Context::default();
+        context.track_name_uses();
+
+        let mut l = Lexer::<DefaultContext>::new_with_context(
+            b"typedef int T; T y;",
+            FileId(1),
+            DefaultContext::default(),
+        );
+        let p = DeclarationListParser::new(&mut l);
+        p.parse(None, &mut context).unwrap();
+
+        let mut l = Lexer::<DefaultContext>::new_with_context(
+            b"T x;",
+            FileId(2),
+            DefaultContext::default(),
+        );
+        let p = DeclarationListParser::new(&mut l);
+        p.parse(None, &mut context).unwrap();
+
+        assert_eq!(
+            context.get_name_uses(),
+            vec![NameUse {
+                name: "T".to_string(),
+                file: FileId(2),
+                origin: FileId(1),
+            }]
+        );
+    }
 }
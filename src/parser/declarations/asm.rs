@@ -53,10 +53,11 @@ impl<'a, L: TLexer> AsmParser<'a, L> {
 
         let tok = self.lexer.next_useful();
 
-        if let Some(code) = tok.get_string() {
+        let slp = StringLiteralParser::new(self.lexer);
+        let (tok, code) = slp.parse(tok, context)?;
+
+        if let Some(code) = code {
             // TODO: add an asm lexer & parser
-            let slp = StringLiteralParser::new(self.lexer);
-            let (tok, code) = slp.parse(&code, context)?;
 
             let tok = tok.unwrap_or_else(|| self.lexer.next_useful());
             if tok != Token::RightParen {
@@ -70,7 +71,7 @@ impl<'a, L: TLexer> AsmParser<'a, L> {
                 None,
                 Some(Asm {
                     attributes: None,
-                    code,
+                    code: code.text().to_string(),
                 }),
             ))
         } else {
@@ -12,6 +12,7 @@ use crate::check_semicolon;
 use crate::lexer::{TLexer, Token};
 use crate::parser::attributes::{Attributes, AttributesParser};
 use crate::parser::context::{Context, ScopeKind, TypeToFix};
+use crate::parser::doc::DocComment;
 use crate::parser::dump::Dump;
 use crate::parser::errors::ParserError;
 use crate::parser::names::{Qualified, QualifiedParser};
@@ -362,8 +363,11 @@ impl<'a, L: TLexer> ClassBodyParser<'a, L> {
         let mut tok = None;
 
         loop {
+            let tk = tok.unwrap_or_else(|| self.lexer.next_useful());
+            let doc = DocComment::get_leading(self.lexer);
+
             let mp = MemberParser::new(self.lexer);
-            let (tk, memb) = mp.parse(tok, context)?;
+            let (tk, memb) = mp.parse(Some(tk), context)?;
 
             let tk = if let Some(memb) = memb {
                 match memb {
@@ -381,7 +385,10 @@ impl<'a, L: TLexer> ClassBodyParser<'a, L> {
                         }
                         tk
                     }
-                    MemberRes::Decl(decl) => {
+                    MemberRes::Decl(mut decl) => {
+                        if let Some(doc) = doc {
+                            decl.set_doc(doc);
+                        }
                         let tk = if decl.has_semicolon() {
                             check_semicolon!(self, tk);
                             None
@@ -397,7 +404,13 @@ impl<'a, L: TLexer> ClassBodyParser<'a, L> {
                 tk
             };
 
+            // A trailing doc comment (///<) is just before the token following the member
             let tk = tk.unwrap_or_else(|| self.lexer.next_useful());
+            if let Some(doc) = DocComment::get_trailing(self.lexer) {
+                if let Some(memb) = current.last_mut() {
+                    memb.set_doc(doc);
+                }
+            }
             tok = if tk == Token::RightBrace || tk == Token::Eof {
                 return Ok((None, body));
             } else {
@@ -465,6 +478,7 @@ public:
             },
             init: None,
             bitfield_size: None,
+            doc: RefCell::new(None),
         });
 
         let expected = Class {
@@ -499,6 +513,7 @@ public:
                         },
                         init: None,
                         bitfield_size: None,
+                        doc: RefCell::new(None),
                     })),
                     Member::Type(Rc::new(TypeDeclarator {
                         typ: Type {
@@ -543,6 +558,7 @@ public:
                         },
                         init: None,
                         bitfield_size: None,
+                        doc: RefCell::new(None),
                     })),
                 ],
                 protected: vec![Member::Type(Rc::new(TypeDeclarator {
@@ -563,6 +579,7 @@ public:
                     },
                     init: Some(Initializer::Equal(ExprNode::Nullptr(Box::new(Nullptr {})))),
                     bitfield_size: None,
+                    doc: RefCell::new(None),
                 }))],
                 private: vec![
                     Member::Type(Rc::clone(&x)),
@@ -596,6 +613,7 @@ public:
                         },
                         init: None,
                         bitfield_size: None,
+                        doc: RefCell::new(None),
                     })),
                 ],
             }),
@@ -635,6 +653,7 @@ struct A {
             },
             init: None,
             bitfield_size: None,
+            doc: RefCell::new(None),
         });
 
         let expected = Class {
@@ -684,6 +703,7 @@ struct A {
                         },
                         init: None,
                         bitfield_size: None,
+                        doc: RefCell::new(None),
                     })),
                     Member::Type(Rc::clone(&x)),
                 ],
@@ -694,4 +714,28 @@ struct A {
 
         assert_eq!(c, expected);
     }
+
+    #[test]
+    fn test_error_in_method() {
+        let mut l = Lexer::<DefaultContext>::new(
+            br#"
+struct A {
+    int f() {
+        return x;
+    }
+
+    int g() {
+        if (x) ) {}
+    }
+
+    int x;
+}
+"#,
+        );
+        let p = ClassParser::new(&mut l);
+        let mut context = Context::default();
+        let err = p.parse(None, &mut context).unwrap_err();
+
+        assert_eq!(err.stringly().sp.start.line, 8);
+    }
 }
@@ -15,6 +15,7 @@ use crate::check_semicolon;
 
 use crate::lexer::{TLexer, Token};
 use crate::parser::attributes::{Attributes, AttributesParser};
+use crate::parser::doc::DocComment;
 use crate::parser::dump::Dump;
 use crate::parser::errors::ParserError;
 use crate::parser::Context;
@@ -77,6 +78,17 @@ impl Declaration {
             _ => true,
         }
     }
+    pub(crate) fn set_doc(&mut self, doc: DocComment) {
+        match self {
+            Self::Type(d) => {
+                d.doc.replace(Some(doc));
+            }
+            Self::Namespace(ns) => {
+                ns.doc = Some(doc);
+            }
+            _ => {}
+        }
+    }
 }
 
 pub(crate) struct DeclarationParser<'a, L: TLexer> {
@@ -164,11 +176,21 @@ impl<'a, L: TLexer> DeclarationParser<'a, L> {
 
 pub struct DeclarationListParser<'a, L: TLexer> {
     lexer: &'a mut L,
+    marks: bool,
 }
 
 impl<'a, L: TLexer> DeclarationListParser<'a, L> {
     pub(crate) fn new(lexer: &'a mut L) -> Self {
-        Self { lexer }
+        Self {
+            lexer,
+            marks: false,
+        }
+    }
+
+    /// Mark the beginning of each declaration in the context (for the top-level ones)
+    pub(crate) fn with_marks(mut self) -> Self {
+        self.marks = true;
+        self
     }
 
     pub(crate) fn parse(
@@ -177,13 +199,31 @@ impl<'a, L: TLexer> DeclarationListParser<'a, L> {
         context: &mut Context,
     ) -> Result<(Option<Token>, Option<Declarations>), ParserError> {
         let mut tok = tok;
-        let mut list = Vec::new();
+        let mut list: Declarations = Vec::new();
 
         loop {
+            // The declarations are attributed to the file containing their first token
+            let tk = tok.unwrap_or_else(|| self.lexer.next_useful());
+            context.set_file(self.lexer.span().file);
+            if self.marks {
+                context.mark_declaration(self.lexer.span());
+            }
+
+            // A trailing doc comment (///<) is just before the token following the declaration
+            if let Some(doc) = DocComment::get_trailing(self.lexer) {
+                if let Some(decl) = list.last_mut() {
+                    decl.set_doc(doc);
+                }
+            }
+            let doc = DocComment::get_leading(self.lexer);
+
             let dp = DeclarationParser::new(self.lexer);
-            let (tk, decl) = dp.parse(tok, None, context)?;
+            let (tk, decl) = dp.parse(Some(tk), None, context)?;
 
-            tok = if let Some(decl) = decl {
+            tok = if let Some(mut decl) = decl {
+                if let Some(doc) = doc {
+                    decl.set_doc(doc);
+                }
                 let tk = if decl.has_semicolon() {
                     check_semicolon!(self, tk);
                     None
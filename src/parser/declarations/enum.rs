@@ -9,6 +9,7 @@ use crate::lexer::{TLexer, Token};
 use crate::parser::attributes::{Attributes, AttributesParser};
 use crate::parser::context::{Context, ScopeKind, TypeToFix};
 use crate::parser::declarations::DeclSpecifierParser;
+use crate::parser::doc::DocComment;
 use crate::parser::dump::Dump;
 use crate::parser::errors::ParserError;
 use crate::parser::expressions::{ExprNode, ExpressionParser};
@@ -37,6 +38,7 @@ pub struct Entry {
     pub(crate) name: String,
     pub(crate) attributes: Option<Attributes>,
     pub(crate) init: Option<ExprNode>,
+    pub(crate) doc: Option<DocComment>,
 }
 
 impl Dump for Entry {
@@ -133,9 +135,10 @@ impl<'a, L: TLexer> EntryParser<'a, L> {
         Ok((
             tok,
             Some(Entry {
-                name: id,
+                name: id.to_string(),
                 attributes,
                 init,
+                doc: None,
             }),
         ))
     }
@@ -151,17 +154,32 @@ impl<'a, L: TLexer> EntriesParser<'a, L> {
     }
 
     fn parse(self, context: &mut Context) -> Result<(Option<Token>, Option<Entries>), ParserError> {
-        let mut entries = Vec::new();
+        let mut entries: Entries = Vec::new();
 
         loop {
+            // A trailing doc comment (///<) is just before the token following the entry
+            let tok = self.lexer.next_useful();
+            if let Some(doc) = DocComment::get_trailing(self.lexer) {
+                if let Some(entry) = entries.last_mut() {
+                    entry.doc = Some(doc);
+                }
+            }
+            let doc = DocComment::get_leading(self.lexer);
+
             let ep = EntryParser::new(self.lexer);
-            let (tok, entry) = ep.parse(None, context)?;
+            let (tok, entry) = ep.parse(Some(tok), context)?;
 
-            if let Some(entry) = entry {
+            if let Some(mut entry) = entry {
+                entry.doc = doc;
                 entries.push(entry);
             }
 
             let tok = tok.unwrap_or_else(|| self.lexer.next_useful());
+            if let Some(doc) = DocComment::get_trailing(self.lexer) {
+                if let Some(entry) = entries.last_mut() {
+                    entry.doc = Some(doc);
+                }
+            }
             match tok {
                 Token::Comma => continue,
                 Token::RightBrace => {
@@ -274,17 +292,20 @@ mod tests {
                     Entry {
                         name: "red".to_string(),
                         attributes: None,
-                        init: None
+                        init: None,
+                        doc: None,
                     },
                     Entry {
                         name: "green".to_string(),
                         attributes: None,
-                        init: None
+                        init: None,
+                        doc: None,
                     },
                     Entry {
                         name: "blue".to_string(),
                         attributes: None,
-                        init: None
+                        init: None,
+                        doc: None,
                     },
                 ]),
             }
@@ -309,17 +330,20 @@ mod tests {
                     Entry {
                         name: "red".to_string(),
                         attributes: None,
-                        init: None
+                        init: None,
+                        doc: None,
                     },
                     Entry {
                         name: "green".to_string(),
                         attributes: None,
-                        init: None
+                        init: None,
+                        doc: None,
                     },
                     Entry {
                         name: "blue".to_string(),
                         attributes: None,
-                        init: None
+                        init: None,
+                        doc: None,
                     },
                 ]),
             }
@@ -349,17 +373,20 @@ mod tests {
                     Entry {
                         name: "red".to_string(),
                         attributes: None,
-                        init: None
+                        init: None,
+                        doc: None,
                     },
                     Entry {
                         name: "green".to_string(),
                         attributes: None,
-                        init: None
+                        init: None,
+                        doc: None,
                     },
                     Entry {
                         name: "blue".to_string(),
                         attributes: None,
-                        init: None
+                        init: None,
+                        doc: None,
                     },
                 ]),
             }
@@ -414,8 +441,9 @@ mod tests {
                         name: "red".to_string(),
                         attributes: None,
                         init: Some(ExprNode::Integer(Box::new(Integer {
-                            value: IntLiteral::Int(0)
-                        })))
+                            value: IntLiteral::new("0").unwrap()
+                        }))),
+                        doc: None,
                     },
                     Entry {
                         name: "green".to_string(),
@@ -426,15 +454,17 @@ mod tests {
                             has_using: false,
                         }]),
                         init: Some(ExprNode::Integer(Box::new(Integer {
-                            value: IntLiteral::Int(2)
-                        })))
+                            value: IntLiteral::new("2").unwrap()
+                        }))),
+                        doc: None,
                     },
                     Entry {
                         name: "blue".to_string(),
                         attributes: None,
                         init: Some(ExprNode::Integer(Box::new(Integer {
-                            value: IntLiteral::Int(0xFF00)
-                        })))
+                            value: IntLiteral::new("0xFF00").unwrap()
+                        }))),
+                        doc: None,
                     },
                 ],),
             }
@@ -459,17 +489,20 @@ mod tests {
                     Entry {
                         name: "red".to_string(),
                         attributes: None,
-                        init: None
+                        init: None,
+                        doc: None,
                     },
                     Entry {
                         name: "green".to_string(),
                         attributes: None,
-                        init: None
+                        init: None,
+                        doc: None,
                     },
                     Entry {
                         name: "blue".to_string(),
                         attributes: None,
-                        init: None
+                        init: None,
+                        doc: None,
                     },
                 ]),
             }
@@ -504,17 +537,20 @@ mod tests {
                     Entry {
                         name: "red".to_string(),
                         attributes: None,
-                        init: None
+                        init: None,
+                        doc: None,
                     },
                     Entry {
                         name: "green".to_string(),
                         attributes: None,
-                        init: None
+                        init: None,
+                        doc: None,
                     },
                     Entry {
                         name: "blue".to_string(),
                         attributes: None,
-                        init: None
+                        init: None,
+                        doc: None,
                     },
                 ]),
             }
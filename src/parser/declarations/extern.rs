@@ -61,7 +61,7 @@ impl<'a, L: TLexer> ExternParser<'a, L> {
                     Ok((
                         None,
                         Some(Declaration::Extern(Extern {
-                            language,
+                            language: language.to_string(),
                             decls: list.unwrap(),
                             multiple: true,
                         })),
@@ -76,7 +76,7 @@ impl<'a, L: TLexer> ExternParser<'a, L> {
                 Ok((
                     tok,
                     Some(Declaration::Extern(Extern {
-                        language,
+                        language: language.to_string(),
                         decls: list.unwrap(),
                         multiple: false,
                     })),
@@ -149,7 +149,9 @@ extern "C" {
                                     },
                                     init: None,
                                     bitfield_size: None,
+                                    doc: RefCell::new(None),
                                 }),
+                                doc: None,
                             }],
                             cv: CVQualifier::empty(),
                             refq: RefQualifier::None,
@@ -172,6 +174,7 @@ extern "C" {
                     },
                     init: None,
                     bitfield_size: None,
+                    doc: RefCell::new(None),
                 }))],
                 multiple: true,
             })
@@ -216,7 +219,9 @@ extern double sqrt(double);
                                 },
                                 init: None,
                                 bitfield_size: None,
+                                doc: RefCell::new(None),
                             }),
+                            doc: None,
                         }],
                         cv: CVQualifier::empty(),
                         refq: RefQualifier::None,
@@ -239,6 +244,7 @@ extern double sqrt(double);
                 },
                 init: None,
                 bitfield_size: None,
+                doc: RefCell::new(None),
             }))
         );
     }
@@ -14,6 +14,7 @@ use super::types::{Identifier, TypeDeclarator, TypeDeclaratorParser};
 use crate::lexer::extra::SavedLexer;
 use crate::lexer::{TLexer, Token};
 use crate::parser::attributes::{Attributes, AttributesParser};
+use crate::parser::doc::DocComment;
 use crate::parser::dump::Dump;
 use crate::parser::errors::ParserError;
 use crate::parser::expressions::{ExprNode, ExpressionParser, Parameters, ParametersParser};
@@ -26,6 +27,7 @@ use crate::parser::{Context, ScopeKind, TypeToFix};
 pub struct Parameter {
     pub(crate) attributes: Option<Attributes>,
     pub(crate) decl: Rc<TypeDeclarator>,
+    pub(crate) doc: Option<DocComment>,
 }
 
 impl Dump for Parameter {
@@ -285,11 +287,20 @@ impl<'a, L: TLexer> ParameterListParser<'a, L> {
             None
         };
 
-        let mut params = Vec::new();
+        let mut params: Vec<Parameter> = Vec::new();
 
         loop {
+            // A trailing doc comment (///<) is just before the token following the parameter
+            let tk = tok.unwrap_or_else(|| self.lexer.next_useful());
+            if let Some(doc) = DocComment::get_trailing(self.lexer) {
+                if let Some(param) = params.last_mut() {
+                    param.doc = Some(doc);
+                }
+            }
+            let doc = DocComment::get_leading(self.lexer);
+
             let ap = AttributesParser::new(self.lexer);
-            let (tk, attributes) = ap.parse(tok, context)?;
+            let (tk, attributes) = ap.parse(Some(tk), context)?;
 
             let dp = TypeDeclaratorParser::new(self.lexer);
             let (tk, decl) = dp.parse(tk, None, true, context)?;
@@ -300,12 +311,21 @@ impl<'a, L: TLexer> ParameterListParser<'a, L> {
             };
 
             let tk = tk.unwrap_or_else(|| self.lexer.next_useful());
+            let doc = DocComment::get_trailing(self.lexer).or(doc);
             match tk {
                 Token::Comma => {
-                    params.push(Parameter { attributes, decl });
+                    params.push(Parameter {
+                        attributes,
+                        decl,
+                        doc,
+                    });
                 }
                 Token::RightParen => {
-                    params.push(Parameter { attributes, decl });
+                    params.push(Parameter {
+                        attributes,
+                        decl,
+                        doc,
+                    });
                     return Ok((None, Some(params)));
                 }
                 _ => {
@@ -404,7 +424,9 @@ impl<'a, L: TLexer> FunctionParser<'a, L> {
             match tok {
                 Token::Default => (self.lexer.next_useful(), FunStatus::Default),
                 Token::Delete => (self.lexer.next_useful(), FunStatus::Delete),
-                Token::LiteralInt(0) => (self.lexer.next_useful(), FunStatus::Pure),
+                Token::LiteralInt(ref x) if x.spelling == "0" => {
+                    (self.lexer.next_useful(), FunStatus::Pure)
+                }
                 _ => {
                     return Err(ParserError::InvalidTokenInFuncDecl {
                         sp: self.lexer.span(),
@@ -575,6 +597,7 @@ impl<'a, L: TLexer> ConvOperatorDeclaratorParser<'a, L> {
                     },
                     init: None,
                     bitfield_size: None,
+                    doc: RefCell::new(None),
                 }),
                 to_fix,
                 saved,
@@ -10,6 +10,7 @@ use super::bitfield::BitFieldDeclaratorParser;
 use super::{StaticAssert, StaticAssertParser, UsingAlias, UsingDecl, UsingEnum, UsingParser};
 use crate::lexer::{TLexer, Token};
 use crate::parser::declarations::{Declaration, TypeDeclarator, TypeDeclaratorParser};
+use crate::parser::doc::DocComment;
 use crate::parser::dump::Dump;
 use crate::parser::errors::ParserError;
 use crate::parser::Context;
@@ -31,6 +32,11 @@ impl Member {
             _ => true,
         }
     }
+    pub(crate) fn set_doc(&mut self, doc: DocComment) {
+        if let Self::Type(d) = self {
+            d.doc.replace(Some(doc));
+        }
+    }
 }
 
 impl Dump for Member {
@@ -191,6 +197,8 @@ impl<'a, L: TLexer> PPPParser<'a, L> {
 #[cfg(test)]
 mod tests {
 
+    use std::cell::RefCell;
+
     use super::*;
     use crate::lexer::{preprocessor::context::DefaultContext, Lexer};
     use crate::parser::declarations::{Identifier, Specifier};
@@ -245,8 +253,9 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: Some(ExprNode::Integer(Box::new(Integer {
-                    value: IntLiteral::Int(4)
+                    value: IntLiteral::new("4").unwrap()
                 }))),
+                doc: RefCell::new(None),
             }))
         );
     }
@@ -278,11 +287,12 @@ mod tests {
                     attributes: None
                 },
                 init: Some(Initializer::Equal(ExprNode::Integer(Box::new(Integer {
-                    value: IntLiteral::Int(1)
+                    value: IntLiteral::new("1").unwrap()
                 })))),
                 bitfield_size: Some(ExprNode::Integer(Box::new(Integer {
-                    value: IntLiteral::Int(4)
+                    value: IntLiteral::new("4").unwrap()
                 }))),
+                doc: RefCell::new(None),
             }))
         );
     }
@@ -315,12 +325,13 @@ mod tests {
                 },
                 init: Some(Initializer::Brace(vec![ExprNode::Integer(Box::new(
                     Integer {
-                        value: IntLiteral::Int(1)
+                        value: IntLiteral::new("1").unwrap()
                     }
                 )),])),
                 bitfield_size: Some(ExprNode::Integer(Box::new(Integer {
-                    value: IntLiteral::Int(4)
+                    value: IntLiteral::new("4").unwrap()
                 }))),
+                doc: RefCell::new(None),
             }))
         );
     }
@@ -10,6 +10,7 @@ use super::{
     DeclHint, Declaration, DeclarationListParser, Declarations, Specifier, TypeDeclaratorParser,
 };
 use crate::lexer::{TLexer, Token};
+use crate::parser::doc::DocComment;
 use crate::parser::dump::Dump;
 use crate::parser::errors::ParserError;
 use crate::parser::names::{Qualified, QualifiedParser};
@@ -45,6 +46,7 @@ impl Dump for NsNames {
 pub struct Namespace {
     pub name: NsNames,
     pub body: Declarations,
+    pub doc: Option<DocComment>,
 }
 
 impl Dump for Namespace {
@@ -94,7 +96,10 @@ impl<'a, L: TLexer> NsNamesParser<'a, L> {
                     inline = true;
                 }
                 Token::Identifier(id) => {
-                    names.push(NsName { inline, name: id });
+                    names.push(NsName {
+                        inline,
+                        name: id.to_string(),
+                    });
                 }
                 Token::ColonColon => {
                     inline = false;
@@ -169,6 +174,7 @@ impl<'a, L: TLexer> NamespaceParser<'a, L> {
                     let ns = Namespace {
                         name,
                         body: body.unwrap(),
+                        doc: None,
                     };
 
                     if name_len != 0 {
@@ -325,7 +331,9 @@ namespace A {
                             },
                             init: None,
                             bitfield_size: None,
+                            doc: RefCell::new(None),
                         }))],
+                        doc: None,
                     },),
                     Declaration::Type(Rc::new(TypeDeclarator {
                         typ: Type {
@@ -357,8 +365,10 @@ namespace A {
                         },
                         init: None,
                         bitfield_size: None,
+                        doc: RefCell::new(None),
                     }))
                 ],
+                doc: None,
             })
         );
     }
@@ -30,6 +30,13 @@ bitflags! {
         const STDCALL = 0b1_0000_0000_0000_0000;
         const THISCALL = 0b10_0000_0000_0000_0000;
         const VECTORCALL = 0b100_0000_0000_0000_0000;
+        const NORETURN = 0b1000_0000_0000_0000_0000;
+        const CUDA_GLOBAL = 0b1_0000_0000_0000_0000_0000;
+        const CUDA_DEVICE = 0b10_0000_0000_0000_0000_0000;
+        const CUDA_HOST = 0b100_0000_0000_0000_0000_0000;
+        const CUDA_SHARED = 0b1000_0000_0000_0000_0000_0000;
+        const CUDA_CONSTANT = 0b1_0000_0000_0000_0000_0000_0000;
+        const CUDA_MANAGED = 0b10_0000_0000_0000_0000_0000_0000;
     }
 }
 
@@ -75,7 +82,21 @@ impl ToString for Specifier {
             THISCALL,
             "__thiscall",
             VECTORCALL,
-            "__vectorcall"
+            "__vectorcall",
+            NORETURN,
+            "_Noreturn",
+            CUDA_GLOBAL,
+            "__global__",
+            CUDA_DEVICE,
+            "__device__",
+            CUDA_HOST,
+            "__host__",
+            CUDA_SHARED,
+            "__shared__",
+            CUDA_CONSTANT,
+            "__constant__",
+            CUDA_MANAGED,
+            "__managed__"
         )
     }
 }
@@ -165,6 +186,34 @@ impl Specifier {
                 *self |= Specifier::VECTORCALL;
                 true
             }
+            Token::Noreturn => {
+                *self |= Specifier::NORETURN;
+                true
+            }
+            Token::CudaGlobal => {
+                *self |= Specifier::CUDA_GLOBAL;
+                true
+            }
+            Token::CudaDevice => {
+                *self |= Specifier::CUDA_DEVICE;
+                true
+            }
+            Token::CudaHost => {
+                *self |= Specifier::CUDA_HOST;
+                true
+            }
+            Token::CudaShared => {
+                *self |= Specifier::CUDA_SHARED;
+                true
+            }
+            Token::CudaConstant => {
+                *self |= Specifier::CUDA_CONSTANT;
+                true
+            }
+            Token::CudaManaged => {
+                *self |= Specifier::CUDA_MANAGED;
+                true
+            }
             _ => false,
         }
     }
@@ -191,7 +240,14 @@ impl Specifier {
             | Token::Fastcall
             | Token::Stdcall
             | Token::Thiscall
-            | Token::Vectorcall => true,
+            | Token::Vectorcall
+            | Token::Noreturn
+            | Token::CudaGlobal
+            | Token::CudaDevice
+            | Token::CudaHost
+            | Token::CudaShared
+            | Token::CudaConstant
+            | Token::CudaManaged => true,
             _ => false,
         }
     }
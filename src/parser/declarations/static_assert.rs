@@ -98,19 +98,17 @@ impl<'a, L: TLexer> StaticAssertParser<'a, L> {
         }
 
         let tok = self.lexer.next_useful();
-        let string = tok.get_string();
+        let slp = StringLiteralParser::new(self.lexer);
+        let (tok, string) = slp.parse(tok, context)?;
 
         let string = if let Some(string) = string {
-            string
+            string.text().to_string()
         } else {
             return Err(ParserError::InvalidArgInStaticAssert {
                 sp: self.lexer.span(),
             });
         };
 
-        let slp = StringLiteralParser::new(self.lexer);
-        let (tok, string) = slp.parse(&string, context)?;
-
         let tok = tok.unwrap_or_else(|| self.lexer.next_useful());
         if tok != Token::RightParen {
             return Err(ParserError::InvalidTokenInStaticAssert {
@@ -3,6 +3,7 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use std::cell::RefCell;
 use std::rc::Rc;
 use termcolor::StandardStreamLock;
 
@@ -16,6 +17,7 @@ use crate::lexer::extra::SavedLexer;
 use crate::lexer::{TLexer, Token};
 use crate::parser::attributes::{Attributes, AttributesParser};
 use crate::parser::context::{Context, SearchResult, TypeToFix};
+use crate::parser::doc::DocComment;
 use crate::parser::dump::Dump;
 use crate::parser::errors::ParserError;
 use crate::parser::expressions::{ExprNode, ExpressionParser, VarDecl, Variable};
@@ -43,6 +45,8 @@ pub struct TypeDeclarator {
     pub identifier: Identifier,
     pub init: Option<Initializer>,
     pub bitfield_size: Option<ExprNode>,
+    /// Set when the declaration is in a list (so after the creation of the Rc)
+    pub doc: RefCell<Option<DocComment>>,
 }
 
 impl Dump for TypeDeclarator {
@@ -235,7 +239,7 @@ impl<'a, L: TLexer> DeclSpecifierParser<'a, L> {
                 let tk = tk.unwrap_or_else(|| self.lexer.next_useful());
                 if let Token::Identifier(id) = tk {
                     let qp = QualifiedParser::new(self.lexer);
-                    let (tk, name) = qp.parse(None, Some(id), context)?;
+                    let (tk, name) = qp.parse(None, Some(id.to_string()), context)?;
                     let name = name.unwrap();
                     if name.is_conv_op() {
                         return Ok((tk, (spec, None, Some(name), to_fix)));
@@ -374,6 +378,7 @@ impl<'a, L: TLexer> NoPtrDeclaratorParser<'a, L> {
                     identifier,
                     init: None,
                     bitfield_size: None,
+                    doc: RefCell::new(None),
                 }),
                 to_fix,
                 saved,
@@ -411,6 +416,7 @@ impl<'a, L: TLexer> NoPtrDeclaratorParser<'a, L> {
                 identifier,
                 init,
                 bitfield_size: None,
+                doc: RefCell::new(None),
             }),
             None,
             None,
@@ -491,6 +497,7 @@ impl<'a, L: TLexer> TypeDeclaratorParser<'a, L> {
                 identifier,
                 init: _,
                 bitfield_size: _,
+                doc: _,
             } = paren_decl;
             let Type {
                 base,
@@ -577,7 +584,7 @@ impl<'a, L: TLexer> DeclOrExprParser<'a, L> {
         let (typ, var, tok) = match tok {
             Token::Identifier(id) => {
                 let qp = QualifiedParser::new(self.lexer);
-                let (tok, name) = qp.parse(None, Some(id), context)?;
+                let (tok, name) = qp.parse(None, Some(id.to_string()), context)?;
 
                 if let Some(res) = context.search(name.as_ref()) {
                     match res {
@@ -725,7 +732,8 @@ mod tests {
 
     use super::super::function::*;
     use super::*;
-    use crate::lexer::{preprocessor::context::DefaultContext, Lexer};
+    use crate::args::Standard;
+    use crate::lexer::{preprocessor::context::DefaultContext, Dialect, Lexer, Symbol};
     use crate::mk_var;
     use crate::parser::array::*;
     use crate::parser::attributes::Attribute;
@@ -753,6 +761,7 @@ mod tests {
             },
             init: None,
             bitfield_size: None,
+            doc: RefCell::new(None),
         });
 
         context.add_type_decl(Rc::clone(&t));
@@ -832,6 +841,21 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_c11_specifiers() {
+        let mut l = Lexer::<DefaultContext>::new(b"_Noreturn static _Atomic _Bool class");
+        l.set_dialect(Dialect::from_standard(Standard::C11));
+        let p = DeclSpecifierParser::new(&mut l);
+        let mut context = Context::default();
+        let (tok, (spec, ty, _, _)) = p.parse(None, None, &mut context).unwrap();
+        let ty = ty.unwrap();
+
+        assert_eq!(spec, Specifier::NORETURN | Specifier::STATIC);
+        assert_eq!(ty.cv, CVQualifier::ATOMIC);
+        assert_eq!(ty.base, BaseType::Primitive(Primitive::Bool));
+        assert_eq!(tok, Some(Token::Identifier(Symbol::intern("class"))));
+    }
+
     #[test]
     fn test_volatile_primitive() {
         for (buf, res) in vec![
@@ -886,6 +910,7 @@ mod tests {
                 },
                 init: Some(Initializer::Equal(ExprNode::Nullptr(Box::new(Nullptr {})))),
                 bitfield_size: None,
+                doc: RefCell::new(None),
             })
         );
     }
@@ -918,6 +943,7 @@ mod tests {
                 },
                 init: Some(Initializer::Equal(ExprNode::Nullptr(Box::new(Nullptr {})))),
                 bitfield_size: None,
+                doc: RefCell::new(None),
             })
         );
     }
@@ -953,6 +979,7 @@ mod tests {
                 },
                 init: Some(Initializer::Equal(ExprNode::Nullptr(Box::new(Nullptr {})))),
                 bitfield_size: None,
+                doc: RefCell::new(None),
             })
         );
     }
@@ -980,10 +1007,11 @@ mod tests {
                 },
                 init: Some(Initializer::Brace(vec![ExprNode::Integer(Box::new(
                     literals::Integer {
-                        value: IntLiteral::Int(314)
+                        value: IntLiteral::new("314").unwrap()
                     }
                 )),])),
                 bitfield_size: None,
+                doc: RefCell::new(None),
             })
         );
     }
@@ -1018,6 +1046,7 @@ mod tests {
                     "NULL"
                 ))))),
                 bitfield_size: None,
+                doc: RefCell::new(None),
             })
         );
     }
@@ -1058,6 +1087,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: RefCell::new(None),
             })
         );
     }
@@ -1104,6 +1134,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: RefCell::new(None),
             })
         );
     }
@@ -1153,6 +1184,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: RefCell::new(None),
             })
         );
     }
@@ -1185,6 +1217,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: RefCell::new(None),
             })
         );
     }
@@ -1217,6 +1250,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: RefCell::new(None),
             })
         );
     }
@@ -1249,6 +1283,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: RefCell::new(None),
             })
         );
     }
@@ -1289,6 +1324,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: RefCell::new(None),
             })
         );
     }
@@ -1315,7 +1351,7 @@ mod tests {
                                 }),
                                 dimensions: vec![Dimension {
                                     size: Some(ExprNode::Integer(Box::new(literals::Integer {
-                                        value: IntLiteral::Int(3)
+                                        value: IntLiteral::new("3").unwrap()
                                     }))),
                                     attributes: None,
                                 }],
@@ -1330,7 +1366,7 @@ mod tests {
                         }),
                         dimensions: vec![Dimension {
                             size: Some(ExprNode::Integer(Box::new(literals::Integer {
-                                value: IntLiteral::Int(2)
+                                value: IntLiteral::new("2").unwrap()
                             }))),
                             attributes: None,
                         }],
@@ -1345,6 +1381,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: RefCell::new(None),
             })
         );
     }
@@ -1394,6 +1431,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: RefCell::new(None),
             })
         );
     }
@@ -1431,7 +1469,9 @@ mod tests {
                                 },
                                 init: None,
                                 bitfield_size: None,
+                                doc: RefCell::new(None),
                             }),
+                            doc: None,
                         }],
                         cv: CVQualifier::empty(),
                         refq: RefQualifier::None,
@@ -1454,6 +1494,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: RefCell::new(None),
             })
         );
     }
@@ -1491,7 +1532,9 @@ mod tests {
                                 },
                                 init: None,
                                 bitfield_size: None,
+                                doc: RefCell::new(None),
                             }),
+                            doc: None,
                         }],
                         cv: CVQualifier::empty(),
                         refq: RefQualifier::None,
@@ -1527,6 +1570,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: RefCell::new(None),
             })
         );
     }
@@ -1577,7 +1621,9 @@ mod tests {
                                 },
                                 init: None,
                                 bitfield_size: None,
+                                doc: RefCell::new(None),
                             }),
+                            doc: None,
                         }],
                         cv: CVQualifier::empty(),
                         refq: RefQualifier::None,
@@ -1600,6 +1646,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: RefCell::new(None),
             })
         );
     }
@@ -1638,7 +1685,9 @@ mod tests {
                                     },
                                     init: None,
                                     bitfield_size: None,
+                                    doc: RefCell::new(None),
                                 }),
+                                doc: None,
                             },
                             Parameter {
                                 attributes: None,
@@ -1660,7 +1709,9 @@ mod tests {
                                     },
                                     init: None,
                                     bitfield_size: None,
+                                    doc: RefCell::new(None),
                                 }),
+                                doc: None,
                             }
                         ],
                         cv: CVQualifier::empty(),
@@ -1684,6 +1735,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: RefCell::new(None),
             })
         );
     }
@@ -1721,11 +1773,13 @@ mod tests {
                                 },
                                 init: Some(Initializer::Equal(ExprNode::Integer(Box::new(
                                     literals::Integer {
-                                        value: IntLiteral::Int(123)
+                                        value: IntLiteral::new("123").unwrap()
                                     }
                                 )))),
                                 bitfield_size: None,
+                                doc: RefCell::new(None),
                             }),
+                            doc: None,
                         }],
                         cv: CVQualifier::empty(),
                         refq: RefQualifier::None,
@@ -1748,6 +1802,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: RefCell::new(None),
             })
         );
     }
@@ -1792,11 +1847,13 @@ mod tests {
                                 },
                                 init: Some(Initializer::Equal(ExprNode::Integer(Box::new(
                                     literals::Integer {
-                                        value: IntLiteral::Int(123)
+                                        value: IntLiteral::new("123").unwrap()
                                     }
                                 )))),
                                 bitfield_size: None,
+                                doc: RefCell::new(None),
                             }),
+                            doc: None,
                         }],
                         cv: CVQualifier::CONST,
                         refq: RefQualifier::RValue,
@@ -1834,6 +1891,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: RefCell::new(None),
             })
         );
     }
@@ -1858,7 +1916,7 @@ mod tests {
                         }),
                         dimensions: vec![Dimension {
                             size: Some(ExprNode::Integer(Box::new(literals::Integer {
-                                value: IntLiteral::Int(123)
+                                value: IntLiteral::new("123").unwrap()
                             }))),
                             attributes: None,
                         }],
@@ -1873,6 +1931,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: RefCell::new(None),
             })
         );
     }
@@ -1910,6 +1969,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: RefCell::new(None),
             })
         );
     }
@@ -1947,13 +2007,14 @@ mod tests {
                 },
                 init: Some(Initializer::Equal(ExprNode::ListInit(Box::new(vec![
                     ExprNode::Integer(Box::new(literals::Integer {
-                        value: IntLiteral::Int(1)
+                        value: IntLiteral::new("1").unwrap()
                     })),
                     ExprNode::Integer(Box::new(literals::Integer {
-                        value: IntLiteral::Int(2)
+                        value: IntLiteral::new("2").unwrap()
                     })),
                 ])))),
                 bitfield_size: None,
+                doc: RefCell::new(None),
             })
         );
     }
@@ -1978,7 +2039,8 @@ mod tests {
                         entries: Some(vec![Entry {
                             name: "a".to_string(),
                             attributes: None,
-                            init: None
+                            init: None,
+                            doc: None,
                         },]),
                     })),
                     cv: CVQualifier::empty(),
@@ -1991,6 +2053,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: RefCell::new(None),
             })
         );
     }
@@ -2027,6 +2090,7 @@ mod tests {
                                 },
                                 init: None,
                                 bitfield_size: None,
+                                doc: RefCell::new(None),
                             })),],
                             protected: vec![],
                             private: vec![],
@@ -2042,6 +2106,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: RefCell::new(None),
             })
         );
     }
@@ -2098,6 +2163,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: RefCell::new(None),
             })
         );
     }
@@ -2149,6 +2215,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: RefCell::new(None),
             })
         );
     }
@@ -2203,6 +2270,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: RefCell::new(None),
             })
         );
     }
@@ -2243,7 +2311,7 @@ mod tests {
                     }),
                 }),
                 arg2: node!(Integer {
-                    value: IntLiteral::Int(7)
+                    value: IntLiteral::new("7").unwrap()
                 }),
             }))
         );
@@ -2319,7 +2387,9 @@ mod tests {
                                 },
                                 init: None,
                                 bitfield_size: None,
+                                doc: RefCell::new(None),
                             }),
+                            doc: None,
                         }],
                         cv: CVQualifier::empty(),
                         refq: RefQualifier::None,
@@ -2347,6 +2417,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: RefCell::new(None),
             }))
         );
     }
@@ -0,0 +1,277 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use crate::lexer::TLexer;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DocParam {
+    pub name: String,
+    pub text: String,
+}
+
+/// A Doxygen/Javadoc comment attached to a declaration
+#[derive(Clone, Debug, PartialEq)]
+pub struct DocComment {
+    /// The comment as written in the source (markers included)
+    pub raw: String,
+    pub brief: String,
+    pub params: Vec<DocParam>,
+    pub returns: Option<String>,
+    pub deprecated: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Section {
+    Brief,
+    Param,
+    Return,
+    Deprecated,
+    Other,
+}
+
+fn append(s: &mut String, text: &str) {
+    if !text.is_empty() {
+        if !s.is_empty() {
+            s.push(' ');
+        }
+        s.push_str(text);
+    }
+}
+
+/// Remove the comment markers from a line
+fn get_text(line: &str) -> &str {
+    let line = line.trim();
+    let line = ["///<", "//!<", "///", "//!", "/**<", "/*!<", "/**", "/*!"]
+        .iter()
+        .find_map(|p| line.strip_prefix(p))
+        .unwrap_or(line);
+    let line = line.strip_suffix("*/").unwrap_or(line).trim();
+    line.strip_prefix('*').unwrap_or(line).trim()
+}
+
+impl DocComment {
+    pub fn new(raw: String) -> Self {
+        let mut doc = Self {
+            raw: String::new(),
+            brief: String::new(),
+            params: Vec::new(),
+            returns: None,
+            deprecated: None,
+        };
+        let mut current = None;
+
+        for line in raw.lines().map(get_text) {
+            if line.is_empty() {
+                // End of paragraph
+                current = None;
+                continue;
+            }
+
+            if let Some(cmd) = line.strip_prefix('@').or_else(|| line.strip_prefix('\\')) {
+                let (cmd, text) = cmd.split_at(cmd.find(char::is_whitespace).unwrap_or(cmd.len()));
+                let text = text.trim();
+                let (section, text) = match cmd {
+                    "brief" | "short" => {
+                        doc.brief.clear();
+                        (Section::Brief, text)
+                    }
+                    "param" | "param[in]" | "param[out]" | "param[in,out]" => {
+                        let (name, text) =
+                            text.split_at(text.find(char::is_whitespace).unwrap_or(text.len()));
+                        doc.params.push(DocParam {
+                            name: name.to_string(),
+                            text: String::new(),
+                        });
+                        (Section::Param, text.trim())
+                    }
+                    "return" | "returns" | "result" => {
+                        doc.returns = Some(String::new());
+                        (Section::Return, text)
+                    }
+                    "deprecated" => {
+                        doc.deprecated = Some(String::new());
+                        (Section::Deprecated, text)
+                    }
+                    _ => (Section::Other, text),
+                };
+                current = Some(section);
+                doc.append(section, text);
+            } else {
+                let section = current.unwrap_or(if doc.brief.is_empty() {
+                    Section::Brief
+                } else {
+                    Section::Other
+                });
+                current = Some(section);
+                doc.append(section, line);
+            }
+        }
+
+        doc.raw = raw;
+        doc
+    }
+
+    fn append(&mut self, section: Section, text: &str) {
+        match section {
+            Section::Brief => append(&mut self.brief, text),
+            Section::Param => append(&mut self.params.last_mut().unwrap().text, text),
+            Section::Return => append(self.returns.as_mut().unwrap(), text),
+            Section::Deprecated => append(self.deprecated.as_mut().unwrap(), text),
+            Section::Other => {}
+        }
+    }
+
+    /// Get the doc comment before the last token read by the lexer
+    pub(crate) fn get_leading<L: TLexer>(lexer: &mut L) -> Option<Self> {
+        lexer.take_doc().map(Self::new)
+    }
+
+    /// Get the trailing doc comment (///< ...) of the declaration before the last token
+    pub(crate) fn get_trailing<L: TLexer>(lexer: &mut L) -> Option<Self> {
+        lexer.take_trailing_doc().map(Self::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::lexer::{preprocessor::context::DefaultContext, Lexer};
+    use crate::parser::declarations::{Declaration, DeclarationListParser, Member};
+    use crate::parser::types::BaseType;
+    use crate::parser::Context;
+    use pretty_assertions::assert_eq;
+
+    fn brief(doc: Option<&DocComment>) -> &str {
+        doc.map_or("", |d| d.brief.as_str())
+    }
+
+    #[test]
+    fn test_doc_comment() {
+        let doc = DocComment::new(
+            "/**\n * Compute the sum.\n * Second line.\n *\n * More details.\n * @param a the first\n *        operand\n * \\param[in] b the second\n * @return the sum\n * @deprecated use add\n */"
+                .to_string(),
+        );
+        assert_eq!(doc.brief, "Compute the sum. Second line.");
+        assert_eq!(
+            doc.params,
+            vec![
+                DocParam {
+                    name: "a".to_string(),
+                    text: "the first operand".to_string(),
+                },
+                DocParam {
+                    name: "b".to_string(),
+                    text: "the second".to_string(),
+                },
+            ]
+        );
+        assert_eq!(doc.returns.unwrap(), "the sum");
+        assert_eq!(doc.deprecated.unwrap(), "use add");
+
+        let doc = DocComment::new("/// Details\n/// @brief The brief\n///< x".to_string());
+        assert_eq!(doc.brief, "The brief x");
+        assert_eq!(doc.raw, "/// Details\n/// @brief The brief\n///< x");
+    }
+
+    #[test]
+    fn test_doc_attached() {
+        let mut l = Lexer::<DefaultContext>::new(
+            br#"
+/// The namespace
+namespace N {
+/**
+ * A class.
+ */
+struct A {
+    int x; ///< The x
+    /// The y
+    int y;
+};
+
+/// A function.
+/// @param a the a
+void f(int a, ///< The a
+       int b /**< The b */);
+
+/// Colors
+enum E {
+    /// Red
+    R,
+    G, ///< Green
+    B ///< Blue
+};
+}
+// Not a doc
+int z;
+"#,
+        );
+        let p = DeclarationListParser::new(&mut l);
+        let mut context = Context::default();
+        let (_, decls) = p.parse(None, &mut context).unwrap();
+        let decls = decls.unwrap();
+
+        let ns = match &decls[0] {
+            Declaration::Namespace(ns) => ns,
+            _ => panic!(),
+        };
+        assert_eq!(brief(ns.doc.as_ref()), "The namespace");
+
+        let get = |i: usize| match &ns.body[i] {
+            Declaration::Type(d) => d.clone(),
+            _ => panic!(),
+        };
+
+        let a = get(0);
+        assert_eq!(brief(a.doc.borrow().as_ref()), "A class.");
+        let members = match &a.typ.base {
+            BaseType::Class(c) => c.body.as_ref().unwrap().public.clone(),
+            _ => panic!(),
+        };
+        let docs: Vec<_> = members
+            .iter()
+            .map(|m| match m {
+                Member::Type(d) => brief(d.doc.borrow().as_ref()).to_string(),
+                _ => panic!(),
+            })
+            .collect();
+        assert_eq!(docs, vec!["The x", "The y"]);
+
+        let f = get(1);
+        {
+            let doc = f.doc.borrow();
+            assert_eq!(brief(doc.as_ref()), "A function.");
+            assert_eq!(doc.as_ref().unwrap().params[0].text, "the a");
+        }
+        let docs: Vec<_> = match &f.typ.base {
+            BaseType::Function(f) => f
+                .params
+                .iter()
+                .map(|p| brief(p.doc.as_ref()).to_string())
+                .collect(),
+            _ => panic!(),
+        };
+        assert_eq!(docs, vec!["The a", "The b"]);
+
+        let e = get(2);
+        assert_eq!(brief(e.doc.borrow().as_ref()), "Colors");
+        let docs: Vec<_> = match &e.typ.base {
+            BaseType::Enum(e) => e
+                .entries
+                .as_ref()
+                .unwrap()
+                .iter()
+                .map(|e| brief(e.doc.as_ref()).to_string())
+                .collect(),
+            _ => panic!(),
+        };
+        assert_eq!(docs, vec!["Red", "Green", "Blue"]);
+
+        match &decls[1] {
+            Declaration::Type(d) => assert!(d.doc.borrow().is_none()),
+            _ => panic!(),
+        }
+    }
+}
@@ -9,46 +9,175 @@ use crate::lexer::Token;
 #[derive(Clone, Debug)]
 pub enum ParserError {
     // TODO: rewrite that stuff
-    InvalidVarInDecl { sp: Span, name: String },
-    InvalidTokenInOp { sp: Span, tok: Token },
-    InvalidTokenInDtor { sp: Span, tok: Token },
-    InvalidTokenInArraySize { sp: Span, tok: Token },
-    InvalidTokenInAttrs { sp: Span, tok: Token },
-    InvalidTokenInAsm { sp: Span, tok: Token },
-    InvalidTokenInExtern { sp: Span, tok: Token },
-    InvalidTokenInParamList { sp: Span, tok: Token },
-    InvalidTokenInFuncDecl { sp: Span, tok: Token },
-    InvalidTokenInThrow { sp: Span, tok: Token },
-    InvalidTokenInClass { sp: Span, tok: Token },
-    InvalidTokenInNs { sp: Span, tok: Token },
-    InvalidTokenInGoto { sp: Span, tok: Token },
-    InvalidTokenInIf { sp: Span, tok: Token },
-    InvalidTokenInSwitch { sp: Span, tok: Token },
-    InvalidTokenInTry { sp: Span, tok: Token },
-    InvalidTokenInPointer { sp: Span, tok: Token },
-    InvalidTokenInStaticAssert { sp: Span, tok: Token },
-    InvalidTokenInUsingEnum { sp: Span, tok: Token },
-    InvalidTokenInEnum { sp: Span, tok: Token },
-    InvalidTokenInUsing { sp: Span, tok: Token },
-    InvalidTokenInAlias { sp: Span, tok: Token },
-    InvalidTokenInConditional { sp: Span, tok: Token },
-    InvalidTokenInDo { sp: Span, tok: Token },
-    InvalidTokenInStmt { sp: Span, tok: Token },
-    InvalidTokenInFor { sp: Span, tok: Token },
-    InvalidTokenInWhile { sp: Span, tok: Token },
-    InvalidTokenInUnit { sp: Span, tok: Token },
-    InvalidTokenInExpr { sp: Span, tok: Token },
-    InvalidExprInFor { sp: Span },
-    InvalidTypeInOp { sp: Span, name: String },
-    InvalidTypeInExpr { sp: Span, name: String },
-    UnknownId { sp: Span, name: String },
-    InvalidArgInStaticAssert { sp: Span },
-    UnbalancedAttr { sp: Span, tok: Token },
-    UnexpectedEof { sp: Span },
-    InvalidBitfieldSize { sp: Span },
-    InvalidCtorInit { sp: Span },
-    InvalidCast { sp: Span },
-    InvalidDeclOrExpr { sp: Span },
+    InvalidVarInDecl {
+        sp: Span,
+        name: String,
+    },
+    InvalidTokenInOp {
+        sp: Span,
+        tok: Token,
+    },
+    InvalidTokenInDtor {
+        sp: Span,
+        tok: Token,
+    },
+    InvalidTokenInArraySize {
+        sp: Span,
+        tok: Token,
+    },
+    InvalidTokenInAttrs {
+        sp: Span,
+        tok: Token,
+    },
+    InvalidTokenInAsm {
+        sp: Span,
+        tok: Token,
+    },
+    InvalidTokenInExtern {
+        sp: Span,
+        tok: Token,
+    },
+    InvalidTokenInParamList {
+        sp: Span,
+        tok: Token,
+    },
+    InvalidTokenInFuncDecl {
+        sp: Span,
+        tok: Token,
+    },
+    InvalidTokenInThrow {
+        sp: Span,
+        tok: Token,
+    },
+    InvalidTokenInClass {
+        sp: Span,
+        tok: Token,
+    },
+    InvalidTokenInNs {
+        sp: Span,
+        tok: Token,
+    },
+    InvalidTokenInGoto {
+        sp: Span,
+        tok: Token,
+    },
+    InvalidTokenInIf {
+        sp: Span,
+        tok: Token,
+    },
+    InvalidTokenInSwitch {
+        sp: Span,
+        tok: Token,
+    },
+    InvalidTokenInTry {
+        sp: Span,
+        tok: Token,
+    },
+    InvalidTokenInPointer {
+        sp: Span,
+        tok: Token,
+    },
+    InvalidTokenInStaticAssert {
+        sp: Span,
+        tok: Token,
+    },
+    InvalidTokenInUsingEnum {
+        sp: Span,
+        tok: Token,
+    },
+    InvalidTokenInEnum {
+        sp: Span,
+        tok: Token,
+    },
+    InvalidTokenInUsing {
+        sp: Span,
+        tok: Token,
+    },
+    InvalidTokenInAlias {
+        sp: Span,
+        tok: Token,
+    },
+    InvalidTokenInConditional {
+        sp: Span,
+        tok: Token,
+    },
+    InvalidTokenInDo {
+        sp: Span,
+        tok: Token,
+    },
+    InvalidTokenInStmt {
+        sp: Span,
+        tok: Token,
+    },
+    InvalidTokenInFor {
+        sp: Span,
+        tok: Token,
+    },
+    InvalidTokenInWhile {
+        sp: Span,
+        tok: Token,
+    },
+    InvalidTokenInUnit {
+        sp: Span,
+        tok: Token,
+    },
+    InvalidTokenInExpr {
+        sp: Span,
+        tok: Token,
+    },
+    InvalidExprInFor {
+        sp: Span,
+    },
+    InvalidTypeInOp {
+        sp: Span,
+        name: String,
+    },
+    InvalidTypeInExpr {
+        sp: Span,
+        name: String,
+    },
+    UnknownId {
+        sp: Span,
+        name: String,
+    },
+    InvalidArgInStaticAssert {
+        sp: Span,
+    },
+    UnbalancedAttr {
+        sp: Span,
+        tok: Token,
+    },
+    UnexpectedEof {
+        sp: Span,
+    },
+    InvalidBitfieldSize {
+        sp: Span,
+    },
+    InvalidCtorInit {
+        sp: Span,
+    },
+    InvalidCast {
+        sp: Span,
+    },
+    InvalidDeclOrExpr {
+        sp: Span,
+    },
+    IncompatibleStrPrefix {
+        sp: Span,
+        prefix: String,
+    },
+    IncompatibleStrSuffix {
+        sp: Span,
+        suffix: String,
+    },
+    EscapeOutOfRange {
+        sp: Span,
+    },
+    InvalidEdit {
+        start: usize,
+        end: usize,
+        len: usize,
+    },
 }
 
 impl ParserError {
@@ -158,6 +287,19 @@ impl ParserError {
             InvalidArgInStaticAssert { sp } => (*sp, format!("Invalid argument in static_assert")),
             InvalidCast { sp } => (*sp, format!("Invalid cast")),
             InvalidDeclOrExpr { sp } => (*sp, format!("Invalid declaration or expression")),
+            IncompatibleStrPrefix { sp, prefix } => (
+                *sp,
+                format!("Concatenation with an incompatible string {}\"\"", prefix),
+            ),
+            IncompatibleStrSuffix { sp, suffix } => (
+                *sp,
+                format!("Concatenation with a string with another suffix {}", suffix),
+            ),
+            EscapeOutOfRange { sp } => (*sp, "Escape sequence out of range in string".to_string()),
+            InvalidEdit { start, end, len } => (
+                Span::default(),
+                format!("Invalid edit {}..{} in a text of {} bytes", start, end, len),
+            ),
         };
         StringlyError { message, sp }
     }
@@ -17,10 +17,7 @@ use crate::parser::declarations::{
 };
 use crate::parser::dump::Dump;
 use crate::parser::errors::ParserError;
-use crate::parser::literals::{
-    Bool, Char, CharLiteral, Float, FloatLiteral, IntLiteral, Integer, Str, StrLiteral,
-    StringLiteralParser,
-};
+use crate::parser::literals::{Bool, Char, CharLiteral, Float, Integer, Str, StringLiteralParser};
 use crate::parser::names::{Qualified, QualifiedParser};
 use crate::parser::types::Type;
 
@@ -360,31 +357,6 @@ impl<'a, L: TLexer> ExpressionParser<'a, L> {
         tok: Option<Token>,
         context: &mut Context,
     ) -> Result<(Option<Token>, Option<ExprNode>), ParserError> {
-        macro_rules! str_literal {
-            ($s: expr, $name: ident) => {{
-                let slp = StringLiteralParser::new(self.lexer);
-                let (tk, x) = slp.parse(&$s, context)?;
-                self.operands.push(ExprNode::Str(Box::new(Str {
-                    value: StrLiteral::$name(x),
-                })));
-                self.last = LastKind::Operand;
-                tk.unwrap_or_else(|| self.lexer.next_useful())
-            }};
-        }
-
-        macro_rules! str_literal_ud {
-            ($x: expr, $name: ident) => {{
-                let slp = StringLiteralParser::new(self.lexer);
-                let (tk, mut s) = slp.parse(&$x.0, context)?;
-                std::mem::swap(&mut $x.0, &mut s);
-                self.operands.push(ExprNode::Str(Box::new(Str {
-                    value: StrLiteral::$name($x),
-                })));
-                self.last = LastKind::Operand;
-                tk.unwrap_or_else(|| self.lexer.next_useful())
-            }};
-        }
-
         let mut tok = tok.unwrap_or_else(|| self.lexer.next_useful());
 
         loop {
@@ -783,7 +755,7 @@ impl<'a, L: TLexer> ExpressionParser<'a, L> {
                     continue;
                 }
                 Token::Identifier(id) => {
-                    tok = self.handle_id(id, context)?;
+                    tok = self.handle_id(id.to_string(), context)?;
                     continue;
                 }
                 Token::LiteralChar(x) => {
@@ -816,174 +788,73 @@ impl<'a, L: TLexer> ExpressionParser<'a, L> {
                     })));
                     self.last = LastKind::Operand;
                 }
-                Token::LiteralCharUD(x) => {
+                Token::LiteralCharUD(c, suf) => {
                     self.operands.push(ExprNode::Char(Box::new(Char {
-                        value: CharLiteral::CharUD(x),
+                        value: CharLiteral::CharUD(Box::new((c, suf.to_string()))),
                     })));
                     self.last = LastKind::Operand;
                 }
-                Token::LiteralLCharUD(x) => {
+                Token::LiteralLCharUD(c, suf) => {
                     self.operands.push(ExprNode::Char(Box::new(Char {
-                        value: CharLiteral::LCharUD(x),
+                        value: CharLiteral::LCharUD(Box::new((c, suf.to_string()))),
                     })));
                     self.last = LastKind::Operand;
                 }
-                Token::LiteralUUCharUD(x) => {
+                Token::LiteralUUCharUD(c, suf) => {
                     self.operands.push(ExprNode::Char(Box::new(Char {
-                        value: CharLiteral::UUCharUD(x),
+                        value: CharLiteral::UUCharUD(Box::new((c, suf.to_string()))),
                     })));
                     self.last = LastKind::Operand;
                 }
-                Token::LiteralUCharUD(x) => {
+                Token::LiteralUCharUD(c, suf) => {
                     self.operands.push(ExprNode::Char(Box::new(Char {
-                        value: CharLiteral::UCharUD(x),
+                        value: CharLiteral::UCharUD(Box::new((c, suf.to_string()))),
                     })));
                     self.last = LastKind::Operand;
                 }
-                Token::LiteralU8CharUD(x) => {
+                Token::LiteralU8CharUD(c, suf) => {
                     self.operands.push(ExprNode::Char(Box::new(Char {
-                        value: CharLiteral::U8CharUD(x),
-                    })));
-                    self.last = LastKind::Operand;
-                }
-                Token::LiteralDouble(x) => {
-                    self.operands.push(ExprNode::Float(Box::new(Float {
-                        value: FloatLiteral::Double(x),
+                        value: CharLiteral::U8CharUD(Box::new((c, suf.to_string()))),
                     })));
                     self.last = LastKind::Operand;
                 }
                 Token::LiteralFloat(x) => {
                     self.operands.push(ExprNode::Float(Box::new(Float {
-                        value: FloatLiteral::Float(x),
-                    })));
-                    self.last = LastKind::Operand;
-                }
-                Token::LiteralFloatUD(x) => {
-                    self.operands.push(ExprNode::Float(Box::new(Float {
-                        value: FloatLiteral::FloatUD(x),
-                    })));
-                    self.last = LastKind::Operand;
-                }
-                Token::LiteralLongDouble(x) => {
-                    self.operands.push(ExprNode::Float(Box::new(Float {
-                        value: FloatLiteral::LongDouble(x),
+                        value: x.get().clone(),
                     })));
                     self.last = LastKind::Operand;
                 }
                 Token::LiteralInt(x) => {
                     self.operands.push(ExprNode::Integer(Box::new(Integer {
-                        value: IntLiteral::Int(x),
-                    })));
-                    self.last = LastKind::Operand;
-                }
-                Token::LiteralUInt(x) => {
-                    self.operands.push(ExprNode::Integer(Box::new(Integer {
-                        value: IntLiteral::UInt(x),
-                    })));
-                    self.last = LastKind::Operand;
-                }
-                Token::LiteralLong(x) => {
-                    self.operands.push(ExprNode::Integer(Box::new(Integer {
-                        value: IntLiteral::Long(x),
+                        value: x.get().clone(),
                     })));
                     self.last = LastKind::Operand;
                 }
-                Token::LiteralULong(x) => {
-                    self.operands.push(ExprNode::Integer(Box::new(Integer {
-                        value: IntLiteral::ULong(x),
-                    })));
+                Token::LiteralString(_)
+                | Token::LiteralLString(_)
+                | Token::LiteralUString(_)
+                | Token::LiteralUUString(_)
+                | Token::LiteralU8String(_)
+                | Token::LiteralRString(_)
+                | Token::LiteralLRString(_)
+                | Token::LiteralURString(_)
+                | Token::LiteralUURString(_)
+                | Token::LiteralU8RString(_)
+                | Token::LiteralStringUD(..)
+                | Token::LiteralLStringUD(..)
+                | Token::LiteralUStringUD(..)
+                | Token::LiteralUUStringUD(..)
+                | Token::LiteralU8StringUD(..)
+                | Token::LiteralRStringUD(..)
+                | Token::LiteralLRStringUD(..)
+                | Token::LiteralURStringUD(..)
+                | Token::LiteralUURStringUD(..)
+                | Token::LiteralU8RStringUD(..) => {
+                    let slp = StringLiteralParser::new(self.lexer);
+                    let (tk, s) = slp.parse(tok, context)?;
+                    self.operands.push(ExprNode::Str(Box::new(s.unwrap())));
                     self.last = LastKind::Operand;
-                }
-                Token::LiteralLongLong(x) => {
-                    self.operands.push(ExprNode::Integer(Box::new(Integer {
-                        value: IntLiteral::LongLong(x),
-                    })));
-                    self.last = LastKind::Operand;
-                }
-                Token::LiteralULongLong(x) => {
-                    self.operands.push(ExprNode::Integer(Box::new(Integer {
-                        value: IntLiteral::ULongLong(x),
-                    })));
-                    self.last = LastKind::Operand;
-                }
-                Token::LiteralString(x) => {
-                    tok = str_literal!(x, Str);
-                    continue;
-                }
-                Token::LiteralLString(x) => {
-                    tok = str_literal!(x, LStr);
-                    continue;
-                }
-                Token::LiteralUString(x) => {
-                    tok = str_literal!(x, UStr);
-                    continue;
-                }
-                Token::LiteralUUString(x) => {
-                    tok = str_literal!(x, UUStr);
-                    continue;
-                }
-                Token::LiteralU8String(x) => {
-                    tok = str_literal!(x, U8Str);
-                    continue;
-                }
-                Token::LiteralRString(x) => {
-                    tok = str_literal!(x, RStr);
-                    continue;
-                }
-                Token::LiteralLRString(x) => {
-                    tok = str_literal!(x, LRStr);
-                    continue;
-                }
-                Token::LiteralURString(x) => {
-                    tok = str_literal!(x, URStr);
-                    continue;
-                }
-                Token::LiteralUURString(x) => {
-                    tok = str_literal!(x, UURStr);
-                    continue;
-                }
-                Token::LiteralU8RString(x) => {
-                    tok = str_literal!(x, U8RStr);
-                    continue;
-                }
-                Token::LiteralStringUD(mut x) => {
-                    tok = str_literal_ud!(x, StrUD);
-                    continue;
-                }
-                Token::LiteralLStringUD(mut x) => {
-                    tok = str_literal_ud!(x, LStrUD);
-                    continue;
-                }
-                Token::LiteralUStringUD(mut x) => {
-                    tok = str_literal_ud!(x, UStrUD);
-                    continue;
-                }
-                Token::LiteralUUStringUD(mut x) => {
-                    tok = str_literal_ud!(x, UUStrUD);
-                    continue;
-                }
-                Token::LiteralU8StringUD(mut x) => {
-                    tok = str_literal_ud!(x, U8StrUD);
-                    continue;
-                }
-                Token::LiteralRStringUD(mut x) => {
-                    tok = str_literal_ud!(x, RStrUD);
-                    continue;
-                }
-                Token::LiteralLRStringUD(mut x) => {
-                    tok = str_literal_ud!(x, LRStrUD);
-                    continue;
-                }
-                Token::LiteralURStringUD(mut x) => {
-                    tok = str_literal_ud!(x, URStrUD);
-                    continue;
-                }
-                Token::LiteralUURStringUD(mut x) => {
-                    tok = str_literal_ud!(x, UURStrUD);
-                    continue;
-                }
-                Token::LiteralU8RStringUD(mut x) => {
-                    tok = str_literal_ud!(x, U8RStrUD);
+                    tok = tk.unwrap_or_else(|| self.lexer.next_useful());
                     continue;
                 }
                 Token::Nullptr => {
@@ -1044,9 +915,12 @@ impl<'a, L: TLexer> ExpressionParser<'a, L> {
 #[cfg(test)]
 mod tests {
 
+    use std::cell::RefCell;
+
     use super::*;
     use crate::lexer::{preprocessor::context::DefaultContext, Lexer};
     use crate::parser::declarations::{types::Identifier, MSModifier, Pointer, PtrKind, Specifier};
+    use crate::parser::literals::StrLiteral;
     use crate::parser::names::Qualified;
     use crate::parser::types::{BaseType, CVQualifier, Primitive, Type};
     use pretty_assertions::assert_eq;
@@ -1238,6 +1112,7 @@ mod tests {
             },
             init: None,
             bitfield_size: None,
+            doc: RefCell::new(None),
         });
         context.add_type_decl(Rc::clone(&x));
 
@@ -1461,7 +1336,7 @@ mod tests {
         let node = parser.parse(None, &mut context).unwrap().1.unwrap();
 
         let expected = node!(Str {
-            value: StrLiteral::Str("abcdefghijklmnopqrs".to_string())
+            value: StrLiteral::LStr("abcdefghijklmnopqrs".to_string())
         });
 
         assert_eq!(node, expected);
@@ -1477,7 +1352,7 @@ mod tests {
         let node = parser.parse(None, &mut context).unwrap().1.unwrap();
 
         let expected = node!(Str {
-            value: StrLiteral::StrUD(Box::new((
+            value: StrLiteral::LStrUD(Box::new((
                 "abcdefghijklmnopqrs".to_string(),
                 "_foo".to_string()
             ))),
@@ -179,7 +179,7 @@ impl<'a, L: TLexer> ExpressionParser<'a, L> {
         match tok {
             Token::Identifier(id) => {
                 let qp = QualifiedParser::new(self.lexer);
-                let (tok, qual) = qp.parse(None, Some(id), context)?;
+                let (tok, qual) = qp.parse(None, Some(id.to_string()), context)?;
                 let qual = qual.unwrap();
 
                 if let Some(res) = context.search(Some(&qual)) {
@@ -368,6 +368,7 @@ mod tests {
             },
             init: None,
             bitfield_size: None,
+            doc: RefCell::new(None),
         });
         context.add_type_decl(Rc::clone(&t));
 
@@ -425,7 +426,9 @@ mod tests {
                             },
                             init: None,
                             bitfield_size: None,
+                            doc: RefCell::new(None),
                         }),
+                        doc: None,
                     }],
                     cv: CVQualifier::empty(),
                     refq: RefQualifier::None,
@@ -470,6 +473,7 @@ mod tests {
             },
             init: None,
             bitfield_size: None,
+            doc: RefCell::new(None),
         });
         context.add_type_decl(Rc::clone(&t));
 
@@ -502,7 +506,9 @@ mod tests {
                             },
                             init: None,
                             bitfield_size: None,
+                            doc: RefCell::new(None),
                         }),
+                        doc: None,
                     }],
                     cv: CVQualifier::empty(),
                     refq: RefQualifier::None,
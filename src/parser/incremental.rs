@@ -0,0 +1,1023 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use hashbrown::HashSet;
+use std::cell::RefCell;
+#[cfg(test)]
+use std::rc::Rc;
+
+use super::context::{Context, Marks};
+use super::declarations::{Declaration, DeclarationListParser};
+use super::literals::WcharSize;
+use super::statements::Compound;
+use super::types::BaseType;
+use super::unit::Unit;
+use crate::errors::Span;
+use crate::lexer::preprocessor::context::PreprocContext;
+use crate::lexer::source::FileId;
+use crate::lexer::{Lexer, TLexer, Token};
+use crate::parser::errors::ParserError;
+
+/// Replace the bytes in start..end by text
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextEdit {
+    pub start: usize,
+    pub end: usize,
+    pub text: Vec<u8>,
+}
+
+/// A token as seen by the parser
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParsedToken {
+    pub tok: Token,
+    pub span: Span,
+    pub doc: Option<String>,
+    pub trailing_doc: Option<String>,
+    /// The token is in the text of the file (it doesn't come from a macro or an include)
+    pub in_file: bool,
+}
+
+impl ParsedToken {
+    fn same(&self, other: &Self) -> bool {
+        self.tok == other.tok && self.doc == other.doc && self.trailing_doc == other.trailing_doc
+    }
+}
+
+/// How the edits have been taken into account
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reparse {
+    Unchanged,
+    /// The whole file has been lexed and parsed again
+    Full,
+    /// Only the tokens around the edits have been lexed again and the damaged
+    /// declarations have been parsed again (with the following ones when they use
+    /// a name declared by the damaged ones)
+    Partial {
+        relexed: usize,
+        reparsed: usize,
+        reused: usize,
+    },
+}
+
+/// Keep the tokens got from a lexer
+struct Recorder<'l, 'a, PC: PreprocContext> {
+    lexer: &'l mut Lexer<'a, PC>,
+    file: FileId,
+    toks: Vec<ParsedToken>,
+    doc: Option<String>,
+    trailing: Option<String>,
+}
+
+impl<'l, 'a, PC: PreprocContext> Recorder<'l, 'a, PC> {
+    fn new(lexer: &'l mut Lexer<'a, PC>, file: FileId) -> Self {
+        Self {
+            lexer,
+            file,
+            toks: Vec::new(),
+            doc: None,
+            trailing: None,
+        }
+    }
+}
+
+impl<'l, 'a, PC: PreprocContext> TLexer for Recorder<'l, 'a, PC> {
+    fn next_useful(&mut self) -> Token {
+        if self.toks.last().is_some_and(|t| t.tok == Token::Eof) {
+            self.doc = None;
+            self.trailing = None;
+            return Token::Eof;
+        }
+
+        let tok = self.lexer.next_useful();
+        let span = self.lexer.span();
+        self.doc = self.lexer.take_doc();
+        self.trailing = self.lexer.take_trailing_doc();
+        self.toks.push(ParsedToken {
+            tok,
+            span,
+            doc: self.doc.clone(),
+            trailing_doc: self.trailing.clone(),
+            // Eof is at the end of the text of the file
+            in_file: tok == Token::Eof
+                || (!self.lexer.buf.preproc_use() && span.file == Some(self.file)),
+        });
+        tok
+    }
+
+    fn span(&self) -> Span {
+        self.toks.last().map_or_else(Span::default, |t| t.span)
+    }
+
+    fn take_doc(&mut self) -> Option<String> {
+        self.doc.take()
+    }
+
+    fn take_trailing_doc(&mut self) -> Option<String> {
+        self.trailing.take()
+    }
+}
+
+/// Give back some recorded tokens (with their doc comments)
+struct Replay<'t> {
+    toks: &'t [ParsedToken],
+    pos: usize,
+    doc: Option<String>,
+    trailing: Option<String>,
+}
+
+impl<'t> Replay<'t> {
+    fn new(toks: &'t [ParsedToken]) -> Self {
+        Self {
+            toks,
+            pos: 0,
+            doc: None,
+            trailing: None,
+        }
+    }
+}
+
+impl<'t> TLexer for Replay<'t> {
+    fn next_useful(&mut self) -> Token {
+        if let Some(t) = self.toks.get(self.pos) {
+            self.pos += 1;
+            self.doc = t.doc.clone();
+            self.trailing = t.trailing_doc.clone();
+            t.tok
+        } else {
+            Token::Eof
+        }
+    }
+
+    fn span(&self) -> Span {
+        let pos = self.pos.min(self.toks.len());
+        if pos == 0 {
+            Span::default()
+        } else {
+            self.toks[pos - 1].span
+        }
+    }
+
+    fn take_doc(&mut self) -> Option<String> {
+        self.doc.take()
+    }
+
+    fn take_trailing_doc(&mut self) -> Option<String> {
+        self.trailing.take()
+    }
+}
+
+struct Parsed<PC> {
+    /// All the tokens (Eof included)
+    tokens: Vec<ParsedToken>,
+    /// The index of the first token of each top-level declaration (and of Eof)
+    bounds: Vec<usize>,
+    /// The number of changes in the context before each top-level declaration (and Eof)
+    changes: Vec<usize>,
+    unit: Unit,
+    context: Context,
+    /// The preprocessor context at the end of the file
+    macros: PC,
+    /// The macros defined or undefined in the file
+    names: HashSet<String>,
+}
+
+/// Parse a file and keep what's needed to parse it again after some edits:
+/// the tokens before and after the edits are reused, the declarations around
+/// the edits too and the context is rolled back to its state before the first
+/// damaged declaration (the changes made by the following ones are replayed).
+///
+/// The edited part is lexed again without any preprocessor state so when it
+/// (or the declarations around it) contains a directive, a macro or some tokens
+/// from an include, the whole file is parsed again.
+pub struct IncrementalParser<PC: PreprocContext + Clone> {
+    source: Vec<u8>,
+    file: FileId,
+    preproc: PC,
+    parsed: Option<Parsed<PC>>,
+}
+
+impl<PC: PreprocContext + Clone> IncrementalParser<PC> {
+    pub fn new(source: Vec<u8>, file: FileId, preproc: PC) -> Self {
+        Self {
+            source,
+            file,
+            preproc,
+            parsed: None,
+        }
+    }
+
+    pub fn source(&self) -> &[u8] {
+        &self.source
+    }
+
+    /// The unit got from the last successful parse
+    pub fn unit(&self) -> Option<&Unit> {
+        self.parsed.as_ref().map(|p| &p.unit)
+    }
+
+    pub fn tokens(&self) -> &[ParsedToken] {
+        self.parsed.as_ref().map_or(&[], |p| &p.tokens)
+    }
+
+    pub fn context(&self) -> Option<&Context> {
+        self.parsed.as_ref().map(|p| &p.context)
+    }
+
+    /// Lex and parse the whole file
+    pub fn parse(&mut self) -> Result<Reparse, ParserError> {
+        self.parsed = None;
+        self.parsed = Some(Self::parse_all(
+            &self.source,
+            self.file,
+            self.preproc.clone(),
+        )?);
+        Ok(Reparse::Full)
+    }
+
+    /// Apply the edits (each one is in the text got after the previous ones) and parse again.
+    /// Nothing is changed if an edit is out of the text.
+    pub fn edit(&mut self, edits: &[TextEdit]) -> Result<Reparse, ParserError> {
+        let mut source = self.source.clone();
+        for edit in edits {
+            if edit.start > edit.end || edit.end > source.len() {
+                return Err(ParserError::InvalidEdit {
+                    start: edit.start,
+                    end: edit.end,
+                    len: source.len(),
+                });
+            }
+            source.splice(edit.start..edit.end, edit.text.iter().copied());
+        }
+
+        let old = std::mem::replace(&mut self.source, source);
+        if let Some(parsed) = self.parsed.take() {
+            if old == self.source {
+                self.parsed = Some(parsed);
+                return Ok(Reparse::Unchanged);
+            }
+            if let Some((parsed, reparse)) = self.update(&old, parsed) {
+                self.parsed = Some(parsed);
+                return Ok(reparse);
+            }
+        }
+        self.parse()
+    }
+
+    fn parse_all(source: &[u8], file: FileId, preproc: PC) -> Result<Parsed<PC>, ParserError> {
+        let mut lexer = Lexer::new_with_context(source, file, preproc);
+        let mut context = Context::default();
+        context.set_standard(lexer.get_standard());
+        context.set_wchar_size(WcharSize::from_dialect(lexer.get_dialect()));
+        context.track_changes();
+
+        let mut rec = Recorder::new(&mut lexer, file);
+        let dlp = DeclarationListParser::new(&mut rec).with_marks();
+        let (tok, decls) = dlp.parse(None, &mut context)?;
+
+        let tok = tok.unwrap_or_else(|| rec.next_useful());
+        if tok != Token::Eof {
+            return Err(ParserError::InvalidTokenInUnit {
+                sp: rec.span(),
+                tok,
+            });
+        }
+
+        let tokens = rec.toks;
+        let (bounds, changes) = get_bounds(&tokens, 0, context.take_marks());
+        Ok(Parsed {
+            tokens,
+            bounds,
+            changes,
+            unit: Unit {
+                decls: decls.unwrap(),
+            },
+            context,
+            macros: lexer.get_context().clone(),
+            names: macro_names(source),
+        })
+    }
+
+    /// Lex again the edited part and parse again the damaged declarations.
+    /// None is returned when the whole file must be parsed again.
+    fn update(&self, old: &[u8], mut parsed: Parsed<PC>) -> Option<(Parsed<PC>, Reparse)> {
+        let new = &self.source[..];
+
+        // The changed bytes are old[start..old_end] and new[start..new_end]
+        let start = old
+            .iter()
+            .zip(new.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let max_suffix = old.len().min(new.len()) - start;
+        let suffix = old
+            .iter()
+            .rev()
+            .zip(new.iter().rev())
+            .take(max_suffix)
+            .take_while(|(a, b)| a == b)
+            .count();
+        let old_end = old.len() - suffix;
+        let new_end = new.len() - suffix;
+        if start > 0 && old[start - 1] == b'\r' {
+            // A \r\n could have been split
+            return None;
+        }
+        let delta = new.len() as isize - old.len() as isize;
+        let lines =
+            count_lines(&new[start..new_end]) as i64 - count_lines(&old[start..old_end]) as i64;
+
+        let toks = &parsed.tokens;
+        let bounds = &parsed.bounds;
+        let n = bounds.len() - 1;
+        if n == 0 {
+            return None;
+        }
+
+        // A declaration owns the text between the tokens around it so an edit between
+        // two declarations damages both of them (for the doc comments)
+        let extent = |k: usize| {
+            let start = toks[..bounds[k]]
+                .iter()
+                .rev()
+                .find(|t| t.in_file)
+                .map_or(0, |t| t.span.end.pos);
+            let end = toks[bounds[k + 1]..]
+                .iter()
+                .find(|t| t.in_file)
+                .map_or(old.len(), |t| t.span.start.pos);
+            (start, end)
+        };
+        let first = (0..n).find(|&k| extent(k).1 >= start)?;
+        let last = (first..n)
+            .take_while(|&k| extent(k).0 <= old_end)
+            .last()
+            .unwrap_or(first);
+
+        // Start to lex on the first token of the damaged declaration (or at the beginning):
+        // it must be before the edit else the inserted text could have doc comments for it
+        let ti = bounds[first];
+        let (position, docs) = if toks[ti].in_file && toks[ti].span.start.pos < start {
+            (Some(toks[ti].span.start), Some(&toks[ti]))
+        } else if first == 0 {
+            (None, None)
+        } else {
+            return None;
+        };
+        let relex_start = position.map_or(0, |loc| loc.pos);
+
+        let mut lexer = Lexer::new_with_context(new, self.file, PC::default());
+        if let Some(loc) = position {
+            lexer.buf.reset_location(loc);
+        }
+        let mut rec = Recorder::new(&mut lexer, self.file);
+
+        // Lex until a token is the first one of an undamaged declaration
+        let mut next = last + 1;
+        let shift = |pos: usize| (pos as isize + delta) as usize;
+        let resync = loop {
+            let tok = rec.next_useful();
+            if tok == Token::Eof {
+                break None;
+            }
+            if rec.toks.len() == 1 {
+                // The doc comments before the first token haven't been seen
+                if let Some(docs) = docs {
+                    rec.toks[0].doc = docs.doc.clone();
+                    rec.toks[0].trailing_doc = docs.trailing_doc.clone();
+                }
+            }
+            let t = rec.toks.last().unwrap();
+            while next < n && shift(toks[bounds[next]].span.start.pos) < t.span.start.pos {
+                next += 1;
+            }
+            if next == n {
+                continue;
+            }
+            let o = &toks[bounds[next]];
+            if o.in_file
+                && o.same(t)
+                && shift(o.span.start.pos) == t.span.start.pos
+                && o.span.start.line as i64 + lines == t.span.start.line as i64
+                && o.span.start.column == t.span.start.column
+            {
+                break rec.toks.pop();
+            }
+        };
+        let relexed = rec.toks;
+        let end = if resync.is_some() {
+            bounds[next]
+        } else {
+            next = n;
+            toks.len()
+        };
+
+        // The relexed part must be the same for the preprocessor
+        let old_stop = toks.get(end).map_or(old.len(), |t| t.span.start.pos);
+        let new_stop = resync.as_ref().map_or(new.len(), |t| t.span.start.pos);
+        if has_directive(&old[relex_start..old_stop])
+            || has_directive(&new[relex_start..new_stop])
+            || toks[ti..end].iter().any(|t| !t.in_file)
+            || relexed.iter().any(|t| match t.tok {
+                Token::Identifier(id) => {
+                    parsed.names.contains(id.as_str()) || parsed.macros.get(&id).is_some()
+                }
+                _ => false,
+            })
+        {
+            return None;
+        }
+        // __LINE__ could have been expanded after the edit
+        if lines != 0
+            && toks[end..]
+                .iter()
+                .any(|t| !t.in_file && t.span.file == Some(self.file))
+        {
+            return None;
+        }
+
+        let mut suffix: Vec<ParsedToken> = toks[end..].to_vec();
+        for t in suffix.iter_mut().filter(|t| t.in_file) {
+            t.span.start.pos = shift(t.span.start.pos);
+            t.span.end.pos = shift(t.span.end.pos);
+            t.span.start.line = (t.span.start.line as i64 + lines) as u32;
+            t.span.end.line = (t.span.end.line as i64 + lines) as u32;
+        }
+
+        let n_relexed = relexed.len();
+        let tail = parsed.context.rollback(parsed.changes[first]);
+
+        // Only a function body has changed: the declaration is updated in place
+        // (the context and the following declarations keep a reference on it)
+        if next == first + 1 {
+            if let Some(body) = get_body(&parsed.unit.decls[first]) {
+                let mut list = relexed.clone();
+                if let Some(resync) = resync.as_ref() {
+                    list.push(ParsedToken {
+                        tok: Token::Eof,
+                        ..resync.clone()
+                    });
+                }
+                let decl_len = list.len() - 1;
+                if same_header(&toks[ti..bounds[next]], &list[..decl_len]) {
+                    let (decls, _) = parse_list(&list, &mut parsed.context)?;
+                    if decls.len() != 1 {
+                        return None;
+                    }
+                    *body.borrow_mut() = get_body(&decls[0])?.borrow().clone();
+                    parsed.context.rollback(parsed.changes[first]);
+                    parsed.context.replay(tail);
+
+                    let diff = n_relexed as isize - (end - ti) as isize;
+                    let mut tokens = parsed.tokens[..ti].to_vec();
+                    tokens.extend(relexed);
+                    tokens.extend(suffix);
+                    parsed.tokens = tokens;
+                    for b in parsed.bounds[first + 1..].iter_mut() {
+                        *b = (*b as isize + diff) as usize;
+                    }
+                    return Some((
+                        parsed,
+                        Reparse::Partial {
+                            relexed: n_relexed,
+                            reparsed: 1,
+                            reused: n - 1,
+                        },
+                    ));
+                }
+            }
+        }
+
+        let mut tokens = parsed.tokens[..ti].to_vec();
+        tokens.extend(relexed);
+        tokens.extend(suffix);
+
+        // Parse again the damaged declarations and reuse the following ones when none
+        // of them uses a name declared by the damaged ones (they'd keep a reference
+        // on the old declaration)
+        if next < n {
+            let mut list = tokens[ti..ti + n_relexed].to_vec();
+            list.push(ParsedToken {
+                tok: Token::Eof,
+                ..tokens[ti + n_relexed].clone()
+            });
+            let (decls, marks) = parse_list(&list, &mut parsed.context)?;
+            let mut old_changes = tail;
+            let rest = old_changes.split_off(parsed.changes[next] - parsed.changes[first]);
+            let new_changes = parsed.context.rollback(parsed.changes[first]);
+            let diff = n_relexed as isize - (end - ti) as isize;
+            let reused_start = (bounds[next] as isize + diff) as usize;
+            let names: Option<HashSet<&str>> = old_changes
+                .iter()
+                .chain(new_changes.iter())
+                .map(|c| c.name())
+                .collect();
+            let independent = names.is_some_and(|names| {
+                !tokens[reused_start..]
+                    .iter()
+                    .any(|t| matches!(t.tok, Token::Identifier(id) if names.contains(id.as_str())))
+            });
+
+            if independent {
+                parsed.context.replay(new_changes);
+                parsed.context.replay(rest);
+
+                let reparsed = decls.len();
+                let (new_bounds, new_changes) = get_bounds(&tokens, ti, marks);
+                let changes_diff = new_changes[reparsed] as isize - parsed.changes[next] as isize;
+                parsed
+                    .bounds
+                    .splice(first..next, new_bounds[..reparsed].iter().copied());
+                for b in parsed.bounds[first + reparsed..].iter_mut() {
+                    *b = (*b as isize + diff) as usize;
+                }
+                parsed
+                    .changes
+                    .splice(first..next, new_changes[..reparsed].iter().copied());
+                for c in parsed.changes[first + reparsed..].iter_mut() {
+                    *c = (*c as isize + changes_diff) as usize;
+                }
+                parsed.unit.decls.splice(first..next, decls);
+                parsed.tokens = tokens;
+
+                return Some((
+                    parsed,
+                    Reparse::Partial {
+                        relexed: n_relexed,
+                        reparsed,
+                        reused: n - (next - first),
+                    },
+                ));
+            }
+        }
+
+        // Parse again everything from the first damaged declaration
+        let (decls, marks) = parse_list(&tokens[ti..], &mut parsed.context)?;
+        let (bounds, changes) = get_bounds(&tokens, ti, marks);
+        parsed.bounds.truncate(first);
+        parsed.bounds.extend(bounds);
+        parsed.changes.truncate(first);
+        parsed.changes.extend(changes);
+        parsed.unit.decls.truncate(first);
+        let reparsed = decls.len();
+        parsed.unit.decls.extend(decls);
+        parsed.tokens = tokens;
+
+        Some((
+            parsed,
+            Reparse::Partial {
+                relexed: n_relexed,
+                reparsed,
+                reused: first,
+            },
+        ))
+    }
+}
+
+/// Parse the declarations in the tokens (which must end with Eof)
+fn parse_list(toks: &[ParsedToken], context: &mut Context) -> Option<(Vec<Declaration>, Marks)> {
+    let mut replay = Replay::new(toks);
+    let dlp = DeclarationListParser::new(&mut replay).with_marks();
+    let (tok, decls) = dlp.parse(None, context).ok()?;
+    let tok = tok.unwrap_or_else(|| replay.next_useful());
+    if tok != Token::Eof || replay.pos != toks.len() {
+        return None;
+    }
+    Some((decls.unwrap(), context.take_marks()))
+}
+
+/// Get the index of the first token of each marked declaration
+fn get_bounds(toks: &[ParsedToken], start: usize, marks: Marks) -> (Vec<usize>, Vec<usize>) {
+    let mut bounds = Vec::with_capacity(marks.len());
+    let mut changes = Vec::with_capacity(marks.len());
+    let mut i = start;
+    for (span, n) in marks {
+        i += toks[i..]
+            .iter()
+            .position(|t| t.span == span)
+            .unwrap_or(toks.len() - 1 - i);
+        bounds.push(i);
+        changes.push(n);
+        i += 1;
+    }
+    (bounds, changes)
+}
+
+fn get_body(decl: &Declaration) -> Option<&RefCell<Option<Compound>>> {
+    if let Declaration::Type(typ) = decl {
+        if let BaseType::Function(fun) = &typ.typ.base {
+            if fun.body.borrow().is_some() {
+                return Some(&fun.body);
+            }
+        }
+    }
+    None
+}
+
+/// Check that two function definitions are the same until the opening brace of their body
+fn same_header(old: &[ParsedToken], new: &[ParsedToken]) -> bool {
+    match (body_start(old), body_start(new)) {
+        (Some(o), Some(n)) => {
+            o == n
+                && old[..=o]
+                    .iter()
+                    .zip(new[..=n].iter())
+                    .all(|(a, b)| a.same(b))
+                && !old[..o].iter().any(|t| t.tok == Token::Try)
+        }
+        _ => false,
+    }
+}
+
+/// Get the index of the brace matching the final one
+fn body_start(toks: &[ParsedToken]) -> Option<usize> {
+    if toks.last()?.tok != Token::RightBrace {
+        return None;
+    }
+    let mut level = 0;
+    for (i, t) in toks.iter().enumerate().rev() {
+        match t.tok {
+            Token::RightBrace => level += 1,
+            Token::LeftBrace => {
+                level -= 1;
+                if level == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Count the newlines (\n, \r\n or \r)
+fn count_lines(text: &[u8]) -> usize {
+    memchr::memchr2_iter(b'\n', b'\r', text)
+        .filter(|&i| !(text[i] == b'\r' && text.get(i + 1) == Some(&b'\n')))
+        .count()
+}
+
+fn trim_start(line: &[u8]) -> &[u8] {
+    let n = line
+        .iter()
+        .take_while(|&&c| c == b' ' || c == b'\t')
+        .count();
+    &line[n..]
+}
+
+/// Check if a line in the text is a preprocessor directive
+fn has_directive(text: &[u8]) -> bool {
+    text.split(|&c| c == b'\n' || c == b'\r')
+        .any(|line| trim_start(line).first() == Some(&b'#'))
+}
+
+/// Get the names of the macros defined or undefined in the text
+fn macro_names(text: &[u8]) -> HashSet<String> {
+    text.split(|&c| c == b'\n' || c == b'\r')
+        .filter_map(|line| {
+            let line = trim_start(trim_start(line).strip_prefix(b"#")?);
+            let line = line
+                .strip_prefix(b"define")
+                .or_else(|| line.strip_prefix(b"undef"))?;
+            let line = trim_start(line);
+            let len = line
+                .iter()
+                .take_while(|c| c.is_ascii_alphanumeric() || **c == b'_')
+                .count();
+            Some(String::from_utf8_lossy(&line[..len]).into_owned())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::lexer::buffer::FileInfo;
+    use crate::lexer::preprocessor::context::{DefaultContext, PreprocContext};
+    use crate::lexer::preprocessor::macros::MacroObject;
+    use crate::parser::context::SearchResult;
+    use crate::parser::names::Qualified;
+    use pretty_assertions::assert_eq;
+
+    const SOURCE: &str = r#"
+/// A number
+typedef int T;
+
+/// Add two numbers
+T add(T a, T b) {
+    return a + b;
+}
+
+namespace N {
+    struct S {
+        int x;
+    };
+}
+
+int sub(int a, int b) {
+    int c = a - b;
+    return c;
+}
+
+N::S s; ///< An S
+"#;
+
+    fn new_parser(source: &str) -> IncrementalParser<DefaultContext> {
+        new_parser_with(source, DefaultContext::default())
+    }
+
+    fn new_parser_with(source: &str, preproc: DefaultContext) -> IncrementalParser<DefaultContext> {
+        let mut p = IncrementalParser::new(source.as_bytes().to_vec(), FileId(0), preproc);
+        assert_eq!(p.parse().unwrap(), Reparse::Full);
+        p
+    }
+
+    fn edit(p: &mut IncrementalParser<DefaultContext>, from: &str, to: &str) -> Reparse {
+        let source = String::from_utf8(p.source().to_vec()).unwrap();
+        let start = source.find(from).unwrap();
+        let reparse = p
+            .edit(&[TextEdit {
+                start,
+                end: start + from.len(),
+                text: to.as_bytes().to_vec(),
+            }])
+            .unwrap();
+
+        // Must be the same as a full parse
+        let full = new_parser_with(&source.replacen(from, to, 1), p.preproc.clone());
+        assert_eq!(p.source(), full.source());
+        assert_eq!(p.tokens(), full.tokens());
+        assert_eq!(p.unit(), full.unit());
+        reparse
+    }
+
+    fn is_type(p: &IncrementalParser<DefaultContext>, name: &str) -> Option<bool> {
+        let res = p.context().unwrap().search(Some(&mk_id!(name)))?;
+        Some(matches!(res, SearchResult::Type(_)))
+    }
+
+    #[test]
+    fn test_body() {
+        let mut p = new_parser(SOURCE);
+        let add = match &p.unit().unwrap().decls[1] {
+            Declaration::Type(add) => Rc::clone(add),
+            _ => unreachable!(),
+        };
+
+        let reparse = edit(&mut p, "a - b", "a - b * 2");
+        assert_eq!(
+            reparse,
+            Reparse::Partial {
+                relexed: 23,
+                reparsed: 1,
+                reused: 4
+            }
+        );
+        let reparse = edit(&mut p, "a + b", "b + a");
+        assert!(matches!(reparse, Reparse::Partial { reparsed: 1, .. }));
+
+        // The declaration has been updated in place
+        match &p.unit().unwrap().decls[1] {
+            Declaration::Type(x) => assert!(Rc::ptr_eq(x, &add)),
+            _ => unreachable!(),
+        }
+        assert_eq!(is_type(&p, "T"), Some(true));
+    }
+
+    #[test]
+    fn test_declarations() {
+        let mut p = new_parser(SOURCE);
+
+        // The following declarations don't use sub
+        let reparse = edit(&mut p, "int sub(int a, int b)", "int sub(int a, T b)");
+        assert_eq!(
+            reparse,
+            Reparse::Partial {
+                relexed: 21,
+                reparsed: 1,
+                reused: 4
+            }
+        );
+
+        // The following declarations use T so they must be parsed again
+        let reparse = edit(&mut p, "typedef int T;", "typedef int U;\ntypedef U T;");
+        assert!(matches!(reparse, Reparse::Partial { reused: 0, .. }));
+        assert_eq!(is_type(&p, "U"), Some(true));
+
+        let reparse = edit(&mut p, "typedef int U;\n", "");
+        assert!(matches!(reparse, Reparse::Partial { reused: 0, .. }));
+        assert_eq!(is_type(&p, "U"), None);
+
+        // A new declaration at the end
+        let reparse = edit(&mut p, "///< An S\n", "///< An S\nint z;\n");
+        assert!(matches!(reparse, Reparse::Partial { reused: 4, .. }));
+        assert_eq!(is_type(&p, "z"), Some(false));
+
+        // The function is removed from the context
+        edit(&mut p, "int sub(int a, T b) {", "int sub2(int a, T b) {");
+        assert_eq!(is_type(&p, "sub"), None);
+        assert_eq!(is_type(&p, "sub2"), Some(false));
+    }
+
+    #[test]
+    fn test_many_declarations() {
+        let source: String = (0..1000).map(|i| format!("int f{}(int x);\n", i)).collect();
+        let mut p = new_parser(&source);
+
+        let reparse = edit(&mut p, "f2(int x)", "f2(long x, int y)");
+        assert_eq!(
+            reparse,
+            Reparse::Partial {
+                relexed: 10,
+                reparsed: 1,
+                reused: 999
+            }
+        );
+        // A new declaration (the one after the edit is damaged too)
+        let reparse = edit(&mut p, "f3(int x);", "f3(int x); int g;");
+        assert_eq!(
+            reparse,
+            Reparse::Partial {
+                relexed: 17,
+                reparsed: 3,
+                reused: 998
+            }
+        );
+        assert_eq!(is_type(&p, "g"), Some(false));
+
+        // f5 is used by a following declaration so they must be parsed again
+        edit(&mut p, "f8(int x)", "f8(int x = f5(1))");
+        let reparse = edit(&mut p, "f5(int x)", "f5(long x)");
+        assert_eq!(
+            reparse,
+            Reparse::Partial {
+                relexed: 7,
+                reparsed: 995,
+                reused: 6
+            }
+        );
+    }
+
+    #[test]
+    fn test_docs() {
+        let mut p = new_parser(SOURCE);
+        edit(&mut p, "/// Add two numbers", "/// Add two integers");
+        edit(&mut p, "///< An S", "///< The S");
+        edit(&mut p, "/// A number\n", "");
+        edit(&mut p, "}\n\nnamespace", "}\n/// A namespace\nnamespace");
+    }
+
+    #[test]
+    fn test_namespace() {
+        let mut p = new_parser(SOURCE);
+        let reparse = edit(&mut p, "int x;", "int x;\n        int y;");
+        assert!(matches!(reparse, Reparse::Partial { reused: 2, .. }));
+        assert!(p
+            .context()
+            .unwrap()
+            .search(Some(&mk_id!("N", "S", "y")))
+            .is_some());
+
+        edit(&mut p, "namespace N {", "namespace M {");
+        assert!(p
+            .context()
+            .unwrap()
+            .search(Some(&mk_id!("N", "S")))
+            .is_none());
+    }
+
+    #[test]
+    fn test_full() {
+        let mut preproc = DefaultContext::default();
+        preproc.add_object(
+            "X".to_string(),
+            MacroObject::new(b"int".to_vec(), false, FileInfo::default()),
+        );
+        let mut p = new_parser_with("X x;\n\nint y;\n", preproc);
+
+        // The macro could have been defined after the edit
+        assert_eq!(edit(&mut p, "int y;", "X y;"), Reparse::Full);
+        // The old tokens come from a macro
+        assert_eq!(edit(&mut p, "X x;", "X z;"), Reparse::Full);
+        assert_eq!(edit(&mut p, "X y;", "X y;"), Reparse::Unchanged);
+
+        let mut p = new_parser("int x;\nint y;\n");
+        assert!(p
+            .edit(&[TextEdit {
+                start: 4,
+                end: 5,
+                text: b"(".to_vec(),
+            }])
+            .is_err());
+        assert!(p.unit().is_none());
+        assert_eq!(edit(&mut p, "int (;", "int z;"), Reparse::Full);
+        assert!(matches!(
+            edit(&mut p, "int y;", "int w;"),
+            Reparse::Partial { reused: 1, .. }
+        ));
+    }
+
+    #[test]
+    fn test_several_edits() {
+        let mut p = new_parser(SOURCE);
+        let source = String::from_utf8(p.source().to_vec()).unwrap();
+        let start = source.find("a + b").unwrap();
+        let reparse = p
+            .edit(&[
+                TextEdit {
+                    start,
+                    end: start + 1,
+                    text: b"b".to_vec(),
+                },
+                TextEdit {
+                    start: start + 4,
+                    end: start + 5,
+                    text: b"a".to_vec(),
+                },
+            ])
+            .unwrap();
+        assert!(matches!(reparse, Reparse::Partial { reparsed: 1, .. }));
+
+        let full = new_parser(&source.replacen("a + b", "b + a", 1));
+        assert_eq!(p.tokens(), full.tokens());
+        assert_eq!(p.unit(), full.unit());
+    }
+
+    #[test]
+    fn test_invalid_edit() {
+        let mut p = new_parser("int x;\n");
+        for (start, end) in [(3, 2), (4, 8), (9, 9)] {
+            assert!(matches!(
+                p.edit(&[TextEdit {
+                    start,
+                    end,
+                    text: b"y".to_vec(),
+                }]),
+                Err(ParserError::InvalidEdit { len: 7, .. })
+            ));
+        }
+
+        // The edits are checked against the text got after the previous ones
+        let res = p.edit(&[
+            TextEdit {
+                start: 4,
+                end: 7,
+                text: Vec::new(),
+            },
+            TextEdit {
+                start: 4,
+                end: 6,
+                text: b"y;".to_vec(),
+            },
+        ]);
+        assert!(matches!(res, Err(ParserError::InvalidEdit { len: 4, .. })));
+        assert_eq!(p.source(), b"int x;\n");
+        assert!(p.unit().is_some());
+    }
+
+    /// Insert the texts at all the positions (and remove the spaces) and compare with a full parse
+    fn check_all_positions(source: &str, texts: &[&str]) {
+        for text in texts {
+            for start in 0..source.len() {
+                // Remove the spaces
+                let end = if text.is_empty() { start + 1 } else { start };
+                if text.is_empty() && !source.as_bytes()[start].is_ascii_whitespace() {
+                    continue;
+                }
+                let mut p = new_parser(source);
+                let mut new = source.to_string();
+                new.replace_range(start..end, text);
+                let res = p.edit(&[TextEdit {
+                    start,
+                    end,
+                    text: text.as_bytes().to_vec(),
+                }]);
+
+                let mut full =
+                    IncrementalParser::new(new.into_bytes(), FileId(0), DefaultContext::default());
+                if full.parse().is_err() {
+                    assert!(res.is_err(), "{:?} at {}", text, start);
+                    continue;
+                }
+                assert!(res.is_ok(), "{:?} at {}", text, start);
+                assert_eq!(p.tokens(), full.tokens(), "{:?} at {}", text, start);
+                assert_eq!(p.unit(), full.unit(), "{:?} at {}", text, start);
+            }
+        }
+    }
+
+    #[test]
+    fn test_all_positions() {
+        check_all_positions(SOURCE, &["\n", " ", "/// d\n", ""]);
+    }
+
+    #[test]
+    fn test_all_positions_crlf() {
+        let source = SOURCE
+            .replace('\n', "\r\n")
+            .replace("a - b;", "a \\\r\n - b;");
+        check_all_positions(&source, &["\r\n", " ", "\\\r\n", "/// d\r\n", ""]);
+    }
+}
@@ -3,40 +3,25 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use std::iter::Peekable;
+use std::str::Chars;
+
 use termcolor::StandardStreamLock;
 
-use crate::lexer::{TLexer, Token};
+use crate::lexer::{Dialect, Symbol, TLexer, Token};
+pub use crate::lexer::{FloatLiteral, IntLiteral};
 use crate::parser::dump::Dump;
 use crate::parser::errors::ParserError;
 use crate::parser::Context;
 
-#[derive(Clone, Debug, PartialEq)]
-pub enum IntLiteral {
-    Int(u64),
-    UInt(u64),
-    Long(u64),
-    ULong(u64),
-    LongLong(u64),
-    ULongLong(u64),
-}
-
 #[derive(Clone, Debug, PartialEq)]
 pub struct Integer {
     pub value: IntLiteral,
 }
 
-impl Into<u64> for &IntLiteral {
-    fn into(self) -> u64 {
-        use IntLiteral::*;
-        match *self {
-            Int(n) | UInt(n) | Long(n) | ULong(n) | LongLong(n) | ULongLong(n) => n,
-        }
-    }
-}
-
 impl ToString for Integer {
     fn to_string(&self) -> String {
-        format!("{}", Into::<u64>::into(&self.value))
+        self.value.spelling.clone()
     }
 }
 
@@ -47,41 +32,286 @@ impl Dump for Integer {
 }
 
 #[derive(Clone, Debug, PartialEq)]
-pub enum FloatLiteral {
-    Float(f64),
-    Double(f64),
-    LongDouble(f64),
-    FloatUD(Box<(f64, String)>),
+pub struct Float {
+    pub value: FloatLiteral,
+}
+
+impl ToString for Float {
+    fn to_string(&self) -> String {
+        self.value.spelling.clone()
+    }
+}
+
+impl Dump for Float {
+    fn dump(&self, name: &str, prefix: &str, last: bool, stdout: &mut StandardStreamLock) {
+        dump_str!(name, self.to_string(), prefix, last, stdout);
+    }
+}
+
+/// The encoding prefix of a character or a string literal
+/// (U is u and UU is U as in the tokens)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Prefix {
+    None,
+    L,
+    U,
+    UU,
+    U8,
+}
+
+impl Prefix {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Prefix::None => "",
+            Prefix::L => "L",
+            Prefix::U => "u",
+            Prefix::UU => "U",
+            Prefix::U8 => "u8",
+        }
+    }
+
+    /// The size in bits of the code units
+    pub fn bits(self, wchar: WcharSize) -> u32 {
+        match self {
+            Prefix::None | Prefix::U8 => 8,
+            Prefix::U => 16,
+            Prefix::UU => 32,
+            Prefix::L => match wchar {
+                WcharSize::U16 => 16,
+                WcharSize::U32 => 32,
+            },
+        }
+    }
 }
 
+/// The size of wchar_t on the target (16 bits on Windows, 32 elsewhere)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WcharSize {
+    U16,
+    #[default]
+    U32,
+}
+
+impl WcharSize {
+    pub fn from_dialect(dialect: Dialect) -> Self {
+        if dialect.wchar_bits() == 16 {
+            WcharSize::U16
+        } else {
+            WcharSize::U32
+        }
+    }
+}
+
+/// The code units of a string literal
 #[derive(Clone, Debug, PartialEq)]
-pub struct Float {
-    pub value: FloatLiteral,
+pub enum CodeUnits {
+    U8(Vec<u8>),
+    U16(Vec<u16>),
+    U32(Vec<u32>),
 }
 
-impl Into<f64> for &FloatLiteral {
-    fn into(self) -> f64 {
-        use FloatLiteral::*;
-        match &*self {
-            Float(x) | Double(x) | LongDouble(x) => *x,
-            FloatUD(ref x) => x.0,
+impl CodeUnits {
+    /// The length of the array (the terminating null included)
+    pub fn len(&self) -> usize {
+        match self {
+            CodeUnits::U8(v) => v.len(),
+            CodeUnits::U16(v) => v.len(),
+            CodeUnits::U32(v) => v.len(),
         }
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
-impl ToString for Float {
-    fn to_string(&self) -> String {
-        match &self.value {
-            FloatLiteral::FloatUD(x) => format!("{}{}", x.0, x.1),
-            x => format!("{}", Into::<f64>::into(x)),
+/// A decoded char or the value of an octal or hexadecimal escape
+enum Item {
+    Char(u32),
+    Unit(u32),
+}
+
+/// Get the content of the braces of a delimited or a named escape (the next char is {)
+fn get_braced(chars: &mut Peekable<Chars>) -> String {
+    chars.next();
+    chars.by_ref().take_while(|c| *c != '}').collect()
+}
+
+/// Decode the escapes in the spelling of a string
+/// (the invalid ones have already been reported by the lexer)
+fn decode<F: FnMut(Item)>(s: &str, mut push: F) {
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            push(Item::Char(u32::from(c)));
+            continue;
         }
+
+        let c = if let Some(c) = chars.next() {
+            c
+        } else {
+            break;
+        };
+        push(match c {
+            'a' => Item::Char(0x07),
+            'b' => Item::Char(0x08),
+            'f' => Item::Char(0x0C),
+            'n' => Item::Char(0x0A),
+            'r' => Item::Char(0x0D),
+            't' => Item::Char(0x09),
+            'v' => Item::Char(0x0B),
+            '0'..='7' => {
+                let mut n = c.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    if let Some(d) = chars.peek().and_then(|c| c.to_digit(8)) {
+                        chars.next();
+                        n = n << 3 | d;
+                    } else {
+                        break;
+                    }
+                }
+                Item::Unit(n)
+            }
+            'x' | 'o' if chars.peek() == Some(&'{') => {
+                let radix = if c == 'x' { 16 } else { 8 };
+                Item::Unit(u32::from_str_radix(&get_braced(&mut chars), radix).unwrap_or(0))
+            }
+            'u' if chars.peek() == Some(&'{') => Item::Char(
+                u32::from_str_radix(&get_braced(&mut chars), 16)
+                    .ok()
+                    .filter(|n| std::char::from_u32(*n).is_some())
+                    .unwrap_or(0xFFFD),
+            ),
+            'N' if chars.peek() == Some(&'{') => Item::Char(
+                unicode_names2::character(&get_braced(&mut chars)).map_or(0xFFFD, u32::from),
+            ),
+            'x' => {
+                let mut n: u32 = 0;
+                while let Some(d) = chars.peek().and_then(|c| c.to_digit(16)) {
+                    chars.next();
+                    n = n.checked_mul(16).map_or(u32::MAX, |n| n | d);
+                }
+                Item::Unit(n)
+            }
+            'u' | 'U' => {
+                let len = if c == 'u' { 4 } else { 8 };
+                let mut n = 0;
+                let mut i = 0;
+                while i < len {
+                    if let Some(d) = chars.peek().and_then(|c| c.to_digit(16)) {
+                        chars.next();
+                        n = n << 4 | d;
+                        i += 1;
+                    } else {
+                        break;
+                    }
+                }
+                if i == len && std::char::from_u32(n).is_some() {
+                    Item::Char(n)
+                } else {
+                    Item::Char(0xFFFD)
+                }
+            }
+            c => Item::Char(u32::from(c)),
+        });
     }
 }
 
-impl Dump for Float {
-    fn dump(&self, name: &str, prefix: &str, last: bool, stdout: &mut StandardStreamLock) {
-        dump_str!(name, self.to_string(), prefix, last, stdout);
+/// Get the code units (of the given size) of a string and whether an escape is out of range
+fn encode(s: &str, raw: bool, bits: u32) -> (Vec<u32>, bool) {
+    let mut units = Vec::with_capacity(s.len() + 1);
+    let mut overflow = false;
+    let mut push = |item| match item {
+        Item::Char(c) => {
+            let c = std::char::from_u32(c).unwrap_or('\u{FFFD}');
+            match bits {
+                8 => units.extend(c.encode_utf8(&mut [0; 4]).bytes().map(u32::from)),
+                16 => units.extend(c.encode_utf16(&mut [0; 2]).iter().map(|&u| u32::from(u))),
+                _ => units.push(u32::from(c)),
+            }
+        }
+        Item::Unit(u) => {
+            if bits < 32 && u >> bits != 0 {
+                overflow = true;
+                units.push(u & ((1 << bits) - 1));
+            } else {
+                units.push(u);
+            }
+        }
+    };
+
+    if raw {
+        s.chars().for_each(|c| push(Item::Char(u32::from(c))));
+    } else {
+        decode(s, &mut push);
     }
+    units.push(0);
+    (units, overflow)
+}
+
+/// Check if the spelling ends with an octal or a hexadecimal escape which
+/// could be continued by the next chars
+fn has_open_escape(s: &str) -> bool {
+    let s = s.as_bytes();
+    let mut i = 0;
+    while i < s.len() {
+        if s[i] != b'\\' {
+            i += 1;
+            continue;
+        }
+        i += 1;
+        match s.get(i) {
+            Some(b'x') => {
+                i += 1;
+                while i < s.len() && s[i].is_ascii_hexdigit() {
+                    i += 1;
+                }
+                if i == s.len() {
+                    return true;
+                }
+            }
+            Some(b'0'..=b'7') => {
+                let start = i;
+                while i < s.len() && i - start < 3 && (b'0'..=b'7').contains(&s[i]) {
+                    i += 1;
+                }
+                if i == s.len() && i - start < 3 {
+                    return true;
+                }
+            }
+            _ => {
+                i += 1;
+            }
+        }
+    }
+    false
+}
+
+/// Get the prefix, the rawness, the text and the suffix of a string token
+fn get_str_parts(tok: Token) -> Option<(Prefix, bool, Symbol, Option<Symbol>)> {
+    Some(match tok {
+        Token::LiteralString(s) => (Prefix::None, false, s, None),
+        Token::LiteralLString(s) => (Prefix::L, false, s, None),
+        Token::LiteralUString(s) => (Prefix::U, false, s, None),
+        Token::LiteralUUString(s) => (Prefix::UU, false, s, None),
+        Token::LiteralU8String(s) => (Prefix::U8, false, s, None),
+        Token::LiteralRString(s) => (Prefix::None, true, s, None),
+        Token::LiteralLRString(s) => (Prefix::L, true, s, None),
+        Token::LiteralURString(s) => (Prefix::U, true, s, None),
+        Token::LiteralUURString(s) => (Prefix::UU, true, s, None),
+        Token::LiteralU8RString(s) => (Prefix::U8, true, s, None),
+        Token::LiteralStringUD(s, suf) => (Prefix::None, false, s, Some(suf)),
+        Token::LiteralLStringUD(s, suf) => (Prefix::L, false, s, Some(suf)),
+        Token::LiteralUStringUD(s, suf) => (Prefix::U, false, s, Some(suf)),
+        Token::LiteralUUStringUD(s, suf) => (Prefix::UU, false, s, Some(suf)),
+        Token::LiteralU8StringUD(s, suf) => (Prefix::U8, false, s, Some(suf)),
+        Token::LiteralRStringUD(s, suf) => (Prefix::None, true, s, Some(suf)),
+        Token::LiteralLRStringUD(s, suf) => (Prefix::L, true, s, Some(suf)),
+        Token::LiteralURStringUD(s, suf) => (Prefix::U, true, s, Some(suf)),
+        Token::LiteralUURStringUD(s, suf) => (Prefix::UU, true, s, Some(suf)),
+        Token::LiteralU8RStringUD(s, suf) => (Prefix::U8, true, s, Some(suf)),
+        _ => return None,
+    })
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -103,6 +333,19 @@ pub struct Char {
     pub value: CharLiteral,
 }
 
+impl Char {
+    pub fn prefix(&self) -> Prefix {
+        use CharLiteral::*;
+        match &self.value {
+            Char(_) | CharUD(_) => Prefix::None,
+            LChar(_) | LCharUD(_) => Prefix::L,
+            UChar(_) | UCharUD(_) => Prefix::U,
+            UUChar(_) | UUCharUD(_) => Prefix::UU,
+            U8Char(_) | U8CharUD(_) => Prefix::U8,
+        }
+    }
+}
+
 impl Into<u32> for &CharLiteral {
     fn into(self) -> u32 {
         use CharLiteral::*;
@@ -173,6 +416,94 @@ pub struct Str {
     pub value: StrLiteral,
 }
 
+impl StrLiteral {
+    fn new(prefix: Prefix, raw: bool, s: String, suffix: Option<String>) -> Self {
+        use StrLiteral::*;
+        if let Some(suf) = suffix {
+            let x = Box::new((s, suf));
+            match (prefix, raw) {
+                (Prefix::None, false) => StrUD(x),
+                (Prefix::L, false) => LStrUD(x),
+                (Prefix::U, false) => UStrUD(x),
+                (Prefix::UU, false) => UUStrUD(x),
+                (Prefix::U8, false) => U8StrUD(x),
+                (Prefix::None, true) => RStrUD(x),
+                (Prefix::L, true) => LRStrUD(x),
+                (Prefix::U, true) => URStrUD(x),
+                (Prefix::UU, true) => UURStrUD(x),
+                (Prefix::U8, true) => U8RStrUD(x),
+            }
+        } else {
+            match (prefix, raw) {
+                (Prefix::None, false) => Str(s),
+                (Prefix::L, false) => LStr(s),
+                (Prefix::U, false) => UStr(s),
+                (Prefix::UU, false) => UUStr(s),
+                (Prefix::U8, false) => U8Str(s),
+                (Prefix::None, true) => RStr(s),
+                (Prefix::L, true) => LRStr(s),
+                (Prefix::U, true) => URStr(s),
+                (Prefix::UU, true) => UURStr(s),
+                (Prefix::U8, true) => U8RStr(s),
+            }
+        }
+    }
+}
+
+impl Str {
+    pub fn prefix(&self) -> Prefix {
+        use StrLiteral::*;
+        match &self.value {
+            Str(_) | RStr(_) | StrUD(_) | RStrUD(_) => Prefix::None,
+            LStr(_) | LRStr(_) | LStrUD(_) | LRStrUD(_) => Prefix::L,
+            UStr(_) | URStr(_) | UStrUD(_) | URStrUD(_) => Prefix::U,
+            UUStr(_) | UURStr(_) | UUStrUD(_) | UURStrUD(_) => Prefix::UU,
+            U8Str(_) | U8RStr(_) | U8StrUD(_) | U8RStrUD(_) => Prefix::U8,
+        }
+    }
+
+    pub fn is_raw(&self) -> bool {
+        use StrLiteral::*;
+        matches!(
+            &self.value,
+            RStr(_)
+                | LRStr(_)
+                | URStr(_)
+                | UURStr(_)
+                | U8RStr(_)
+                | RStrUD(_)
+                | LRStrUD(_)
+                | URStrUD(_)
+                | UURStrUD(_)
+                | U8RStrUD(_)
+        )
+    }
+
+    /// The spelling of the string (without the prefix and the quotes)
+    pub fn text(&self) -> &str {
+        use StrLiteral::*;
+        match &self.value {
+            Str(s) | LStr(s) | UStr(s) | UUStr(s) | U8Str(s) | RStr(s) | LRStr(s) | URStr(s)
+            | UURStr(s) | U8RStr(s) => s,
+
+            StrUD(x) | LStrUD(x) | UStrUD(x) | UUStrUD(x) | U8StrUD(x) | RStrUD(x) | LRStrUD(x)
+            | URStrUD(x) | UURStrUD(x) | U8RStrUD(x) => &x.0,
+        }
+    }
+
+    /// Get the array of code units of the string (with the terminating null):
+    /// the escapes which don't fit in a code unit are truncated
+    pub fn code_units(&self, wchar: WcharSize) -> CodeUnits {
+        let bits = self.prefix().bits(wchar);
+        let (units, _) = encode(self.text(), self.is_raw(), bits);
+        match bits {
+            8 => CodeUnits::U8(units.into_iter().map(|u| u as u8).collect()),
+            16 => CodeUnits::U16(units.into_iter().map(|u| u as u16).collect()),
+            _ => CodeUnits::U32(units),
+        }
+    }
+}
+
 impl ToString for Str {
     fn to_string(&self) -> String {
         use StrLiteral::*;
@@ -222,59 +553,236 @@ impl<'a, L: TLexer> StringLiteralParser<'a, L> {
         Self { lexer }
     }
 
-    #[inline(always)]
-    fn concat(first: &str, lens: usize, strings: Vec<String>) -> String {
-        let mut res = String::with_capacity(lens + first.len() + 1);
-        res.push_str(first);
-        for s in strings {
-            res.push_str(&s);
+    /// Concatenate the adjacent strings: a raw string is escaped when
+    /// it's concatenated with a non-raw one
+    fn concat(pieces: Vec<(bool, Symbol)>) -> (bool, String) {
+        let raw = pieces.iter().all(|(raw, _)| *raw);
+        let mut res = String::with_capacity(pieces.iter().map(|(_, s)| s.len()).sum());
+        let mut open = false;
+
+        for (is_raw, s) in pieces {
+            let mut chars = s.chars();
+            if open {
+                // "\x1" "2" isn't "\x12"
+                if let Some(c) = s.chars().next().filter(|c| c.is_ascii_hexdigit()) {
+                    res.push_str(&format!("\\{:03o}", u32::from(c)));
+                    chars.next();
+                }
+            }
+
+            if raw || !is_raw {
+                res.push_str(chars.as_str());
+                open = !raw && has_open_escape(&s);
+            } else {
+                for c in chars {
+                    match c {
+                        '\\' => res.push_str("\\\\"),
+                        '"' => res.push_str("\\\""),
+                        '\n' => res.push_str("\\n"),
+                        c => res.push(c),
+                    }
+                }
+                open = false;
+            }
         }
-        res
+
+        (raw, res)
     }
 
     pub(crate) fn parse(
         self,
-        first: &str,
-        _context: &mut Context,
-    ) -> Result<(Option<Token>, String), ParserError> {
-        let mut strings = Vec::with_capacity(32); // TODO: put a good value here if it's useful
-        let mut lens = 0;
+        tok: Token,
+        context: &mut Context,
+    ) -> Result<(Option<Token>, Option<Str>), ParserError> {
+        let (mut prefix, raw, s, mut suffix) = if let Some(parts) = get_str_parts(tok) {
+            parts
+        } else {
+            return Ok((Some(tok), None));
+        };
+        let sp = self.lexer.span();
+        let mut pieces = vec![(raw, s)];
 
         let tok = loop {
             let tok = self.lexer.next_useful();
-            match tok {
-                Token::LiteralString(s)
-                | Token::LiteralLString(s)
-                | Token::LiteralUString(s)
-                | Token::LiteralUUString(s)
-                | Token::LiteralU8String(s)
-                | Token::LiteralRString(s)
-                | Token::LiteralLRString(s)
-                | Token::LiteralURString(s)
-                | Token::LiteralUURString(s)
-                | Token::LiteralU8RString(s) => {
-                    lens += s.len();
-                    strings.push(s);
-                }
-                Token::LiteralStringUD(s_suf)
-                | Token::LiteralLStringUD(s_suf)
-                | Token::LiteralUStringUD(s_suf)
-                | Token::LiteralUUStringUD(s_suf)
-                | Token::LiteralU8StringUD(s_suf)
-                | Token::LiteralRStringUD(s_suf)
-                | Token::LiteralLRStringUD(s_suf)
-                | Token::LiteralURStringUD(s_suf)
-                | Token::LiteralUURStringUD(s_suf)
-                | Token::LiteralU8RStringUD(s_suf) => {
-                    lens += s_suf.0.len();
-                    strings.push(s_suf.0);
+            let (p, raw, s, suf) = if let Some(parts) = get_str_parts(tok) {
+                parts
+            } else {
+                break tok;
+            };
+
+            prefix = match (prefix, p) {
+                (Prefix::None, p) => p,
+                (prefix, Prefix::None) => prefix,
+                (prefix, p) if prefix == p => p,
+                (_, p) => {
+                    return Err(ParserError::IncompatibleStrPrefix {
+                        sp: self.lexer.span(),
+                        prefix: p.as_str().to_string(),
+                    });
                 }
-                _ => {
-                    break tok;
+            };
+
+            suffix = match (suffix, suf) {
+                (Some(first), Some(suf)) if first != suf => {
+                    return Err(ParserError::IncompatibleStrSuffix {
+                        sp: self.lexer.span(),
+                        suffix: suf.to_string(),
+                    });
                 }
+                (first, second) => first.or(second),
+            };
+
+            pieces.push((raw, s));
+        };
+
+        let (raw, text) = Self::concat(pieces);
+        if !raw && text.contains('\\') {
+            let (_, overflow) = encode(&text, false, prefix.bits(context.get_wchar_size()));
+            if overflow {
+                return Err(ParserError::EscapeOutOfRange { sp });
             }
+        }
+
+        let value = StrLiteral::new(prefix, raw, text, suffix.map(|s| s.to_string()));
+        Ok((Some(tok), Some(Str { value })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::lexer::{preprocessor::context::DefaultContext, Lexer};
+    use pretty_assertions::assert_eq;
+
+    fn parse(s: &str) -> Result<Str, ParserError> {
+        let mut lexer = Lexer::<DefaultContext>::new(s.as_bytes());
+        let mut context = Context::default();
+        let tok = lexer.next_useful();
+        let slp = StringLiteralParser::new(&mut lexer);
+        slp.parse(tok, &mut context).map(|(_, s)| s.unwrap())
+    }
+
+    fn units(s: &str) -> CodeUnits {
+        parse(s).unwrap().code_units(WcharSize::U32)
+    }
+
+    #[test]
+    fn test_code_units() {
+        assert_eq!(
+            units(r#""a\tb\\\"\0\101\x41\u00e9""#),
+            CodeUnits::U8(b"a\tb\\\"\0AA\xC3\xA9\0".to_vec())
+        );
+        // An escape gives a code unit and not a char
+        assert_eq!(
+            units(r#"u8"\xe9é""#),
+            CodeUnits::U8(b"\xE9\xC3\xA9\0".to_vec())
+        );
+        assert_eq!(
+            units(r#"u"a\U0001F600\xD800""#),
+            CodeUnits::U16(vec![0x61, 0xD83D, 0xDE00, 0xD800, 0])
+        );
+        assert_eq!(
+            units(r#"U"a😀\x12345""#),
+            CodeUnits::U32(vec![0x61, 0x1F600, 0x12345, 0])
+        );
+        assert_eq!(
+            parse(r#"L"a😀""#).unwrap().code_units(WcharSize::U16),
+            CodeUnits::U16(vec![0x61, 0xD83D, 0xDE00, 0])
+        );
+        assert_eq!(units(r#"L"a😀""#), CodeUnits::U32(vec![0x61, 0x1F600, 0]));
+        assert_eq!(
+            units(r#"U"\x{1F600}\o{101}\u{e9}\N{LATIN SMALL LETTER A}""#),
+            CodeUnits::U32(vec![0x1F600, 0o101, 0xe9, 0x61, 0])
+        );
+        assert_eq!(units(r#""""#).len(), 1);
+        assert_eq!(units("R\"x(a\\n\n)x\"").len(), 5);
+    }
+
+    #[test]
+    fn test_concat() {
+        let s = parse(r#""a" u"b" "c""#).unwrap();
+        assert_eq!(s.value, StrLiteral::UStr("abc".to_string()));
+        assert_eq!(s.code_units(WcharSize::U32).len(), 4);
+
+        // The escapes are decoded before the concatenation
+        assert_eq!(
+            units(r#""\x1" "2" "\12" "3" "\x" "4""#),
+            CodeUnits::U8(vec![1, b'2', 0o12, b'3', 0, b'4', 0])
+        );
+
+        let s = parse("R\"(a\\n\"\n)\" \"b\\n\" R\"(c)\"").unwrap();
+        assert_eq!(s.value, StrLiteral::Str("a\\\\n\\\"\\nb\\nc".to_string()));
+        assert_eq!(
+            s.code_units(WcharSize::U32),
+            CodeUnits::U8(b"a\\n\"\nb\nc\0".to_vec())
+        );
+
+        let s = parse(r#"R"(a)" u8R"(\n)""#).unwrap();
+        assert_eq!(s.value, StrLiteral::U8RStr("a\\n".to_string()));
+
+        let s = parse(r#""a"_x "b" L"c"_x"#).unwrap();
+        assert_eq!(
+            s.value,
+            StrLiteral::LStrUD(Box::new(("abc".to_string(), "_x".to_string())))
+        );
+    }
+
+    #[test]
+    fn test_concat_errors() {
+        let e = parse(r#""a" u"b" U"c""#).unwrap_err().stringly();
+        assert_eq!(
+            (e.message.as_str(), e.sp.start.pos),
+            ("Concatenation with an incompatible string U\"\"", 9)
+        );
+
+        let e = parse(r#""a"_x "b"_y"#).unwrap_err().stringly();
+        assert_eq!(
+            e.message,
+            "Concatenation with a string with another suffix _y"
+        );
+
+        let e = parse(r#""a\x100""#).unwrap_err().stringly();
+        assert_eq!(e.message, "Escape sequence out of range in string");
+        assert!(parse(r#"u"a\x100""#).is_ok());
+        assert!(parse(r#"u"a\x10000""#).is_err());
+    }
+
+    #[test]
+    fn test_hex_escapes() {
+        // The lexer and the decoder of the strings read all the hex digits
+        let mut lexer = Lexer::<DefaultContext>::new(br"u'\x1234' U'\x0001F600' '\x1234'");
+        assert_eq!(lexer.next_useful(), Token::LiteralUChar(0x1234));
+        assert_eq!(lexer.next_useful(), Token::LiteralUUChar(0x1F600));
+        lexer.next_useful();
+        let e = lexer.errors[0].stringly();
+        assert_eq!(
+            (e.message.as_str(), e.sp.start.pos),
+            ("escape sequence out of range", 24)
+        );
+
+        assert_eq!(units(r#"u"\x1234""#), CodeUnits::U16(vec![0x1234, 0]));
+        assert_eq!(units(r#"U"\x0001F600""#), CodeUnits::U32(vec![0x1F600, 0]));
+        let e = parse(r#""\x1234""#).unwrap_err().stringly();
+        assert_eq!(e.message, "Escape sequence out of range in string");
+    }
+
+    #[test]
+    fn test_wchar_size() {
+        let parse = |s: &str, wchar| {
+            let mut lexer = Lexer::<DefaultContext>::new(s.as_bytes());
+            let mut context = Context::default();
+            context.set_wchar_size(wchar);
+            let tok = lexer.next_useful();
+            let slp = StringLiteralParser::new(&mut lexer);
+            slp.parse(tok, &mut context).map(|(_, s)| s.unwrap())
         };
 
-        Ok((Some(tok), Self::concat(first, lens, strings)))
+        assert!(parse(r#"L"\x10000""#, WcharSize::U32).is_ok());
+        assert!(parse(r#"L"\xffff""#, WcharSize::U16).is_ok());
+        let e = parse(r#"L"\x10000""#, WcharSize::U16)
+            .unwrap_err()
+            .stringly();
+        assert_eq!(e.message, "Escape sequence out of range in string");
     }
 }
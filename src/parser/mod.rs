@@ -6,6 +6,9 @@
 pub mod dump;
 pub use self::dump::*;
 
+pub mod doc;
+pub use self::doc::*;
+
 #[macro_use]
 pub mod names;
 pub use self::names::*;
@@ -38,6 +41,9 @@ pub use self::context::*;
 pub mod unit;
 pub use self::unit::*;
 
+pub mod incremental;
+pub use self::incremental::*;
+
 pub mod errors;
 
 /*
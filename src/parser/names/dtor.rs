@@ -33,7 +33,12 @@ impl<'a, L: TLexer> DtorParser<'a, L> {
 
         let tok = self.lexer.next_useful();
         if let Token::Identifier(name) = tok {
-            Ok((None, Some(Destructor { name })))
+            Ok((
+                None,
+                Some(Destructor {
+                    name: name.to_string(),
+                }),
+            ))
         } else {
             Err(ParserError::InvalidTokenInDtor {
                 sp: self.lexer.span(),
@@ -169,7 +169,9 @@ impl<'a, L: TLexer> QualifiedParser<'a, L> {
                     wait_id = false;
                 }*/
                 Token::Identifier(val) if wait_id => {
-                    names.push(Name::Identifier(Identifier { val }));
+                    names.push(Name::Identifier(Identifier {
+                        val: val.to_string(),
+                    }));
                     wait_id = false;
                 }
                 Token::Identifier(_) if !wait_id => {
@@ -79,7 +79,7 @@ impl<'a, L: TLexer> OperatorParser<'a, L> {
             Token::LiteralString(_) => {
                 let tok = self.lexer.next_useful();
                 if let Token::Identifier(id) = tok {
-                    Ok((None, Some(Operator::UD(id))))
+                    Ok((None, Some(Operator::UD(id.to_string()))))
                 } else {
                     Err(ParserError::InvalidTokenInOp {
                         sp: self.lexer.span(),
@@ -87,10 +87,7 @@ impl<'a, L: TLexer> OperatorParser<'a, L> {
                     })
                 }
             }
-            Token::LiteralStringUD(s_ud) => {
-                let (_, ud) = *s_ud;
-                Ok((None, Some(Operator::UD(ud))))
-            }
+            Token::LiteralStringUD(_, ud) => Ok((None, Some(Operator::UD(ud.to_string())))),
             Token::New => {
                 let tok = self.lexer.next_useful();
                 if tok == Token::LeftBrack {
@@ -235,7 +235,7 @@ impl<'a, L: TLexer> StatementParser<'a, L> {
             Token::SemiColon => Ok((None, Some(Statement::Empty))),
             _ => {
                 let doep = DeclOrExprParser::new(self.lexer);
-                let (_, doe) = doep.parse(Some(tok.clone()), context)?;
+                let (_, doe) = doep.parse(Some(tok), context)?;
 
                 if let Some(doe) = doe {
                     return Ok(match doe {
@@ -260,6 +260,7 @@ impl<'a, L: TLexer> StatementParser<'a, L> {
 #[cfg(test)]
 mod tests {
 
+    use std::cell::RefCell;
     use std::rc::Rc;
 
     use super::*;
@@ -331,10 +332,11 @@ mod tests {
             },
             init: Some(Initializer::Equal(ExprNode::Integer(Box::new(
                 literals::Integer {
-                    value: IntLiteral::Int(1),
+                    value: IntLiteral::new("1").unwrap(),
                 },
             )))),
             bitfield_size: None,
+            doc: RefCell::new(None),
         });
 
         assert_eq!(
@@ -350,7 +352,7 @@ mod tests {
                             decl: VarDecl::Direct(Rc::clone(&a)),
                         })),
                         arg2: ExprNode::Integer(Box::new(literals::Integer {
-                            value: IntLiteral::Int(2)
+                            value: IntLiteral::new("2").unwrap()
                         })),
                     }))),
                     Statement::Type(Rc::new(TypeDeclarator {
@@ -366,10 +368,11 @@ mod tests {
                         },
                         init: Some(Initializer::Equal(ExprNode::Integer(Box::new(
                             literals::Integer {
-                                value: IntLiteral::Int(1)
+                                value: IntLiteral::new("1").unwrap()
                             }
                         )))),
                         bitfield_size: None,
+                        doc: RefCell::new(None),
                     })),
                     Statement::Type(Rc::new(TypeDeclarator {
                         typ: Type {
@@ -389,6 +392,7 @@ mod tests {
                         },
                         init: Some(Initializer::Equal(ExprNode::Nullptr(Box::new(Nullptr {})))),
                         bitfield_size: None,
+                        doc: RefCell::new(None),
                     })),
                     Statement::Empty,
                 ]
@@ -512,6 +516,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: RefCell::new(None),
             })),
             handler: Box::new(Statement::Compound(Box::new(Compound {
                 attributes: None,
@@ -564,6 +569,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: RefCell::new(None),
             })),
             handler: Box::new(Statement::Compound(Box::new(Compound {
                 attributes: None,
@@ -653,10 +659,11 @@ mod tests {
             },
             init: Some(Initializer::Equal(ExprNode::Integer(Box::new(
                 literals::Integer {
-                    value: IntLiteral::Int(0),
+                    value: IntLiteral::new("0").unwrap(),
                 },
             )))),
             bitfield_size: None,
+            doc: RefCell::new(None),
         });
 
         let expected = Statement::For(Box::new(For {
@@ -718,6 +725,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: RefCell::new(None),
             }),
             expr: node!(CallExpr {
                 callee: node!(BinaryOp {
@@ -758,6 +766,7 @@ mod tests {
             },
             init: None,
             bitfield_size: None,
+            doc: RefCell::new(None),
         });
         let thing = Rc::new(TypeDeclarator {
             typ: Type {
@@ -778,6 +787,7 @@ mod tests {
                 params: vec![]
             }))),
             bitfield_size: None,
+            doc: RefCell::new(None),
         });
 
         context.add_type_decl(Rc::clone(&t));
@@ -805,6 +815,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: RefCell::new(None),
             }),
             expr: node!(CallExpr {
                 callee: node!(BinaryOp {
@@ -857,6 +868,7 @@ mod tests {
             },
             init: None,
             bitfield_size: None,
+            doc: RefCell::new(None),
         });
         context.add_type_decl(Rc::clone(&x));
 
@@ -874,20 +886,20 @@ mod tests {
                     Statement::Case(Box::new(Case {
                         attributes: None,
                         value: ExprNode::Integer(Box::new(literals::Integer {
-                            value: IntLiteral::Int(1),
+                            value: IntLiteral::new("1").unwrap(),
                         })),
                     })),
                     Statement::Break(Box::new(Break { attributes: None })),
                     Statement::Case(Box::new(Case {
                         attributes: None,
                         value: ExprNode::Integer(Box::new(literals::Integer {
-                            value: IntLiteral::Int(2),
+                            value: IntLiteral::new("2").unwrap(),
                         })),
                     })),
                     Statement::Case(Box::new(Case {
                         attributes: None,
                         value: ExprNode::Integer(Box::new(literals::Integer {
-                            value: IntLiteral::Int(3),
+                            value: IntLiteral::new("3").unwrap(),
                         })),
                     })),
                     Statement::Break(Box::new(Break { attributes: None })),
@@ -910,18 +922,18 @@ mod tests {
         let expected = Statement::While(Box::new(While {
             attributes: None,
             condition: DeclOrExpr::Expr(ExprNode::Integer(Box::new(literals::Integer {
-                value: IntLiteral::Int(0),
+                value: IntLiteral::new("0").unwrap(),
             }))),
             body: Statement::While(Box::new(While {
                 attributes: None,
                 condition: DeclOrExpr::Expr(ExprNode::Integer(Box::new(literals::Integer {
-                    value: IntLiteral::Int(0),
+                    value: IntLiteral::new("0").unwrap(),
                 }))),
                 body: Statement::Do(Box::new(Do {
                     attributes: None,
                     body: Statement::Empty,
                     condition: ExprNode::Integer(Box::new(literals::Integer {
-                        value: IntLiteral::Int(0),
+                        value: IntLiteral::new("0").unwrap(),
                     })),
                 })),
             })),
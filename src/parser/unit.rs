@@ -11,10 +11,11 @@ use crate::lexer::preprocessor::context::PreprocContext;
 use crate::lexer::{Lexer, TLexer, Token};
 use crate::parser::dump::Dump;
 use crate::parser::errors::ParserError;
+use crate::parser::literals::WcharSize;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Unit {
-    decls: Declarations,
+    pub(crate) decls: Declarations,
 }
 
 impl Dump for Unit {
@@ -37,6 +38,10 @@ impl<'a, PC: PreprocContext> UnitParser<'a, PC> {
     }
 
     pub fn parse(&mut self) -> Result<Unit, ParserError> {
+        self.context.set_standard(self.lexer.get_standard());
+        self.context
+            .set_wchar_size(WcharSize::from_dialect(self.lexer.get_dialect()));
+
         let dlp = DeclarationListParser::new(&mut self.lexer);
         let (tok, decls) = dlp.parse(None, &mut self.context)?;
 